@@ -147,6 +147,12 @@ fn extract_and_store(bundle_id: &str, state: &mut DaemonState) {
                         content.title.as_deref().unwrap_or("untitled")
                     );
                 }
+                DedupResult::Forgotten => {
+                    log::debug!(
+                        "[AX-DAEMON] 🚫 Forgotten source, not stored: {}",
+                        content.title.as_deref().unwrap_or("untitled")
+                    );
+                }
             },
             Err(e) => {
                 log::error!("[AX-DAEMON] ❌ Failed to store content: {}", e);
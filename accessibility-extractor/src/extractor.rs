@@ -36,7 +36,9 @@
 //! - Requirement 9.7: Return appropriate errors on unsupported platforms
 
 use crate::types::{CapturePayload, ChunkMeta, ExtractedContent, ExtractionError};
-use crate::types::{generate_content_hash, generate_doc_id};
+use crate::types::generate_content_hash;
+use capture_protocol::ContentType;
+use content_db::doc_id;
 
 #[cfg(target_os = "macos")]
 use crate::platform::macos::MacOSExtractor;
@@ -405,6 +407,8 @@ impl AccessibilityExtractor {
     /// - `author`: Set to None (not available from accessibility extraction)
     /// - `channel`: Set to None (not available from accessibility extraction)
     /// - `timestamp`: Copied from ExtractedContent
+    /// - `app_name`: Copied from ExtractedContent
+    /// - `bundle_id`: Set to None (the extractor only knows the app name)
     ///
     /// # URL Format
     ///
@@ -454,43 +458,18 @@ impl AccessibilityExtractor {
             source: content.source.clone(),
             url,
             content: content.content.clone(),
+            content_type: ContentType::Plain,
             title: content.title.clone(),
             author: None,
             channel: None,
             timestamp: Some(content.timestamp),
+            app_name: Some(content.app_name.clone()),
+            bundle_id: None,
+            metadata: Default::default(),
+            messages: Vec::new(),
         }
     }
 
-    /// Generate a unique document identifier (ehl_doc_id) using UUID v4.
-    ///
-    /// This function generates a unique identifier for each extracted document.
-    /// The identifier is a UUID v4 (random) string that can be used to uniquely
-    /// identify documents in the database and ingestion pipeline.
-    ///
-    /// # Returns
-    ///
-    /// A `String` containing a UUID v4 in the standard hyphenated format
-    /// (e.g., "550e8400-e29b-41d4-a716-446655440000").
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// use accessibility_extractor::extractor::AccessibilityExtractor;
-    ///
-    /// let doc_id = AccessibilityExtractor::generate_doc_id();
-    /// println!("Generated document ID: {}", doc_id);
-    ///
-    /// // Each call generates a unique ID
-    /// let another_id = AccessibilityExtractor::generate_doc_id();
-    /// assert_ne!(doc_id, another_id);
-    /// ```
-    ///
-    /// # Requirements
-    /// - Requirement 11.8: Generate a unique document identifier (ehl_doc_id) for each extracted document
-    pub fn generate_doc_id() -> String {
-        generate_doc_id()
-    }
-
     // =========================================================================
     // Ingestion Pipeline Integration
     // =========================================================================
@@ -522,7 +501,7 @@ impl AccessibilityExtractor {
     // - `source_ref`: Application bundle ID
     // - `url`: Generated accessibility:// URL
     // - `title`: Document title
-    // - `ehl_doc_id`: Unique document ID from `generate_doc_id()`
+    // - `ehl_doc_id`: `content_db::doc_id::doc_id(source, url)`, stable across captures
     // - `content_hash`: SHA-256 hash from `generate_content_hash()`
     //
     // ## Deduplication
@@ -563,21 +542,21 @@ impl AccessibilityExtractor {
     /// # Arguments
     ///
     /// * `content` - The extracted content to convert
-    /// * `chunk_idx` - Index of this chunk within the document (0-based)
+    /// * `chunk_index` - Index of this chunk within the document (0-based)
     /// * `total_chunks` - Total number of chunks for this document
     ///
     /// # Returns
     ///
-    /// A `ChunkMeta` instance with all fields populated:
-    /// - `id`: Unique document identifier (UUID v4)
+    /// A `ChunkMeta` (the shape the ingestion server writes) with:
+    /// - `id`: Document ID derived from the source and URL (`content_db::doc_id`)
     /// - `source`: Application source identifier (e.g., "word", "excel")
-    /// - `timestamp`: Unix timestamp of extraction
-    /// - `header`: First 200 characters of content
-    /// - `chunk_idx`: Index of this chunk
-    /// - `total_chunks`: Total number of chunks
-    /// - `app_id`: Application bundle ID (derived from app_name)
     /// - `url`: Generated accessibility:// URL
-    /// - `title`: Document title (if available)
+    /// - `title`: Document title, or "untitled"
+    /// - `chunk_index` / `total_chunks`: As given
+    /// - `source_type` / `extraction_method`: "accessibility" / from ExtractedContent
+    /// - `app_name`: Application display name
+    /// - `bundle_id`: Derived from app_name
+    /// - `captured_at`: Unix timestamp of extraction
     ///
     /// # Example
     ///
@@ -597,19 +576,16 @@ impl AccessibilityExtractor {
     /// let chunk_meta = AccessibilityExtractor::to_chunk_meta(&content, 0, 1);
     ///
     /// assert_eq!(chunk_meta.source, "word");
-    /// assert_eq!(chunk_meta.chunk_idx, 0);
+    /// assert_eq!(chunk_meta.chunk_index, 0);
     /// assert_eq!(chunk_meta.total_chunks, 1);
-    /// assert!(chunk_meta.url.is_some());
+    /// assert!(chunk_meta.url.starts_with("accessibility://"));
     /// assert!(chunk_meta.title.is_some());
     /// ```
     ///
     /// # Requirements
     /// - Requirement 11.9: Support integration with the existing ingestion pipeline
     /// - Requirement 11.11: Populate the chunks table with extracted content and appropriate metadata
-    pub fn to_chunk_meta(content: &ExtractedContent, chunk_idx: u32, total_chunks: u32) -> ChunkMeta {
-        // Generate unique document ID
-        let doc_id = generate_doc_id();
-
+    pub fn to_chunk_meta(content: &ExtractedContent, chunk_index: usize, total_chunks: usize) -> ChunkMeta {
         // Generate accessibility:// URL
         let url = format!(
             "accessibility://{}/{}",
@@ -617,29 +593,38 @@ impl AccessibilityExtractor {
             content.title.as_deref().unwrap_or("untitled")
         );
 
-        // Derive app_id from app_name (convert to bundle ID format)
+        // Derive the bundle ID from app_name
         // This is a best-effort mapping; the actual bundle ID may differ
-        let app_id = Self::derive_bundle_id(&content.app_name);
-
-        // Create ChunkMeta with all fields populated
-        ChunkMeta::new(
-            doc_id,
-            content.source.clone(),
-            content.timestamp,
-            &content.content,
-            chunk_idx,
+        let bundle_id = Self::derive_bundle_id(&content.app_name);
+
+        ChunkMeta {
+            id: doc_id::doc_id(&content.source, &url),
+            source: content.source.clone(),
+            url,
+            original_url: None,
+            title: Some(content.title.clone().unwrap_or_else(|| "untitled".to_string())),
+            author: None,
+            channel: None,
+            chunk_index,
             total_chunks,
-        )
-        .with_app_id(app_id)
-        .with_url(url)
-        .with_title(content.title.clone().unwrap_or_else(|| "untitled".to_string()))
+            source_type: "accessibility".to_string(),
+            extraction_method: Some(content.extraction_method.clone()),
+            app_name: Some(content.app_name.clone()),
+            bundle_id: Some(bundle_id),
+            metadata: Default::default(),
+            captured_at: Some(content.timestamp),
+            revision_id: None,
+            char_start: None,
+            char_end: None,
+            pipeline_version: None,
+        }
     }
 
     /// Convert ExtractedContent to chunk metadata JSON string.
     ///
     /// This function converts the extracted content into a JSON string suitable
     /// for storage in the `meta` column of the `chunks` table. It creates a
-    /// single chunk (chunk_idx=0, total_chunks=1) from the content.
+    /// single chunk (chunk_index=0, total_chunks=1) from the content.
     ///
     /// For documents that need to be split into multiple chunks, use
     /// `to_chunk_meta()` directly with appropriate chunk indices.
@@ -672,7 +657,7 @@ impl AccessibilityExtractor {
     ///
     /// // The JSON contains all required fields
     /// assert!(json.contains("\"source\":\"word\""));
-    /// assert!(json.contains("\"chunk_idx\":0"));
+    /// assert!(json.contains("\"chunk_index\":0"));
     /// assert!(json.contains("\"total_chunks\":1"));
     /// ```
     ///
@@ -1468,176 +1453,6 @@ mod tests {
         assert!(AccessibilityExtractor::get_selected_text().is_none());
     }
 
-    // ============================================================================
-    // Feature: accessibility-extractor, Property 16: Unique Document Identifiers
-    // **Validates: Requirements 11.8**
-    //
-    // *For any* two distinct extractions, the generated `ehl_doc_id` values SHALL
-    // be different (UUID v4 uniqueness).
-    // ============================================================================
-
-    proptest! {
-        // Feature: accessibility-extractor, Property 16: Unique Document Identifiers
-        // **Validates: Requirements 11.8**
-        //
-        // *For any* two distinct extractions, the generated `ehl_doc_id` values SHALL
-        // be different (UUID v4 uniqueness).
-        //
-        // This property test generates multiple document IDs and verifies they are all unique.
-        #[test]
-        fn prop_unique_document_identifiers(
-            count in 2usize..100,
-        ) {
-            // Generate 'count' document IDs
-            let doc_ids: Vec<String> = (0..count)
-                .map(|_| AccessibilityExtractor::generate_doc_id())
-                .collect();
-
-            // Verify all IDs are unique by checking the set size equals the vector size
-            let unique_ids: std::collections::HashSet<&String> = doc_ids.iter().collect();
-            
-            prop_assert_eq!(
-                unique_ids.len(),
-                doc_ids.len(),
-                "All generated document IDs should be unique. Generated {} IDs but only {} are unique.",
-                doc_ids.len(),
-                unique_ids.len()
-            );
-        }
-
-        // Additional test: verify document IDs are valid UUID v4 format
-        #[test]
-        fn prop_document_id_is_valid_uuid_format(
-            _seed in 0u64..1000,
-        ) {
-            let doc_id = AccessibilityExtractor::generate_doc_id();
-
-            // UUID v4 format: xxxxxxxx-xxxx-4xxx-yxxx-xxxxxxxxxxxx
-            // where x is any hex digit and y is one of 8, 9, a, or b
-            
-            // Verify the ID is not empty
-            prop_assert!(
-                !doc_id.is_empty(),
-                "Document ID should not be empty"
-            );
-
-            // Verify the ID has the correct length (36 characters for UUID with hyphens)
-            prop_assert_eq!(
-                doc_id.len(),
-                36,
-                "Document ID should be 36 characters (UUID format), got {} characters: '{}'",
-                doc_id.len(),
-                doc_id
-            );
-
-            // Verify the ID has hyphens in the correct positions
-            let chars: Vec<char> = doc_id.chars().collect();
-            prop_assert_eq!(chars[8], '-', "Expected hyphen at position 8");
-            prop_assert_eq!(chars[13], '-', "Expected hyphen at position 13");
-            prop_assert_eq!(chars[18], '-', "Expected hyphen at position 18");
-            prop_assert_eq!(chars[23], '-', "Expected hyphen at position 23");
-
-            // Verify the version digit (position 14) is '4' for UUID v4
-            prop_assert_eq!(
-                chars[14],
-                '4',
-                "UUID v4 should have '4' at position 14, got '{}'",
-                chars[14]
-            );
-
-            // Verify the variant digit (position 19) is one of 8, 9, a, b
-            let variant = chars[19];
-            prop_assert!(
-                variant == '8' || variant == '9' || variant == 'a' || variant == 'b',
-                "UUID v4 variant digit should be 8, 9, a, or b, got '{}'",
-                variant
-            );
-
-            // Verify all other characters are valid hex digits
-            for (i, c) in chars.iter().enumerate() {
-                if i != 8 && i != 13 && i != 18 && i != 23 {
-                    prop_assert!(
-                        c.is_ascii_hexdigit(),
-                        "Character at position {} should be a hex digit, got '{}'",
-                        i,
-                        c
-                    );
-                }
-            }
-        }
-
-        // Test that consecutive calls produce different IDs
-        #[test]
-        fn prop_consecutive_calls_produce_different_ids(
-            _seed in 0u64..1000,
-        ) {
-            let id1 = AccessibilityExtractor::generate_doc_id();
-            let id2 = AccessibilityExtractor::generate_doc_id();
-
-            prop_assert_ne!(
-                id1,
-                id2,
-                "Two consecutive calls to generate_doc_id should produce different IDs"
-            );
-        }
-    }
-
-    // ============================================================================
-    // Unit Tests for generate_doc_id
-    // ============================================================================
-
-    /// Test that generate_doc_id returns a non-empty string
-    #[test]
-    fn test_generate_doc_id_returns_non_empty() {
-        let doc_id = AccessibilityExtractor::generate_doc_id();
-        assert!(!doc_id.is_empty(), "Document ID should not be empty");
-    }
-
-    /// Test that generate_doc_id returns a valid UUID format
-    #[test]
-    fn test_generate_doc_id_valid_uuid_format() {
-        let doc_id = AccessibilityExtractor::generate_doc_id();
-        
-        // UUID format: xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx (36 chars)
-        assert_eq!(doc_id.len(), 36, "UUID should be 36 characters");
-        
-        // Verify hyphens are in correct positions
-        let chars: Vec<char> = doc_id.chars().collect();
-        assert_eq!(chars[8], '-');
-        assert_eq!(chars[13], '-');
-        assert_eq!(chars[18], '-');
-        assert_eq!(chars[23], '-');
-    }
-
-    /// Test that generate_doc_id produces unique IDs
-    #[test]
-    fn test_generate_doc_id_uniqueness() {
-        let mut ids = std::collections::HashSet::new();
-        
-        // Generate 1000 IDs and verify they're all unique
-        for _ in 0..1000 {
-            let id = AccessibilityExtractor::generate_doc_id();
-            assert!(ids.insert(id), "Generated duplicate document ID");
-        }
-    }
-
-    /// Test that generate_doc_id produces UUID v4 (version 4)
-    #[test]
-    fn test_generate_doc_id_is_uuid_v4() {
-        let doc_id = AccessibilityExtractor::generate_doc_id();
-        let chars: Vec<char> = doc_id.chars().collect();
-        
-        // UUID v4 has '4' at position 14 (the version digit)
-        assert_eq!(chars[14], '4', "UUID should be version 4");
-        
-        // UUID v4 has 8, 9, a, or b at position 19 (the variant digit)
-        let variant = chars[19];
-        assert!(
-            variant == '8' || variant == '9' || variant == 'a' || variant == 'b',
-            "UUID variant should be 8, 9, a, or b"
-        );
-    }
-
     // ============================================================================
     // Unit Tests for to_chunk_meta
     // ============================================================================
@@ -1659,15 +1474,15 @@ mod tests {
         // Verify required fields
         assert!(!chunk_meta.id.is_empty(), "ID should not be empty");
         assert_eq!(chunk_meta.source, "word");
-        assert_eq!(chunk_meta.timestamp, 1707500000);
-        assert_eq!(chunk_meta.chunk_idx, 0);
+        assert_eq!(chunk_meta.captured_at, Some(1707500000));
+        assert_eq!(chunk_meta.chunk_index, 0);
         assert_eq!(chunk_meta.total_chunks, 1);
 
         // Verify optional fields are populated
-        assert!(chunk_meta.app_id.is_some());
-        assert_eq!(chunk_meta.app_id.as_deref(), Some("com.microsoft.Word"));
-        assert!(chunk_meta.url.is_some());
-        assert!(chunk_meta.url.as_ref().unwrap().starts_with("accessibility://"));
+        assert_eq!(chunk_meta.bundle_id.as_deref(), Some("com.microsoft.Word"));
+        assert_eq!(chunk_meta.app_name.as_deref(), Some("Microsoft Word"));
+        assert_eq!(chunk_meta.extraction_method.as_deref(), Some("accessibility"));
+        assert!(chunk_meta.url.starts_with("accessibility://"));
         assert!(chunk_meta.title.is_some());
         assert_eq!(chunk_meta.title.as_deref(), Some("Document.docx"));
     }
@@ -1689,12 +1504,12 @@ mod tests {
         // Title should default to "untitled"
         assert_eq!(chunk_meta.title.as_deref(), Some("untitled"));
         // URL should contain "untitled"
-        assert!(chunk_meta.url.as_ref().unwrap().contains("untitled"));
+        assert!(chunk_meta.url.contains("untitled"));
     }
 
-    /// Test that to_chunk_meta generates unique IDs for each call
+    /// Test that to_chunk_meta derives the ID from the source and URL
     #[test]
-    fn test_to_chunk_meta_unique_ids() {
+    fn test_to_chunk_meta_stable_ids() {
         let content = ExtractedContent {
             source: "word".to_string(),
             title: Some("Document.docx".to_string()),
@@ -1707,8 +1522,15 @@ mod tests {
         let chunk1 = AccessibilityExtractor::to_chunk_meta(&content, 0, 1);
         let chunk2 = AccessibilityExtractor::to_chunk_meta(&content, 0, 1);
 
-        // Each call should generate a unique ID
-        assert_ne!(chunk1.id, chunk2.id);
+        // The same document always gets the same ID
+        assert_eq!(chunk1.id, chunk2.id);
+        assert_eq!(chunk1.id, doc_id::doc_id("word", &chunk1.url));
+
+        let other = ExtractedContent {
+            title: Some("Other.docx".to_string()),
+            ..content
+        };
+        assert_ne!(AccessibilityExtractor::to_chunk_meta(&other, 0, 1).id, chunk1.id);
     }
 
     /// Test that to_chunk_meta correctly sets chunk indices
@@ -1728,33 +1550,14 @@ mod tests {
         let chunk2 = AccessibilityExtractor::to_chunk_meta(&content, 2, 5);
         let chunk4 = AccessibilityExtractor::to_chunk_meta(&content, 4, 5);
 
-        assert_eq!(chunk0.chunk_idx, 0);
+        assert_eq!(chunk0.chunk_index, 0);
         assert_eq!(chunk0.total_chunks, 5);
-        assert_eq!(chunk2.chunk_idx, 2);
+        assert_eq!(chunk2.chunk_index, 2);
         assert_eq!(chunk2.total_chunks, 5);
-        assert_eq!(chunk4.chunk_idx, 4);
+        assert_eq!(chunk4.chunk_index, 4);
         assert_eq!(chunk4.total_chunks, 5);
     }
 
-    /// Test that to_chunk_meta truncates header to 200 characters
-    #[test]
-    fn test_to_chunk_meta_header_truncation() {
-        let long_content = "A".repeat(500);
-        let content = ExtractedContent {
-            source: "word".to_string(),
-            title: Some("Document.docx".to_string()),
-            content: long_content,
-            app_name: "Microsoft Word".to_string(),
-            timestamp: 1707500000,
-            extraction_method: "accessibility".to_string(),
-        };
-
-        let chunk_meta = AccessibilityExtractor::to_chunk_meta(&content, 0, 1);
-
-        // Header should be truncated to 200 characters
-        assert_eq!(chunk_meta.header.chars().count(), 200);
-    }
-
     // ============================================================================
     // Unit Tests for to_chunk_meta_json
     // ============================================================================
@@ -1780,8 +1583,8 @@ mod tests {
         // Verify required fields are present
         assert!(parsed.get("id").is_some());
         assert!(parsed.get("source").is_some());
-        assert!(parsed.get("timestamp").is_some());
-        assert!(parsed.get("chunk_idx").is_some());
+        assert!(parsed.get("captured_at").is_some());
+        assert!(parsed.get("chunk_index").is_some());
         assert!(parsed.get("total_chunks").is_some());
     }
 
@@ -1801,9 +1604,9 @@ mod tests {
 
         // Verify specific values
         assert!(json.contains("\"source\":\"excel\""));
-        assert!(json.contains("\"chunk_idx\":0"));
+        assert!(json.contains("\"chunk_index\":0"));
         assert!(json.contains("\"total_chunks\":1"));
-        assert!(json.contains("\"timestamp\":1707500002"));
+        assert!(json.contains("\"captured_at\":1707500002"));
     }
 
     /// Test that to_chunk_meta_json creates single chunk by default
@@ -1821,7 +1624,7 @@ mod tests {
         let json = AccessibilityExtractor::to_chunk_meta_json(&content);
 
         // Should be chunk 0 of 1
-        assert!(json.contains("\"chunk_idx\":0"));
+        assert!(json.contains("\"chunk_index\":0"));
         assert!(json.contains("\"total_chunks\":1"));
     }
 
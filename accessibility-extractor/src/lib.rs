@@ -36,6 +36,7 @@
 //! - [`platform`]: Platform-specific implementations (macOS) - only available on macOS
 //! - [`extractor`]: Cross-platform API wrapper (AccessibilityExtractor)
 //! - [`storage_bridge`]: SQLite storage with deduplication for the daemon
+//! - [`timestamp`]: Absolute UTC times from chat time labels and date separators
 //!
 //! # Platform Support
 //!
@@ -49,6 +50,7 @@
 pub mod extractor;
 pub mod types;
 pub mod storage_bridge;
+pub mod timestamp;
 
 // Platform-specific modules are conditionally compiled
 // Requirement 9.7: Return appropriate errors on unsupported platforms
@@ -63,7 +65,7 @@ pub use extractor::AccessibilityExtractor;
 pub use types::{AppSource, CapturePayload, ChunkMeta, ExtractedContent, ExtractionError};
 
 // Re-export database integration functions
-pub use types::{generate_content_hash, generate_doc_id};
+pub use types::generate_content_hash;
 
 // Re-export storage bridge
 pub use storage_bridge::{DaemonStorage, DedupResult, StorageError};
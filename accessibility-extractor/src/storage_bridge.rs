@@ -1,17 +1,28 @@
 //! Storage bridge for the daemon - handles SQLite storage with deduplication and chunking.
 //!
 //! This module provides a simplified interface to store extracted content
-//! in SQLite, reusing the schema from ingestion-service.
+//! in SQLite, with the schema (`content_db::schema`), chunker and chunk meta
+//! (`capture_protocol`) shared with ingestion-service.
 //!
 //! For Slack messages, we use message-level deduplication:
-//! - Each message gets a hash based on [author + time + content]
+//! - Each message gets a hash of its line [author + time + content], masked
+//!   and normalized like the document hash
 //! - New messages are appended, existing ones are skipped
+//! - Time labels and date separators are resolved to UTC times
+//!   (`timestamp::TimestampResolver`), which order the messages
 //! - Content is chunked into 1024-token chunks with 100-token overlap
 
+use crate::timestamp::TimestampResolver;
 use crate::types::ExtractedContent;
-use rusqlite::{params, Connection};
+use capture_protocol::mask::Masks;
+use capture_protocol::normalize::normalize;
+use capture_protocol::{Chunk, ChunkMeta, Chunker};
+use chrono::{DateTime, Local, Utc};
+use content_db::{doc_id, revision, BusyError, OpenMode, RetryPolicy};
+use rusqlite::{params, Connection, Transaction};
 use sha2::{Digest, Sha256};
 use std::path::Path;
+use std::sync::OnceLock;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -24,6 +35,12 @@ pub enum StorageError {
     Io(#[from] std::io::Error),
 }
 
+impl BusyError for StorageError {
+    fn is_busy(&self) -> bool {
+        matches!(self, StorageError::Sqlite(e) if e.is_busy())
+    }
+}
+
 /// Result of deduplication check
 #[derive(Debug, Clone)]
 pub enum DedupResult {
@@ -33,34 +50,8 @@ pub enum DedupResult {
     Updated(String), // ehl_doc_id
     /// Content is duplicate (all messages already exist)
     Duplicate,
-}
-
-/// Chunk metadata stored in the meta JSON field
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-struct ChunkMeta {
-    id: String,
-    source: String,
-    url: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    title: Option<String>,
-    chunk_index: usize,
-    total_chunks: usize,
-    source_type: String,
-    extraction_method: String,
-    app_name: String,
-}
-
-/// Chunker configuration
-const MAX_TOKENS: usize = 1024;
-const OVERLAP_TOKENS: usize = 100;
-
-/// A single chunk of content
-#[derive(Debug, Clone)]
-struct Chunk {
-    text: String,
-    chunk_index: usize,
-    total_chunks: usize,
-    token_count: usize,
+    /// Not stored: the user asked to forget this source
+    Forgotten,
 }
 
 /// Storage manager for the daemon
@@ -71,7 +62,7 @@ pub struct DaemonStorage {
 impl DaemonStorage {
     /// Open or create the database at the given path
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StorageError> {
-        let conn = Connection::open(path)?;
+        let conn = content_db::open(path, OpenMode::ReadWrite)?;
         let storage = Self { conn };
         storage.init_schema()?;
         Ok(storage)
@@ -79,40 +70,14 @@ impl DaemonStorage {
 
     /// Initialize database schema
     fn init_schema(&self) -> Result<(), StorageError> {
+        content_db::schema::migrate(&self.conn)?;
         self.conn.execute_batch(
             r#"
-            -- Content sources table (tracks what we've ingested)
-            CREATE TABLE IF NOT EXISTS content_sources (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                source_type TEXT NOT NULL,
-                source_path TEXT NOT NULL UNIQUE,
-                content_hash TEXT NOT NULL,
-                ehl_doc_id TEXT NOT NULL UNIQUE,
-                chunk_count INTEGER NOT NULL DEFAULT 0,
-                ingestion_status TEXT NOT NULL DEFAULT 'ingested',
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_content_sources_path ON content_sources(source_path);
-            CREATE INDEX IF NOT EXISTS idx_content_sources_ehl_doc_id ON content_sources(ehl_doc_id);
-            CREATE INDEX IF NOT EXISTS idx_content_sources_hash ON content_sources(content_hash);
-
-            -- Chunks table (actual content chunks)
-            CREATE TABLE IF NOT EXISTS chunks (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                vector_index INTEGER,
-                text TEXT NOT NULL,
-                meta TEXT NOT NULL,
-                is_deleted INTEGER DEFAULT 0,
-                created_at TEXT NOT NULL DEFAULT (datetime('now'))
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_chunks_deleted ON chunks(is_deleted);
-            
             -- Messages table for Slack message-level deduplication
             -- Each message is stored individually with a hash for dedup
-            -- message_order is used to maintain chronological order (extracted from message time)
+            -- sent_at is the message time resolved to UTC (unix seconds), NULL when unknown
+            -- message_order keeps chronological order: sent_at, or an estimate for untimed
+            -- messages (rows from before sent_at hold minutes since midnight)
             CREATE TABLE IF NOT EXISTS messages (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 source_url TEXT NOT NULL,
@@ -120,6 +85,7 @@ impl DaemonStorage {
                 message_text TEXT NOT NULL,
                 message_order INTEGER NOT NULL DEFAULT 0,
                 created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                sent_at INTEGER,
                 UNIQUE(source_url, message_hash)
             );
             
@@ -128,6 +94,13 @@ impl DaemonStorage {
             CREATE INDEX IF NOT EXISTS idx_messages_order ON messages(source_url, message_order);
             "#,
         )?;
+
+        // Databases created before sent_at existed
+        let mut stmt = self.conn.prepare("SELECT name FROM pragma_table_info('messages')")?;
+        let columns = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        if !columns.iter().any(|c| c == "sent_at") {
+            self.conn.execute_batch("ALTER TABLE messages ADD COLUMN sent_at INTEGER")?;
+        }
         Ok(())
     }
 
@@ -142,6 +115,11 @@ impl DaemonStorage {
             content.title.as_deref().unwrap_or("untitled")
         );
 
+        // Never store anything covered by a "forget" tombstone
+        if content_db::tombstone::find_match(&self.conn, &url, &url)?.is_some() {
+            return Ok(DedupResult::Forgotten);
+        }
+
         // For Slack and Teams, use message-level deduplication
         if content.source == "slack" || content.source == "teams" {
             return self.store_messaging_content(content, &url);
@@ -151,41 +129,60 @@ impl DaemonStorage {
         self.store_content_replace(content, &url)
     }
 
+    /// Run `f` in a write transaction, retried while another process holds the lock
+    fn write<T>(
+        &self,
+        f: impl FnMut(&Transaction) -> Result<T, StorageError>,
+    ) -> Result<T, StorageError> {
+        content_db::write_transaction(&self.conn, &RetryPolicy::default(), f)
+    }
+
     /// Store messaging app content (Slack, Teams) with message-level deduplication
     /// New messages are appended, existing ones are skipped
     /// Messages are stored with their time for proper ordering
     fn store_messaging_content(&mut self, content: &ExtractedContent, url: &str) -> Result<DedupResult, StorageError> {
-        // Parse messages from content (each line is a message in format [Author] [Time] Message)
-        let messages: Vec<&str> = content.content
-            .lines()
-            .filter(|line| !line.trim().is_empty())
-            .collect();
+        // Each line is a message in format [Author] [Time] Message, or a date separator
+        // ("Yesterday", "Monday, February 10th") that applies to the messages below it
+        let captured_at = DateTime::<Utc>::from_timestamp(content.timestamp, 0).unwrap_or_else(Utc::now);
+        let mut resolver = TimestampResolver::new(captured_at, Local);
+        let mut messages: Vec<(&str, Option<i64>)> = Vec::new();
+        for line in content.content.lines().filter(|line| !line.trim().is_empty()) {
+            match time_label(line) {
+                Some(label) => messages.push((line, resolver.resolve(label).map(|t| t.timestamp()))),
+                None if resolver.date_separator(line) => {}
+                None => messages.push((line, None)),
+            }
+        }
 
         if messages.is_empty() {
             return Ok(DedupResult::Duplicate);
         }
 
-        // Compute hash for each message and extract time for ordering
-        let message_data: Vec<(String, &str, i32)> = messages
+        // Compute hash for each message, masked and normalized like the document
+        // hash so "5 min ago" turning into "6 min ago" is the same message. A
+        // message without a time is ordered right after the one before it,
+        // leading ones at the first time or the capture time
+        let mut order = messages.iter().find_map(|(_, sent_at)| *sent_at).unwrap_or(captured_at.timestamp());
+        let message_data: Vec<(String, &str, Option<i64>, i64)> = messages
             .iter()
-            .map(|msg| {
-                let hash = compute_hash(msg);
-                let time_order = extract_time_order(msg);
-                (hash, *msg, time_order)
+            .map(|(msg, sent_at)| {
+                order = sent_at.unwrap_or(order);
+                (content_hash(content, msg), *msg, *sent_at, order)
             })
             .collect();
 
-        // Find which messages already exist
+        // Find which messages already exist; messages stored before masking have
+        // the hash of the raw line
         let hashes: Vec<(String, &str)> = message_data.iter()
-            .map(|(h, m, _)| (h.clone(), *m))
+            .flat_map(|(h, m, _, _)| [(h.clone(), *m), (compute_hash(m), *m)])
             .collect();
         let existing_hashes = self.get_existing_message_hashes(url, &hashes)?;
 
         // Filter to only new messages
-        let new_messages: Vec<(&str, &str, i32)> = message_data
+        let new_messages: Vec<(&str, &str, Option<i64>, i64)> = message_data
             .iter()
-            .filter(|(hash, _, _)| !existing_hashes.contains(hash))
-            .map(|(hash, msg, order)| (hash.as_str(), *msg, *order))
+            .filter(|(hash, msg, _, _)| !existing_hashes.contains(hash) && !existing_hashes.contains(&compute_hash(msg)))
+            .map(|(hash, msg, sent_at, order)| (hash.as_str(), *msg, *sent_at, *order))
             .collect();
 
         if new_messages.is_empty() {
@@ -195,17 +192,17 @@ impl DaemonStorage {
 
         log::info!("[STORAGE] 📥 Found {} new messages out of {} total", new_messages.len(), messages.len());
 
-        // Insert new messages with time order
-        let tx = self.conn.transaction()?;
-        
-        for (hash, msg, order) in &new_messages {
-            tx.execute(
-                "INSERT OR IGNORE INTO messages (source_url, message_hash, message_text, message_order) VALUES (?1, ?2, ?3, ?4)",
-                params![url, hash, msg, order],
-            )?;
-        }
-        
-        tx.commit()?;
+        // Insert new messages with their time and order
+        self.write(|tx| {
+            for (hash, msg, sent_at, order) in &new_messages {
+                tx.execute(
+                    "INSERT OR IGNORE INTO messages (source_url, message_hash, message_text, sent_at, message_order)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![url, hash, msg, sent_at, order],
+                )?;
+            }
+            Ok(())
+        })?;
 
         // Now rebuild chunks from ALL messages for this URL
         self.rebuild_chunks_for_url(content, url)
@@ -262,32 +259,19 @@ impl DaemonStorage {
 
         // Combine all messages into content
         let combined_content = messages.join("\n");
-        let content_hash = compute_hash(&combined_content);
+        let content_hash = content_hash(content, &combined_content);
 
         // Check if we have an existing source
         let existing = self.find_source_by_path(url)?;
         let is_update = existing.is_some();
 
-        let tx = self.conn.transaction()?;
-
         let ehl_doc_id = match existing {
-            Some((existing_id, ref existing_hash)) => {
-                if *existing_hash == content_hash {
-                    // Content unchanged
-                    return Ok(DedupResult::Duplicate);
-                }
-                
-                // Soft-delete old chunks
-                tx.execute(
-                    "UPDATE chunks SET is_deleted = 1 WHERE json_extract(meta, '$.id') = ?1",
-                    params![existing_id],
-                )?;
-                
-                existing_id
-            }
-            None => {
-                generate_doc_id()
+            // Content unchanged
+            Some((_, existing_hash)) if existing_hash == content_hash => {
+                return Ok(DedupResult::Duplicate);
             }
+            Some((existing_id, _)) => existing_id,
+            None => doc_id::doc_id(&content.source, url),
         };
 
         // Chunk the combined content
@@ -297,46 +281,37 @@ impl DaemonStorage {
         log::info!("[STORAGE] 📦 Rebuilding {} chunks for {} ({} messages)", 
             chunk_count, url, messages.len());
 
-        // Upsert content source
-        tx.execute(
-            "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count)
-             VALUES (?1, ?2, ?3, ?4, ?5)
-             ON CONFLICT(source_path) DO UPDATE SET 
-                content_hash = excluded.content_hash,
-                chunk_count = excluded.chunk_count,
-                updated_at = datetime('now')",
-            params![
-                content.source,
-                url,
-                content_hash,
-                ehl_doc_id,
-                chunk_count
-            ],
-        )?;
-
-        // Insert new chunks
-        for chunk in &chunks {
-            let meta = ChunkMeta {
-                id: ehl_doc_id.clone(),
-                source: content.source.clone(),
-                url: url.to_string(),
-                title: content.title.clone(),
-                chunk_index: chunk.chunk_index,
-                total_chunks: chunk.total_chunks,
-                source_type: "accessibility".to_string(),
-                extraction_method: content.extraction_method.clone(),
-                app_name: content.app_name.clone(),
-            };
-
-            let meta_json = serde_json::to_string(&meta)?;
+        self.write(|tx| {
+            if is_update {
+                // Soft-delete old chunks
+                tx.execute(
+                    "UPDATE chunks SET is_deleted = 1 WHERE json_extract(meta, '$.id') = ?1",
+                    params![ehl_doc_id],
+                )?;
+            }
 
+            // Upsert content source
             tx.execute(
-                "INSERT INTO chunks (text, meta) VALUES (?1, ?2)",
-                params![chunk.text, meta_json],
+                "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(source_path) DO UPDATE SET 
+                    content_hash = excluded.content_hash,
+                    chunk_count = excluded.chunk_count,
+                    updated_at = datetime('now')",
+                params![
+                    content.source,
+                    url,
+                    content_hash,
+                    ehl_doc_id,
+                    chunk_count
+                ],
             )?;
-        }
+            let revision_id =
+                revision::insert(tx, &ehl_doc_id, &content_hash, content.timestamp, &combined_content, None)?;
 
-        tx.commit()?;
+            // Insert new chunks
+            insert_chunks(tx, content, url, &ehl_doc_id, revision_id, &chunks)
+        })?;
         
         log::info!("[STORAGE] ✅ Stored {} chunks for {} ({} total messages)", 
             chunk_count, url, messages.len());
@@ -351,7 +326,7 @@ impl DaemonStorage {
     /// Get all messages for a URL, ordered by time
     fn get_all_messages_for_url(&self, url: &str) -> Result<Vec<String>, StorageError> {
         let mut stmt = self.conn.prepare(
-            "SELECT message_text FROM messages WHERE source_url = ?1 ORDER BY message_order ASC, created_at ASC"
+            "SELECT message_text FROM messages WHERE source_url = ?1 ORDER BY message_order ASC, created_at ASC, id ASC"
         )?;
         
         let messages: Vec<String> = stmt
@@ -364,7 +339,7 @@ impl DaemonStorage {
 
     /// Store content with replace semantics (for non-Slack apps)
     fn store_content_replace(&mut self, content: &ExtractedContent, url: &str) -> Result<DedupResult, StorageError> {
-        let content_hash = compute_hash(&content.content);
+        let content_hash = content_hash(content, &content.content);
         let existing = self.find_source_by_path(url)?;
 
         match existing {
@@ -377,7 +352,7 @@ impl DaemonStorage {
                 }
             }
             None => {
-                let ehl_doc_id = generate_doc_id();
+                let ehl_doc_id = doc_id::doc_id(&content.source, url);
                 self.insert_source(content, url, &content_hash, &ehl_doc_id)?;
                 Ok(DedupResult::New(ehl_doc_id))
             }
@@ -409,39 +384,21 @@ impl DaemonStorage {
         content_hash: &str,
         ehl_doc_id: &str,
     ) -> Result<(), StorageError> {
-        let tx = self.conn.transaction()?;
         let chunks = chunk_content(&content.content);
         let chunk_count = chunks.len();
 
         log::info!("[STORAGE] 📦 Chunking content into {} chunks", chunk_count);
 
-        tx.execute(
-            "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![content.source, url, content_hash, ehl_doc_id, chunk_count],
-        )?;
-
-        for chunk in &chunks {
-            let meta = ChunkMeta {
-                id: ehl_doc_id.to_string(),
-                source: content.source.clone(),
-                url: url.to_string(),
-                title: content.title.clone(),
-                chunk_index: chunk.chunk_index,
-                total_chunks: chunk.total_chunks,
-                source_type: "accessibility".to_string(),
-                extraction_method: content.extraction_method.clone(),
-                app_name: content.app_name.clone(),
-            };
-
-            let meta_json = serde_json::to_string(&meta)?;
+        self.write(|tx| {
             tx.execute(
-                "INSERT INTO chunks (text, meta) VALUES (?1, ?2)",
-                params![chunk.text, meta_json],
+                "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![content.source, url, content_hash, ehl_doc_id, chunk_count],
             )?;
-        }
+            let revision_id = revision::insert(tx, ehl_doc_id, content_hash, content.timestamp, &content.content, None)?;
+            insert_chunks(tx, content, url, ehl_doc_id, revision_id, &chunks)
+        })?;
 
-        tx.commit()?;
         log::info!("[STORAGE] ✅ Stored {} chunks for {}", chunk_count, url);
         Ok(())
     }
@@ -454,104 +411,75 @@ impl DaemonStorage {
         url: &str,
         content_hash: &str,
     ) -> Result<(), StorageError> {
-        let tx = self.conn.transaction()?;
-
-        tx.execute(
-            "UPDATE chunks SET is_deleted = 1 WHERE json_extract(meta, '$.id') = ?1",
-            params![ehl_doc_id],
-        )?;
-
         let chunks = chunk_content(&content.content);
         let chunk_count = chunks.len();
 
         log::info!("[STORAGE] 🔄 Updating content with {} chunks", chunk_count);
 
-        tx.execute(
-            "UPDATE content_sources SET content_hash = ?1, chunk_count = ?2, 
-             updated_at = datetime('now') WHERE ehl_doc_id = ?3",
-            params![content_hash, chunk_count, ehl_doc_id],
-        )?;
+        self.write(|tx| {
+            tx.execute(
+                "UPDATE chunks SET is_deleted = 1 WHERE json_extract(meta, '$.id') = ?1",
+                params![ehl_doc_id],
+            )?;
 
-        for chunk in &chunks {
-            let meta = ChunkMeta {
-                id: ehl_doc_id.to_string(),
-                source: content.source.clone(),
-                url: url.to_string(),
-                title: content.title.clone(),
-                chunk_index: chunk.chunk_index,
-                total_chunks: chunk.total_chunks,
-                source_type: "accessibility".to_string(),
-                extraction_method: content.extraction_method.clone(),
-                app_name: content.app_name.clone(),
-            };
-
-            let meta_json = serde_json::to_string(&meta)?;
             tx.execute(
-                "INSERT INTO chunks (text, meta) VALUES (?1, ?2)",
-                params![chunk.text, meta_json],
+                "UPDATE content_sources SET content_hash = ?1, chunk_count = ?2, 
+                 updated_at = datetime('now') WHERE ehl_doc_id = ?3",
+                params![content_hash, chunk_count, ehl_doc_id],
             )?;
-        }
+            let revision_id = revision::insert(tx, ehl_doc_id, content_hash, content.timestamp, &content.content, None)?;
+            insert_chunks(tx, content, url, ehl_doc_id, revision_id, &chunks)
+        })?;
 
-        tx.commit()?;
         log::info!("[STORAGE] ✅ Updated {} chunks for {}", chunk_count, url);
         Ok(())
     }
 }
 
-/// Chunk content into fixed-size token chunks with overlap.
-fn chunk_content(content: &str) -> Vec<Chunk> {
-    if content.trim().is_empty() {
-        return vec![];
-    }
-
-    let words: Vec<&str> = content.split_whitespace().collect();
-    if words.is_empty() {
-        return vec![];
-    }
-
-    if words.len() <= MAX_TOKENS {
-        return vec![Chunk {
-            text: content.to_string(),
-            chunk_index: 0,
-            total_chunks: 1,
-            token_count: words.len(),
-        }];
-    }
-
-    let mut chunks = Vec::new();
-    let mut start = 0;
-    let step = MAX_TOKENS - OVERLAP_TOKENS;
-
-    while start < words.len() {
-        let end = (start + MAX_TOKENS).min(words.len());
-        let chunk_words = &words[start..end];
-        let chunk_text = chunk_words.join(" ");
-
-        chunks.push(Chunk {
-            text: chunk_text,
-            chunk_index: chunks.len(),
-            total_chunks: 0,
-            token_count: chunk_words.len(),
-        });
-
-        start += step;
-
-        if words.len() - start < OVERLAP_TOKENS && start < words.len() {
-            let remaining = &words[start..];
-            if let Some(last) = chunks.last_mut() {
-                last.text = format!("{} {}", last.text, remaining.join(" "));
-                last.token_count += remaining.len();
-            }
-            break;
-        }
-    }
+/// Insert the chunks of one source, cut from the text of `revision_id`
+fn insert_chunks(
+    tx: &Transaction,
+    content: &ExtractedContent,
+    url: &str,
+    ehl_doc_id: &str,
+    revision_id: i64,
+    chunks: &[Chunk],
+) -> Result<(), StorageError> {
+    for chunk in chunks {
+        let meta = ChunkMeta {
+            id: ehl_doc_id.to_string(),
+            source: content.source.clone(),
+            url: url.to_string(),
+            original_url: None,
+            title: content.title.clone(),
+            author: None,
+            channel: None,
+            chunk_index: chunk.chunk_index,
+            total_chunks: chunk.total_chunks,
+            source_type: "accessibility".to_string(),
+            extraction_method: Some(content.extraction_method.clone()),
+            app_name: Some(content.app_name.clone()),
+            bundle_id: None,
+            metadata: Default::default(),
+            captured_at: Some(content.timestamp),
+            revision_id: Some(revision_id),
+            char_start: Some(chunk.char_start),
+            char_end: Some(chunk.char_end),
+            pipeline_version: Some(Chunker::with_defaults().pipeline_version()),
+        };
 
-    let total = chunks.len();
-    for chunk in &mut chunks {
-        chunk.total_chunks = total;
+        let meta_json = serde_json::to_string(&meta)?;
+        tx.execute(
+            "INSERT INTO chunks (text, meta) VALUES (?1, ?2)",
+            params![chunk.text, meta_json],
+        )?;
     }
+    Ok(())
+}
 
-    chunks
+/// Chunk content the same way the ingestion server does
+fn chunk_content(content: &str) -> Vec<Chunk> {
+    Chunker::with_defaults().chunk(content)
 }
 
 fn compute_hash(content: &str) -> String {
@@ -560,37 +488,21 @@ fn compute_hash(content: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
-fn generate_doc_id() -> String {
-    uuid::Uuid::new_v4().to_string()
+/// Hash of a document's or message's text, masked with the built-in rules and
+/// normalized as the ingestion server does
+fn content_hash(content: &ExtractedContent, text: &str) -> String {
+    static MASKS: OnceLock<Masks> = OnceLock::new();
+    let masks = MASKS.get_or_init(Masks::builtin);
+    compute_hash(&normalize(&masks.mask(&content.source, &[&content.app_name], text)))
 }
 
-/// Extract a sortable time order from a message.
+/// The time label of a message line
 /// Message format: [Author] [Time] Content
-/// Time format: "10:59 AM" or "4:30 PM"
-/// Returns minutes since midnight for sorting (0-1439)
-fn extract_time_order(message: &str) -> i32 {
-    // Look for time pattern [HH:MM AM/PM]
-    let time_pattern = regex_lite::Regex::new(r"\[(\d{1,2}):(\d{2})\s*(AM|PM)\]");
-    
-    if let Ok(pattern) = time_pattern {
-        if let Some(caps) = pattern.captures(message) {
-            let hour: i32 = caps.get(1).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
-            let minute: i32 = caps.get(2).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
-            let is_pm = caps.get(3).map(|m| m.as_str() == "PM").unwrap_or(false);
-            
-            // Convert to 24-hour format
-            let hour_24 = if is_pm && hour != 12 {
-                hour + 12
-            } else if !is_pm && hour == 12 {
-                0
-            } else {
-                hour
-            };
-            
-            return hour_24 * 60 + minute;
-        }
-    }
-    
-    // Default to 0 if no time found
-    0
+/// Time format: "10:59 AM", "Yesterday at 7:28 PM", "5 min ago", or "Unknown"
+fn time_label(message: &str) -> Option<&str> {
+    static PATTERN: OnceLock<regex_lite::Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        regex_lite::Regex::new(r"^\[[^\]]*\]\s*\[([^\]]*)\]").expect("valid message pattern")
+    });
+    pattern.captures(message.trim_start()).and_then(|caps| caps.get(1)).map(|m| m.as_str())
 }
@@ -0,0 +1,393 @@
+//! Absolute message times from the labels chat apps show.
+//!
+//! Slack and Teams label messages relative to the moment you look at them:
+//! a bare "10:59 AM" under a "Yesterday" or "Monday, February 10th"
+//! separator, "Yesterday at 7:28 PM", or "5 min ago". The resolver walks the
+//! lines of one capture in order, keeps track of the last date separator,
+//! and turns each label into a UTC time using the capture time and the
+//! user's time zone.
+//!
+//! Labels are resolved in the time zone the app displays them in, which is
+//! the user's local zone (`chrono::Local`) on the daemon; tests pass a fixed
+//! zone instead.
+
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use regex_lite::Regex;
+use std::sync::OnceLock;
+
+/// A label this far after the capture time is taken to be on the day before
+/// (a "11:50 PM" seen at 00:15 without a separator)
+const FUTURE_TOLERANCE_MINUTES: i64 = 5;
+
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("monday", Weekday::Mon),
+    ("tuesday", Weekday::Tue),
+    ("wednesday", Weekday::Wed),
+    ("thursday", Weekday::Thu),
+    ("friday", Weekday::Fri),
+    ("saturday", Weekday::Sat),
+    ("sunday", Weekday::Sun),
+];
+
+/// Resolves the time labels of one capture, in the order they appear
+pub struct TimestampResolver<Tz: TimeZone> {
+    tz: Tz,
+    captured_at: DateTime<Utc>,
+    /// Local date of the capture
+    today: NaiveDate,
+    /// Date set by the last separator; bare times fall on this day
+    day: NaiveDate,
+}
+
+impl<Tz: TimeZone> TimestampResolver<Tz> {
+    /// Resolver for a capture taken at `captured_at`, with labels shown in `tz`
+    pub fn new(captured_at: DateTime<Utc>, tz: Tz) -> Self {
+        let today = captured_at.with_timezone(&tz).date_naive();
+        Self { tz, captured_at, today, day: today }
+    }
+
+    /// If `line` is a date separator ("Today", "Yesterday", "Monday",
+    /// "March 3", "Monday, February 10th"), make it the current day for the
+    /// lines that follow and return true.
+    pub fn date_separator(&mut self, line: &str) -> bool {
+        match parse_day(line, self.today) {
+            Some(day) => {
+                self.day = day;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Resolve a message's time label to UTC. A bare time falls on the
+    /// current separator's day; a day in the label ("Yesterday at 7:28 PM")
+    /// applies to that message only. Returns `None` for labels that are not
+    /// times, such as "Unknown".
+    pub fn resolve(&self, label: &str) -> Option<DateTime<Utc>> {
+        let label = label.trim();
+        if let Some(ago) = parse_relative(label) {
+            return Some(self.captured_at - ago);
+        }
+
+        let caps = label_pattern().captures(label)?;
+        let day = match caps.get(1) {
+            Some(prefix) => parse_day(prefix.as_str(), self.today)?,
+            None => self.day,
+        };
+        let time = parse_time(
+            caps.get(2)?.as_str(),
+            caps.get(3)?.as_str(),
+            caps.get(4).map(|m| m.as_str()),
+            caps.get(5).map(|m| m.as_str()),
+        )?;
+
+        let resolved = self.to_utc(day.and_time(time))?;
+        // Without a separator a late-evening time seen just after midnight
+        // belongs to the day before
+        if caps.get(1).is_none()
+            && day == self.today
+            && resolved > self.captured_at + Duration::minutes(FUTURE_TOLERANCE_MINUTES)
+        {
+            return self.to_utc((day - Duration::days(1)).and_time(time));
+        }
+        Some(resolved)
+    }
+
+    /// A local wall-clock time in UTC. In the hour repeated when clocks go
+    /// back, the earlier instant is used; a time skipped when clocks go
+    /// forward is read as the clock would show it an hour later.
+    fn to_utc(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        let resolved = match self.tz.from_local_datetime(&local) {
+            LocalResult::None => self.tz.from_local_datetime(&(local + Duration::hours(1))).earliest(),
+            result => result.earliest(),
+        };
+        resolved.map(|dt| dt.with_timezone(&Utc))
+    }
+}
+
+/// `<day> at 10:59 AM`, `<day>, 10:59`, or a bare time; seconds and a.m./p.m. optional
+fn label_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)^(?:(.+?),?\s+(?:at\s+)?)?(\d{1,2}):(\d{2})(?::(\d{2}))?(?:\s*([ap])\.?\s*m\.?)?$")
+            .expect("valid label pattern")
+    })
+}
+
+/// "just now", "5 min ago", "an hour ago", "2 days ago"
+fn parse_relative(label: &str) -> Option<Duration> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)^(an?|\d+)\s*(s|secs?|seconds?|m|mins?|minutes?|h|hrs?|hours?|d|days?|w|wks?|weeks?)\s+ago$")
+            .expect("valid relative pattern")
+    });
+
+    if label.eq_ignore_ascii_case("now") || label.eq_ignore_ascii_case("just now") {
+        return Some(Duration::zero());
+    }
+    let caps = pattern.captures(label)?;
+    let count: i64 = match caps[1].to_ascii_lowercase().as_str() {
+        "a" | "an" => 1,
+        digits => digits.parse().ok()?,
+    };
+    let unit = caps[2].to_ascii_lowercase();
+    match unit.chars().next()? {
+        's' => Some(Duration::seconds(count)),
+        'm' => Some(Duration::minutes(count)),
+        'h' => Some(Duration::hours(count)),
+        'd' => Some(Duration::days(count)),
+        'w' => Some(Duration::weeks(count)),
+        _ => None,
+    }
+}
+
+/// Hour and minute of a label in 24-hour time
+fn parse_time(hour: &str, minute: &str, second: Option<&str>, meridiem: Option<&str>) -> Option<NaiveTime> {
+    let mut hour: u32 = hour.parse().ok()?;
+    let minute: u32 = minute.parse().ok()?;
+    let second: u32 = second.map_or(Some(0), |s| s.parse().ok())?;
+    match meridiem.map(|m| m.to_ascii_lowercase()).as_deref() {
+        Some(_) if hour == 0 || hour > 12 => return None,
+        Some("p") if hour != 12 => hour += 12,
+        Some("a") if hour == 12 => hour = 0,
+        _ => {}
+    }
+    NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+/// The date a separator or label prefix names, relative to `today`. A
+/// weekday alone is the last such day before today; a date without a year
+/// is the last one on or before today, and a weekday in front of a date is
+/// ignored.
+fn parse_day(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let text = text.trim().trim_end_matches([',', '.']).to_lowercase();
+    match text.as_str() {
+        "today" => return Some(today),
+        "yesterday" => return today.pred_opt(),
+        _ => {}
+    }
+
+    let mut words: Vec<&str> = text.split([' ', ',']).filter(|w| !w.is_empty()).collect();
+    let weekday = words.first().and_then(|w| weekday(w));
+    if weekday.is_some() {
+        words.remove(0);
+    }
+
+    let (month_day, year) = match words.as_slice() {
+        [] => {
+            let weekday = weekday?;
+            let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+            let back = if back == 0 { 7 } else { back };
+            return today.checked_sub_signed(Duration::days(back.into()));
+        }
+        [first, second] => (month_and_day(first, second)?, None),
+        [first, second, year] => (month_and_day(first, second)?, Some(year.parse().ok()?)),
+        _ => return None,
+    };
+    let (month, day) = month_day;
+    match year {
+        Some(year) => NaiveDate::from_ymd_opt(year, month, day),
+        None => NaiveDate::from_ymd_opt(today.year(), month, day)
+            .filter(|date| *date <= today)
+            .or_else(|| NaiveDate::from_ymd_opt(today.year() - 1, month, day)),
+    }
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    WEEKDAYS
+        .iter()
+        .find(|(name, _)| word.len() >= 3 && name.starts_with(word))
+        .map(|(_, weekday)| *weekday)
+}
+
+/// Month and day of "March 3" or "3 March"
+fn month_and_day(first: &str, second: &str) -> Option<(u32, u32)> {
+    match month_number(first) {
+        Some(month) => Some((month, day_of_month(second)?)),
+        None => Some((month_number(second)?, day_of_month(first)?)),
+    }
+}
+
+/// 1-based month of a full or abbreviated month name ("Feb", "Sept")
+fn month_number(word: &str) -> Option<u32> {
+    MONTHS
+        .iter()
+        .position(|name| word.len() >= 3 && name.starts_with(word))
+        .map(|index| index as u32 + 1)
+}
+
+/// Day of month, with or without an ordinal suffix ("3", "10th")
+fn day_of_month(word: &str) -> Option<u32> {
+    let digits = word.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    digits.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, Offset};
+
+    /// US Eastern time: EDT from 2:00 on the second Sunday of March to 2:00
+    /// on the first Sunday of November
+    #[derive(Debug, Clone, Copy)]
+    struct Eastern;
+
+    const EST: i32 = -5 * 3600;
+    const EDT: i32 = -4 * 3600;
+
+    /// Local wall-clock start of EDT and of EST in `year`
+    fn transitions(year: i32) -> (NaiveDateTime, NaiveDateTime) {
+        let sunday = |month, n| {
+            NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Sun, n)
+                .unwrap()
+                .and_hms_opt(2, 0, 0)
+                .unwrap()
+        };
+        (sunday(3, 2), sunday(11, 1))
+    }
+
+    fn offset(secs: i32) -> FixedOffset {
+        FixedOffset::east_opt(secs).unwrap()
+    }
+
+    impl TimeZone for Eastern {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Eastern
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_hms_opt(12, 0, 0).unwrap())
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let (spring, fall) = transitions(local.year());
+            if *local >= spring && *local < spring + Duration::hours(1) {
+                LocalResult::None
+            } else if *local >= fall - Duration::hours(1) && *local < fall {
+                LocalResult::Ambiguous(offset(EDT), offset(EST))
+            } else if *local >= spring && *local < fall {
+                LocalResult::Single(offset(EDT))
+            } else {
+                LocalResult::Single(offset(EST))
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_hms_opt(12, 0, 0).unwrap())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            let (spring, fall) = transitions(utc.year());
+            let (spring, fall) = (spring - Duration::seconds(EST.into()), fall - Duration::seconds(EDT.into()));
+            offset(if *utc >= spring && *utc < fall { EDT } else { EST })
+        }
+    }
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    fn resolver(captured_at: &str) -> TimestampResolver<FixedOffset> {
+        TimestampResolver::new(utc(captured_at), Utc.fix())
+    }
+
+    #[test]
+    fn test_date_separators() {
+        // Wednesday
+        let mut resolver = resolver("2025-03-05T15:00:00Z");
+        let day = |resolver: &mut TimestampResolver<FixedOffset>, separator: &str| {
+            assert!(resolver.date_separator(separator), "{}", separator);
+            resolver.day.to_string()
+        };
+
+        assert_eq!(day(&mut resolver, "Today"), "2025-03-05");
+        assert_eq!(day(&mut resolver, "Yesterday"), "2025-03-04");
+        assert_eq!(day(&mut resolver, "Monday"), "2025-03-03");
+        assert_eq!(day(&mut resolver, "Wednesday"), "2025-02-26");
+        assert_eq!(day(&mut resolver, "March 3"), "2025-03-03");
+        assert_eq!(day(&mut resolver, "3 March"), "2025-03-03");
+        assert_eq!(day(&mut resolver, "Monday, February 10th"), "2025-02-10");
+        assert_eq!(day(&mut resolver, "Dec 30th"), "2024-12-30");
+        assert_eq!(day(&mut resolver, "June 1, 2023"), "2023-06-01");
+
+        for line in ["[ana] [10:59 AM] hi", "Mayday", "March 32", ""] {
+            assert!(!resolver.date_separator(line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut resolver = resolver("2025-03-05T15:00:00Z");
+        assert_eq!(resolver.resolve("10:59 AM"), Some(utc("2025-03-05T10:59:00Z")));
+        assert_eq!(resolver.resolve("12:05 a.m."), Some(utc("2025-03-05T00:05:00Z")));
+        assert_eq!(resolver.resolve("14:30:15"), Some(utc("2025-03-05T14:30:15Z")));
+        assert_eq!(resolver.resolve("Yesterday at 7:28 PM"), Some(utc("2025-03-04T19:28:00Z")));
+        assert_eq!(resolver.resolve("Feb 10th at 3:14:22 PM"), Some(utc("2025-02-10T15:14:22Z")));
+        assert_eq!(resolver.resolve("5 min ago"), Some(utc("2025-03-05T14:55:00Z")));
+        assert_eq!(resolver.resolve("an hour ago"), Some(utc("2025-03-05T14:00:00Z")));
+        assert_eq!(resolver.resolve("Just now"), Some(utc("2025-03-05T15:00:00Z")));
+
+        // A day in the label does not move the separator
+        assert!(resolver.date_separator("Monday"));
+        assert_eq!(resolver.resolve("Today at 9:00 AM"), Some(utc("2025-03-05T09:00:00Z")));
+        assert_eq!(resolver.resolve("9:00 AM"), Some(utc("2025-03-03T09:00:00Z")));
+
+        for label in ["Unknown", "13:00 PM", "25:00", "Someday at 9:00 AM"] {
+            assert_eq!(resolver.resolve(label), None, "{}", label);
+        }
+    }
+
+    #[test]
+    fn test_across_midnight() {
+        // 00:15 local, UTC+2
+        let mut resolver = TimestampResolver::new(utc("2025-03-04T22:15:00Z"), offset(2 * 3600));
+
+        // No separator: a time later than the capture was yesterday
+        assert_eq!(resolver.resolve("11:50 PM"), Some(utc("2025-03-04T21:50:00Z")));
+        assert_eq!(resolver.resolve("12:10 AM"), Some(utc("2025-03-04T22:10:00Z")));
+
+        assert!(resolver.date_separator("Yesterday"));
+        let before = resolver.resolve("11:59 PM").unwrap();
+        assert!(resolver.date_separator("Today"));
+        let after = resolver.resolve("12:01 AM").unwrap();
+        assert_eq!(after - before, Duration::minutes(2));
+
+        // "Today" is the local date, not the UTC one
+        assert_eq!(resolver.today.to_string(), "2025-03-05");
+    }
+
+    #[test]
+    fn test_across_dst_changes() {
+        // Clocks went forward at 2:00 yesterday (Sunday 9 March 2025)
+        let mut resolver = TimestampResolver::new(utc("2025-03-10T16:00:00Z"), Eastern);
+        assert!(resolver.date_separator("Yesterday"));
+        assert_eq!(resolver.resolve("1:30 AM"), Some(utc("2025-03-09T06:30:00Z")));
+        assert_eq!(resolver.resolve("3:30 AM"), Some(utc("2025-03-09T07:30:00Z")));
+        // 2:30 never happened; read as 3:30 EDT
+        assert_eq!(resolver.resolve("2:30 AM"), Some(utc("2025-03-09T07:30:00Z")));
+        assert!(resolver.date_separator("Today"));
+        assert_eq!(resolver.resolve("11:00 AM"), Some(utc("2025-03-10T15:00:00Z")));
+
+        // Clocks went back at 2:00 yesterday (Sunday 2 November 2025)
+        let mut resolver = TimestampResolver::new(utc("2025-11-03T17:00:00Z"), Eastern);
+        assert!(resolver.date_separator("Yesterday"));
+        assert_eq!(resolver.resolve("12:30 AM"), Some(utc("2025-11-02T04:30:00Z")));
+        // 1:30 happened twice; the first (EDT) is used
+        assert_eq!(resolver.resolve("1:30 AM"), Some(utc("2025-11-02T05:30:00Z")));
+        assert_eq!(resolver.resolve("2:30 AM"), Some(utc("2025-11-02T07:30:00Z")));
+        assert!(resolver.date_separator("Today"));
+        assert_eq!(resolver.resolve("12:00 PM"), Some(utc("2025-11-03T17:00:00Z")));
+
+        // 23:00 EDT on the 9th is already the 10th in UTC
+        let resolver = TimestampResolver::new(utc("2025-03-10T03:00:00Z"), Eastern);
+        assert_eq!(resolver.today.to_string(), "2025-03-09");
+        assert_eq!(resolver.resolve("10:00 PM"), Some(utc("2025-03-10T02:00:00Z")));
+    }
+}
@@ -2,14 +2,14 @@
 //!
 //! This module defines the fundamental types used throughout the crate:
 //! - `ExtractedContent`: The main output type for extracted content
-//! - `CapturePayload`: BrowserCapturePayload-compatible output format
+//! - `CapturePayload` / `ChunkMeta`: the shared capture formats, re-exported
+//!   from `capture-protocol`
 //! - `ExtractionError`: Error types that can occur during extraction
 //! - `AppSource`: Known application source identifiers
 
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use thiserror::Error;
-use uuid::Uuid;
 
 /// Extracted content from an application.
 ///
@@ -39,214 +39,10 @@ pub struct ExtractedContent {
     pub extraction_method: String,
 }
 
-/// BrowserCapturePayload-compatible output format.
-///
-/// This struct provides compatibility with the existing ingestion pipeline
-/// by conforming to the BrowserCapturePayload JSON schema.
-///
-/// # Requirements
-/// - Requirement 8.3: Implements Serialize and Deserialize traits
-/// - Requirement 11.7: Conforms to BrowserCapturePayload JSON schema
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct CapturePayload {
-    /// Source identifier (word, excel, pages, etc.)
-    pub source: String,
-
-    /// URL-like identifier for the content
-    pub url: String,
-
-    /// The extracted text content
-    pub content: String,
-
-    /// Optional document title
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub title: Option<String>,
-
-    /// Optional author
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub author: Option<String>,
-
-    /// Optional channel/workspace
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub channel: Option<String>,
-
-    /// Unix timestamp
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub timestamp: Option<i64>,
-}
-
-/// Chunk metadata for database integration.
-///
-/// This struct represents the metadata stored in the `meta` JSON field of the
-/// `chunks` table. It conforms to the existing database schema used by the
-/// ingestion pipeline.
-///
-/// # Requirements
-/// - Requirement 11.11: Populate the chunks table with extracted content and appropriate metadata
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct ChunkMeta {
-    /// Unique document identifier (UUID v4)
-    pub id: String,
-
-    /// Source application identifier (e.g., "word", "excel", "pages")
-    pub source: String,
-
-    /// Unix timestamp of extraction
-    pub timestamp: i64,
-
-    /// First 200 characters of content (preview/header)
-    pub header: String,
-
-    /// Index of this chunk within the document (0-based)
-    pub chunk_idx: u32,
-
-    /// Total number of chunks for this document
-    pub total_chunks: u32,
-
-    /// File path (None for accessibility-extracted content)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub path: Option<String>,
-
-    /// File extension (None for accessibility-extracted content)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ext: Option<String>,
-
-    /// File size in bytes (None for accessibility-extracted content)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub file_size: Option<u64>,
-
-    /// File modification timestamp (None for accessibility-extracted content)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub file_modified: Option<i64>,
-
-    /// Application bundle ID (e.g., "com.microsoft.Word")
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub app_id: Option<String>,
-
-    /// Channel/workspace (None for accessibility-extracted content)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub channel: Option<String>,
-
-    /// Author (None for accessibility-extracted content)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub author: Option<String>,
-
-    /// URL-like identifier for the content
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub url: Option<String>,
-
-    /// Document title
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub title: Option<String>,
-}
-
-impl ChunkMeta {
-    /// Creates a new ChunkMeta with the required fields.
-    ///
-    /// # Arguments
-    ///
-    /// * `id` - Unique document identifier (UUID v4)
-    /// * `source` - Source application identifier
-    /// * `timestamp` - Unix timestamp of extraction
-    /// * `content` - Full content (used to generate header)
-    /// * `chunk_idx` - Index of this chunk
-    /// * `total_chunks` - Total number of chunks
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// use accessibility_extractor::types::ChunkMeta;
-    ///
-    /// let meta = ChunkMeta::new(
-    ///     "550e8400-e29b-41d4-a716-446655440000".to_string(),
-    ///     "word".to_string(),
-    ///     1707500000,
-    ///     "This is the document content...",
-    ///     0,
-    ///     1,
-    /// );
-    ///
-    /// assert_eq!(meta.source, "word");
-    /// assert_eq!(meta.chunk_idx, 0);
-    /// ```
-    pub fn new(
-        id: String,
-        source: String,
-        timestamp: i64,
-        content: &str,
-        chunk_idx: u32,
-        total_chunks: u32,
-    ) -> Self {
-        // Generate header from first 200 characters of content
-        let header: String = content.chars().take(200).collect();
-
-        ChunkMeta {
-            id,
-            source,
-            timestamp,
-            header,
-            chunk_idx,
-            total_chunks,
-            path: None,
-            ext: None,
-            file_size: None,
-            file_modified: None,
-            app_id: None,
-            channel: None,
-            author: None,
-            url: None,
-            title: None,
-        }
-    }
-
-    /// Sets the application bundle ID.
-    pub fn with_app_id(mut self, app_id: String) -> Self {
-        self.app_id = Some(app_id);
-        self
-    }
-
-    /// Sets the URL.
-    pub fn with_url(mut self, url: String) -> Self {
-        self.url = Some(url);
-        self
-    }
-
-    /// Sets the title.
-    pub fn with_title(mut self, title: String) -> Self {
-        self.title = Some(title);
-        self
-    }
-}
-
-/// Generate a unique document identifier (ehl_doc_id) using UUID v4.
-///
-/// This function generates a unique identifier for each extracted document.
-/// The identifier is a UUID v4 (random) string that can be used to uniquely
-/// identify documents in the database and ingestion pipeline.
-///
-/// # Returns
-///
-/// A `String` containing a UUID v4 in the standard hyphenated format
-/// (e.g., "550e8400-e29b-41d4-a716-446655440000").
-///
-/// # Example
-///
-/// ```
-/// use accessibility_extractor::types::generate_doc_id;
-///
-/// let doc_id = generate_doc_id();
-/// println!("Generated document ID: {}", doc_id);
-///
-/// // Each call generates a unique ID
-/// let another_id = generate_doc_id();
-/// assert_ne!(doc_id, another_id);
-/// ```
-///
-/// # Requirements
-/// - Requirement 11.8: Generate a unique document identifier (ehl_doc_id) for each extracted document
-pub fn generate_doc_id() -> String {
-    Uuid::new_v4().to_string()
-}
+/// Payload sent to the ingestion service, and the metadata stored with each
+/// chunk. Both are defined once in `capture-protocol` for every component.
+pub use capture_protocol::{CapturePayload, ChunkMeta};
+use capture_protocol::ContentType;
 
 /// Generate a content hash using SHA-256.
 ///
@@ -902,10 +698,15 @@ mod tests {
             source: "word".to_string(),
             url: "accessibility://Microsoft_Word/Document.docx".to_string(),
             content: "Document content".to_string(),
+            content_type: ContentType::Plain,
             title: Some("Document.docx".to_string()),
             author: None,
             channel: None,
             timestamp: Some(1707500000),
+            app_name: Some("Microsoft Word".to_string()),
+            bundle_id: None,
+            metadata: Default::default(),
+            messages: Vec::new(),
         };
 
         let json = serde_json::to_string(&payload).expect("Serialization should succeed");
@@ -921,10 +722,15 @@ mod tests {
             source: "excel".to_string(),
             url: "accessibility://Microsoft_Excel/Sheet.xlsx".to_string(),
             content: "Spreadsheet content".to_string(),
+            content_type: ContentType::Plain,
             title: None,
             author: None,
             channel: None,
             timestamp: None,
+            app_name: None,
+            bundle_id: None,
+            metadata: Default::default(),
+            messages: Vec::new(),
         };
 
         let json = serde_json::to_string(&payload).expect("Serialization should succeed");
@@ -996,137 +802,60 @@ mod tests {
     // Unit Tests for ChunkMeta
     // ============================================================================
 
-    #[test]
-    fn test_chunk_meta_new() {
-        let meta = ChunkMeta::new(
-            "550e8400-e29b-41d4-a716-446655440000".to_string(),
-            "word".to_string(),
-            1707500000,
-            "This is the document content.",
-            0,
-            1,
-        );
-
-        assert_eq!(meta.id, "550e8400-e29b-41d4-a716-446655440000");
-        assert_eq!(meta.source, "word");
-        assert_eq!(meta.timestamp, 1707500000);
-        assert_eq!(meta.header, "This is the document content.");
-        assert_eq!(meta.chunk_idx, 0);
-        assert_eq!(meta.total_chunks, 1);
-        assert!(meta.path.is_none());
-        assert!(meta.app_id.is_none());
-        assert!(meta.url.is_none());
-        assert!(meta.title.is_none());
-    }
-
-    #[test]
-    fn test_chunk_meta_header_truncation() {
-        // Create content longer than 200 characters
-        let long_content = "A".repeat(300);
-        let meta = ChunkMeta::new(
-            "test-id".to_string(),
-            "word".to_string(),
-            1707500000,
-            &long_content,
-            0,
-            1,
-        );
-
-        // Header should be truncated to 200 characters
-        assert_eq!(meta.header.chars().count(), 200);
-        assert_eq!(meta.header, "A".repeat(200));
-    }
-
-    #[test]
-    fn test_chunk_meta_builder_methods() {
-        let meta = ChunkMeta::new(
-            "test-id".to_string(),
-            "word".to_string(),
-            1707500000,
-            "Content",
-            0,
-            1,
-        )
-        .with_app_id("com.microsoft.Word".to_string())
-        .with_url("accessibility://Microsoft_Word/Document.docx".to_string())
-        .with_title("Document.docx".to_string());
-
-        assert_eq!(meta.app_id, Some("com.microsoft.Word".to_string()));
-        assert_eq!(
-            meta.url,
-            Some("accessibility://Microsoft_Word/Document.docx".to_string())
-        );
-        assert_eq!(meta.title, Some("Document.docx".to_string()));
+    fn chunk_meta(id: String, source: String, timestamp: i64, chunk_index: usize, total_chunks: usize) -> ChunkMeta {
+        ChunkMeta {
+            id,
+            source,
+            url: "accessibility://Microsoft_Word/Document.docx".to_string(),
+            original_url: None,
+            title: None,
+            author: None,
+            channel: None,
+            chunk_index,
+            total_chunks,
+            source_type: "accessibility".to_string(),
+            extraction_method: Some("accessibility".to_string()),
+            app_name: Some("Microsoft Word".to_string()),
+            bundle_id: None,
+            metadata: Default::default(),
+            captured_at: Some(timestamp),
+            revision_id: None,
+            char_start: None,
+            char_end: None,
+            pipeline_version: None,
+        }
     }
 
     #[test]
     fn test_chunk_meta_serialization() {
-        let meta = ChunkMeta::new(
-            "550e8400-e29b-41d4-a716-446655440000".to_string(),
-            "word".to_string(),
-            1707500000,
-            "Document content",
-            0,
-            1,
-        )
-        .with_app_id("com.microsoft.Word".to_string())
-        .with_title("Document.docx".to_string());
+        let meta = ChunkMeta {
+            bundle_id: Some("com.microsoft.Word".to_string()),
+            title: Some("Document.docx".to_string()),
+            ..chunk_meta("550e8400-e29b-41d4-a716-446655440000".to_string(), "word".to_string(), 1707500000, 0, 1)
+        };
 
         let json = serde_json::to_string(&meta).expect("Serialization should succeed");
         let deserialized: ChunkMeta =
             serde_json::from_str(&json).expect("Deserialization should succeed");
 
         assert_eq!(meta, deserialized);
+        // Same field names the ingestion server writes
+        assert!(json.contains("\"chunk_index\":0"));
     }
 
     #[test]
     fn test_chunk_meta_skips_none_fields() {
-        let meta = ChunkMeta::new(
-            "test-id".to_string(),
-            "word".to_string(),
-            1707500000,
-            "Content",
-            0,
-            1,
-        );
+        let meta = chunk_meta("test-id".to_string(), "word".to_string(), 1707500000, 0, 1);
 
         let json = serde_json::to_string(&meta).expect("Serialization should succeed");
 
         // None fields should be skipped in serialization
-        assert!(!json.contains("\"path\""));
-        assert!(!json.contains("\"ext\""));
-        assert!(!json.contains("\"file_size\""));
-        assert!(!json.contains("\"file_modified\""));
-        assert!(!json.contains("\"app_id\""));
+        assert!(!json.contains("\"original_url\""));
+        assert!(!json.contains("\"bundle_id\""));
         assert!(!json.contains("\"channel\""));
         assert!(!json.contains("\"author\""));
-        assert!(!json.contains("\"url\""));
         assert!(!json.contains("\"title\""));
-    }
-
-    // ============================================================================
-    // Unit Tests for generate_doc_id
-    // ============================================================================
-
-    #[test]
-    fn test_generate_doc_id_format() {
-        let doc_id = generate_doc_id();
-
-        // UUID v4 format: 8-4-4-4-12 (36 characters with hyphens)
-        assert_eq!(doc_id.len(), 36);
-        assert_eq!(doc_id.chars().filter(|c| *c == '-').count(), 4);
-    }
-
-    #[test]
-    fn test_generate_doc_id_uniqueness() {
-        let id1 = generate_doc_id();
-        let id2 = generate_doc_id();
-        let id3 = generate_doc_id();
-
-        // Each call should generate a unique ID
-        assert_ne!(id1, id2);
-        assert_ne!(id2, id3);
-        assert_ne!(id1, id3);
+        assert!(!json.contains("\"revision_id\""));
     }
 
     // ============================================================================
@@ -1185,30 +914,7 @@ mod tests {
     // Property-Based Tests for Database Integration Types
     // ============================================================================
 
-    // Feature: accessibility-extractor, Property 16: Unique Document Identifiers
-    // **Validates: Requirements 11.8**
-    //
-    // *For any* two distinct extractions, the generated `ehl_doc_id` values
-    // SHALL be different (UUID v4 uniqueness).
     proptest! {
-        #[test]
-        fn prop_generate_doc_id_uniqueness(
-            _seed in 0u64..1000000,  // Just to run multiple iterations
-        ) {
-            let id1 = generate_doc_id();
-            let id2 = generate_doc_id();
-
-            // Verify UUID format first (36 characters with 4 hyphens)
-            prop_assert_eq!(id1.len(), 36, "UUID should be 36 characters");
-            prop_assert_eq!(id2.len(), 36, "UUID should be 36 characters");
-
-            // Each call should generate a unique ID
-            prop_assert_ne!(
-                id1, id2,
-                "Two consecutive calls to generate_doc_id should produce different IDs"
-            );
-        }
-
         // Property test for content hash determinism
         #[test]
         fn prop_content_hash_deterministic(
@@ -1250,56 +956,16 @@ mod tests {
             );
         }
 
-        // Property test for ChunkMeta header truncation
-        #[test]
-        fn prop_chunk_meta_header_max_200_chars(
-            content in ".{1,500}",
-        ) {
-            let meta = ChunkMeta::new(
-                "test-id".to_string(),
-                "word".to_string(),
-                1707500000,
-                &content,
-                0,
-                1,
-            );
-
-            // Header should never exceed 200 characters (not bytes)
-            let header_char_count = meta.header.chars().count();
-            prop_assert!(
-                header_char_count <= 200,
-                "Header should be at most 200 characters, got {}",
-                header_char_count
-            );
-
-            // If content is <= 200 chars, header should equal content
-            let content_char_count = content.chars().count();
-            if content_char_count <= 200 {
-                prop_assert_eq!(
-                    meta.header, content,
-                    "Header should equal content when content <= 200 chars"
-                );
-            }
-        }
-
         // Property test for ChunkMeta serialization round-trip
         #[test]
         fn prop_chunk_meta_roundtrip(
             id in "[a-f0-9]{8}-[a-f0-9]{4}-[a-f0-9]{4}-[a-f0-9]{4}-[a-f0-9]{12}",
             source in "[a-z]{1,20}",
             timestamp in 0i64..i64::MAX,
-            content in ".{1,200}",
-            chunk_idx in 0u32..100,
-            total_chunks in 1u32..100,
+            chunk_index in 0usize..100,
+            total_chunks in 1usize..100,
         ) {
-            let original = ChunkMeta::new(
-                id,
-                source,
-                timestamp,
-                &content,
-                chunk_idx,
-                total_chunks,
-            );
+            let original = chunk_meta(id, source, timestamp, chunk_index, total_chunks);
 
             let json = serde_json::to_string(&original).unwrap();
             let restored: ChunkMeta = serde_json::from_str(&json).unwrap();
@@ -0,0 +1,462 @@
+//! Content chunker - splits text into fixed-size token chunks
+//! 
+//! For tabular data (Excel, CSV), preserves row structure by chunking at row boundaries.
+//! Content made of independent sections (conversations) is chunked at section boundaries.
+
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Version of the chunking pipeline. Bump it when a change to the chunker (or
+/// to what ingestion does to text before chunking) should re-cut stored chunks.
+pub const PIPELINE_VERSION: u32 = 1;
+
+/// Chunk configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChunkerConfig {
+    /// Maximum tokens per chunk (1 word = 1 token)
+    pub max_tokens: usize,
+    /// Overlap tokens between chunks for context continuity
+    pub overlap_tokens: usize,
+}
+
+impl Default for ChunkerConfig {
+    fn default() -> Self {
+        Self {
+            max_tokens: 1024,
+            overlap_tokens: 100,
+        }
+    }
+}
+
+/// A single chunk of content
+///
+/// `text` is always the exact slice `char_start..char_end` of the chunked
+/// content, in characters (Unicode scalar values).
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub text: String,
+    pub chunk_index: usize,
+    pub total_chunks: usize,
+    pub token_count: usize,
+    pub char_start: usize,
+    pub char_end: usize,
+}
+
+/// A word or line of the content, as byte and character offsets
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: usize,
+    end: usize,
+    char_start: usize,
+    char_end: usize,
+}
+
+/// Whitespace-separated words, split like `str::split_whitespace`
+fn word_spans(content: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut word: Option<(usize, usize)> = None;
+    let mut chars = 0;
+
+    for (byte, c) in content.char_indices() {
+        if c.is_whitespace() {
+            if let Some((start, char_start)) = word.take() {
+                spans.push(Span { start, end: byte, char_start, char_end: chars });
+            }
+        } else if word.is_none() {
+            word = Some((byte, chars));
+        }
+        chars += 1;
+    }
+    if let Some((start, char_start)) = word {
+        spans.push(Span { start, end: content.len(), char_start, char_end: chars });
+    }
+    spans
+}
+
+/// Lines without their terminators, split like `str::lines`
+fn line_spans(content: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let (mut start, mut char_start, mut chars) = (0, 0, 0);
+
+    for (byte, c) in content.char_indices() {
+        if c == '\n' {
+            let end = if content[..byte].ends_with('\r') { byte - 1 } else { byte };
+            spans.push(Span { start, end, char_start, char_end: chars - (byte - end) });
+            start = byte + 1;
+            char_start = chars + 1;
+        }
+        chars += 1;
+    }
+    if start < content.len() {
+        spans.push(Span { start, end: content.len(), char_start, char_end: chars });
+    }
+    spans
+}
+
+/// The chunk covering `spans`, from the start of the first to the end of the last
+fn chunk_of(content: &str, spans: &[Span], chunk_index: usize, token_count: usize) -> Chunk {
+    let (first, last) = (spans[0], spans[spans.len() - 1]);
+    Chunk {
+        text: content[first.start..last.end].to_string(),
+        chunk_index,
+        total_chunks: 0,
+        token_count,
+        char_start: first.char_start,
+        char_end: last.char_end,
+    }
+}
+
+/// The chunk holding the byte range `range` of `content` whole
+fn slice_chunk(content: &str, range: Range<usize>, token_count: usize) -> Chunk {
+    let char_start = content[..range.start].chars().count();
+    let text = content[range].to_string();
+    Chunk {
+        char_end: char_start + text.chars().count(),
+        text,
+        chunk_index: 0,
+        total_chunks: 0,
+        token_count,
+        char_start,
+    }
+}
+
+/// Chunker splits content into fixed-size token chunks
+pub struct Chunker {
+    config: ChunkerConfig,
+}
+
+impl Chunker {
+    pub fn new(config: ChunkerConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn with_defaults() -> Self {
+        Self::new(ChunkerConfig::default())
+    }
+
+    /// The chunker's part of the pipeline version recorded on every chunk:
+    /// `PIPELINE_VERSION:max_tokens/overlap_tokens`. The ingestion server
+    /// appends its redaction rules; chunks with another version are out of date.
+    pub fn pipeline_version(&self) -> String {
+        format!("{}:{}/{}", PIPELINE_VERSION, self.config.max_tokens, self.config.overlap_tokens)
+    }
+
+    /// Check if content appears to be tabular (Excel/CSV-like)
+    fn is_tabular_content(content: &str) -> bool {
+        // Check if content has tabs or consistent structure suggesting tabular data
+        let lines: Vec<&str> = content.lines().take(10).collect();
+        if lines.len() < 2 {
+            return false;
+        }
+        
+        // Check if multiple lines have tabs
+        let lines_with_tabs = lines.iter().filter(|l| l.contains('\t')).count();
+        lines_with_tabs >= 2
+    }
+
+    /// Split content into chunks, preserving structure for tabular data
+    pub fn chunk(&self, content: &str) -> Vec<Chunk> {
+        if content.trim().is_empty() {
+            return vec![];
+        }
+
+        // For tabular content, use line-based chunking to preserve structure
+        let mut chunks = if Self::is_tabular_content(content) {
+            self.chunk_tabular(content)
+        } else {
+            // For regular text, use word-based chunking
+            self.chunk_text(content)
+        };
+
+        // Set total_chunks
+        let total = chunks.len();
+        for chunk in &mut chunks {
+            chunk.total_chunks = total;
+        }
+
+        chunks
+    }
+
+    /// Split content made of independent sections, given as byte ranges in
+    /// order. Whole sections are packed into a chunk while they fit; only a
+    /// section longer than a chunk is split, and chunks do not overlap across
+    /// sections.
+    pub fn chunk_sections(&self, content: &str, sections: &[Range<usize>]) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        // Sections packed so far and their word count
+        let mut packed: Option<(Range<usize>, usize)> = None;
+
+        for section in sections {
+            let words = content[section.clone()].split_whitespace().count();
+            if words == 0 {
+                continue;
+            }
+            if let Some((range, count)) = &mut packed {
+                if *count + words <= self.config.max_tokens {
+                    range.end = section.end;
+                    *count += words;
+                    continue;
+                }
+                chunks.push(slice_chunk(content, range.clone(), *count));
+                packed = None;
+            }
+
+            if words <= self.config.max_tokens {
+                packed = Some((section.clone(), words));
+            } else {
+                let offset = content[..section.start].chars().count();
+                for mut chunk in self.chunk_text(&content[section.clone()]) {
+                    chunk.char_start += offset;
+                    chunk.char_end += offset;
+                    chunks.push(chunk);
+                }
+            }
+        }
+        if let Some((range, count)) = packed {
+            chunks.push(slice_chunk(content, range, count));
+        }
+
+        let total = chunks.len();
+        for (index, chunk) in chunks.iter_mut().enumerate() {
+            chunk.chunk_index = index;
+            chunk.total_chunks = total;
+        }
+        chunks
+    }
+
+    /// Chunk tabular content by lines, preserving row structure
+    fn chunk_tabular(&self, content: &str) -> Vec<Chunk> {
+        let lines = line_spans(content);
+        // Estimate tokens per line (count words in each line)
+        let tokens: Vec<usize> = lines
+            .iter()
+            .map(|line| content[line.start..line.end].split_whitespace().count().max(1))
+            .collect();
+
+        let mut chunks = Vec::new();
+        let mut first = 0;
+        let mut current_tokens = 0;
+
+        for (i, &line_tokens) in tokens.iter().enumerate() {
+            // If adding this line would exceed max_tokens, start a new chunk
+            if current_tokens + line_tokens > self.config.max_tokens && i > first {
+                chunks.push(chunk_of(content, &lines[first..i], chunks.len(), current_tokens));
+
+                // Keep some overlap lines for context, but always move forward
+                first = i - (i - first - 1).min(3);
+                current_tokens = tokens[first..i].iter().sum();
+            }
+            current_tokens += line_tokens;
+        }
+
+        // Add remaining content
+        if first < lines.len() {
+            chunks.push(chunk_of(content, &lines[first..], chunks.len(), current_tokens));
+        }
+
+        chunks
+    }
+
+    /// Chunk regular text content by words
+    fn chunk_text(&self, content: &str) -> Vec<Chunk> {
+        let words = word_spans(content);
+
+        if words.is_empty() {
+            return vec![];
+        }
+
+        // If content fits in one chunk, return as-is (preserve original formatting)
+        if words.len() <= self.config.max_tokens {
+            return vec![Chunk {
+                text: content.to_string(),
+                chunk_index: 0,
+                total_chunks: 1,
+                token_count: words.len(),
+                char_start: 0,
+                char_end: content.chars().count(),
+            }];
+        }
+
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        let step = self.config.max_tokens - self.config.overlap_tokens;
+
+        loop {
+            let mut end = (start + self.config.max_tokens).min(words.len());
+            let next = start + step;
+
+            // Avoid tiny final chunks: include the remaining words in this one
+            if next < words.len() && words.len() - next < self.config.overlap_tokens {
+                end = words.len();
+            }
+
+            chunks.push(chunk_of(content, &words[start..end], chunks.len(), end - start));
+            if end == words.len() {
+                break;
+            }
+            start = next;
+        }
+
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_content_single_chunk() {
+        let chunker = Chunker::with_defaults();
+        let content = "Hello world this is a test";
+        let chunks = chunker.chunk(content);
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].chunk_index, 0);
+        assert_eq!(chunks[0].total_chunks, 1);
+        assert_eq!(chunks[0].token_count, 6);
+    }
+
+    #[test]
+    fn test_large_content_multiple_chunks() {
+        let config = ChunkerConfig {
+            max_tokens: 10,
+            overlap_tokens: 2,
+        };
+        let chunker = Chunker::new(config);
+
+        // 25 words
+        let content = "one two three four five six seven eight nine ten eleven twelve thirteen fourteen fifteen sixteen seventeen eighteen nineteen twenty twenty-one twenty-two twenty-three twenty-four twenty-five";
+        let chunks = chunker.chunk(content);
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.token_count <= 12); // max + some overlap tolerance
+        }
+    }
+
+    #[test]
+    fn test_empty_content() {
+        let chunker = Chunker::with_defaults();
+        let chunks = chunker.chunk("");
+        assert!(chunks.is_empty());
+    }
+
+    #[test]
+    fn test_tabular_content_preserves_structure() {
+        let chunker = Chunker::with_defaults();
+        // Simulated Excel content with tabs
+        let content = "Name\tAge\tCity\nAlice\t30\tNew York\nBob\t25\tLos Angeles\nCharlie\t35\tChicago";
+        let chunks = chunker.chunk(content);
+
+        assert_eq!(chunks.len(), 1);
+        // Verify tabs are preserved
+        assert!(chunks[0].text.contains('\t'));
+        // Verify newlines are preserved
+        assert!(chunks[0].text.contains('\n'));
+    }
+
+    #[test]
+    fn test_chunks_are_exact_spans() {
+        let chunker = Chunker::new(ChunkerConfig {
+            max_tokens: 10,
+            overlap_tokens: 3,
+        });
+        let content: String = (0..57)
+            .map(|i| format!("wörd{}{}", i, if i % 7 == 6 { "\n\n" } else { "  " }))
+            .collect();
+        let chars: Vec<char> = content.chars().collect();
+        let chunks = chunker.chunk(&content);
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            let span: String = chars[chunk.char_start..chunk.char_end].iter().collect();
+            assert_eq!(chunk.text, span);
+            assert_eq!(chunk.text.split_whitespace().count(), chunk.token_count);
+        }
+        assert_eq!(chunks[0].char_start, 0);
+        assert_eq!(chunks.last().unwrap().text.split_whitespace().last(), Some("wörd56"));
+        // Consecutive chunks overlap by `overlap_tokens` words
+        assert_eq!(chunks[1].text.split_whitespace().next(), Some("wörd7"));
+    }
+
+    #[test]
+    fn test_word_count_just_over_a_chunk() {
+        let chunker = Chunker::with_defaults();
+        for words in [1030, 3000] {
+            let content = vec!["word"; words].join(" ");
+            let chunks = chunker.chunk(&content);
+            assert_eq!(chunks.last().unwrap().char_end, content.len());
+            assert!(chunks.iter().all(|chunk| chunk.token_count <= 1024 + 100));
+        }
+    }
+
+    #[test]
+    fn test_tabular_spans_keep_line_endings() {
+        let chunker = Chunker::new(ChunkerConfig {
+            max_tokens: 6,
+            overlap_tokens: 0,
+        });
+        let content = "A\tB\r\n1\t2\r\n3\t4\r\n5\t6\r\n";
+        let chunks = chunker.chunk(content);
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].text, "A\tB\r\n1\t2\r\n3\t4");
+        assert_eq!(chunks[1].text, "1\t2\r\n3\t4\r\n5\t6");
+        assert_eq!(&content[chunks[1].char_start..chunks[1].char_end], chunks[1].text);
+        assert_eq!(chunks[1].char_end, content.len() - 2);
+    }
+
+    #[test]
+    fn test_sections_are_not_split() {
+        let chunker = Chunker::new(ChunkerConfig {
+            max_tokens: 10,
+            overlap_tokens: 2,
+        });
+        let long = vec!["z"; 25].join(" ");
+        let sections = ["a b c d", "é f g h i j k", "l m", &long];
+        let mut content = String::new();
+        let mut ranges = Vec::new();
+        for section in sections {
+            if !content.is_empty() {
+                content.push_str("\n\n");
+            }
+            ranges.push(content.len()..content.len() + section.len());
+            content.push_str(section);
+        }
+        let chunks = chunker.chunk_sections(&content, &ranges);
+
+        // 4 + 7 words overflow a chunk, 7 + 2 fit, the long section is split on its own
+        assert_eq!(chunks[0].text, "a b c d");
+        assert_eq!(chunks[1].text, "é f g h i j k\n\nl m");
+        assert!(chunks[2..].iter().all(|chunk| chunk.text.split_whitespace().all(|w| w == "z")));
+        assert_eq!(chunks.len(), 2 + chunker.chunk(&long).len());
+
+        let chars: Vec<char> = content.chars().collect();
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.chunk_index, index);
+            assert_eq!(chunk.total_chunks, chunks.len());
+            assert_eq!(chars[chunk.char_start..chunk.char_end].iter().collect::<String>(), chunk.text);
+        }
+    }
+
+    #[test]
+    fn test_tabular_detection() {
+        // Should detect as tabular
+        let tabular = "A\tB\tC\n1\t2\t3\n4\t5\t6";
+        assert!(Chunker::is_tabular_content(tabular));
+
+        // Should not detect as tabular (no tabs)
+        let regular = "Hello world\nThis is text\nNo tabs here";
+        assert!(!Chunker::is_tabular_content(regular));
+    }
+
+    #[test]
+    fn test_pipeline_version() {
+        assert_eq!(Chunker::with_defaults().pipeline_version(), format!("{}:1024/100", PIPELINE_VERSION));
+        let config: ChunkerConfig = serde_json::from_str(r#"{"max_tokens": 256}"#).unwrap();
+        assert_eq!(config.overlap_tokens, 100);
+        assert_ne!(Chunker::new(config).pipeline_version(), Chunker::with_defaults().pipeline_version());
+    }
+}
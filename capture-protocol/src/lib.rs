@@ -0,0 +1,34 @@
+//! Capture formats shared by every component
+//!
+//! The unified router and the browser extension send [`CapturePayload`]s to
+//! the ingestion server, which answers with an [`IngestionResponse`]. The
+//! ingestion server and the accessibility daemon both write chunks whose
+//! `meta` column holds a [`ChunkMeta`], cut by the same [`chunker`]. Keeping
+//! these types in one crate means a field added for one component reaches
+//! the others instead of being silently dropped on deserialize.
+//! Site-specific context travels in the open-ended [`metadata`] map rather
+//! than in new fields; chat captures can also carry their [`message`]s.
+//!
+//! Content hashes are taken over [`normalize`]d text, with volatile parts
+//! such as relative times removed by [`mask`] rules, so invisible differences
+//! and ticking clocks between captures don't count as changes. PII is
+//! removed by the [`redact`] rules the router and the ingestion server share.
+//!
+//! [`schema`] generates JSON Schemas for the wire formats; the published
+//! copies live in `capture-protocol/schema/` for non-Rust clients.
+
+pub mod chunker;
+pub mod mask;
+pub mod message;
+pub mod meta;
+pub mod metadata;
+pub mod normalize;
+pub mod payload;
+pub mod redact;
+pub mod schema;
+
+pub use chunker::{Chunk, Chunker, ChunkerConfig};
+pub use message::ChatMessage;
+pub use meta::ChunkMeta;
+pub use metadata::Metadata;
+pub use payload::{CapturePayload, ChunkDiff, ContentType, IngestionAction, IngestionResponse, ResponseStatus, SkipReason, TextDiff};
@@ -0,0 +1,242 @@
+//! Volatile-content masking for content hashes
+//!
+//! Some text changes on every capture without the document changing: "3
+//! minutes ago" becomes "4 minutes ago", an unread badge ticks up, a clock
+//! advances. Content hashes are taken over [`Masks::mask`]ed text so such
+//! captures dedup; the stored text keeps the values as captured.
+//!
+//! Rules are regexes, optionally scoped to source types or apps, whose
+//! matches are replaced (by nothing, unless a `replacement` is given):
+//!
+//! ```toml
+//! [[masking.rules]]
+//! apps = ["Slack", "com.tinyspeck.slackmacgap"]
+//! pattern = '\b\d+ unread\b'
+//!
+//! [[masking.rules]]
+//! sources = ["chrome"]
+//! pattern = '(?i)\b\d[\d,.]*[KM]? views\b'
+//! replacement = "<views>"
+//! ```
+//!
+//! The built-in rules cover common relative times ("5 min ago", "an hour
+//! ago", "just now", "in 3 days") in every source.
+
+use crate::payload::CapturePayload;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use thiserror::Error;
+
+/// A masking rule as written in config
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MaskRule {
+    /// Source types this rule applies to (empty = every source)
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// App names or bundle IDs this rule applies to, ignoring case (empty = every app)
+    #[serde(default)]
+    pub apps: Vec<String>,
+    /// Regex whose matches are masked
+    pub pattern: String,
+    /// What matches are replaced with; `$1` and `$name` refer to capture groups
+    #[serde(default)]
+    pub replacement: String,
+}
+
+impl MaskRule {
+    fn new(pattern: &str) -> Self {
+        Self {
+            sources: Vec::new(),
+            apps: Vec::new(),
+            pattern: pattern.to_string(),
+            replacement: String::new(),
+        }
+    }
+}
+
+/// The `[masking]` config section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskConfig {
+    /// Also apply the built-in relative-time rules (after any configured ones)
+    #[serde(default = "default_true")]
+    pub builtin_rules: bool,
+    /// Configured rules, applied in order before the built-in ones
+    #[serde(default)]
+    pub rules: Vec<MaskRule>,
+}
+
+impl Default for MaskConfig {
+    fn default() -> Self {
+        Self {
+            builtin_rules: true,
+            rules: Vec::new(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Relative times in English, as shown by most sites and chat apps
+pub fn builtin_rules() -> Vec<MaskRule> {
+    vec![
+        // "3 minutes ago", "5 min ago", "2h ago", "an hour ago", "a few seconds ago"
+        MaskRule::new(
+            r"(?i)\b(?:\d+|an?|one|a few|several)\s*(?:s|secs?|seconds?|m|mins?|minutes?|h|hrs?|hours?|d|days?|w|wks?|weeks?|mo|mos|months?|y|yrs?|years?)\s+ago\b",
+        ),
+        // "in 5 minutes", "in a day"
+        MaskRule::new(r"(?i)\bin\s+(?:\d+|an?|a few)\s+(?:seconds?|minutes?|hours?|days?|weeks?|months?|years?)\b"),
+        MaskRule::new(r"(?i)\b(?:just now|a moment ago|moments ago)\b"),
+    ]
+}
+
+/// Error compiling a configured rule
+#[derive(Debug, Error)]
+#[error("invalid masking pattern {pattern:?}: {error}")]
+pub struct MaskError {
+    pub pattern: String,
+    #[source]
+    pub error: regex::Error,
+}
+
+/// A rule with its regex compiled
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    rule: MaskRule,
+    regex: Regex,
+}
+
+impl CompiledRule {
+    fn applies(&self, source: &str, apps: &[&str]) -> bool {
+        (self.rule.sources.is_empty() || self.rule.sources.iter().any(|s| s == source))
+            && (self.rule.apps.is_empty()
+                || self.rule.apps.iter().any(|rule_app| apps.iter().any(|app| app.eq_ignore_ascii_case(rule_app))))
+    }
+}
+
+/// Compiled masking rules
+#[derive(Debug, Clone)]
+pub struct Masks {
+    rules: Vec<CompiledRule>,
+}
+
+impl Masks {
+    /// Compile the configured rules, followed by the built-in ones if enabled
+    pub fn compile(config: &MaskConfig) -> Result<Self, MaskError> {
+        let builtin = if config.builtin_rules { builtin_rules() } else { Vec::new() };
+        let rules = config
+            .rules
+            .iter()
+            .cloned()
+            .chain(builtin)
+            .map(|rule| match Regex::new(&rule.pattern) {
+                Ok(regex) => Ok(CompiledRule { rule, regex }),
+                Err(error) => Err(MaskError {
+                    pattern: rule.pattern,
+                    error,
+                }),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// The built-in rules only
+    pub fn builtin() -> Self {
+        Self::compile(&MaskConfig::default()).expect("built-in masking rules compile")
+    }
+
+    /// Mask `text` captured by `source` from the app known by any of `apps`
+    /// (its name and bundle ID)
+    pub fn mask<'a>(&self, source: &str, apps: &[&str], text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for rule in self.rules.iter().filter(|rule| rule.applies(source, apps)) {
+            let masked = match rule.regex.replace_all(&text, rule.rule.replacement.as_str()) {
+                Cow::Owned(masked) => Some(masked),
+                Cow::Borrowed(_) => None,
+            };
+            if let Some(masked) = masked {
+                text = Cow::Owned(masked);
+            }
+        }
+        text
+    }
+
+    /// Mask a payload's content
+    pub fn mask_payload<'a>(&self, payload: &'a CapturePayload) -> Cow<'a, str> {
+        self.mask_like(payload, &payload.content)
+    }
+
+    /// Mask `text` with the rules that apply to `payload`'s source and app
+    pub fn mask_like<'a>(&self, payload: &CapturePayload, text: &'a str) -> Cow<'a, str> {
+        let apps: Vec<&str> = [payload.app_name.as_deref(), payload.bundle_id.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+        self.mask(&payload.source, &apps, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normalize::normalize;
+
+    #[test]
+    fn test_builtin_relative_times() {
+        let masks = Masks::builtin();
+        let hashed = |text: &str| normalize(&masks.mask("chrome", &[], text));
+
+        let expected = hashed("Posted 3 minutes ago by Ana\nDeploy finished");
+        for variant in [
+            "Posted 4 minutes ago by Ana\nDeploy finished",
+            "Posted an hour ago by Ana\nDeploy finished",
+            "Posted 2h ago by Ana\nDeploy finished",
+            "Posted just now by Ana\nDeploy finished",
+            "Posted A few seconds ago by Ana\nDeploy finished",
+        ] {
+            assert_eq!(hashed(variant), expected, "{:?}", variant);
+        }
+        assert_eq!(hashed("Expires in 5 days"), hashed("Expires in a day"));
+
+        // Numbers that are not relative times are kept
+        assert_ne!(hashed("Shipped 3 items"), hashed("Shipped 4 items"));
+        assert_eq!(masks.mask("chrome", &[], "10 meters ago"), "10 meters ago");
+    }
+
+    #[test]
+    fn test_rules_are_scoped() {
+        let masks = Masks::compile(&MaskConfig {
+            builtin_rules: false,
+            rules: vec![
+                MaskRule {
+                    apps: vec!["slack".to_string()],
+                    ..MaskRule::new(r"\b\d+ unread\b")
+                },
+                MaskRule {
+                    sources: vec!["chrome".to_string()],
+                    replacement: "<views>".to_string(),
+                    ..MaskRule::new(r"\b\d[\d,]* views\b")
+                },
+            ],
+        })
+        .unwrap();
+
+        assert_eq!(masks.mask("accessibility", &["Slack"], "general 12 unread"), "general ");
+        assert_eq!(masks.mask("accessibility", &["Teams"], "general 12 unread"), "general 12 unread");
+        assert_eq!(masks.mask("chrome", &[], "Video 1,024 views"), "Video <views>");
+        assert_eq!(masks.mask("firefox", &[], "Video 1,024 views"), "Video 1,024 views");
+        assert_eq!(masks.mask("chrome", &[], "5 minutes ago"), "5 minutes ago");
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let error = Masks::compile(&MaskConfig {
+            builtin_rules: true,
+            rules: vec![MaskRule::new("(unclosed")],
+        })
+        .unwrap_err();
+        assert_eq!(error.pattern, "(unclosed");
+    }
+}
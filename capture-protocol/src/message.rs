@@ -0,0 +1,61 @@
+//! Chat messages carried by a capture
+//!
+//! A chat capture (Slack or Teams in the browser, WhatsApp through OCR) is a
+//! window onto a conversation that keeps scrolling, so consecutive captures
+//! overlap. Extractors that can tell messages apart send them as
+//! [`CapturePayload::messages`](crate::CapturePayload); the ingestion server
+//! then stores each message once and rebuilds the conversation from all the
+//! messages it has seen, instead of replacing the document on every capture.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// One message of a conversation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ChatMessage {
+    /// Message text
+    pub text: String,
+
+    /// Sender display name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+
+    /// When the message was sent (unix seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+
+    /// Thread the message belongs to; a thread's first message carries it too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+
+    /// The site's id for the message (Slack ts, Teams message id). Without
+    /// it a message is identified by author, timestamp and text, so an edit
+    /// is stored as a new message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+}
+
+impl ChatMessage {
+    /// A message with only its text
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            author: None,
+            timestamp: None,
+            thread_id: None,
+            message_id: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimal_message() {
+        let message: ChatMessage = serde_json::from_str(r#"{"text": "lunch?"}"#).unwrap();
+        assert_eq!(message, ChatMessage::new("lunch?"));
+        assert_eq!(serde_json::to_string(&message).unwrap(), r#"{"text":"lunch?"}"#);
+    }
+}
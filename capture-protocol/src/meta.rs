@@ -0,0 +1,131 @@
+//! Chunk metadata stored in the `meta` JSON column of `chunks`
+
+use crate::chunker::Chunk;
+use crate::metadata::Metadata;
+use crate::payload::CapturePayload;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Chunk metadata stored in the meta JSON field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ChunkMeta {
+    pub id: String,             // ehl_doc_id
+    pub source: String,         // source type
+    pub url: String,            // canonical source path
+    /// URL as captured, when it differs from `url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    pub chunk_index: usize,
+    pub total_chunks: usize,
+    pub source_type: String,    // "capture" or "accessibility"
+    /// How the accessibility daemon read the content (e.g. "accessibility")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extraction_method: Option<String>,
+    /// Application display name (e.g., "Microsoft Word")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    /// Application bundle ID (e.g., "com.microsoft.Word")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundle_id: Option<String>,
+    /// Capture time of the revision this chunk belongs to (unix seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captured_at: Option<i64>,
+    /// The `source_revisions` row this chunk was cut from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision_id: Option<i64>,
+    /// Character span of the chunk in the revision text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_start: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_end: Option<usize>,
+    /// `Chunker::pipeline_version` of the chunker that cut this chunk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline_version: Option<String>,
+    /// The payload's structured metadata, queryable as `$.metadata.<key>`
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    #[schemars(schema_with = "crate::metadata::json_schema")]
+    pub metadata: Metadata,
+}
+
+impl ChunkMeta {
+    /// Build the meta for one chunk of a captured payload, located in `revision_id` if given
+    pub fn for_chunk(
+        ehl_doc_id: &str,
+        source_path: &str,
+        payload: &CapturePayload,
+        chunk: &Chunk,
+        revision_id: Option<i64>,
+    ) -> Self {
+        Self {
+            id: ehl_doc_id.to_string(),
+            source: payload.source.clone(),
+            url: source_path.to_string(),
+            original_url: (payload.url != source_path).then(|| payload.url.clone()),
+            title: payload.title.clone(),
+            author: payload.author.clone(),
+            channel: payload.channel.clone(),
+            chunk_index: chunk.chunk_index,
+            total_chunks: chunk.total_chunks,
+            source_type: "capture".to_string(),
+            extraction_method: None,
+            app_name: payload.app_name.clone(),
+            bundle_id: payload.bundle_id.clone(),
+            captured_at: Some(payload.captured_at()),
+            revision_id,
+            char_start: revision_id.map(|_| chunk.char_start),
+            char_end: revision_id.map(|_| chunk.char_end),
+            pipeline_version: None,
+            metadata: payload.metadata.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunker::Chunker;
+
+    #[test]
+    fn test_meta_round_trip() {
+        let payload = CapturePayload {
+            title: Some("Example".to_string()),
+            timestamp: Some(1_707_500_000),
+            app_name: Some("Google Chrome".to_string()),
+            bundle_id: Some("com.google.Chrome".to_string()),
+            metadata: serde_json::from_value(serde_json::json!({"owner": "ana@example.com"})).unwrap(),
+            ..CapturePayload::new("browser", "https://example.com/a?utm_source=x", "one two three")
+        };
+        let chunks = Chunker::with_defaults().chunk(&payload.content);
+        let meta = ChunkMeta::for_chunk("doc-1", "https://example.com/a", &payload, &chunks[0], Some(7));
+        assert_eq!(meta.original_url.as_deref(), Some("https://example.com/a?utm_source=x"));
+        assert_eq!((meta.char_start, meta.char_end), (Some(0), Some(13)));
+        assert_eq!(meta.metadata, payload.metadata);
+
+        let json = serde_json::to_string(&meta).unwrap();
+        assert_eq!(serde_json::from_str::<ChunkMeta>(&json).unwrap(), meta);
+    }
+
+    #[test]
+    fn test_meta_written_by_the_daemon() {
+        // The accessibility daemon leaves the capture-only fields out
+        let meta: ChunkMeta = serde_json::from_str(
+            r#"{"id":"doc-2","source":"word","url":"accessibility://Microsoft_Word/Report.docx",
+                "title":"Report.docx","chunk_index":0,"total_chunks":1,"source_type":"accessibility",
+                "extraction_method":"accessibility","app_name":"Microsoft Word"}"#,
+        )
+        .unwrap();
+        assert_eq!(meta.extraction_method.as_deref(), Some("accessibility"));
+        assert_eq!(meta.revision_id, None);
+
+        let value = serde_json::to_value(&meta).unwrap();
+        assert!(value.get("captured_at").is_none());
+        assert!(value.get("metadata").is_none());
+        assert_eq!(serde_json::from_value::<ChunkMeta>(value).unwrap(), meta);
+    }
+}
@@ -0,0 +1,212 @@
+//! Structured metadata carried by a capture
+//!
+//! Sites have context that does not fit the fixed payload fields: a Jira
+//! ticket's status and assignee, an email's recipients, a Slack thread id.
+//! Extractors put it in [`CapturePayload::metadata`](crate::CapturePayload),
+//! a map of JSON scalars (or lists of scalars) that is stored with every
+//! chunk and can be filtered on.
+//!
+//! Any key that passes [`validate`] is accepted. The keys in [`keys`] have a
+//! fixed meaning and type, so consumers can rely on them across sites.
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Metadata map, sorted by key so its JSON form is canonical
+pub type Metadata = BTreeMap<String, Value>;
+
+/// Most keys a payload may carry
+pub const MAX_KEYS: usize = 32;
+/// Longest key, in bytes
+pub const MAX_KEY_LEN: usize = 64;
+/// Largest value, in bytes of JSON
+pub const MAX_VALUE_LEN: usize = 4096;
+
+/// Well-known keys
+pub mod keys {
+    /// Ticket or issue key, e.g. "PROJ-123"
+    pub const TICKET_KEY: &str = "ticket_key";
+    /// Workflow status, e.g. "In Progress"
+    pub const STATUS: &str = "status";
+    /// Person the item is assigned to
+    pub const ASSIGNEE: &str = "assignee";
+    /// Person who filed the item
+    pub const REPORTER: &str = "reporter";
+    /// Priority as shown by the site, e.g. "High"
+    pub const PRIORITY: &str = "priority";
+    /// Labels or tags
+    pub const LABELS: &str = "labels";
+    /// Email recipients
+    pub const RECIPIENTS: &str = "recipients";
+    /// Email CC recipients
+    pub const CC: &str = "cc";
+    /// Owner of a document
+    pub const OWNER: &str = "owner";
+    /// Conversation thread identifier (Slack thread ts, email thread id)
+    pub const THREAD_ID: &str = "thread_id";
+}
+
+/// Type a well-known key must have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    StringList,
+}
+
+impl ValueKind {
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            ValueKind::String => value.is_string(),
+            ValueKind::StringList => value.as_array().is_some_and(|items| items.iter().all(Value::is_string)),
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            ValueKind::String => "a string",
+            ValueKind::StringList => "a list of strings",
+        }
+    }
+}
+
+/// Well-known keys and their types
+pub const WELL_KNOWN: &[(&str, ValueKind)] = &[
+    (keys::TICKET_KEY, ValueKind::String),
+    (keys::STATUS, ValueKind::String),
+    (keys::ASSIGNEE, ValueKind::String),
+    (keys::REPORTER, ValueKind::String),
+    (keys::PRIORITY, ValueKind::String),
+    (keys::LABELS, ValueKind::StringList),
+    (keys::RECIPIENTS, ValueKind::StringList),
+    (keys::CC, ValueKind::StringList),
+    (keys::OWNER, ValueKind::String),
+    (keys::THREAD_ID, ValueKind::String),
+];
+
+/// Why a metadata map was rejected
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MetadataError {
+    #[error("too many metadata keys ({0}, at most {MAX_KEYS})")]
+    TooManyKeys(usize),
+    #[error("invalid metadata key {0:?}: use lowercase letters, digits, '_' and '.', starting with a letter")]
+    InvalidKey(String),
+    #[error("metadata value for {0:?} must be a string, number, boolean or a list of those")]
+    UnsupportedValue(String),
+    #[error("metadata value for {0:?} is larger than {MAX_VALUE_LEN} bytes")]
+    ValueTooLarge(String),
+    #[error("metadata key {key:?} must be {expected}")]
+    WrongType { key: String, expected: &'static str },
+}
+
+/// Check keys, value shapes, sizes and the types of well-known keys
+pub fn validate(metadata: &Metadata) -> Result<(), MetadataError> {
+    if metadata.len() > MAX_KEYS {
+        return Err(MetadataError::TooManyKeys(metadata.len()));
+    }
+    for (key, value) in metadata {
+        if !valid_key(key) {
+            return Err(MetadataError::InvalidKey(key.clone()));
+        }
+        let supported = match value {
+            Value::Array(items) => items.iter().all(is_scalar),
+            value => is_scalar(value),
+        };
+        if !supported {
+            return Err(MetadataError::UnsupportedValue(key.clone()));
+        }
+        if value.to_string().len() > MAX_VALUE_LEN {
+            return Err(MetadataError::ValueTooLarge(key.clone()));
+        }
+        if let Some((_, kind)) = WELL_KNOWN.iter().find(|(known, _)| *known == key.as_str()) {
+            if !kind.matches(value) {
+                return Err(MetadataError::WrongType { key: key.clone(), expected: kind.describe() });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn valid_key(key: &str) -> bool {
+    key.len() <= MAX_KEY_LEN
+        && key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '.')
+}
+
+fn is_scalar(value: &Value) -> bool {
+    matches!(value, Value::String(_) | Value::Number(_) | Value::Bool(_))
+}
+
+/// JSON Schema of a metadata map, matching [`validate`] except for sizes
+pub(crate) fn json_schema(_: &mut SchemaGenerator) -> Schema {
+    let scalar = serde_json::json!({ "type": ["string", "number", "boolean"] });
+    let mut properties = serde_json::Map::new();
+    for (key, kind) in WELL_KNOWN {
+        let schema = match kind {
+            ValueKind::String => serde_json::json!({ "type": "string" }),
+            ValueKind::StringList => serde_json::json!({ "type": "array", "items": { "type": "string" } }),
+        };
+        properties.insert(key.to_string(), schema);
+    }
+
+    serde_json::from_value(serde_json::json!({
+        "description": "Structured context from the site (see capture_protocol::metadata)",
+        "type": "object",
+        "maxProperties": MAX_KEYS,
+        "propertyNames": { "pattern": "^[a-z][a-z0-9_.]*$", "maxLength": MAX_KEY_LEN },
+        "properties": properties,
+        "additionalProperties": { "anyOf": [scalar, { "type": "array", "items": scalar }] }
+    }))
+    .expect("metadata schema is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn metadata(value: Value) -> Metadata {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_valid_metadata() {
+        let valid = metadata(json!({
+            "ticket_key": "PROJ-123",
+            "labels": ["backend", "p1"],
+            "story_points": 3,
+            "jira.sprint": "Sprint 12",
+            "blocked": false,
+        }));
+        assert_eq!(validate(&valid), Ok(()));
+        assert_eq!(validate(&Metadata::new()), Ok(()));
+    }
+
+    #[test]
+    fn test_invalid_metadata() {
+        let cases = [
+            (json!({ "Status": "Done" }), MetadataError::InvalidKey("Status".to_string())),
+            (json!({ "1st": "x" }), MetadataError::InvalidKey("1st".to_string())),
+            (json!({ "owner": null }), MetadataError::UnsupportedValue("owner".to_string())),
+            (json!({ "nested": { "a": 1 } }), MetadataError::UnsupportedValue("nested".to_string())),
+            (json!({ "pairs": [[1, 2]] }), MetadataError::UnsupportedValue("pairs".to_string())),
+            (
+                json!({ "status": 3 }),
+                MetadataError::WrongType { key: "status".to_string(), expected: "a string" },
+            ),
+            (
+                json!({ "recipients": "a@example.com" }),
+                MetadataError::WrongType { key: "recipients".to_string(), expected: "a list of strings" },
+            ),
+            (json!({ "body": "x".repeat(MAX_VALUE_LEN) }), MetadataError::ValueTooLarge("body".to_string())),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(validate(&metadata(value)), Err(expected));
+        }
+
+        let many: Metadata = (0..=MAX_KEYS).map(|i| (format!("k{}", i), json!(i))).collect();
+        assert_eq!(validate(&many), Err(MetadataError::TooManyKeys(MAX_KEYS + 1)));
+    }
+}
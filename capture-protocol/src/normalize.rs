@@ -0,0 +1,120 @@
+//! Text normalization for content hashes
+//!
+//! Two captures of the same document can differ in ways no reader sees: CRLF
+//! line endings, trailing spaces, a non-breaking space, a zero-width
+//! character, an "é" written as "e" plus a combining accent. Content hashes
+//! are taken over [`normalize`]d text so such captures dedup instead of
+//! rewriting the stored source. The text itself is stored as captured unless
+//! the ingestion server is configured to store it normalized.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Characters with no visible rendering that pages and apps sprinkle into
+/// text: zero-width spaces and joiners, the BOM, soft hyphens and
+/// directional marks
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{034F}'
+            | '\u{061C}'
+            | '\u{180E}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{FEFF}'
+    )
+}
+
+/// Normalize text for hashing:
+///
+/// - Unicode NFC
+/// - line endings (CRLF, CR, Unicode line and paragraph separators) become `\n`
+/// - invisible characters are removed
+/// - every other whitespace character (NBSP, ideographic space, ...) is a
+///   space; runs of spaces and tabs collapse to one, a tab if the run had one
+///   so tabular text keeps its columns
+/// - lines are trimmed, runs of blank lines collapse to one, and leading
+///   and trailing blank lines are dropped
+///
+/// Text that is already normal comes back unchanged, so its hash is the
+/// hash of the raw text.
+pub fn normalize(text: &str) -> String {
+    let mut cleaned = String::with_capacity(text.len());
+    let mut chars = text.nfc().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                cleaned.push('\n');
+            }
+            '\n' | '\u{000B}' | '\u{000C}' | '\u{0085}' | '\u{2028}' | '\u{2029}' => cleaned.push('\n'),
+            '\t' => cleaned.push('\t'),
+            c if is_invisible(c) => {}
+            c if c.is_whitespace() => cleaned.push(' '),
+            c => cleaned.push(c),
+        }
+    }
+
+    let mut normalized = String::with_capacity(cleaned.len());
+    let mut blank = false;
+    for line in cleaned.split('\n') {
+        let line = line.trim_matches([' ', '\t']);
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        if !normalized.is_empty() {
+            normalized.push_str(if blank { "\n\n" } else { "\n" });
+        }
+        blank = false;
+
+        let mut gap: Option<char> = None;
+        for c in line.chars() {
+            if c == ' ' || c == '\t' {
+                gap = Some(if c == '\t' || gap == Some('\t') { '\t' } else { ' ' });
+            } else {
+                normalized.extend(gap.take());
+                normalized.push(c);
+            }
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invisible_differences_normalize_away() {
+        let text = "Quarterly plan\n\nShip the importer\tQ3";
+        assert_eq!(normalize(text), text);
+
+        for variant in [
+            "Quarterly plan\r\n\r\nShip the importer\tQ3\r\n",
+            "  Quarterly plan  \n\n\n\nShip the importer \t Q3\n\n",
+            "\u{FEFF}Quarterly\u{00A0}plan\n\nShip the\u{200B} importer\tQ3",
+            "Quarterly  plan\u{2029}\nShip the im\u{00AD}porter\tQ3",
+        ] {
+            assert_eq!(normalize(variant), text, "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn test_nfc() {
+        assert_eq!(normalize("Cafe\u{0301} menu"), "Caf\u{00E9} menu");
+        assert_eq!(normalize("Caf\u{00E9} menu"), "Caf\u{00E9} menu");
+    }
+
+    #[test]
+    fn test_visible_differences_remain() {
+        assert_ne!(normalize("one two"), normalize("one\ntwo"));
+        assert_ne!(normalize("a\tb"), normalize("a b"));
+        assert_ne!(normalize("one\ntwo"), normalize("one\n\ntwo"));
+        assert_eq!(normalize(" \r\n\u{200B} "), "");
+    }
+}
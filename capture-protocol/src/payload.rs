@@ -0,0 +1,331 @@
+//! Socket protocol: capture payloads in, ingestion responses out
+
+use crate::message::ChatMessage;
+use crate::metadata::Metadata;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Payload received from any ingestion source (browser, clipboard, etc.)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CapturePayload {
+    /// Source type: "slack", "gmail", "jira", "browser", "clipboard", etc.
+    pub source: String,
+
+    /// Location identifier (URL, path, or empty string if none)
+    pub url: String,
+
+    /// The text content to ingest
+    pub content: String,
+
+    /// Format of `content`. HTML is reduced to its main content as markdown
+    /// before it is stored.
+    #[serde(default, skip_serializing_if = "ContentType::is_plain")]
+    pub content_type: ContentType,
+
+    /// Optional title/subject
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    /// Optional author/sender
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+
+    /// Optional channel/project/workspace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+
+    /// Optional unix timestamp in seconds (defaults to now)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+
+    /// Application display name (e.g., "Microsoft Word", "Google Chrome")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+
+    /// Application bundle ID (e.g., "com.microsoft.Word")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle_id: Option<String>,
+
+    /// Structured context from the site: ticket status, recipients, thread id, ...
+    /// Rules and well-known keys are in `capture_protocol::metadata`.
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    #[schemars(schema_with = "crate::metadata::json_schema")]
+    pub metadata: Metadata,
+
+    /// Individual messages, for chat captures. When present the server stores
+    /// the conversation built from them and ignores `content`, which clients
+    /// should still fill in for servers that predate this field.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<ChatMessage>,
+}
+
+/// Format of a payload's `content`, as a MIME type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ContentType {
+    #[default]
+    #[serde(rename = "text/plain")]
+    Plain,
+    #[serde(rename = "text/markdown")]
+    Markdown,
+    #[serde(rename = "text/html")]
+    Html,
+}
+
+impl ContentType {
+    pub fn is_plain(&self) -> bool {
+        *self == ContentType::Plain
+    }
+}
+
+impl CapturePayload {
+    /// A plain-text payload with no optional fields set
+    pub fn new(source: impl Into<String>, url: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            url: url.into(),
+            content: content.into(),
+            content_type: ContentType::Plain,
+            title: None,
+            author: None,
+            channel: None,
+            timestamp: None,
+            app_name: None,
+            bundle_id: None,
+            metadata: Metadata::default(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Capture time in unix seconds: the payload timestamp, or now if absent
+    pub fn captured_at(&self) -> i64 {
+        self.timestamp
+            .unwrap_or_else(|| chrono::Utc::now().timestamp())
+    }
+}
+
+/// Response sent back to the caller
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IngestionResponse {
+    pub status: ResponseStatus,
+    pub action: IngestionAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ehl_doc_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_count: Option<usize>,
+    /// How an update changed the stored chunks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_diff: Option<ChunkDiff>,
+    /// How the text differs from the stored revision, on updates and on
+    /// changes skipped as insignificant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_diff: Option<TextDiff>,
+    /// Why a payload was skipped, for clients that act on it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<SkipReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseStatus {
+    Ok,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum IngestionAction {
+    Created,
+    Updated,
+    Skipped,
+    Failed,
+}
+
+/// Chunks an update inserted, soft-deleted and carried over unchanged
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct ChunkDiff {
+    pub added: usize,
+    pub removed: usize,
+    pub kept: usize,
+}
+
+/// Characters and lines an update inserts and deletes, compared with the
+/// stored text (both masked and normalized as for hashing). An edited line
+/// counts as one line deleted and one inserted.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct TextDiff {
+    pub inserted_chars: usize,
+    pub deleted_chars: usize,
+    pub inserted_lines: usize,
+    pub deleted_lines: usize,
+    /// The larger of inserted and deleted characters over the length of the
+    /// longer text, 0-1
+    pub change_ratio: f64,
+}
+
+impl TextDiff {
+    /// Whether the texts are the same
+    pub fn is_empty(&self) -> bool {
+        self.inserted_chars == 0 && self.deleted_chars == 0
+    }
+}
+
+/// Why a payload was not stored
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Same content as the stored revision
+    Unchanged,
+    /// Changed too little to be worth a new revision
+    Insignificant,
+    /// Covered by a "forget" tombstone
+    Forgotten,
+    /// Source updated too often; the latest payload may be stored later
+    Throttled,
+    /// Mostly boilerplate, or too little text to be worth keeping
+    LowInformation,
+}
+
+impl IngestionResponse {
+    pub fn created(ehl_doc_id: String, chunk_count: usize) -> Self {
+        Self {
+            status: ResponseStatus::Ok,
+            action: IngestionAction::Created,
+            ehl_doc_id: Some(ehl_doc_id),
+            chunk_count: Some(chunk_count),
+            chunk_diff: None,
+            text_diff: None,
+            reason: None,
+            message: None,
+        }
+    }
+
+    pub fn updated(ehl_doc_id: String, chunk_count: usize, chunk_diff: ChunkDiff) -> Self {
+        Self {
+            status: ResponseStatus::Ok,
+            action: IngestionAction::Updated,
+            ehl_doc_id: Some(ehl_doc_id),
+            chunk_count: Some(chunk_count),
+            chunk_diff: Some(chunk_diff),
+            text_diff: None,
+            reason: None,
+            message: None,
+        }
+    }
+
+    pub fn skipped(reason: SkipReason, message: &str) -> Self {
+        Self {
+            status: ResponseStatus::Ok,
+            action: IngestionAction::Skipped,
+            ehl_doc_id: None,
+            chunk_count: None,
+            chunk_diff: None,
+            text_diff: None,
+            reason: Some(reason),
+            message: Some(message.to_string()),
+        }
+    }
+
+    /// Attach how the text changed
+    pub fn with_text_diff(mut self, text_diff: TextDiff) -> Self {
+        self.text_diff = Some(text_diff);
+        self
+    }
+
+    pub fn error(message: &str) -> Self {
+        Self {
+            status: ResponseStatus::Error,
+            action: IngestionAction::Failed,
+            ehl_doc_id: None,
+            chunk_count: None,
+            chunk_diff: None,
+            text_diff: None,
+            reason: None,
+            message: Some(message.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full_payload() -> CapturePayload {
+        CapturePayload {
+            source: "slack".to_string(),
+            url: "https://app.slack.com/client/T1/C2".to_string(),
+            content: "Deploy is done\nThanks!".to_string(),
+            content_type: ContentType::Markdown,
+            title: Some("#releases".to_string()),
+            author: Some("Ana".to_string()),
+            channel: Some("releases".to_string()),
+            timestamp: Some(1_707_500_000),
+            app_name: Some("Slack".to_string()),
+            bundle_id: Some("com.tinyspeck.slackmacgap".to_string()),
+            metadata: serde_json::from_value(serde_json::json!({"thread_id": "1707499000.000100"})).unwrap(),
+            messages: vec![
+                ChatMessage {
+                    author: Some("Ana".to_string()),
+                    timestamp: Some(1_707_499_990),
+                    message_id: Some("1707499990.000200".to_string()),
+                    ..ChatMessage::new("Deploy is done")
+                },
+                ChatMessage::new("Thanks!"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_payload_round_trip() {
+        let payload = full_payload();
+        let json = serde_json::to_string(&payload).unwrap();
+        assert_eq!(serde_json::from_str::<CapturePayload>(&json).unwrap(), payload);
+
+        // Every field is written, so none can be lost between components
+        let value = serde_json::to_value(&payload).unwrap();
+        assert_eq!(value.as_object().unwrap().len(), 12);
+    }
+
+    #[test]
+    fn test_minimal_payload() {
+        let payload: CapturePayload =
+            serde_json::from_str(r#"{"source":"clipboard","url":"","content":"hello"}"#).unwrap();
+        assert_eq!(payload.title, None);
+        assert_eq!(payload.timestamp, None);
+        assert_eq!(payload.content_type, ContentType::Plain);
+        assert!(payload.metadata.is_empty());
+        assert!(payload.messages.is_empty());
+        assert_eq!(
+            serde_json::to_string(&payload).unwrap(),
+            r#"{"source":"clipboard","url":"","content":"hello"}"#
+        );
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        let responses = [
+            IngestionResponse::created("doc-1".to_string(), 3),
+            IngestionResponse::updated("doc-1".to_string(), 4, ChunkDiff { added: 1, removed: 0, kept: 3 }),
+            IngestionResponse::updated("doc-1".to_string(), 4, ChunkDiff::default()).with_text_diff(TextDiff {
+                inserted_chars: 12,
+                deleted_chars: 3,
+                inserted_lines: 1,
+                deleted_lines: 1,
+                change_ratio: 0.05,
+            }),
+            IngestionResponse::skipped(SkipReason::LowInformation, "Mostly boilerplate"),
+            IngestionResponse::error("Invalid JSON"),
+        ];
+        for response in responses {
+            let json = serde_json::to_string(&response).unwrap();
+            assert_eq!(serde_json::from_str::<IngestionResponse>(&json).unwrap(), response);
+        }
+
+        let json = serde_json::to_string(&IngestionResponse::skipped(SkipReason::Unchanged, "Duplicate")).unwrap();
+        assert_eq!(
+            json,
+            r#"{"status":"ok","action":"skipped","reason":"unchanged","message":"Duplicate"}"#
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! PII redaction of captured text
+//!
+//! The router redacts every capture before sending it, and the ingestion
+//! server applies the same rules again: not every client goes through the
+//! router, and `ingestion-server reprocess` re-applies them to stored text
+//! after they change. [`Redactor::version`] names the rules in effect and is
+//! recorded in the pipeline version of every chunk.
+//!
+//! ```toml
+//! redact_credit_cards = true    # Luhn-valid card numbers only
+//! redact_ssn = true
+//! redact_api_keys = true        # key/token/password assignments and AWS keys
+//! redact_emails = false
+//! redact_phone_numbers = false
+//! ```
+//!
+//! Redacting already redacted text changes nothing.
+
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+/// Version of the redaction patterns. Bump it when a pattern changes, so
+/// stored text is redacted again by the next reprocessing run.
+pub const REDACTION_VERSION: u32 = 1;
+
+/// Which kinds of PII are redacted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub redact_credit_cards: bool,
+    pub redact_ssn: bool,
+    pub redact_api_keys: bool,
+    pub redact_emails: bool,
+    pub redact_phone_numbers: bool,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            redact_credit_cards: true,
+            redact_ssn: true,
+            redact_api_keys: true,
+            redact_emails: false,
+            redact_phone_numbers: false,
+        }
+    }
+}
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("redaction pattern compiles"))
+}
+
+fn credit_card() -> &'static Regex {
+    // 13-19 digits, optionally grouped by spaces or dashes
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    regex(&PATTERN, r"\b(?:\d{4}[-\s]?){3,4}\d{1,4}\b")
+}
+
+fn ssn() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    regex(&PATTERN, r"\b\d{3}-\d{2}-\d{4}\b")
+}
+
+fn api_key() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    regex(
+        &PATTERN,
+        r#"(?i)(api[_-]?key|apikey|secret[_-]?key|access[_-]?token|auth[_-]?token)['"]?\s*[:=]\s*['"]?([a-zA-Z0-9_\-]{20,})"#,
+    )
+}
+
+fn aws_key() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    regex(&PATTERN, r"(?i)(AKIA[0-9A-Z]{16})")
+}
+
+fn password_field() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    regex(&PATTERN, r#"(?i)(password|passwd|pwd)['"]?\s*[:=]\s*['"]?([^\s'"]{4,})"#)
+}
+
+fn email() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    regex(&PATTERN, r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b")
+}
+
+fn phone() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    regex(&PATTERN, r"\b(?:\+1[-.\s]?)?\(?\d{3}\)?[-.\s]?\d{3}[-.\s]?\d{4}\b")
+}
+
+/// Applies the enabled redaction rules
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    config: RedactionConfig,
+}
+
+impl Redactor {
+    pub fn new(config: RedactionConfig) -> Self {
+        Self { config }
+    }
+
+    /// The enabled rules, as recorded in chunk pipeline versions:
+    /// `r{REDACTION_VERSION}:` followed by the rule names
+    pub fn version(&self) -> String {
+        let rules = [
+            (self.config.redact_credit_cards, "cards"),
+            (self.config.redact_ssn, "ssn"),
+            (self.config.redact_api_keys, "keys"),
+            (self.config.redact_emails, "emails"),
+            (self.config.redact_phone_numbers, "phones"),
+        ];
+        let enabled: Vec<&str> = rules.iter().filter(|(on, _)| *on).map(|(_, name)| *name).collect();
+        format!("r{}:{}", REDACTION_VERSION, enabled.join(","))
+    }
+
+    /// Replace the PII in `text` with `[REDACTED_*]` markers
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        let mut apply = |regex: &Regex, replace: &dyn Fn(&Captures) -> String| {
+            if let Cow::Owned(redacted) = regex.replace_all(&text, |caps: &Captures| replace(caps)) {
+                text = Cow::Owned(redacted);
+            }
+        };
+
+        if self.config.redact_credit_cards {
+            // Only Luhn-valid numbers, to spare order numbers and the like
+            apply(credit_card(), &|caps| {
+                let digits: String = caps[0].chars().filter(|c| c.is_ascii_digit()).collect();
+                if is_valid_luhn(&digits) {
+                    "[REDACTED_CARD]".to_string()
+                } else {
+                    caps[0].to_string()
+                }
+            });
+        }
+        if self.config.redact_ssn {
+            apply(ssn(), &|_| "[REDACTED_SSN]".to_string());
+        }
+        if self.config.redact_api_keys {
+            apply(api_key(), &|caps| format!("{}=[REDACTED_KEY]", &caps[1]));
+            apply(aws_key(), &|_| "[REDACTED_AWS_KEY]".to_string());
+            apply(password_field(), &|caps| format!("{}=[REDACTED_PASSWORD]", &caps[1]));
+        }
+        if self.config.redact_emails {
+            apply(email(), &|_| "[REDACTED_EMAIL]".to_string());
+        }
+        if self.config.redact_phone_numbers {
+            apply(phone(), &|_| "[REDACTED_PHONE]".to_string());
+        }
+        text
+    }
+}
+
+/// Luhn checksum of a 13-19 digit card number
+pub fn is_valid_luhn(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.len() < 13 || digits.len() > 19 {
+        return false;
+    }
+
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (1, doubled) if doubled > 9 => doubled - 9,
+            (1, doubled) => doubled,
+            _ => d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_luhn() {
+        assert!(is_valid_luhn("4532015112830366"));
+        assert!(is_valid_luhn("5425233430109903"));
+        assert!(!is_valid_luhn("1234567890123456"));
+        assert!(!is_valid_luhn("453201511283"));
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let redactor = Redactor::default();
+        let text = "Card 4532015112830366, order 1234567890123456, SSN 123-45-6789, \
+                    api_key=sk_test_FAKE_KEY_FOR_TESTING_1234, mail ana@example.com";
+        let redacted = redactor.redact(text);
+        assert_eq!(
+            redacted,
+            "Card [REDACTED_CARD], order 1234567890123456, SSN [REDACTED_SSN], \
+             api_key=[REDACTED_KEY], mail ana@example.com"
+        );
+        // Idempotent, and text without PII is not copied
+        assert_eq!(redactor.redact(&redacted), redacted);
+        assert!(matches!(redactor.redact("nothing to see"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_version_names_the_enabled_rules() {
+        assert_eq!(Redactor::default().version(), format!("r{}:cards,ssn,keys", REDACTION_VERSION));
+        let emails = Redactor::new(RedactionConfig {
+            redact_emails: true,
+            ..Default::default()
+        });
+        assert_eq!(emails.redact("ana@example.com"), "[REDACTED_EMAIL]");
+        assert_ne!(emails.version(), Redactor::default().version());
+    }
+}
@@ -0,0 +1,89 @@
+//! JSON Schemas for the shared formats
+//!
+//! The copies in `capture-protocol/schema/` are what non-Rust clients (the
+//! browser extension, native host, external indexers) validate against. A
+//! test fails when they drift from the Rust types; regenerate them with
+//! `UPDATE_SCHEMAS=1 cargo test` in `capture-protocol/`.
+
+use crate::meta::ChunkMeta;
+use crate::payload::{CapturePayload, IngestionResponse};
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+
+/// A published schema: file name under `schema/` and how to generate it
+pub struct Published {
+    pub file: &'static str,
+    pub generate: fn() -> RootSchema,
+}
+
+/// Every schema published in `capture-protocol/schema/`
+pub const PUBLISHED: &[Published] = &[
+    Published { file: "capture-payload.schema.json", generate: capture_payload },
+    Published { file: "ingestion-response.schema.json", generate: ingestion_response },
+    Published { file: "chunk-meta.schema.json", generate: chunk_meta },
+];
+
+/// One line of JSON sent to the ingestion socket
+pub fn capture_payload() -> RootSchema {
+    schema_for!(CapturePayload)
+}
+
+/// One line of JSON the ingestion socket answers with
+pub fn ingestion_response() -> RootSchema {
+    schema_for!(IngestionResponse)
+}
+
+/// The `meta` column of a chunk
+pub fn chunk_meta() -> RootSchema {
+    schema_for!(ChunkMeta)
+}
+
+/// Pretty-printed schema as written to the published file
+pub fn to_json(schema: &RootSchema) -> String {
+    let mut json = serde_json::to_string_pretty(schema).expect("schema serializes");
+    json.push('\n');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_published_schemas_are_current() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("schema");
+        let update = std::env::var_os("UPDATE_SCHEMAS").is_some();
+
+        for published in PUBLISHED {
+            let path = dir.join(published.file);
+            let generated = to_json(&(published.generate)());
+            if update {
+                std::fs::write(&path, &generated).unwrap();
+                continue;
+            }
+            let stored = std::fs::read_to_string(&path).unwrap_or_default();
+            assert!(
+                stored == generated,
+                "{} is out of date; run UPDATE_SCHEMAS=1 cargo test in capture-protocol/",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_schema_lists_every_payload_field() {
+        let schema = serde_json::to_value(capture_payload()).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        let fields = [
+            "source", "url", "content", "content_type", "title", "author", "channel", "timestamp", "app_name", "bundle_id", "metadata",
+            "messages",
+        ];
+        for field in fields {
+            assert!(properties.contains_key(field), "missing {}", field);
+        }
+
+        let required: Vec<&str> = schema["required"].as_array().unwrap().iter().filter_map(|v| v.as_str()).collect();
+        assert_eq!(required, vec!["content", "source", "url"]);
+    }
+}
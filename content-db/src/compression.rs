@@ -0,0 +1,335 @@
+//! Optional zstd compression of chunk text
+//!
+//! A compressed chunk keeps an empty `text` and stores its body in
+//! `text_zst` as a single zstd frame. Frames made with a trained dictionary
+//! carry the dictionary's id in their header; dictionaries live in
+//! `compression_dicts` and are never changed once written, so old frames stay
+//! readable after a new dictionary is trained.
+//!
+//! Readers do not decode by hand: [`configure`](crate::configure) registers a
+//! `chunk_text(text, text_zst)` SQL function on every connection, and queries
+//! select `chunk_text(text, text_zst)` wherever they used to select `text`.
+
+use rusqlite::functions::{Context, FunctionFlags};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+use zstd::zstd_safe;
+
+/// Creates the dictionary table; safe to run on every open
+pub const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS compression_dicts (
+        dict_id INTEGER PRIMARY KEY,
+        dict BLOB NOT NULL,
+        sample_count INTEGER NOT NULL,
+        created_at INTEGER NOT NULL
+    );";
+
+/// zstd level used when the config does not set one
+pub const DEFAULT_LEVEL: i32 = 3;
+
+/// Upper bound on a trained dictionary (zstd's own default)
+pub const DICT_MAX_SIZE: usize = 112_640;
+
+/// Decoded dictionaries by id, shared by every connection in the process
+fn decoder_dicts() -> &'static Mutex<HashMap<u32, Arc<DecoderDictionary<'static>>>> {
+    static DICTS: OnceLock<Mutex<HashMap<u32, Arc<DecoderDictionary<'static>>>>> = OnceLock::new();
+    DICTS.get_or_init(Default::default)
+}
+
+/// Create the dictionary table, and the `text_zst` column if the chunks
+/// table exists without it
+pub fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(SCHEMA)?;
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('chunks')")?;
+    let columns = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+    if !columns.is_empty() && !columns.iter().any(|c| c == "text_zst") {
+        conn.execute_batch("ALTER TABLE chunks ADD COLUMN text_zst BLOB")?;
+    }
+    Ok(())
+}
+
+/// Register `chunk_text(text, text_zst)`, which returns `text` unless
+/// `text_zst` holds a frame, in which case it returns the decompressed frame
+pub fn register_functions(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        "chunk_text",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx: &Context| match ctx.get_raw(1).as_blob_or_null()? {
+            Some(frame) => {
+                // SAFETY: the reference does not outlive this call, and only
+                // reads the dictionary table
+                let conn = unsafe { ctx.get_connection()? };
+                decompress(&conn, frame)
+            }
+            None => ctx.get::<String>(0),
+        },
+    )
+}
+
+/// Compresses chunk text, with the dictionary it was created with if any
+pub struct Compressor {
+    level: i32,
+    dict: Option<(u32, EncoderDictionary<'static>)>,
+}
+
+impl Compressor {
+    /// Plain zstd at `level`
+    pub fn new(level: i32) -> Self {
+        Self { level, dict: None }
+    }
+
+    /// zstd at `level` with a dictionary produced by [`train`]
+    pub fn with_dictionary(level: i32, dict: &[u8]) -> Self {
+        let dict = zstd_safe::get_dict_id_from_dict(dict).map(|id| (id.get(), EncoderDictionary::copy(dict, level)));
+        Self { level, dict }
+    }
+
+    /// Use the most recently trained dictionary in the database, if any
+    pub fn load(conn: &Connection, level: i32) -> rusqlite::Result<Self> {
+        let dict: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT dict FROM compression_dicts ORDER BY created_at DESC, rowid DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(match dict {
+            Some(dict) => Self::with_dictionary(level, &dict),
+            None => Self::new(level),
+        })
+    }
+
+    pub fn level(&self) -> i32 {
+        self.level
+    }
+
+    /// Id of the dictionary frames are compressed with
+    pub fn dict_id(&self) -> Option<u32> {
+        self.dict.as_ref().map(|(id, _)| *id)
+    }
+
+    /// Compress `text` into one frame that records its own size
+    pub fn compress(&self, text: &str) -> io::Result<Vec<u8>> {
+        match &self.dict {
+            Some((_, dict)) => zstd::bulk::Compressor::with_prepared_dictionary(dict)?.compress(text.as_bytes()),
+            None => zstd::bulk::compress(text.as_bytes(), self.level),
+        }
+    }
+
+    /// The `(text, text_zst)` pair to store: compressed only when that is
+    /// actually smaller, since very short chunks grow under zstd
+    pub fn encode(&self, text: &str) -> io::Result<(String, Option<Vec<u8>>)> {
+        let frame = self.compress(text)?;
+        if frame.len() < text.len() {
+            Ok((String::new(), Some(frame)))
+        } else {
+            Ok((text.to_string(), None))
+        }
+    }
+}
+
+/// Decompress a frame written by [`Compressor`], loading its dictionary
+/// from `conn` the first time it is seen
+pub fn decompress(conn: &Connection, frame: &[u8]) -> rusqlite::Result<String> {
+    let capacity = match zstd_safe::get_frame_content_size(frame) {
+        Ok(Some(size)) => size as usize,
+        _ => return Err(user_error("chunk frame has no content size")),
+    };
+
+    let bytes = match zstd_safe::get_dict_id_from_frame(frame) {
+        Some(dict_id) => {
+            let dict = decoder_dict(conn, dict_id.get())?;
+            zstd::bulk::Decompressor::with_prepared_dictionary(&dict)
+                .and_then(|mut d| d.decompress(frame, capacity))
+                .map_err(user_error)?
+        }
+        None => zstd::bulk::decompress(frame, capacity).map_err(user_error)?,
+    };
+    String::from_utf8(bytes).map_err(user_error)
+}
+
+fn decoder_dict(conn: &Connection, dict_id: u32) -> rusqlite::Result<Arc<DecoderDictionary<'static>>> {
+    if let Some(dict) = decoder_dicts().lock().unwrap_or_else(|e| e.into_inner()).get(&dict_id) {
+        return Ok(dict.clone());
+    }
+    let bytes: Vec<u8> = conn
+        .query_row(
+            "SELECT dict FROM compression_dicts WHERE dict_id = ?1",
+            params![dict_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| user_error(format!("unknown compression dictionary {}", dict_id)))?;
+    let dict = Arc::new(DecoderDictionary::copy(&bytes));
+    decoder_dicts()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(dict_id, dict.clone());
+    Ok(dict)
+}
+
+fn user_error<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> rusqlite::Error {
+    rusqlite::Error::UserFunctionError(e.into())
+}
+
+/// Train a dictionary on sample chunk texts. zstd needs a reasonable amount
+/// of data; with too few samples this fails rather than produce a useless
+/// dictionary.
+pub fn train<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> io::Result<Vec<u8>> {
+    zstd::dict::from_samples(samples, max_size)
+}
+
+/// Store a trained dictionary; returns its id
+pub fn store_dictionary(conn: &Connection, dict: &[u8], sample_count: usize) -> rusqlite::Result<u32> {
+    let dict_id = zstd_safe::get_dict_id_from_dict(dict)
+        .ok_or_else(|| user_error("not a zstd dictionary"))?
+        .get();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    conn.execute(
+        "INSERT OR IGNORE INTO compression_dicts (dict_id, dict, sample_count, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![dict_id, dict, sample_count as i64, now],
+    )?;
+    Ok(dict_id)
+}
+
+/// How much chunk text compression is saving
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompressionStats {
+    /// Chunk rows (live and soft-deleted) stored as zstd frames
+    pub compressed_chunks: usize,
+    /// Chunk rows stored as plain text
+    pub uncompressed_chunks: usize,
+    /// Size of the compressed chunks' text before compression
+    pub original_bytes: u64,
+    /// Size of their frames
+    pub stored_bytes: u64,
+    /// `original_bytes / stored_bytes` (0 when nothing is compressed)
+    pub ratio: f64,
+    pub dictionaries: usize,
+}
+
+/// Compression statistics over the whole chunks table
+pub fn stats(conn: &Connection) -> rusqlite::Result<CompressionStats> {
+    let mut stats = CompressionStats::default();
+
+    // The content size is in the frame header, at most 18 bytes in
+    let mut stmt = conn.prepare("SELECT length(text_zst), substr(text_zst, 1, 18) FROM chunks WHERE text_zst IS NOT NULL")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let stored: i64 = row.get(0)?;
+        let header: Vec<u8> = row.get(1)?;
+        stats.compressed_chunks += 1;
+        stats.stored_bytes += stored as u64;
+        stats.original_bytes += zstd_safe::get_frame_content_size(&header).ok().flatten().unwrap_or(0);
+    }
+
+    let uncompressed: i64 = conn.query_row("SELECT COUNT(*) FROM chunks WHERE text_zst IS NULL", [], |row| row.get(0))?;
+    let dictionaries: i64 = conn.query_row("SELECT COUNT(*) FROM compression_dicts", [], |row| row.get(0))?;
+    stats.uncompressed_chunks = uncompressed as usize;
+    stats.dictionaries = dictionaries as usize;
+    if stats.stored_bytes > 0 {
+        stats.ratio = stats.original_bytes as f64 / stats.stored_bytes as f64;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE chunks (id INTEGER PRIMARY KEY, text TEXT NOT NULL, meta TEXT NOT NULL);")
+            .unwrap();
+        ensure_schema(&conn).unwrap();
+        register_functions(&conn).unwrap();
+        conn
+    }
+
+    fn insert(conn: &Connection, compressor: &Compressor, text: &str) {
+        let (text, frame) = compressor.encode(text).unwrap();
+        conn.execute(
+            "INSERT INTO chunks (text, meta, text_zst) VALUES (?1, '{}', ?2)",
+            params![text, frame],
+        )
+        .unwrap();
+    }
+
+    fn read_all(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn.prepare("SELECT chunk_text(text, text_zst) FROM chunks ORDER BY id").unwrap();
+        let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+        rows.collect::<Result<_, _>>().unwrap()
+    }
+
+    /// Text that looks like captured chat: shared structure, varying details
+    fn sample(i: usize) -> String {
+        format!(
+            "[Alice Smith] [10:{:02} AM] Deploy #{} of the ingestion service finished; \
+             {} chunks reindexed, latency p95 {}ms. Next window is Thursday at 3pm, \
+             please review the runbook in #eng-infra before then. ",
+            i % 60,
+            i,
+            i * 7,
+            100 + i % 37
+        )
+        .repeat(3)
+    }
+
+    #[test]
+    fn test_round_trip_and_short_text() {
+        let conn = chunks_db();
+        let compressor = Compressor::new(DEFAULT_LEVEL);
+        insert(&conn, &compressor, &sample(1));
+        insert(&conn, &compressor, "hi");
+        insert(&conn, &compressor, "Grüße — ünïcödé ✓ ".repeat(20).as_str());
+
+        // Short text is left alone because zstd would only grow it
+        let plain: i64 = conn
+            .query_row("SELECT COUNT(*) FROM chunks WHERE text_zst IS NULL", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(plain, 1);
+        assert_eq!(read_all(&conn), [sample(1), "hi".to_string(), "Grüße — ünïcödé ✓ ".repeat(20)]);
+
+        // Transparent to LIKE as well
+        let hits: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM chunks WHERE chunk_text(text, text_zst) LIKE '%runbook%'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(hits, 1);
+    }
+
+    #[test]
+    fn test_dictionary() {
+        let conn = chunks_db();
+        let samples: Vec<String> = (0..400).map(sample).collect();
+        let dict = train(&samples, 16 * 1024).unwrap();
+        let dict_id = store_dictionary(&conn, &dict, samples.len()).unwrap();
+
+        let compressor = Compressor::load(&conn, DEFAULT_LEVEL).unwrap();
+        assert_eq!(compressor.dict_id(), Some(dict_id));
+        let plain = Compressor::new(DEFAULT_LEVEL);
+        let text = sample(1000);
+        assert!(compressor.compress(&text).unwrap().len() < plain.compress(&text).unwrap().len());
+
+        insert(&conn, &compressor, &text);
+        insert(&conn, &plain, &sample(1001));
+        assert_eq!(read_all(&conn), [text, sample(1001)]);
+
+        let stats = stats(&conn).unwrap();
+        assert_eq!((stats.compressed_chunks, stats.uncompressed_chunks, stats.dictionaries), (2, 0, 1));
+        assert_eq!(stats.original_bytes, (sample(1000).len() + sample(1001).len()) as u64);
+        assert!(stats.ratio > 1.0);
+    }
+}
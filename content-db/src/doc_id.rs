@@ -0,0 +1,141 @@
+//! Document IDs derived from the source
+//!
+//! A source's `ehl_doc_id` is a name-based UUID (version 5) of its canonical
+//! source path, in a namespace per source type ([`doc_id`]). The same source
+//! gets the same ID on every machine and after a database rebuild, so links
+//! from outside (the external vector index) stay valid.
+//!
+//! IDs used to be random (version 4). The version 7 migration
+//! ([`migrate_ids`]) gives every stored source its derived ID and records the
+//! old one in `doc_id_aliases`; [`resolve`] maps an old ID to the current one.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::{uuid, Uuid};
+
+/// Root of every document ID namespace. Changing it changes every ID.
+pub const NAMESPACE: Uuid = uuid!("8f0c6f4e-3b1d-5c2a-9e47-d1a6b2c8e305");
+
+/// Creates the alias table; safe to run on every open
+pub const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS doc_id_aliases (
+        old_id TEXT PRIMARY KEY,
+        ehl_doc_id TEXT NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (datetime('now'))
+    );
+    CREATE INDEX IF NOT EXISTS idx_doc_id_aliases_doc ON doc_id_aliases(ehl_doc_id);";
+
+/// The document ID of a source
+pub fn doc_id(source_type: &str, source_path: &str) -> String {
+    let namespace = Uuid::new_v5(&NAMESPACE, source_type.as_bytes());
+    Uuid::new_v5(&namespace, source_path.as_bytes()).to_string()
+}
+
+pub fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(SCHEMA)
+}
+
+/// The current ID of a document: `id` itself, or the ID it was migrated to
+pub fn resolve(conn: &Connection, id: &str) -> rusqlite::Result<String> {
+    let current: Option<String> = conn
+        .query_row("SELECT ehl_doc_id FROM doc_id_aliases WHERE old_id = ?1", params![id], |row| row.get(0))
+        .optional()?;
+    Ok(current.unwrap_or_else(|| id.to_string()))
+}
+
+/// Give every source whose ID is not the derived one its derived ID, and
+/// rewrite the references to it in chunks, revisions and chat messages. The
+/// old ID is kept as an alias, and aliases of it are pointed at the new one.
+/// Returns how many sources changed.
+pub fn migrate_ids(conn: &Connection) -> rusqlite::Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+
+    let sources: Vec<(i64, String, String, String)> = tx
+        .prepare("SELECT id, source_type, source_path, ehl_doc_id FROM content_sources")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<Result<_, _>>()?;
+
+    let mut changed = 0;
+    for (id, source_type, source_path, old_id) in sources {
+        let new_id = doc_id(&source_type, &source_path);
+        if new_id == old_id {
+            continue;
+        }
+        tx.execute("UPDATE content_sources SET ehl_doc_id = ?1 WHERE id = ?2", params![new_id, id])?;
+        tx.execute("UPDATE doc_id_aliases SET ehl_doc_id = ?1 WHERE ehl_doc_id = ?2", params![new_id, old_id])?;
+        tx.execute(
+            "INSERT OR REPLACE INTO doc_id_aliases (old_id, ehl_doc_id) VALUES (?1, ?2)",
+            params![old_id, new_id],
+        )?;
+        changed += 1;
+    }
+
+    if changed > 0 {
+        // Rows of sources removed before the migration have no alias and keep their ID
+        tx.execute_batch(
+            "UPDATE chunks SET meta = json_set(meta, '$.id', (SELECT a.ehl_doc_id FROM doc_id_aliases a
+                 WHERE a.old_id = json_extract(CASE WHEN json_valid(chunks.meta) THEN chunks.meta END, '$.id')))
+             WHERE json_extract(CASE WHEN json_valid(meta) THEN meta END, '$.id') IN (SELECT old_id FROM doc_id_aliases);
+             UPDATE source_revisions SET ehl_doc_id = (SELECT a.ehl_doc_id FROM doc_id_aliases a
+                 WHERE a.old_id = source_revisions.ehl_doc_id)
+             WHERE ehl_doc_id IN (SELECT old_id FROM doc_id_aliases);
+             UPDATE chat_messages SET ehl_doc_id = (SELECT a.ehl_doc_id FROM doc_id_aliases a
+                 WHERE a.old_id = chat_messages.ehl_doc_id)
+             WHERE ehl_doc_id IN (SELECT old_id FROM doc_id_aliases);",
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_doc_id_is_stable_and_namespaced() {
+        let id = doc_id("chrome", "https://example.com/a");
+        assert_eq!(id, doc_id("chrome", "https://example.com/a"));
+        assert_ne!(id, doc_id("chrome", "https://example.com/b"));
+        assert_ne!(id, doc_id("gdocs", "https://example.com/a"));
+        assert_eq!(Uuid::parse_str(&id).unwrap().get_version_num(), 5);
+    }
+
+    #[test]
+    fn test_migrate_ids() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::compression::register_functions(&conn).unwrap();
+        crate::schema::migrate(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id)
+                 VALUES ('slack', 'https://acme.slack.com/archives/C1', 'h', 'random-1');
+             INSERT INTO chunks (text, meta) VALUES ('one', '{\"id\": \"random-1\", \"chunk_index\": 0}');
+             INSERT INTO chunks (text, meta) VALUES ('gone', '{\"id\": \"removed\"}');
+             INSERT INTO chunks (text, meta) VALUES ('bad', 'not json');
+             INSERT INTO source_revisions (ehl_doc_id, content_hash, text, captured_at) VALUES ('random-1', 'h', 'one', 0);
+             INSERT INTO chat_messages (ehl_doc_id, message_key, sort_at, text) VALUES ('random-1', 'k', 0, 'one');",
+        )
+        .unwrap();
+
+        assert_eq!(migrate_ids(&conn).unwrap(), 1);
+        let new_id = doc_id("slack", "https://acme.slack.com/archives/C1");
+        let ids = |sql: &str| -> Vec<String> {
+            conn.prepare(sql).unwrap().query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect()
+        };
+        assert_eq!(ids("SELECT ehl_doc_id FROM content_sources"), vec![new_id.clone()]);
+        assert_eq!(
+            ids("SELECT json_extract(meta, '$.id') FROM chunks WHERE json_valid(meta)"),
+            vec![new_id.clone(), "removed".to_string()]
+        );
+        assert_eq!(ids("SELECT ehl_doc_id FROM source_revisions"), vec![new_id.clone()]);
+        assert_eq!(ids("SELECT ehl_doc_id FROM chat_messages"), vec![new_id.clone()]);
+        assert!(ids("SELECT meta FROM chunks WHERE text = 'one'")[0].contains("\"chunk_index\":0"));
+
+        assert_eq!(resolve(&conn, "random-1").unwrap(), new_id);
+        assert_eq!(resolve(&conn, &new_id).unwrap(), new_id);
+        assert_eq!(resolve(&conn, "unknown").unwrap(), "unknown");
+
+        // Nothing left to do the second time
+        assert_eq!(migrate_ids(&conn).unwrap(), 0);
+    }
+}
@@ -0,0 +1,264 @@
+//! Shared connection setup for `content.db`
+//!
+//! The ingestion server, the accessibility daemon and the viewer all open the
+//! same SQLite file. Every connection goes through [`open`] so they agree on:
+//!
+//! - WAL journaling, so readers never block the writer and vice versa
+//! - a busy timeout, so short lock waits are absorbed by SQLite itself
+//! - retrying whole write transactions on `SQLITE_BUSY` with backoff
+//!   ([`write_transaction`]), for the cases the busy handler cannot cover
+//!   (e.g. a stale WAL snapshot)
+//!
+//! The viewer only reads and opens the database with [`OpenMode::ReadOnly`].
+//!
+//! [`schema`] creates and migrates the tables every writer shares.
+//! [`tombstone`] holds the "forget" list every writer checks before storing.
+//! [`compression`] stores chunk text as zstd frames; every connection opened
+//! here can read it back through the `chunk_text` SQL function.
+//! [`revision`] keeps the exact full text of each stored revision.
+//! [`message`] keeps the individual messages of chat sources.
+//! [`doc_id`] derives document IDs from the source and maps old IDs to them.
+
+pub mod compression;
+pub mod doc_id;
+pub mod message;
+pub mod revision;
+pub mod schema;
+pub mod tombstone;
+
+use rusqlite::{Connection, ErrorCode, OpenFlags, Transaction, TransactionBehavior};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long SQLite waits on a lock before returning `SQLITE_BUSY`
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How a connection is opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenMode {
+    /// Create the file if needed and switch it to WAL
+    ReadWrite,
+    /// Read-only; the file must already exist
+    ReadOnly,
+}
+
+/// Backoff for retrying busy write transactions
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled on each further retry
+    pub initial_backoff: Duration,
+    /// Upper bound on a single delay
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (0-based), with up to 50% jitter so
+    /// competing processes do not retry in lockstep
+    fn backoff(&self, retry: u32) -> Duration {
+        let base = self
+            .initial_backoff
+            .saturating_mul(1u32 << retry.min(16))
+            .min(self.max_backoff);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let jitter = base.mul_f64(f64::from(nanos % 1000) / 2000.0);
+        base + jitter
+    }
+}
+
+/// Errors that can tell whether they were caused by a locked database
+pub trait BusyError {
+    fn is_busy(&self) -> bool;
+}
+
+impl BusyError for rusqlite::Error {
+    fn is_busy(&self) -> bool {
+        matches!(
+            self,
+            rusqlite::Error::SqliteFailure(e, _)
+                if matches!(e.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+        )
+    }
+}
+
+/// Open `content.db` with the shared pragmas applied
+pub fn open<P: AsRef<Path>>(path: P, mode: OpenMode) -> rusqlite::Result<Connection> {
+    let conn = match mode {
+        OpenMode::ReadWrite => Connection::open(path)?,
+        OpenMode::ReadOnly => Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX
+                | OpenFlags::SQLITE_OPEN_URI,
+        )?,
+    };
+    configure(&conn, mode)?;
+    Ok(conn)
+}
+
+/// Apply the shared pragmas and SQL functions to an already open connection
+pub fn configure(conn: &Connection, mode: OpenMode) -> rusqlite::Result<()> {
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    compression::register_functions(conn)?;
+    if mode == OpenMode::ReadWrite {
+        // The journal mode is stored in the file; switching needs a brief
+        // exclusive lock, which can race another process opening the file
+        retry_busy(&RetryPolicy::default(), || {
+            conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get::<_, String>(0))
+        })?;
+        // Durable across application crashes in WAL mode, and much cheaper than FULL
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+    }
+    Ok(())
+}
+
+/// Run `f`, retrying with backoff while it fails with a busy error
+pub fn retry_busy<T, E: BusyError>(
+    policy: &RetryPolicy,
+    mut f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let mut retry = 0;
+    loop {
+        match f() {
+            Err(e) if e.is_busy() && retry + 1 < policy.max_attempts => {
+                std::thread::sleep(policy.backoff(retry));
+                retry += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Run `f` inside an IMMEDIATE transaction and commit it, retrying the whole
+/// transaction while the database is busy.
+///
+/// Taking the write lock up front avoids the deferred-transaction upgrade
+/// failure, where a reader that later writes gets `SQLITE_BUSY` without the
+/// busy handler ever running.
+pub fn write_transaction<T, E>(
+    conn: &Connection,
+    policy: &RetryPolicy,
+    mut f: impl FnMut(&Transaction) -> Result<T, E>,
+) -> Result<T, E>
+where
+    E: BusyError + From<rusqlite::Error>,
+{
+    retry_busy(policy, || {
+        let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+        let value = f(&tx)?;
+        tx.commit()?;
+        Ok(value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn busy() -> rusqlite::Error {
+        rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY), None)
+    }
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        }
+    }
+
+    #[test]
+    fn test_read_write_enables_wal() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = open(dir.path().join("content.db"), OpenMode::ReadWrite).unwrap();
+        let mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[test]
+    fn test_read_only_rejects_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("content.db");
+        open(&path, OpenMode::ReadWrite)
+            .unwrap()
+            .execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1);")
+            .unwrap();
+
+        let reader = open(&path, OpenMode::ReadOnly).unwrap();
+        let count: i64 = reader.query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+        assert!(reader.execute("INSERT INTO t VALUES (2)", []).is_err());
+
+        // Read-only never creates the file
+        assert!(open(dir.path().join("missing.db"), OpenMode::ReadOnly).is_err());
+    }
+
+    #[test]
+    fn test_retry_busy() {
+        let calls = Cell::new(0);
+        let result = retry_busy(&fast_policy(), || {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 {
+                Err(busy())
+            } else {
+                Ok(calls.get())
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        // Gives up after max_attempts
+        calls.set(0);
+        let result: rusqlite::Result<()> = retry_busy(&fast_policy(), || {
+            calls.set(calls.get() + 1);
+            Err(busy())
+        });
+        assert!(result.unwrap_err().is_busy());
+        assert_eq!(calls.get(), 4);
+
+        // Other errors are not retried
+        calls.set(0);
+        let result: rusqlite::Result<()> = retry_busy(&fast_policy(), || {
+            calls.set(calls.get() + 1);
+            Err(rusqlite::Error::QueryReturnedNoRows)
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_write_transaction_waits_for_other_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("content.db");
+        let a = open(&path, OpenMode::ReadWrite).unwrap();
+        let b = open(&path, OpenMode::ReadWrite).unwrap();
+        a.execute_batch("CREATE TABLE t (x INTEGER)").unwrap();
+        // No busy handler on `b`, so a held lock surfaces as SQLITE_BUSY
+        b.busy_timeout(Duration::ZERO).unwrap();
+
+        a.execute_batch("BEGIN IMMEDIATE; INSERT INTO t VALUES (1);").unwrap();
+        let blocked: rusqlite::Result<()> =
+            write_transaction(&b, &fast_policy(), |tx| tx.execute("INSERT INTO t VALUES (2)", []).map(|_| ()));
+        assert!(blocked.unwrap_err().is_busy());
+
+        a.execute_batch("COMMIT").unwrap();
+        write_transaction(&b, &fast_policy(), |tx| tx.execute("INSERT INTO t VALUES (2)", []).map(|_| ()))
+            .unwrap();
+        let count: i64 = a.query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+    }
+}
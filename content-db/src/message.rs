@@ -0,0 +1,136 @@
+//! Individual messages of chat sources
+//!
+//! A chat capture shows a scrolling window of a conversation, so each one
+//! overlaps the last. The ingestion server stores every message it has seen
+//! here, once per source, and rebuilds the source text from all of them.
+//! (The accessibility daemon keeps its own `messages` table, keyed by URL.)
+//!
+//! Messages are identified per source by a `message_key` chosen by the
+//! writer: the site's message id when it has one, so an edit replaces the
+//! text, or else a hash of author, time and text.
+
+use rusqlite::{params, Connection};
+
+/// Creates the chat messages table; safe to run on every open
+pub const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS chat_messages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        ehl_doc_id TEXT NOT NULL,
+        message_key TEXT NOT NULL,
+        message_id TEXT,
+        thread_id TEXT,
+        author TEXT,
+        sent_at INTEGER,
+        sort_at INTEGER NOT NULL,
+        text TEXT NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (datetime('now')),
+        UNIQUE(ehl_doc_id, message_key)
+    );
+    CREATE INDEX IF NOT EXISTS idx_chat_messages_order ON chat_messages(ehl_doc_id, sort_at, id);";
+
+/// One stored message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredMessage {
+    pub message_key: String,
+    pub message_id: Option<String>,
+    pub thread_id: Option<String>,
+    pub author: Option<String>,
+    /// Send time as captured (unix seconds)
+    pub sent_at: Option<i64>,
+    /// Position in the conversation (unix seconds): `sent_at`, or an
+    /// estimate for messages captured without a time. Ties keep the order
+    /// messages were first stored in.
+    pub sort_at: i64,
+    pub text: String,
+}
+
+pub fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(SCHEMA)
+}
+
+/// Store a message of a source. A message already stored is left alone,
+/// except that its text is replaced if it changed (an edit). Returns
+/// whether anything was written.
+pub fn upsert(conn: &Connection, ehl_doc_id: &str, message: &StoredMessage) -> rusqlite::Result<bool> {
+    let changed = conn.execute(
+        "INSERT INTO chat_messages (ehl_doc_id, message_key, message_id, thread_id, author, sent_at, sort_at, text)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(ehl_doc_id, message_key) DO UPDATE SET text = excluded.text
+         WHERE chat_messages.text != excluded.text",
+        params![
+            ehl_doc_id,
+            message.message_key,
+            message.message_id,
+            message.thread_id,
+            message.author,
+            message.sent_at,
+            message.sort_at,
+            message.text
+        ],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Every message of a source, in conversation order
+pub fn list(conn: &Connection, ehl_doc_id: &str) -> rusqlite::Result<Vec<StoredMessage>> {
+    let mut stmt = conn.prepare(
+        "SELECT message_key, message_id, thread_id, author, sent_at, sort_at, text
+         FROM chat_messages WHERE ehl_doc_id = ?1 ORDER BY sort_at, id",
+    )?;
+    let rows = stmt.query_map(params![ehl_doc_id], |row| {
+        Ok(StoredMessage {
+            message_key: row.get(0)?,
+            message_id: row.get(1)?,
+            thread_id: row.get(2)?,
+            author: row.get(3)?,
+            sent_at: row.get(4)?,
+            sort_at: row.get(5)?,
+            text: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Delete every message of a source
+pub fn delete(conn: &Connection, ehl_doc_id: &str) -> rusqlite::Result<usize> {
+    conn.execute("DELETE FROM chat_messages WHERE ehl_doc_id = ?1", params![ehl_doc_id])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(key: &str, sort_at: i64, text: &str) -> StoredMessage {
+        StoredMessage {
+            message_key: key.to_string(),
+            message_id: None,
+            thread_id: None,
+            author: Some("ana".to_string()),
+            sent_at: Some(sort_at),
+            sort_at,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_upsert_and_list() {
+        let conn = Connection::open_in_memory().unwrap();
+        ensure_schema(&conn).unwrap();
+
+        assert!(upsert(&conn, "doc-1", &message("b", 200, "second")).unwrap());
+        assert!(upsert(&conn, "doc-1", &message("a", 100, "first")).unwrap());
+        assert!(upsert(&conn, "doc-1", &message("c", 200, "third")).unwrap());
+        assert!(upsert(&conn, "doc-2", &message("a", 100, "other source")).unwrap());
+
+        // Seen again: nothing to write; edited: text replaced, position kept
+        assert!(!upsert(&conn, "doc-1", &message("a", 100, "first")).unwrap());
+        assert!(upsert(&conn, "doc-1", &message("b", 900, "second (edited)")).unwrap());
+
+        let texts: Vec<String> = list(&conn, "doc-1").unwrap().into_iter().map(|m| m.text).collect();
+        assert_eq!(texts, vec!["first", "second (edited)", "third"]);
+
+        assert_eq!(delete(&conn, "doc-1").unwrap(), 3);
+        assert!(list(&conn, "doc-1").unwrap().is_empty());
+        assert_eq!(list(&conn, "doc-2").unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,160 @@
+//! Full text of each stored source revision
+//!
+//! Chunks overlap, so a document cannot be rebuilt exactly by joining them.
+//! Every writer stores the text it chunked here, one row per revision, and
+//! reads the current text back with [`latest`]. Chunks written by the
+//! ingestion server record the revision and their character span in it
+//! (`revision_id`, `char_start`, `char_end` in the chunk meta).
+//!
+//! Like chunks, revision text may be a zstd frame in `text_zst` (see
+//! [`crate::compression`]); it is read through `chunk_text(text, text_zst)`.
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Creates the revisions table; safe to run on every open
+pub const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS source_revisions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        ehl_doc_id TEXT NOT NULL,
+        content_hash TEXT NOT NULL,
+        text TEXT NOT NULL,
+        text_zst BLOB,
+        captured_at INTEGER NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (datetime('now'))
+    );
+    CREATE INDEX IF NOT EXISTS idx_source_revisions_doc ON source_revisions(ehl_doc_id, id);";
+
+/// One revision of a source's text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revision {
+    pub id: i64,
+    pub ehl_doc_id: String,
+    pub content_hash: String,
+    pub text: String,
+    /// Unix seconds
+    pub captured_at: i64,
+}
+
+pub fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(SCHEMA)
+}
+
+/// Store a revision; `text` is empty when `text_zst` holds it compressed.
+/// Returns the new revision id.
+pub fn insert(
+    conn: &Connection,
+    ehl_doc_id: &str,
+    content_hash: &str,
+    captured_at: i64,
+    text: &str,
+    text_zst: Option<&[u8]>,
+) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO source_revisions (ehl_doc_id, content_hash, text, text_zst, captured_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![ehl_doc_id, content_hash, text, text_zst, captured_at],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// The newest revision of a source, if any writer stored one
+pub fn latest(conn: &Connection, ehl_doc_id: &str) -> rusqlite::Result<Option<Revision>> {
+    conn.query_row(
+        "SELECT id, ehl_doc_id, content_hash, chunk_text(text, text_zst), captured_at
+         FROM source_revisions WHERE ehl_doc_id = ?1 ORDER BY id DESC LIMIT 1",
+        params![ehl_doc_id],
+        |row| {
+            Ok(Revision {
+                id: row.get(0)?,
+                ehl_doc_id: row.get(1)?,
+                content_hash: row.get(2)?,
+                text: row.get(3)?,
+                captured_at: row.get(4)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Delete every revision of a source
+pub fn delete(conn: &Connection, ehl_doc_id: &str) -> rusqlite::Result<usize> {
+    conn.execute("DELETE FROM source_revisions WHERE ehl_doc_id = ?1", params![ehl_doc_id])
+}
+
+/// Delete revisions nothing needs any more: all but the newest of each
+/// existing source, unless a remaining chunk still points into them
+pub fn purge_superseded(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.execute(
+        "DELETE FROM source_revisions
+         WHERE id NOT IN (
+             SELECT MAX(r.id) FROM source_revisions r
+             JOIN content_sources s ON s.ehl_doc_id = r.ehl_doc_id
+             GROUP BY r.ehl_doc_id
+         )
+         AND id NOT IN (
+             SELECT revision_id FROM (
+                 SELECT json_extract(CASE WHEN json_valid(meta) THEN meta END, '$.revision_id') AS revision_id
+                 FROM chunks
+             )
+             WHERE revision_id IS NOT NULL
+         )",
+        [],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::compression::register_functions(&conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE content_sources (ehl_doc_id TEXT NOT NULL UNIQUE);
+             CREATE TABLE chunks (id INTEGER PRIMARY KEY, text TEXT NOT NULL, meta TEXT NOT NULL);",
+        )
+        .unwrap();
+        ensure_schema(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_latest_and_purge() {
+        let conn = test_db();
+        conn.execute("INSERT INTO content_sources (ehl_doc_id) VALUES ('doc-1')", []).unwrap();
+
+        let first = insert(&conn, "doc-1", "h1", 100, "first  text\n", None).unwrap();
+        let frame = crate::compression::Compressor::new(3).compress("second text").unwrap();
+        let second = insert(&conn, "doc-1", "h2", 200, "", Some(&frame)).unwrap();
+        let third = insert(&conn, "doc-1", "h3", 300, "third text", None).unwrap();
+        // A deleted source whose revision nothing refers to
+        insert(&conn, "doc-gone", "h4", 300, "gone", None).unwrap();
+
+        let revision = latest(&conn, "doc-1").unwrap().unwrap();
+        assert_eq!(revision.id, third);
+        assert_eq!(revision.text, "third text");
+        assert_eq!(latest(&conn, "doc-missing").unwrap(), None);
+
+        // A soft-deleted chunk still cites the second revision
+        conn.execute(
+            "INSERT INTO chunks (text, meta) VALUES ('second', json_object('id', 'doc-1', 'revision_id', ?1))",
+            [second],
+        )
+        .unwrap();
+        // Malformed meta must not fail the purge
+        conn.execute("INSERT INTO chunks (text, meta) VALUES ('broken', '{not json')", []).unwrap();
+        assert_eq!(purge_superseded(&conn).unwrap(), 2);
+
+        let left: Vec<i64> = conn
+            .prepare("SELECT id FROM source_revisions ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(left, vec![second, third]);
+        assert!(!left.contains(&first));
+
+        assert_eq!(delete(&conn, "doc-1").unwrap(), 2);
+    }
+}
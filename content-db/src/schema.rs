@@ -0,0 +1,183 @@
+//! Tables shared by every writer, and their migrations
+//!
+//! The ingestion server and the accessibility daemon both create and write
+//! `content_sources` and `chunks`; [`migrate`] is the one place their layout
+//! is defined, so a database created by either is complete for both.
+//! Writer-specific tables (the daemon's `messages`) stay with their writer.
+
+use crate::{compression, doc_id, message, revision, tombstone};
+use rusqlite::Connection;
+use std::collections::HashSet;
+
+/// Schema version recorded in `PRAGMA user_version`; bumped whenever
+/// [`migrate`] gains a migration
+pub const SCHEMA_VERSION: i32 = 8;
+
+/// Tables as first released, before any versioned migration
+const CORE: &str = "
+    -- Content sources table (tracks what we've ingested)
+    CREATE TABLE IF NOT EXISTS content_sources (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        source_type TEXT NOT NULL,
+        source_path TEXT NOT NULL UNIQUE,
+        content_hash TEXT NOT NULL,
+        ehl_doc_id TEXT NOT NULL UNIQUE,
+        chunk_count INTEGER NOT NULL DEFAULT 0,
+        ingestion_status TEXT NOT NULL DEFAULT 'ingested',
+        created_at TEXT NOT NULL DEFAULT (datetime('now')),
+        updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+        captured_at INTEGER
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_content_sources_path ON content_sources(source_path);
+    CREATE INDEX IF NOT EXISTS idx_content_sources_ehl_doc_id ON content_sources(ehl_doc_id);
+    CREATE INDEX IF NOT EXISTS idx_content_sources_hash ON content_sources(content_hash);
+
+    -- Chunks table (actual content chunks)
+    CREATE TABLE IF NOT EXISTS chunks (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        vector_index INTEGER,
+        text TEXT NOT NULL,
+        meta TEXT NOT NULL,
+        is_deleted INTEGER DEFAULT 0,
+        created_at TEXT NOT NULL DEFAULT (datetime('now'))
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_chunks_deleted ON chunks(is_deleted);";
+
+/// Schema version of an open database (0 for databases that predate versioning)
+pub fn version(conn: &Connection) -> rusqlite::Result<i32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Create missing tables and bring older databases up to [`SCHEMA_VERSION`];
+/// safe to run on every open
+pub fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(CORE)?;
+
+    // Databases created before capture times were recorded
+    add_column_if_missing(conn, "content_sources", "captured_at", "INTEGER")?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_content_sources_captured_at ON content_sources(captured_at);",
+    )?;
+
+    // Version 2: forget tombstones
+    tombstone::ensure_schema(conn)?;
+
+    // Version 3: chunks keyed by the SHA-256 of their text. Older rows (and
+    // rows written by other processes) may have NULL and are hashed on read.
+    add_column_if_missing(conn, "chunks", "content_hash", "TEXT")?;
+
+    // Version 4: optional zstd chunk bodies (`text_zst`) and their dictionaries
+    compression::ensure_schema(conn)?;
+
+    // Version 5: exact full text of each revision. Sources stored earlier
+    // have none until their next update and are rebuilt from chunks.
+    revision::ensure_schema(conn)?;
+
+    // Version 6: messages of chat sources, stored one by one
+    message::ensure_schema(conn)?;
+
+    // Version 7: document IDs derived from the source path instead of
+    // random, with the old IDs kept as aliases
+    doc_id::ensure_schema(conn)?;
+    if version(conn)? < 7 {
+        doc_id::migrate_ids(conn)?;
+    }
+
+    // Version 8: http(s) URLs with no rule went from losing their whole query
+    // to a cleanup that keeps meaningful params. Source types already stored
+    // with such paths are recorded so they keep the old paths.
+    conn.execute_batch("CREATE TABLE IF NOT EXISTS legacy_url_sources (source_type TEXT PRIMARY KEY);")?;
+    if version(conn)? < 8 {
+        conn.execute(
+            "INSERT OR IGNORE INTO legacy_url_sources (source_type)
+             SELECT DISTINCT source_type FROM content_sources
+             WHERE source_path LIKE 'http://%' OR source_path LIKE 'https://%'",
+            [],
+        )?;
+    }
+
+    if version(conn)? < SCHEMA_VERSION {
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    }
+    Ok(())
+}
+
+/// Source types whose http(s) paths predate the generic URL cleanup
+pub fn legacy_url_sources(conn: &Connection) -> rusqlite::Result<HashSet<String>> {
+    conn.prepare("SELECT source_type FROM legacy_url_sources")?
+        .query_map([], |row| row.get(0))?
+        .collect()
+}
+
+/// Add a column to an existing table if an older schema lacks it
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(Result::ok)
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_is_idempotent() {
+        let conn = Connection::open_in_memory().unwrap();
+        compression::register_functions(&conn).unwrap();
+        assert_eq!(version(&conn).unwrap(), 0);
+
+        migrate(&conn).unwrap();
+        migrate(&conn).unwrap();
+        assert_eq!(version(&conn).unwrap(), SCHEMA_VERSION);
+
+        let columns: Vec<String> = conn
+            .prepare("SELECT name FROM pragma_table_info('chunks')")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(columns.iter().any(|c| c == "content_hash"));
+        assert!(columns.iter().any(|c| c == "text_zst"));
+        assert!(conn.prepare("SELECT sort_at FROM chat_messages").is_ok());
+        assert!(conn.prepare("SELECT old_id FROM doc_id_aliases").is_ok());
+    }
+
+    #[test]
+    fn test_upgrade_records_legacy_url_sources() {
+        let conn = Connection::open_in_memory().unwrap();
+        compression::register_functions(&conn).unwrap();
+        conn.execute_batch(CORE).unwrap();
+        conn.execute_batch(
+            "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id)
+             VALUES ('chrome', 'https://example.com/page', 'h1', 'd1'),
+                    ('gdocs', 'gdocs://abc', 'h2', 'd2');",
+        )
+        .unwrap();
+
+        migrate(&conn).unwrap();
+        let legacy = legacy_url_sources(&conn).unwrap();
+        assert_eq!(legacy, HashSet::from(["chrome".to_string()]));
+
+        // Sources stored after the upgrade are not legacy
+        conn.execute_batch(
+            "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id)
+             VALUES ('firefox', 'https://example.com/other', 'h3', 'd3');",
+        )
+        .unwrap();
+        migrate(&conn).unwrap();
+        assert_eq!(legacy_url_sources(&conn).unwrap(), legacy);
+    }
+}
@@ -0,0 +1,293 @@
+//! "Forget" tombstones: sources that must never be stored again
+//!
+//! Every component that writes captured content checks [`find_match`] before
+//! storing, so deleting a source with a tombstone keeps it from coming back
+//! on the next capture. A tombstone matches on one of:
+//!
+//! - [`TombstoneKind::Path`]: an exact canonical source path or URL
+//! - [`TombstoneKind::Pattern`]: a case-insensitive glob where `*` matches
+//!   any run of characters, e.g. `https://mail.example.com/*`
+//! - [`TombstoneKind::Site`]: a host and all of its subdomains
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Creates the tombstones table; safe to run on every open
+pub const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS tombstones (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind TEXT NOT NULL,
+        value TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        expires_at INTEGER,
+        UNIQUE(kind, value)
+    );";
+
+/// What a tombstone's value is matched against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TombstoneKind {
+    Path,
+    Pattern,
+    Site,
+}
+
+impl TombstoneKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            TombstoneKind::Path => "path",
+            TombstoneKind::Pattern => "pattern",
+            TombstoneKind::Site => "site",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "path" => Some(TombstoneKind::Path),
+            "pattern" => Some(TombstoneKind::Pattern),
+            "site" => Some(TombstoneKind::Site),
+            _ => None,
+        }
+    }
+}
+
+/// A stored tombstone
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub id: i64,
+    pub kind: TombstoneKind,
+    pub value: String,
+    /// Unix seconds
+    pub created_at: i64,
+    /// Unix seconds; `None` means forever
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+impl Tombstone {
+    /// Whether this tombstone covers a capture with the given canonical
+    /// source path and URL as captured
+    pub fn matches(&self, source_path: &str, url: &str) -> bool {
+        let candidates = [source_path, url];
+        match self.kind {
+            TombstoneKind::Path => candidates.iter().any(|c| *c == self.value),
+            TombstoneKind::Pattern => {
+                let pattern = self.value.to_lowercase();
+                candidates.iter().any(|c| glob_match(&pattern, &c.to_lowercase()))
+            }
+            TombstoneKind::Site => candidates.iter().filter_map(|c| host(c)).any(|host| {
+                host == self.value
+                    || host
+                        .strip_suffix(self.value.as_str())
+                        .is_some_and(|prefix| prefix.ends_with('.'))
+            }),
+        }
+    }
+
+    pub fn is_active(&self, now: i64) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+/// Normalize a user-supplied value for storage: sites are reduced to a bare
+/// lowercase host without `www.`, so "https://www.Example.com/a" forgets all
+/// of example.com
+pub fn normalize(kind: TombstoneKind, value: &str) -> String {
+    let value = value.trim();
+    match kind {
+        TombstoneKind::Site => {
+            let host = host(value).unwrap_or_else(|| value.trim_end_matches('/').to_lowercase());
+            host.strip_prefix("www.").map(String::from).unwrap_or(host)
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Lowercase host of `scheme://[user@]host[:port]/...`, if there is one
+fn host(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = host.split(':').next()?;
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+/// Match `text` against a glob where `*` matches any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it is currently matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last `*` swallow one more character
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn from_row(row: &Row) -> rusqlite::Result<Tombstone> {
+    let kind: String = row.get(1)?;
+    Ok(Tombstone {
+        id: row.get(0)?,
+        kind: TombstoneKind::parse(&kind).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                1,
+                rusqlite::types::Type::Text,
+                format!("unknown tombstone kind {:?}", kind).into(),
+            )
+        })?,
+        value: row.get(2)?,
+        created_at: row.get(3)?,
+        expires_at: row.get(4)?,
+    })
+}
+
+/// Create the tombstones table if it is missing
+pub fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(SCHEMA)
+}
+
+/// All tombstones, including expired ones, oldest first
+pub fn list(conn: &Connection) -> rusqlite::Result<Vec<Tombstone>> {
+    let mut stmt =
+        conn.prepare("SELECT id, kind, value, created_at, expires_at FROM tombstones ORDER BY id")?;
+    let rows = stmt.query_map([], from_row)?;
+    rows.collect()
+}
+
+/// The first unexpired tombstone covering a capture, if any
+pub fn find_match(conn: &Connection, source_path: &str, url: &str) -> rusqlite::Result<Option<Tombstone>> {
+    let now = now();
+    Ok(list(conn)?
+        .into_iter()
+        .find(|t| t.is_active(now) && t.matches(source_path, url)))
+}
+
+/// Add a tombstone, or refresh the expiry of an identical one
+pub fn insert(
+    conn: &Connection,
+    kind: TombstoneKind,
+    value: &str,
+    expires_at: Option<i64>,
+) -> rusqlite::Result<Tombstone> {
+    let value = normalize(kind, value);
+    conn.execute(
+        "INSERT INTO tombstones (kind, value, created_at, expires_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(kind, value) DO UPDATE SET expires_at = excluded.expires_at",
+        params![kind.as_str(), value, now(), expires_at],
+    )?;
+    conn.query_row(
+        "SELECT id, kind, value, created_at, expires_at FROM tombstones WHERE kind = ?1 AND value = ?2",
+        params![kind.as_str(), value],
+        from_row,
+    )
+}
+
+/// Delete a tombstone by id; returns it if it existed
+pub fn remove(conn: &Connection, id: i64) -> rusqlite::Result<Option<Tombstone>> {
+    let tombstone = conn
+        .query_row(
+            "SELECT id, kind, value, created_at, expires_at FROM tombstones WHERE id = ?1",
+            params![id],
+            from_row,
+        )
+        .optional()?;
+    conn.execute("DELETE FROM tombstones WHERE id = ?1", params![id])?;
+    Ok(tombstone)
+}
+
+/// Drop tombstones whose expiry has passed; returns how many were removed
+pub fn purge_expired(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.execute(
+        "DELETE FROM tombstones WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+        params![now()],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tombstone(kind: TombstoneKind, value: &str) -> Tombstone {
+        Tombstone {
+            id: 1,
+            kind,
+            value: normalize(kind, value),
+            created_at: 0,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("https://mail.example.com/*", "https://mail.example.com/inbox/1"));
+        assert!(glob_match("*bank*", "https://www.mybank.com/"));
+        assert!(glob_match("a*b*c", "aXXbYYbc"));
+        assert!(!glob_match("https://example.com/*", "https://example.org/a"));
+        assert!(!glob_match("a*b", "aXXbc"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn test_matches() {
+        let path = tombstone(TombstoneKind::Path, "https://example.com/a");
+        assert!(path.matches("https://example.com/a", "https://example.com/a?utm_source=x"));
+        assert!(!path.matches("https://example.com/ab", "https://example.com/ab"));
+
+        // Checked against the URL as captured too
+        let pattern = tombstone(TombstoneKind::Pattern, "*utm_source=secret*");
+        assert!(pattern.matches("https://example.com/a", "https://example.com/a?UTM_SOURCE=secret"));
+
+        let site = tombstone(TombstoneKind::Site, "https://www.Bank.com/login");
+        assert_eq!(site.value, "bank.com");
+        assert!(site.matches("https://bank.com/", ""));
+        assert!(site.matches("https://online.bank.com:8443/x", ""));
+        assert!(site.matches("https://user@bank.com/x", ""));
+        assert!(!site.matches("https://notbank.com/", "https://notbank.com/"));
+        assert!(!site.matches("accessibility://Notes/bank.com", ""));
+    }
+
+    #[test]
+    fn test_store_and_expire() {
+        let conn = Connection::open_in_memory().unwrap();
+        ensure_schema(&conn).unwrap();
+
+        let forever = insert(&conn, TombstoneKind::Site, "example.com", None).unwrap();
+        let expired = insert(&conn, TombstoneKind::Path, "https://other.com/a", Some(now() - 10)).unwrap();
+        assert!(find_match(&conn, "https://a.example.com/x", "").unwrap().is_some());
+        assert!(find_match(&conn, "https://other.com/a", "").unwrap().is_none());
+
+        // Re-adding refreshes the expiry instead of duplicating
+        let renewed = insert(&conn, TombstoneKind::Path, "https://other.com/a", None).unwrap();
+        assert_eq!(renewed.id, expired.id);
+        assert!(find_match(&conn, "https://other.com/a", "").unwrap().is_some());
+        assert_eq!(list(&conn).unwrap().len(), 2);
+
+        assert_eq!(remove(&conn, forever.id).unwrap(), Some(forever));
+        assert!(find_match(&conn, "https://a.example.com/x", "").unwrap().is_none());
+
+        insert(&conn, TombstoneKind::Pattern, "*", Some(now() - 1)).unwrap();
+        assert_eq!(purge_expired(&conn).unwrap(), 1);
+    }
+}
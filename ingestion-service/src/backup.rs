@@ -0,0 +1,420 @@
+//! Online snapshots of content.db and restore
+//!
+//! Snapshots use SQLite's online backup API from a separate read-only
+//! connection. All pages are copied in a single step, i.e. under one read
+//! transaction, so the copy is a consistent point in time; in WAL mode the
+//! server keeps writing meanwhile. Copying the file itself is not safe while
+//! the server runs.
+//!
+//! Snapshots are named `content-YYYYMMDD-HHMMSS-mmm.db` (`.db.zst` when
+//! compressed) and rotated so only the newest `keep` remain:
+//!
+//! ```toml
+//! [backup]
+//! dir = "/path/to/backups"   # default: "backups" next to the database
+//! interval_minutes = 1440    # scheduled snapshots; omit to disable
+//! keep = 7                   # 0 keeps every snapshot
+//! compress = true
+//! ```
+
+use crate::storage::{self, Storage, StorageError, SCHEMA_VERSION};
+use chrono::Utc;
+use content_db::OpenMode;
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{Connection, DatabaseName};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Extension of compressed snapshots
+const COMPRESSED_EXT: &str = "zst";
+
+/// zstd level for compressed snapshots
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// How often a busy backup step is retried, and how long to wait in between
+const MAX_BUSY_WAITS: u32 = 50;
+const BUSY_WAIT: Duration = Duration::from_millis(100);
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Storage error: {0}")]
+    Storage(#[from] StorageError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Database stayed busy; snapshot abandoned")]
+    Busy,
+    #[error("Integrity check failed: {0}")]
+    Integrity(String),
+    #[error("Snapshot has schema version {found}, this build supports up to {supported}")]
+    SchemaVersion { found: i32, supported: i32 },
+    #[error("Not a content database: missing table {0}")]
+    MissingTable(String),
+}
+
+/// The `[backup]` config section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Snapshot directory (default: `backups` next to the database)
+    pub dir: Option<PathBuf>,
+    /// Take a snapshot this often; `None` disables scheduled snapshots
+    pub interval_minutes: Option<u64>,
+    /// Snapshots to keep after rotation (0 = keep all)
+    pub keep: usize,
+    /// Compress snapshots with zstd
+    pub compress: bool,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            interval_minutes: None,
+            keep: 7,
+            compress: false,
+        }
+    }
+}
+
+/// A snapshot written to disk
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SnapshotInfo {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub compressed: bool,
+    pub schema_version: i32,
+    /// Older snapshots deleted by rotation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rotated: Vec<PathBuf>,
+}
+
+/// Takes snapshots of one database, one at a time
+pub struct Snapshotter {
+    db_path: PathBuf,
+    config: BackupConfig,
+    running: Mutex<()>,
+}
+
+impl Snapshotter {
+    pub fn new(db_path: PathBuf, config: BackupConfig) -> Self {
+        Self {
+            db_path,
+            config,
+            running: Mutex::new(()),
+        }
+    }
+
+    /// Directory snapshots are written to
+    pub fn dir(&self) -> PathBuf {
+        self.config.dir.clone().unwrap_or_else(|| {
+            self.db_path
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .join("backups")
+        })
+    }
+
+    /// Interval between scheduled snapshots, if enabled
+    pub fn interval(&self) -> Option<Duration> {
+        self.config
+            .interval_minutes
+            .filter(|minutes| *minutes > 0)
+            .map(|minutes| Duration::from_secs(minutes * 60))
+    }
+
+    /// Snapshot the database and rotate old snapshots. `compress` overrides
+    /// the configured default. Blocks until done.
+    pub fn snapshot(&self, compress: Option<bool>) -> Result<SnapshotInfo, BackupError> {
+        let _running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        let mut info = snapshot(
+            &self.db_path,
+            &self.dir(),
+            compress.unwrap_or(self.config.compress),
+        )?;
+        info.rotated = rotate(&self.dir(), self.config.keep)?;
+        Ok(info)
+    }
+}
+
+/// Write a consistent snapshot of `db_path` into `dir`
+pub fn snapshot(db_path: &Path, dir: &Path, compress: bool) -> Result<SnapshotInfo, BackupError> {
+    std::fs::create_dir_all(dir)?;
+    let name = format!("content-{}.db", Utc::now().format("%Y%m%d-%H%M%S-%3f"));
+    let final_path = if compress {
+        dir.join(format!("{}.{}", name, COMPRESSED_EXT))
+    } else {
+        dir.join(&name)
+    };
+    // Written under a temporary name so a crash never leaves a partial snapshot
+    // that looks complete
+    let partial = dir.join(format!("{}.partial", name));
+
+    let result = write_snapshot(db_path, &partial).and_then(|schema_version| {
+        if compress {
+            let compressed = dir.join(format!("{}.{}.partial", name, COMPRESSED_EXT));
+            zstd::stream::copy_encode(File::open(&partial)?, File::create(&compressed)?, COMPRESSION_LEVEL)?;
+            std::fs::remove_file(&partial)?;
+            std::fs::rename(&compressed, &final_path)?;
+        } else {
+            std::fs::rename(&partial, &final_path)?;
+        }
+        Ok(schema_version)
+    });
+    let schema_version = match result {
+        Ok(version) => version,
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            return Err(e);
+        }
+    };
+
+    Ok(SnapshotInfo {
+        size_bytes: std::fs::metadata(&final_path)?.len(),
+        path: final_path,
+        compressed: compress,
+        schema_version,
+        rotated: Vec::new(),
+    })
+}
+
+/// Copy every page of `db_path` into a new database at `dest`
+fn write_snapshot(db_path: &Path, dest: &Path) -> Result<i32, BackupError> {
+    let src = content_db::open(db_path, OpenMode::ReadOnly)?;
+    let mut dst = Connection::open(dest)?;
+    {
+        let backup = Backup::new(&src, &mut dst)?;
+        let mut waits = 0;
+        loop {
+            match backup.step(-1)? {
+                StepResult::Done => break,
+                StepResult::More => {}
+                StepResult::Busy | StepResult::Locked if waits < MAX_BUSY_WAITS => {
+                    waits += 1;
+                    std::thread::sleep(BUSY_WAIT);
+                }
+                _ => return Err(BackupError::Busy),
+            }
+        }
+    }
+    // The copied header says WAL; a snapshot is a single self-contained file
+    dst.query_row("PRAGMA journal_mode = DELETE", [], |row| row.get::<_, String>(0))?;
+    Ok(storage::schema_version(&dst)?)
+}
+
+/// Snapshot files in `dir`, oldest first
+pub fn list_snapshots(dir: &Path) -> Result<Vec<PathBuf>, BackupError> {
+    let mut snapshots = Vec::new();
+    if !dir.exists() {
+        return Ok(snapshots);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if name.starts_with("content-") && (name.ends_with(".db") || name.ends_with(".db.zst")) {
+            snapshots.push(path);
+        }
+    }
+    // Names embed the timestamp, so name order is age order
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// Delete all but the newest `keep` snapshots (0 keeps everything)
+pub fn rotate(dir: &Path, keep: usize) -> Result<Vec<PathBuf>, BackupError> {
+    let snapshots = list_snapshots(dir)?;
+    if keep == 0 || snapshots.len() <= keep {
+        return Ok(Vec::new());
+    }
+    let expired = snapshots[..snapshots.len() - keep].to_vec();
+    for path in &expired {
+        std::fs::remove_file(path)?;
+    }
+    Ok(expired)
+}
+
+/// Check that a snapshot is an intact content database this build can open;
+/// returns its schema version
+pub fn validate(path: &Path) -> Result<i32, BackupError> {
+    let conn = content_db::open(path, OpenMode::ReadOnly)?;
+
+    let problems: Vec<String> = conn
+        .prepare("PRAGMA integrity_check")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    if problems != ["ok"] {
+        return Err(BackupError::Integrity(problems.join("; ")));
+    }
+
+    for table in ["content_sources", "chunks"] {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            [table],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(BackupError::MissingTable(table.to_string()));
+        }
+    }
+
+    let found = storage::schema_version(&conn)?;
+    if found > SCHEMA_VERSION {
+        return Err(BackupError::SchemaVersion {
+            found,
+            supported: SCHEMA_VERSION,
+        });
+    }
+    Ok(found)
+}
+
+/// Replace the database at `db_path` with a validated snapshot. The server
+/// must not be running. Returns the snapshot's schema version; older schemas
+/// are migrated in place.
+pub fn restore(snapshot: &Path, db_path: &Path) -> Result<i32, BackupError> {
+    let compressed = snapshot.extension().is_some_and(|ext| ext == COMPRESSED_EXT);
+    let staged = db_path.with_extension("restore.partial");
+    let source = if compressed {
+        zstd::stream::copy_decode(File::open(snapshot)?, File::create(&staged)?)?;
+        staged.as_path()
+    } else {
+        snapshot
+    };
+
+    let result = validate(source).and_then(|version| {
+        let mut dst = content_db::open(db_path, OpenMode::ReadWrite)?;
+        dst.restore(DatabaseName::Main, source, None::<fn(rusqlite::backup::Progress)>)?;
+        drop(dst);
+        // Reapply WAL mode and bring older schemas up to date
+        Storage::open(db_path)?;
+        Ok(version)
+    });
+
+    if compressed {
+        let _ = std::fs::remove_file(&staged);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunker::Chunker;
+    use crate::payload::CapturePayload;
+
+    fn make_db(path: &Path, sources: std::ops::Range<usize>) {
+        let mut storage = Storage::open(path).unwrap();
+        let chunker = Chunker::with_defaults();
+        for i in sources {
+            let url = format!("https://example.com/{}", i);
+            let payload = CapturePayload::new("chrome", url, format!("Page {} about snapshots", i));
+            let chunks = chunker.chunk(&payload.content);
+            storage
+                .insert_source(&payload, &payload.url, "hash", &format!("doc-{}", i), &chunks)
+                .unwrap();
+        }
+    }
+
+    fn source_count(path: &Path) -> usize {
+        Storage::open(path).unwrap().stats().unwrap().source_count
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        for compress in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let db = dir.path().join("content.db");
+            make_db(&db, 0..3);
+
+            let info = snapshot(&db, &dir.path().join("backups"), compress).unwrap();
+            assert_eq!(info.compressed, compress);
+            assert_eq!(info.schema_version, SCHEMA_VERSION);
+            assert!(info.size_bytes > 0);
+
+            // Keep writing after the snapshot, then roll back to it
+            make_db(&db, 3..5);
+            assert_eq!(source_count(&db), 5);
+
+            assert_eq!(restore(&info.path, &db).unwrap(), SCHEMA_VERSION);
+            assert_eq!(source_count(&db), 3);
+        }
+    }
+
+    #[test]
+    fn test_snapshot_while_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("content.db");
+        make_db(&db, 0..1);
+
+        let writer_db = db.clone();
+        let writer = std::thread::spawn(move || make_db(&writer_db, 1..200));
+        let info = snapshot(&db, dir.path(), false).unwrap();
+        writer.join().unwrap();
+
+        // Whatever point in time was captured, it is internally consistent
+        validate(&info.path).unwrap();
+        let storage = Storage::open(&info.path).unwrap();
+        let stats = storage.stats().unwrap();
+        assert_eq!(stats.chunk_count, stats.source_count);
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("content.db");
+        make_db(&db, 0..1);
+
+        let snapshotter = Snapshotter::new(
+            db,
+            BackupConfig {
+                keep: 2,
+                ..Default::default()
+            },
+        );
+        let mut taken = Vec::new();
+        for _ in 0..4 {
+            taken.push(snapshotter.snapshot(None).unwrap().path);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(list_snapshots(&snapshotter.dir()).unwrap(), taken[2..]);
+    }
+
+    #[test]
+    fn test_restore_rejects_invalid_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("content.db");
+        make_db(&db, 0..2);
+
+        // Newer schema than this build understands
+        let newer = dir.path().join("newer.db");
+        make_db(&newer, 0..1);
+        Connection::open(&newer)
+            .unwrap()
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        assert!(matches!(restore(&newer, &db), Err(BackupError::SchemaVersion { .. })));
+
+        // Some other SQLite database
+        let other = dir.path().join("other.db");
+        Connection::open(&other)
+            .unwrap()
+            .execute_batch("CREATE TABLE notes (body TEXT)")
+            .unwrap();
+        assert!(matches!(restore(&other, &db), Err(BackupError::MissingTable(_))));
+
+        // Not a database at all
+        let garbage = dir.path().join("garbage.db");
+        std::fs::write(&garbage, vec![0x5a; 8192]).unwrap();
+        assert!(restore(&garbage, &db).is_err());
+
+        // The live database is untouched
+        assert_eq!(source_count(&db), 2);
+    }
+}
@@ -0,0 +1,796 @@
+//! Command-line client for the ingestion service
+//!
+//! Talks to a running `ingestion-server` over its socket. When no server is
+//! listening it opens the database directly instead, so everything except
+//! `tail` also works while the service is down.
+//!
+//! # Usage
+//!
+//! ```bash
+//! ingestion-ctl stats
+//! ingestion-ctl ls --source slack --since 2024-06-01 --limit 20
+//! ingestion-ctl ls --meta status=Done --meta labels=backend
+//! ingestion-ctl show 3f2b8c1e-...
+//! ingestion-ctl search "quarterly roadmap"
+//! ingestion-ctl rm 3f2b8c1e-...
+//! ingestion-ctl export --source gdocs -o corpus.jsonl
+//! ingestion-ctl import corpus.jsonl
+//! ingestion-ctl gc --vacuum
+//! ingestion-ctl tail
+//! ingestion-ctl forget --site mybank.com
+//! ingestion-ctl forget https://example.com/private --for 30
+//! ingestion-ctl tombstones
+//! ingestion-ctl unforget 3
+//! ```
+
+use chrono::{DateTime, Local};
+use content_db::tombstone::{Tombstone, TombstoneKind};
+use ingestion_service::control::{
+    ControlRequest, ControlResponse, IngestEvent, DEFAULT_EXPORT_LIMIT, DEFAULT_SEARCH_LIMIT,
+};
+use ingestion_service::export::{self, ExportRecord, ImportSummary};
+use ingestion_service::metadata::Metadata;
+use ingestion_service::payload::ResponseStatus;
+use ingestion_service::{IngestionServer, ServerConfig};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufReader as StdBufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::UnixStream;
+
+type CtlResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// Records sent per `import` request over the socket
+const IMPORT_BATCH: usize = 100;
+
+/// Subcommand and its arguments
+enum Command {
+    Stats,
+    Ls,
+    Show(String),
+    Search(String),
+    Rm(Vec<String>),
+    Export,
+    Import(PathBuf),
+    Gc,
+    Tail,
+    Forget(String),
+    Tombstones,
+    Unforget(i64),
+}
+
+/// Flags shared by the subcommands that accept them
+#[derive(Default)]
+struct Options {
+    source_types: Vec<String>,
+    since: Option<i64>,
+    until: Option<i64>,
+    /// `--meta key=value` filters
+    metadata: Metadata,
+    limit: Option<usize>,
+    output: Option<PathBuf>,
+    vacuum: bool,
+    include_indexed: bool,
+    /// Print raw JSON responses instead of tables
+    json: bool,
+    /// Never use the socket
+    direct: bool,
+    /// How `forget` matches its argument
+    forget_kind: Option<TombstoneKind>,
+    /// `forget` expiry in days from now
+    forget_days: Option<i64>,
+}
+
+/// Parse command line arguments (without the program name)
+fn parse_args(args: Vec<String>) -> Result<(Command, Options, ServerConfig), String> {
+    let mut options = Options::default();
+    let mut positional: Vec<String> = Vec::new();
+    let mut config_path: Option<PathBuf> = None;
+    let mut db_path: Option<PathBuf> = None;
+    let mut socket_path: Option<PathBuf> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let mut value = || {
+            i += 1;
+            args.get(i)
+                .cloned()
+                .ok_or_else(|| format!("{} requires a value", flag))
+        };
+        match flag {
+            "--help" | "-h" => {
+                print_help();
+                std::process::exit(0);
+            }
+            "--config" | "-c" => config_path = Some(PathBuf::from(value()?)),
+            "--db" => db_path = Some(PathBuf::from(value()?)),
+            "--socket" => socket_path = Some(PathBuf::from(value()?)),
+            "--source" => options.source_types.push(value()?),
+            "--since" | "--until" => {
+                let raw = value()?;
+                let date = export::parse_date(&raw).ok_or_else(|| format!("Invalid date: {}", raw))?;
+                if flag == "--since" {
+                    options.since = Some(date.timestamp());
+                } else {
+                    options.until = Some(date.timestamp());
+                }
+            }
+            "--meta" => {
+                let raw = value()?;
+                let (key, value) = raw.split_once('=').ok_or_else(|| format!("Expected key=value: {}", raw))?;
+                // `status=Done` is a string; `points=3` and `blocked=true` keep their JSON type
+                let value = serde_json::from_str::<serde_json::Value>(value)
+                    .ok()
+                    .filter(|v| v.is_number() || v.is_boolean() || v.is_string())
+                    .unwrap_or_else(|| serde_json::Value::String(value.to_string()));
+                options.metadata.insert(key.to_string(), value);
+            }
+            "--limit" | "-n" => {
+                let raw = value()?;
+                options.limit = Some(raw.parse().map_err(|_| format!("Invalid limit: {}", raw))?);
+            }
+            "--output" | "-o" => options.output = Some(PathBuf::from(value()?)),
+            "--vacuum" => options.vacuum = true,
+            "--include-indexed" => options.include_indexed = true,
+            "--json" => options.json = true,
+            "--direct" => options.direct = true,
+            "--site" => options.forget_kind = Some(TombstoneKind::Site),
+            "--pattern" => options.forget_kind = Some(TombstoneKind::Pattern),
+            "--for" => {
+                let raw = value()?;
+                options.forget_days = Some(
+                    raw.parse()
+                        .map_err(|_| format!("Invalid number of days: {}", raw))?,
+                );
+            }
+            other if other.starts_with('-') && other.len() > 1 => {
+                return Err(format!("Unknown option: {}", other))
+            }
+            other => positional.push(other.to_string()),
+        }
+        i += 1;
+    }
+
+    let mut positional = positional.into_iter();
+    let name = positional.next().ok_or("No command given")?;
+    let rest: Vec<String> = positional.collect();
+    let one = |what: &str| match rest.as_slice() {
+        [value] => Ok(value.clone()),
+        _ => Err(format!("{} requires exactly one {}", name, what)),
+    };
+
+    let command = match name.as_str() {
+        "stats" => Command::Stats,
+        "ls" => Command::Ls,
+        "show" => Command::Show(one("document id")?),
+        "search" => Command::Search(rest.join(" ")),
+        "rm" if !rest.is_empty() => Command::Rm(rest.clone()),
+        "rm" => return Err("rm requires at least one document id".to_string()),
+        "export" => Command::Export,
+        "import" => Command::Import(PathBuf::from(one("file")?)),
+        "gc" => Command::Gc,
+        "tail" => Command::Tail,
+        "forget" => Command::Forget(one("path, pattern or site")?),
+        "tombstones" => Command::Tombstones,
+        "unforget" => {
+            let raw = one("tombstone id")?;
+            Command::Unforget(raw.parse().map_err(|_| format!("Invalid tombstone id: {}", raw))?)
+        }
+        other => return Err(format!("Unknown command: {}", other)),
+    };
+    if matches!(command, Command::Search(ref query) if query.is_empty()) {
+        return Err("search requires a query".to_string());
+    }
+    let takes_no_args = matches!(
+        command,
+        Command::Stats | Command::Ls | Command::Export | Command::Gc | Command::Tail | Command::Tombstones
+    );
+    if takes_no_args && !rest.is_empty() {
+        return Err(format!("Unexpected argument for {}: {}", name, rest.join(" ")));
+    }
+
+    let mut config = match config_path {
+        Some(path) => ServerConfig::load_from_path(&path),
+        None => ServerConfig::load(),
+    };
+    if let Some(path) = db_path {
+        config.db_path = path;
+    }
+    if let Some(path) = socket_path {
+        config.socket_path = path;
+    }
+
+    Ok((command, options, config))
+}
+
+fn print_help() {
+    println!("Clace Ingestion Control");
+    println!();
+    println!("Usage: ingestion-ctl <command> [options]");
+    println!();
+    println!("Commands:");
+    println!("  stats                Source, chunk and cache counts");
+    println!("  ls                   List sources (--source, --since, --until, --meta, --limit)");
+    println!("  show <doc-id>        Show a source and its chunks");
+    println!("  search <query>       Substring search over chunk text (--source, --meta, --limit)");
+    println!("  rm <doc-id>...       Delete sources");
+    println!("  export               Write sources as JSONL to stdout or --output");
+    println!("  import <file>        Replay a JSONL export through the dedup path");
+    println!("  gc                   Purge deleted chunks (--vacuum, --include-indexed)");
+    println!("  tail                 Follow ingest events (needs a running server)");
+    println!("  forget <path>        Erase a source and never capture it again (--site, --pattern, --for, --until)");
+    println!("  tombstones           List forgotten paths, patterns and sites");
+    println!("  unforget <id>        Remove a tombstone so captures are stored again");
+    println!();
+    println!("Options:");
+    println!("  -c, --config <path>  Config file (default: {:?})", ServerConfig::default_config_path());
+    println!("  --db <path>          Database path");
+    println!("  --socket <path>      Socket path");
+    println!("  --direct             Open the database even if a server is running");
+    println!("  --json               Print JSON instead of tables");
+    println!("  --source <type>      Only this source type (repeatable)");
+    println!("  --since <date>       Captured at or after date (YYYY-MM-DD or RFC 3339)");
+    println!("  --until <date>       Captured before date");
+    println!("  --meta <key=value>   Metadata has this value, or a list containing it (repeatable)");
+    println!("  -n, --limit <n>      Show at most n results");
+    println!("  -o, --output <file>  Export destination (default: stdout)");
+    println!("  --vacuum             Compact the database file after gc");
+    println!("  --include-indexed    Also purge deleted chunks that have a vector index");
+    println!("  --site               forget: argument is a site, covering all its subdomains");
+    println!("  --pattern            forget: argument is a glob where * matches anything");
+    println!("  --for <days>         forget: expire the tombstone after this many days");
+}
+
+/// Where requests go: a running server, or the database opened in-process
+enum Backend {
+    Socket {
+        reader: BufReader<OwnedReadHalf>,
+        writer: OwnedWriteHalf,
+    },
+    Direct(Box<IngestionServer>),
+}
+
+impl Backend {
+    /// Connect to the server, falling back to the database when none is running
+    async fn connect(config: ServerConfig, direct: bool) -> CtlResult<Self> {
+        if !direct {
+            match UnixStream::connect(&config.socket_path).await {
+                Ok(stream) => {
+                    let (reader, writer) = stream.into_split();
+                    return Ok(Backend::Socket {
+                        reader: BufReader::new(reader),
+                        writer,
+                    });
+                }
+                Err(e) => eprintln!(
+                    "No server on {:?} ({}); opening {:?} directly",
+                    config.socket_path, e, config.db_path
+                ),
+            }
+        }
+
+        // Don't leave an empty database behind on a typo'd path
+        if !config.db_path.exists() {
+            return Err(format!("No database at {:?}", config.db_path).into());
+        }
+        Ok(Backend::Direct(Box::new(IngestionServer::new(config)?)))
+    }
+
+    /// Send one line and read one line back
+    async fn round_trip<Req: Serialize, Resp: DeserializeOwned>(
+        reader: &mut BufReader<OwnedReadHalf>,
+        writer: &mut OwnedWriteHalf,
+        request: &Req,
+    ) -> CtlResult<Resp> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await?;
+        writer.flush().await?;
+
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err("Server closed the connection".into());
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    /// Run a control request; error responses become errors
+    async fn control(&mut self, request: ControlRequest) -> CtlResult<ControlResponse> {
+        let response: ControlResponse = match self {
+            Backend::Socket { reader, writer } => Self::round_trip(reader, writer, &request).await?,
+            Backend::Direct(server) => server.control(request).await,
+        };
+        match response.status {
+            ResponseStatus::Ok => Ok(response),
+            ResponseStatus::Error => Err(response.message.unwrap_or_else(|| "Request failed".into()).into()),
+        }
+    }
+
+    /// Replay a batch of export records
+    async fn import(&mut self, records: Vec<ExportRecord>) -> CtlResult<ImportSummary> {
+        let response = self.control(ControlRequest::Import { records }).await?;
+        Ok(response.import.ok_or("Missing import summary in response")?)
+    }
+}
+
+/// Format unix seconds as local time
+fn format_time(secs: i64) -> String {
+    DateTime::from_timestamp(secs, 0)
+        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| secs.to_string())
+}
+
+/// Format a byte count for humans
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn format_tombstone(tombstone: &Tombstone) -> String {
+    let expires = match tombstone.expires_at {
+        Some(expires_at) => format!("until {}", format_time(expires_at)),
+        None => "forever".to_string(),
+    };
+    format!(
+        "{:>4}  {:<8} {}  ({})",
+        tombstone.id,
+        format!("{:?}", tombstone.kind).to_lowercase(),
+        tombstone.value,
+        expires
+    )
+}
+
+fn print_json<T: Serialize>(value: &T) -> CtlResult<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+async fn run(command: Command, options: Options, config: ServerConfig) -> CtlResult<()> {
+    let socket_path = config.socket_path.clone();
+    let mut backend = Backend::connect(config, options.direct).await?;
+
+    match command {
+        Command::Stats => {
+            let response = backend.control(ControlRequest::Stats).await?;
+            let stats = response.stats.ok_or("Missing stats in response")?;
+            if options.json {
+                return print_json(&stats);
+            }
+            println!("sources:   {}", stats.storage.source_count);
+            for (source_type, count) in &stats.storage.sources_by_type {
+                println!("  {:<16} {}", source_type, count);
+            }
+            println!(
+                "chunks:    {} live, {} deleted",
+                stats.storage.chunk_count, stats.storage.deleted_chunk_count
+            );
+            if stats.storage.stale_chunk_count > 0 {
+                println!(
+                    "outdated:  {} chunks from an older pipeline (run `ingestion-server reprocess`)",
+                    stats.storage.stale_chunk_count
+                );
+            }
+            println!("database:  {}", format_size(stats.storage.size_bytes));
+            let compression = &stats.storage.compression;
+            if compression.compressed_chunks > 0 {
+                println!(
+                    "compressed: {} chunks, {} -> {} ({:.2}x), {} plain",
+                    compression.compressed_chunks,
+                    format_size(compression.original_bytes),
+                    format_size(compression.stored_bytes),
+                    compression.ratio,
+                    compression.uncompressed_chunks
+                );
+            }
+            if let Backend::Socket { .. } = backend {
+                println!("cache:     {} / {} entries", stats.cache.entries, stats.cache.max_entries);
+                println!(
+                    "throttle:  {} deferred now, {} deferred, {} dropped since start",
+                    stats.throttle.pending, stats.throttle.deferred, stats.throttle.dropped
+                );
+                println!(
+                    "quality:   {} boilerplate lines learned, {} stripped, {} payloads skipped",
+                    stats.quality.boilerplate_lines, stats.quality.stripped_lines, stats.quality.skipped
+                );
+            }
+        }
+        Command::Ls => {
+            let request = ControlRequest::ListSources {
+                source_types: options.source_types,
+                since: options.since,
+                until: options.until,
+                metadata: options.metadata,
+            };
+            let mut sources = backend.control(request).await?.sources.unwrap_or_default();
+            // Oldest first from the server; keep the most recent ones
+            if let Some(limit) = options.limit {
+                sources.drain(..sources.len().saturating_sub(limit));
+            }
+            if options.json {
+                return print_json(&sources);
+            }
+            for source in &sources {
+                println!(
+                    "{}  {}  {:<12} {:>4}  {}",
+                    source.ehl_doc_id,
+                    format_time(source.captured_at),
+                    source.source_type,
+                    source.chunk_count,
+                    source.source_path
+                );
+            }
+            eprintln!("{} sources", sources.len());
+        }
+        Command::Show(ehl_doc_id) => {
+            let response = backend.control(ControlRequest::Show { ehl_doc_id }).await?;
+            if options.json {
+                return print_json(&response);
+            }
+            let source = response.sources.and_then(|s| s.into_iter().next()).ok_or("Missing source in response")?;
+            println!("id:        {}", source.ehl_doc_id);
+            println!("type:      {}", source.source_type);
+            println!("path:      {}", source.source_path);
+            println!("hash:      {}", source.content_hash);
+            println!("status:    {}", source.ingestion_status);
+            println!("captured:  {}", format_time(source.captured_at));
+            println!("created:   {}", source.created_at);
+            println!("updated:   {}", source.updated_at);
+            println!("chunks:    {}", source.chunk_count);
+            for chunk in response.chunks.unwrap_or_default() {
+                let index = chunk.meta.get("chunk_index").and_then(|v| v.as_u64()).unwrap_or(0);
+                println!();
+                println!("--- chunk {} ---", index);
+                println!("{}", chunk.text);
+            }
+        }
+        Command::Search(query) => {
+            let request = ControlRequest::Search {
+                query,
+                source_types: options.source_types,
+                metadata: options.metadata,
+                limit: options.limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+            };
+            let hits = backend.control(request).await?.hits.unwrap_or_default();
+            if options.json {
+                return print_json(&hits);
+            }
+            for hit in &hits {
+                println!(
+                    "{}  {}  chunk {}  {}",
+                    hit.source.ehl_doc_id, hit.source.source_type, hit.chunk_index, hit.source.source_path
+                );
+                println!("    {}", hit.snippet);
+            }
+            eprintln!("{} matches", hits.len());
+        }
+        Command::Rm(ehl_doc_ids) => {
+            let response = backend.control(ControlRequest::Remove { ehl_doc_ids }).await?;
+            for source in response.sources.unwrap_or_default() {
+                println!("removed {}  {}", source.ehl_doc_id, source.source_path);
+            }
+            // An alias ID removes the source under its current ID, so the
+            // server reports what it could not find
+            let missing = response.not_found.unwrap_or_default();
+            for id in &missing {
+                eprintln!("not found: {}", id);
+            }
+            if !missing.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Command::Export => {
+            let mut writer: Box<dyn Write> = match &options.output {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            // Written a page at a time as the server sends them
+            let mut exported = 0;
+            let mut after = None;
+            loop {
+                let request = ControlRequest::Export {
+                    source_types: options.source_types.clone(),
+                    since: options.since,
+                    until: options.until,
+                    metadata: options.metadata.clone(),
+                    after,
+                    limit: DEFAULT_EXPORT_LIMIT,
+                };
+                let response = backend.control(request).await?;
+                for record in response.records.unwrap_or_default() {
+                    serde_json::to_writer(&mut writer, &record)?;
+                    writer.write_all(b"\n")?;
+                    exported += 1;
+                }
+                match response.next {
+                    Some(next) => after = Some(next),
+                    None => break,
+                }
+            }
+            writer.flush()?;
+            eprintln!("Exported {} sources", exported);
+        }
+        Command::Import(input) => {
+            let reader = StdBufReader::new(File::open(&input)?);
+            let summary = match &mut backend {
+                Backend::Direct(server) => server.import_jsonl(reader).await?,
+                Backend::Socket { .. } => {
+                    let mut summary = ImportSummary::default();
+                    let mut batch = Vec::new();
+                    for record in export::read_jsonl(reader) {
+                        match record {
+                            Ok(record) => batch.push(record),
+                            Err(e) => {
+                                eprintln!("Skipping unreadable record: {}", e);
+                                summary.failed += 1;
+                            }
+                        }
+                        if batch.len() == IMPORT_BATCH {
+                            summary.add(&backend.import(std::mem::take(&mut batch)).await?);
+                        }
+                    }
+                    if !batch.is_empty() {
+                        summary.add(&backend.import(batch).await?);
+                    }
+                    summary
+                }
+            };
+            if options.json {
+                return print_json(&summary);
+            }
+            println!(
+                "Imported {:?}: {} created, {} updated, {} skipped, {} failed",
+                input, summary.created, summary.updated, summary.skipped, summary.failed
+            );
+        }
+        Command::Gc => {
+            let request = ControlRequest::Gc {
+                include_indexed: options.include_indexed,
+                vacuum: options.vacuum,
+            };
+            let summary = backend.control(request).await?.gc.ok_or("Missing gc summary in response")?;
+            if options.json {
+                return print_json(&summary);
+            }
+            println!("purged {} deleted chunks", summary.purged_chunks);
+            if summary.kept_indexed > 0 {
+                println!(
+                    "kept {} deleted chunks with a vector index (use --include-indexed once the index is rebuilt)",
+                    summary.kept_indexed
+                );
+            }
+            if summary.purged_revisions > 0 {
+                println!("purged {} superseded revisions", summary.purged_revisions);
+            }
+            println!(
+                "database: {} -> {}",
+                format_size(summary.size_before),
+                format_size(summary.size_after)
+            );
+        }
+        Command::Forget(value) => {
+            let expires_at = match options.forget_days {
+                Some(days) => Some(Local::now().timestamp() + days * 24 * 60 * 60),
+                None => options.until,
+            };
+            let request = ControlRequest::Forget {
+                kind: options.forget_kind.unwrap_or(TombstoneKind::Path),
+                value,
+                expires_at,
+            };
+            let response = backend.control(request).await?;
+            if options.json {
+                return print_json(&response);
+            }
+            for tombstone in response.tombstones.unwrap_or_default() {
+                println!("tombstone {}", format_tombstone(&tombstone));
+            }
+            for source in response.sources.unwrap_or_default() {
+                println!("erased {}  {}", source.ehl_doc_id, source.source_path);
+            }
+        }
+        Command::Tombstones => {
+            let tombstones = backend.control(ControlRequest::Tombstones).await?.tombstones.unwrap_or_default();
+            if options.json {
+                return print_json(&tombstones);
+            }
+            for tombstone in &tombstones {
+                println!("{}", format_tombstone(tombstone));
+            }
+            eprintln!("{} tombstones", tombstones.len());
+        }
+        Command::Unforget(id) => {
+            let response = backend.control(ControlRequest::Unforget { id }).await?;
+            for tombstone in response.tombstones.unwrap_or_default() {
+                println!("removed tombstone {}", format_tombstone(&tombstone));
+            }
+        }
+        Command::Tail => match backend {
+            Backend::Socket { reader, writer } => tail(reader, writer, &socket_path, options.json).await?,
+            Backend::Direct(_) => return Err(format!("tail needs a running server on {:?}", socket_path).into()),
+        },
+    }
+    Ok(())
+}
+
+/// Print ingest events as the server on `socket_path` processes them
+async fn tail(
+    mut reader: BufReader<OwnedReadHalf>,
+    mut writer: OwnedWriteHalf,
+    socket_path: &Path,
+    json: bool,
+) -> CtlResult<()> {
+    let ack: ControlResponse = Backend::round_trip(&mut reader, &mut writer, &ControlRequest::Tail).await?;
+    if ack.status == ResponseStatus::Error {
+        return Err(ack.message.unwrap_or_else(|| "tail refused".into()).into());
+    }
+    eprintln!("Following {:?} (Ctrl+C to stop)", socket_path);
+
+    let mut line = String::new();
+    while reader.read_line(&mut line).await? > 0 {
+        if json {
+            print!("{}", line);
+        } else {
+            let event: IngestEvent = serde_json::from_str(&line)?;
+            let time = DateTime::from_timestamp_millis(event.received_at)
+                .map(|t| t.with_timezone(&Local).format("%H:%M:%S").to_string())
+                .unwrap_or_default();
+            let mut detail = match (&event.response.chunk_count, &event.response.message) {
+                (Some(chunks), _) => match &event.response.chunk_diff {
+                    Some(diff) => format!("{} chunks: +{} -{} ={}", chunks, diff.added, diff.removed, diff.kept),
+                    None => format!("{} chunks", chunks),
+                },
+                (None, Some(message)) => message.clone(),
+                (None, None) => String::new(),
+            };
+            if let Some(diff) = &event.response.text_diff {
+                detail.push_str(&format!(
+                    "; text +{} -{} chars, +{} -{} lines",
+                    diff.inserted_chars, diff.deleted_chars, diff.inserted_lines, diff.deleted_lines
+                ));
+            }
+            println!(
+                "{}  {:<7}  {:<12} {}  ({})",
+                time,
+                format!("{:?}", event.response.action).to_lowercase(),
+                event.source,
+                event.url,
+                detail
+            );
+        }
+        line.clear();
+    }
+    eprintln!("Server closed the connection");
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let (command, options, config) = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!();
+            print_help();
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(command, options, config).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Parse `args`, reading config from a file that does not exist
+    fn parse(args: &[&str]) -> Result<(Command, Options, ServerConfig), String> {
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        args.extend(["--config".to_string(), "/nonexistent/ingestion.toml".to_string()]);
+        parse_args(args)
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        let (command, options, config) = parse(&[
+            "ls", "--source", "slack", "--source", "gdocs", "--since", "2024-06-01", "-n", "5", "--json",
+            "--db", "/tmp/content.db", "--socket", "/tmp/ingestion.sock",
+        ])
+        .unwrap();
+        assert!(matches!(command, Command::Ls));
+        assert_eq!(options.source_types, vec!["slack", "gdocs"]);
+        assert_eq!(options.since, Some(1_717_200_000));
+        assert_eq!(options.limit, Some(5));
+        assert!(options.json && !options.direct);
+        assert_eq!(config.db_path, PathBuf::from("/tmp/content.db"));
+        assert_eq!(config.socket_path, PathBuf::from("/tmp/ingestion.sock"));
+
+        let (command, options, _) = parse(&["forget", "mybank.com", "--site", "--for", "30"]).unwrap();
+        assert!(matches!(command, Command::Forget(ref value) if value == "mybank.com"));
+        assert_eq!(options.forget_kind, Some(TombstoneKind::Site));
+        assert_eq!(options.forget_days, Some(30));
+
+        let (command, _, _) = parse(&["search", "quarterly", "roadmap"]).unwrap();
+        assert!(matches!(command, Command::Search(ref query) if query == "quarterly roadmap"));
+    }
+
+    #[test]
+    fn test_parse_meta_keeps_json_scalars() {
+        let (_, options, _) = parse(&[
+            "ls", "--meta", "status=Done", "--meta", "points=3", "--meta", "blocked=true", "--meta",
+            "labels=[\"a\"]", "--meta", "query=a=b",
+        ])
+        .unwrap();
+        let metadata = serde_json::to_value(&options.metadata).unwrap();
+        assert_eq!(
+            metadata,
+            json!({ "status": "Done", "points": 3, "blocked": true, "labels": "[\"a\"]", "query": "a=b" })
+        );
+        assert!(parse(&["ls", "--meta", "status"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_arguments() {
+        for args in [
+            &["stats", "extra"][..],
+            &["export", "corpus.jsonl"],
+            &["tail", "now"],
+            &["show"],
+            &["show", "a", "b"],
+            &["rm"],
+            &["search"],
+            &["unforget", "three"],
+            &["ls", "--since", "yesterday"],
+            &["ls", "--limit", "-1"],
+            &["ls", "--limit"],
+            &["ls", "--verbose"],
+            &["launch"],
+            &[],
+        ] {
+            assert!(parse(args).is_err(), "{:?} should not parse", args);
+        }
+
+        let (command, _, _) = parse(&["rm", "id-1", "id-2"]).unwrap();
+        assert!(matches!(command, Command::Rm(ref ids) if ids == &["id-1", "id-2"]));
+        let (command, _, _) = parse(&["unforget", "3"]).unwrap();
+        assert!(matches!(command, Command::Unforget(3)));
+    }
+
+    #[tokio::test]
+    async fn test_connect_falls_back_to_the_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ServerConfig {
+            socket_path: dir.path().join("ingestion.sock"),
+            db_path: dir.path().join("content.db"),
+            ..Default::default()
+        };
+
+        // No server and no database: nothing to open
+        let missing = Backend::connect(config.clone(), false).await;
+        assert!(missing.is_err());
+        assert!(!config.db_path.exists());
+
+        drop(IngestionServer::new(config.clone()).unwrap());
+        let mut backend = Backend::connect(config, false).await.unwrap();
+        assert!(matches!(backend, Backend::Direct(_)));
+        let stats = backend.control(ControlRequest::Stats).await.unwrap().stats.unwrap();
+        assert_eq!(stats.storage.source_count, 0);
+    }
+}
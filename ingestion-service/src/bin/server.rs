@@ -2,10 +2,254 @@
 //!
 //! Run this to start the ingestion service as a standalone process.
 //! In production, this would be integrated into the Tauri app.
+//!
+//! # Usage
+//!
+//! ```bash
+//! # Run the socket server
+//! ingestion-server
+//!
+//! # Export sources as JSONL (optionally filtered)
+//! ingestion-server export --source gdocs --since 2024-01-01 --output corpus.jsonl
+//!
+//! # Import a JSONL export through the normal dedup path
+//! ingestion-server import corpus.jsonl
+//!
+//! # Show how a URL is canonicalized by the configured rules
+//! ingestion-server canonicalize notion "https://www.notion.so/acme/Roadmap-0123...?pvs=4"
+//!
+//! # Snapshot the database (safe while the server is running)
+//! ingestion-server snapshot --compress
+//!
+//! # Report (or fix) sources whose chunks are missing, duplicated or miscounted
+//! ingestion-server check --repair
+//!
+//! # Compress stored chunk text, training a zstd dictionary first
+//! ingestion-server compress --train-dictionary
+//!
+//! # Redact and re-chunk sources after the [chunker] or [redaction] settings change
+//! ingestion-server reprocess
+//!
+//! # Replace the database with a snapshot (the server must be stopped)
+//! ingestion-server restore ~/.local/share/clace-ingestion/backups/content-20240101-120000-000.db
+//! ```
 
-use ingestion_service::IngestionServer;
+use capture_protocol::mask::Masks;
+use capture_protocol::redact::Redactor;
+use ingestion_service::backup::{self, Snapshotter};
+use ingestion_service::canonical::CanonicalRules;
+use ingestion_service::export;
+use ingestion_service::chunker::Chunker;
+use ingestion_service::integrity::IntegrityReport;
+use ingestion_service::reprocess;
+use ingestion_service::storage::SourceFilter;
+use ingestion_service::{IngestionServer, ServerConfig, Storage};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+/// What the binary was asked to do
+enum Command {
+    Serve,
+    Export {
+        filter: SourceFilter,
+        output: Option<PathBuf>,
+    },
+    Import {
+        input: PathBuf,
+    },
+    Canonicalize {
+        source: String,
+        url: String,
+    },
+    Snapshot {
+        compress: Option<bool>,
+    },
+    Restore {
+        input: PathBuf,
+    },
+    Check {
+        repair: bool,
+    },
+    Compress {
+        train_dictionary: bool,
+    },
+    Reprocess,
+}
+
+/// Chunk rows compressed per write transaction by `compress`
+const COMPRESS_BATCH: usize = 500;
+
+/// Live chunks sampled when training a dictionary
+const DICT_SAMPLES: usize = 20_000;
+
+/// Parse command line arguments into a command and server configuration
+fn parse_args() -> Result<(Command, ServerConfig), String> {
+    let args: Vec<String> = std::env::args().collect();
+    let mut command = Command::Serve;
+    let mut config_path: Option<PathBuf> = None;
+    let mut db_path: Option<PathBuf> = None;
+    let mut socket_path: Option<PathBuf> = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--help" | "-h" => {
+                print_help();
+                std::process::exit(0);
+            }
+            "export" => {
+                command = Command::Export {
+                    filter: SourceFilter::default(),
+                    output: None,
+                };
+            }
+            "import" => {
+                i += 1;
+                let input = args.get(i).ok_or("import requires a file path")?;
+                command = Command::Import {
+                    input: PathBuf::from(input),
+                };
+            }
+            "canonicalize" => {
+                let source = args.get(i + 1).ok_or("canonicalize requires a source")?;
+                let url = args.get(i + 2).ok_or("canonicalize requires a URL")?;
+                command = Command::Canonicalize {
+                    source: source.clone(),
+                    url: url.clone(),
+                };
+                i += 2;
+            }
+            "snapshot" => {
+                command = Command::Snapshot { compress: None };
+            }
+            "--compress" => {
+                let Command::Snapshot { compress } = &mut command else {
+                    return Err("--compress is only valid with snapshot".to_string());
+                };
+                *compress = Some(true);
+            }
+            "restore" => {
+                i += 1;
+                let input = args.get(i).ok_or("restore requires a snapshot path")?;
+                command = Command::Restore {
+                    input: PathBuf::from(input),
+                };
+            }
+            "check" => {
+                command = Command::Check { repair: false };
+            }
+            "--repair" => {
+                let Command::Check { repair } = &mut command else {
+                    return Err("--repair is only valid with check".to_string());
+                };
+                *repair = true;
+            }
+            "compress" => {
+                command = Command::Compress {
+                    train_dictionary: false,
+                };
+            }
+            "reprocess" => {
+                command = Command::Reprocess;
+            }
+            "--train-dictionary" => {
+                let Command::Compress { train_dictionary } = &mut command else {
+                    return Err("--train-dictionary is only valid with compress".to_string());
+                };
+                *train_dictionary = true;
+            }
+            "--config" | "-c" => {
+                i += 1;
+                let path = args.get(i).ok_or("--config requires a path")?;
+                config_path = Some(PathBuf::from(path));
+            }
+            "--db" => {
+                i += 1;
+                let path = args.get(i).ok_or("--db requires a path")?;
+                db_path = Some(PathBuf::from(path));
+            }
+            "--socket" => {
+                i += 1;
+                let path = args.get(i).ok_or("--socket requires a path")?;
+                socket_path = Some(PathBuf::from(path));
+            }
+            "--source" | "--since" | "--until" | "--output" | "-o" => {
+                let flag = args[i].clone();
+                i += 1;
+                let value = args
+                    .get(i)
+                    .ok_or_else(|| format!("{} requires a value", flag))?;
+                let Command::Export { filter, output } = &mut command else {
+                    return Err(format!("{} is only valid with export", flag));
+                };
+                match flag.as_str() {
+                    "--source" => filter.source_types.push(value.clone()),
+                    "--since" => {
+                        filter.since = Some(
+                            export::parse_date(value)
+                                .ok_or_else(|| format!("Invalid date: {}", value))?,
+                        )
+                    }
+                    "--until" => {
+                        filter.until = Some(
+                            export::parse_date(value)
+                                .ok_or_else(|| format!("Invalid date: {}", value))?,
+                        )
+                    }
+                    _ => *output = Some(PathBuf::from(value)),
+                }
+            }
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+        i += 1;
+    }
+
+    let mut config = match config_path {
+        Some(path) => ServerConfig::load_from_path(&path),
+        None => ServerConfig::load(),
+    };
+    if let Some(path) = db_path {
+        config.db_path = path;
+    }
+    if let Some(path) = socket_path {
+        config.socket_path = path;
+    }
+
+    Ok((command, config))
+}
+
+fn print_help() {
+    println!("Clace Ingestion Service");
+    println!();
+    println!("Usage: ingestion-server [command] [options]");
+    println!();
+    println!("Commands:");
+    println!("  (none)               Run the socket server");
+    println!("  export               Write sources as JSONL to stdout or --output");
+    println!("  import <file>        Replay a JSONL export through the dedup path");
+    println!("  canonicalize <source> <url>");
+    println!("                       Show the canonical source path for a URL");
+    println!("  snapshot             Write a consistent copy of the database to the backup dir");
+    println!("  restore <file>       Replace the database with a snapshot (server must be stopped)");
+    println!("  check                Report chunk count, orphan, duplicate and hash problems");
+    println!("  compress             Compress chunk text stored before compression was enabled");
+    println!("  reprocess            Redact and re-chunk sources from an older pipeline, [chunker] or [redaction] settings");
+    println!();
+    println!("Options:");
+    println!("  -c, --config <path>  Config file (default: {:?})", ServerConfig::default_config_path());
+    println!("  --db <path>          Database path");
+    println!("  --socket <path>      Socket path");
+    println!("  --source <type>      Export only this source type (repeatable)");
+    println!("  --since <date>       Export sources captured at or after date (YYYY-MM-DD or RFC 3339)");
+    println!("  --until <date>       Export sources captured before date");
+    println!("  -o, --output <file>  Export destination (default: stdout)");
+    println!("  --compress           Compress the snapshot with zstd");
+    println!("  --repair             Fix the problems found by check");
+    println!("  --train-dictionary   Train a zstd dictionary on recent chunks before compressing");
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logging
@@ -13,12 +257,211 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with(tracing_subscriber::EnvFilter::new(
             std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
         ))
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .init();
 
+    let (command, config) = match parse_args() {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            print_help();
+            std::process::exit(2);
+        }
+    };
+
+    match command {
+        Command::Serve => serve(config).await,
+        Command::Export { filter, output } => {
+            let server = IngestionServer::new(config)?;
+            let count = match output {
+                Some(path) => {
+                    server
+                        .export_jsonl(&filter, BufWriter::new(File::create(&path)?))
+                        .await?
+                }
+                None => server.export_jsonl(&filter, std::io::stdout().lock()).await?,
+            };
+            eprintln!("Exported {} sources", count);
+            Ok(())
+        }
+        Command::Import { input } => {
+            let server = IngestionServer::new(config)?;
+            let summary = server
+                .import_jsonl(BufReader::new(File::open(&input)?))
+                .await?;
+            eprintln!(
+                "Imported {:?}: {} created, {} updated, {} skipped, {} failed",
+                input, summary.created, summary.updated, summary.skipped, summary.failed
+            );
+            Ok(())
+        }
+        Command::Canonicalize { source, url } => {
+            let mut rules = CanonicalRules::compile(&config.canonicalization)?;
+            if config.db_path.exists() {
+                rules.set_legacy_sources(Storage::open(&config.db_path)?.legacy_url_sources()?);
+            }
+            let result = rules.explain(&source, &url);
+            println!("{}", result.source_path);
+            match result.rule {
+                Some(rule) => eprintln!(
+                    "matched rule: pattern {:?} -> template {:?}",
+                    rule.pattern, rule.template
+                ),
+                None => eprintln!("no rule matched; used the default for source {:?}", source),
+            }
+            Ok(())
+        }
+        Command::Snapshot { compress } => {
+            let snapshotter = Snapshotter::new(config.db_path, config.backup);
+            let info = snapshotter.snapshot(compress)?;
+            println!("{}", info.path.display());
+            eprintln!(
+                "Snapshot written: {} bytes, schema version {}, {} old snapshots removed",
+                info.size_bytes,
+                info.schema_version,
+                info.rotated.len()
+            );
+            Ok(())
+        }
+        Command::Restore { input } => restore(config, &input),
+        Command::Check { repair } => {
+            let mut storage = Storage::open(&config.db_path)?;
+            storage.set_masks(Masks::compile(&config.masking)?);
+            let report = if repair {
+                storage.repair_integrity()?
+            } else {
+                storage.check_integrity()?
+            };
+            print_integrity_report(&report);
+            if repair {
+                eprintln!("Repaired {} problems", report.issue_count());
+            } else if !report.is_clean() {
+                eprintln!("Found {} problems; run `check --repair` to fix them", report.issue_count());
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Compress { train_dictionary } => {
+            let mut storage = Storage::open(&config.db_path)?;
+            storage.enable_compression(config.compression.level)?;
+            if train_dictionary {
+                let dict_id = storage.train_compression_dictionary(DICT_SAMPLES)?;
+                eprintln!("Trained dictionary {}", dict_id);
+            }
+            let compressed = storage.compress_existing(COMPRESS_BATCH)?;
+            let stats = storage.stats()?.compression;
+            eprintln!(
+                "Compressed {} chunks; {} compressed in total, {} -> {} bytes ({:.2}x), {} left as plain text",
+                compressed,
+                stats.compressed_chunks,
+                stats.original_bytes,
+                stats.stored_bytes,
+                stats.ratio,
+                stats.uncompressed_chunks
+            );
+            if compressed > 0 {
+                eprintln!("Run `ingestion-ctl gc --vacuum` to return the freed pages to the filesystem");
+            }
+            Ok(())
+        }
+        Command::Reprocess => {
+            let chunker = Chunker::new(config.chunker.clone());
+            let redactor = Redactor::new(config.redaction.clone());
+            let pipeline_version = reprocess::pipeline_version(&chunker, &redactor);
+            let mut storage = Storage::open(&config.db_path)?;
+            storage.set_pipeline_version(pipeline_version.clone());
+            storage.set_masks(Masks::compile(&config.masking)?);
+            if config.compression.enabled {
+                storage.enable_compression(config.compression.level)?;
+            }
+            let summary = reprocess::run(&mut storage, &chunker, &redactor, |progress| match progress.result {
+                Ok(diff) => eprintln!(
+                    "[{}/{}] {}: {} added, {} removed, {} kept",
+                    progress.done, progress.total, progress.source.source_path, diff.added, diff.removed, diff.kept
+                ),
+                Err(e) => eprintln!(
+                    "[{}/{}] {}: failed: {}",
+                    progress.done, progress.total, progress.source.source_path, e
+                ),
+            })?;
+            eprintln!(
+                "Reprocessed {} sources with pipeline {}: {} chunks added, {} removed, {} kept; {} failed",
+                summary.reprocessed,
+                pipeline_version,
+                summary.chunks.added,
+                summary.chunks.removed,
+                summary.chunks.kept,
+                summary.failed
+            );
+            if summary.failed > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Print one line per problem to stdout
+fn print_integrity_report(report: &IntegrityReport) {
+    for m in &report.count_mismatches {
+        println!(
+            "chunk_count\t{}\t{}\trecorded {}, live {}",
+            m.ehl_doc_id, m.source_path, m.recorded, m.actual
+        );
+    }
+    for o in &report.orphaned_chunks {
+        println!(
+            "orphan\tchunk {}\t{}\t{}",
+            o.chunk_id,
+            o.ehl_doc_id.as_deref().unwrap_or("(unreadable meta)"),
+            if o.is_deleted { "deleted" } else { "live" }
+        );
+    }
+    for d in &report.duplicate_indexes {
+        println!(
+            "duplicate\t{}\tchunk_index {}\tchunks {:?}",
+            d.ehl_doc_id, d.chunk_index, d.chunk_ids
+        );
+    }
+    for h in &report.hash_mismatches {
+        println!(
+            "hash\t{}\t{}\trecorded {}, actual {}",
+            h.ehl_doc_id, h.source_path, h.recorded, h.actual
+        );
+    }
+}
+
+/// Restore a snapshot over the configured database
+fn restore(config: ServerConfig, input: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    // Replacing the file under a live server would lose its writes
+    if std::os::unix::net::UnixStream::connect(&config.socket_path).is_ok() {
+        return Err(format!(
+            "ingestion server is running on {:?}; stop it before restoring",
+            config.socket_path
+        )
+        .into());
+    }
+
+    // Keep the current database around in case the restore was a mistake
+    if config.db_path.exists() {
+        let snapshotter = Snapshotter::new(config.db_path.clone(), config.backup);
+        let previous = backup::snapshot(&config.db_path, &snapshotter.dir(), false)?;
+        eprintln!("Saved current database to {:?}", previous.path);
+    }
+
+    let version = backup::restore(input, &config.db_path)?;
+    eprintln!(
+        "Restored {:?} to {:?} (schema version {})",
+        input, config.db_path, version
+    );
+    Ok(())
+}
+
+/// Run the socket server until Ctrl+C
+async fn serve(config: ServerConfig) -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting Clace Ingestion Service...");
 
-    let server = IngestionServer::with_defaults()?;
+    let server = IngestionServer::new(config)?;
 
     println!("Socket: {:?}", server.socket_path());
     println!("Press Ctrl+C to stop");
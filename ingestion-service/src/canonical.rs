@@ -0,0 +1,613 @@
+//! Source path canonicalization rules
+//!
+//! Maps a payload's `(source, url)` to the canonical `source_path` used for
+//! dedup. Rules come from config and are compiled once at startup:
+//!
+//! ```toml
+//! [[canonicalization.rules]]
+//! sources = ["notion"]
+//! pattern = 'notion\.so/(?:[^/]+/)?(?:[^/?#]*-)?(?P<page_id>[0-9a-f]{32})'
+//! template = "notion://{page_id}"
+//! ```
+//!
+//! Templates substitute named capture groups and these built-in placeholders:
+//! `{url}`, `{host}`, `{path}` and `{query.NAME}`. A placeholder may be piped
+//! through `trim`, `lower` and `urlencode`, e.g. `{query.q|trim|lower|urlencode}`.
+//! A rule only applies when its pattern matches and every placeholder resolves.
+//!
+//! URLs no rule applies to get a generic cleanup when they are http(s): the
+//! host is lowercased, tracking and session parameters are dropped, the
+//! remaining query parameters are sorted and the fragment is removed (unless it
+//! is a `#/route` or `#!/route`). Per-domain exceptions live under
+//! `[canonicalization.url]`:
+//!
+//! ```toml
+//! [canonicalization.url]
+//! strip_params = ["ref"]
+//!
+//! [[canonicalization.url.domains]]
+//! domain = "example.com"   # also matches subdomains
+//! keep_params = ["sid"]
+//! keep_fragment = true
+//! ```
+//!
+//! Other URLs with no applicable rule are kept verbatim for sources that have
+//! rules, and lose their query string and fragment otherwise.
+//!
+//! Before the generic cleanup existed, http(s) URLs were treated like those
+//! other URLs. Source types the database already held such paths for (see
+//! `content_db::schema::legacy_url_sources`) keep that treatment, so their next
+//! capture finds the stored source instead of creating a second one, unless
+//! `clean_legacy_sources` is set.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+/// A canonicalization rule as written in config
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CanonicalRule {
+    /// Source types this rule applies to (empty = every source)
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Regex matched against the URL
+    pub pattern: String,
+    /// Canonical path template
+    pub template: String,
+}
+
+impl CanonicalRule {
+    fn new(sources: &[&str], pattern: &str, template: &str) -> Self {
+        Self {
+            sources: sources.iter().map(|s| s.to_string()).collect(),
+            pattern: pattern.to_string(),
+            template: template.to_string(),
+        }
+    }
+}
+
+/// The `[canonicalization]` config section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanonicalizationConfig {
+    /// Also apply the built-in rules (after any configured ones)
+    #[serde(default = "default_true")]
+    pub builtin_rules: bool,
+    /// Configured rules, tried in order before the built-in ones
+    #[serde(default)]
+    pub rules: Vec<CanonicalRule>,
+    /// Generic cleanup for http(s) URLs no rule applies to
+    #[serde(default)]
+    pub url: UrlCleanupConfig,
+}
+
+impl Default for CanonicalizationConfig {
+    fn default() -> Self {
+        Self {
+            builtin_rules: true,
+            rules: Vec::new(),
+            url: UrlCleanupConfig::default(),
+        }
+    }
+}
+
+/// The `[canonicalization.url]` config section
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UrlCleanupConfig {
+    /// Query params to drop in addition to the built-in tracking and session
+    /// params; a trailing `*` matches a prefix
+    #[serde(default)]
+    pub strip_params: Vec<String>,
+    /// Per-domain exceptions, applied on top of the built-in ones
+    #[serde(default)]
+    pub domains: Vec<DomainException>,
+    /// Also clean URLs of source types stored before the cleanup existed; their
+    /// sources with query strings are captured again under new paths
+    #[serde(default)]
+    pub clean_legacy_sources: bool,
+}
+
+/// Cleanup overrides for one domain and its subdomains
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DomainException {
+    pub domain: String,
+    /// Extra params to drop on this domain
+    #[serde(default)]
+    pub strip_params: Vec<String>,
+    /// Params kept even though they would be dropped (`*` keeps every param)
+    #[serde(default)]
+    pub keep_params: Vec<String>,
+    /// Keep the fragment (for apps that route in it)
+    #[serde(default)]
+    pub keep_fragment: bool,
+    /// Keep query params in their original order
+    #[serde(default)]
+    pub keep_param_order: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Rules reproducing the historical hard-coded normalization
+pub fn builtin_rules() -> Vec<CanonicalRule> {
+    vec![
+        CanonicalRule::new(&["gdocs"], r"/document/d/(?P<id>[a-zA-Z0-9_-]+)", "gdocs://{id}"),
+        CanonicalRule::new(&["gsheets"], r"/spreadsheets/d/(?P<id>[a-zA-Z0-9_-]+)", "gsheets://{id}"),
+        CanonicalRule::new(&["gslides"], r"/presentation/d/(?P<id>[a-zA-Z0-9_-]+)", "gslides://{id}"),
+        // Content scripts already send canonical gemini:// URLs
+        CanonicalRule::new(&["gemini"], r"^gemini://", "{url}"),
+        CanonicalRule::new(&["gemini"], r"/(?:app|c)/(?P<id>[a-zA-Z0-9_-]+)", "gemini://conversation/{id}"),
+        CanonicalRule::new(&["google-ai", "google-search"], r"^(?:google-ai|google)://", "{url}"),
+        CanonicalRule::new(&["google-ai"], r"^https?://", "google-ai://search/{query.q|lower|trim|urlencode}"),
+        CanonicalRule::new(&["google-search"], r"^https?://", "google://search/{query.q|lower|trim|urlencode}"),
+        CanonicalRule::new(&["jira"], r"^https?://", "jira://{host}:{query.selectedIssue}"),
+        CanonicalRule::new(&["jira"], r"^https?://[^/?#]+[^?#]*/browse/(?P<issue>[A-Z][A-Z0-9]+-\d+)", "jira://{host}:{issue}"),
+        CanonicalRule::new(&["slack"], r"^https?://[^/?#]+[^?#]*/archives/", "slack://{host}:{path}"),
+        CanonicalRule::new(&["teams"], r"^accessibility://", "{url}"),
+        CanonicalRule::new(&["teams"], r"^https?://[^/?#]+[^?#]*/(?:conversations|channel)/", "teams://{host}:{path}"),
+    ]
+}
+
+/// Query params dropped from every http(s) URL: click/campaign tracking and
+/// session identifiers. A trailing `*` matches a prefix.
+const BUILTIN_STRIP_PARAMS: &[&str] = &[
+    "utm_*", "fbclid", "gclid", "gclsrc", "dclid", "gbraid", "wbraid", "msclkid", "yclid",
+    "twclid", "ttclid", "li_fat_id", "igshid", "mc_cid", "mc_eid", "_ga", "_gl", "_hsenc",
+    "_hsmi", "__hstc", "__hssc", "__hsfp", "mkt_tok", "oly_anon_id", "oly_enc_id", "ref_src",
+    "vero_id", "wickedid", "s_cid", "sessionid", "session_id", "sessid", "sid", "phpsessid",
+    "jsessionid", "aspsessionid*", "cfid", "cftoken",
+];
+
+/// Domains whose URLs need gentler cleanup
+fn builtin_domain_exceptions() -> Vec<DomainException> {
+    vec![
+        // Gmail routes to threads in the fragment (#inbox/FMfcgz...)
+        DomainException {
+            domain: "mail.google.com".to_string(),
+            keep_fragment: true,
+            ..Default::default()
+        },
+    ]
+}
+
+/// Error compiling a configured rule
+#[derive(Debug)]
+pub struct RuleError {
+    pub pattern: String,
+    pub error: regex::Error,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid canonicalization pattern {:?}: {}", self.pattern, self.error)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// A rule with its regex compiled
+#[derive(Debug)]
+struct CompiledRule {
+    rule: CanonicalRule,
+    regex: regex::Regex,
+}
+
+/// How a URL was canonicalized (returned by `CanonicalRules::explain`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Canonicalization {
+    /// The canonical source path
+    pub source_path: String,
+    /// The rule that produced it, if any
+    pub rule: Option<CanonicalRule>,
+}
+
+/// Compiled canonicalization rules
+#[derive(Debug)]
+pub struct CanonicalRules {
+    rules: Vec<CompiledRule>,
+    /// Sources that have at least one source-specific rule
+    ruled_sources: HashSet<String>,
+    strip_params: Vec<String>,
+    domains: Vec<DomainException>,
+    /// Sources whose http(s) URLs keep the pre-cleanup fallback
+    legacy_sources: HashSet<String>,
+    clean_legacy_sources: bool,
+}
+
+impl CanonicalRules {
+    /// Compile the configured rules (plus built-ins when enabled)
+    pub fn compile(config: &CanonicalizationConfig) -> Result<Self, RuleError> {
+        let mut all_rules = config.rules.clone();
+        if config.builtin_rules {
+            all_rules.extend(builtin_rules());
+        }
+
+        let mut rules = Vec::with_capacity(all_rules.len());
+        let mut ruled_sources = HashSet::new();
+        for rule in all_rules {
+            let regex = regex::Regex::new(&rule.pattern).map_err(|error| RuleError {
+                pattern: rule.pattern.clone(),
+                error,
+            })?;
+            ruled_sources.extend(rule.sources.iter().cloned());
+            rules.push(CompiledRule { rule, regex });
+        }
+
+        let mut domains = builtin_domain_exceptions();
+        domains.extend(config.url.domains.iter().cloned());
+        for domain in &mut domains {
+            domain.domain = domain.domain.trim_start_matches('.').to_lowercase();
+        }
+
+        Ok(Self {
+            rules,
+            ruled_sources,
+            strip_params: BUILTIN_STRIP_PARAMS
+                .iter()
+                .map(|p| p.to_string())
+                .chain(config.url.strip_params.iter().map(|p| p.to_lowercase()))
+                .collect(),
+            domains,
+            legacy_sources: HashSet::new(),
+            clean_legacy_sources: config.url.clean_legacy_sources,
+        })
+    }
+
+    /// Source types stored before the generic cleanup, which keep their old
+    /// paths unless `clean_legacy_sources` is set
+    pub fn set_legacy_sources(&mut self, sources: HashSet<String>) {
+        if !self.clean_legacy_sources {
+            self.legacy_sources = sources;
+        }
+    }
+
+    /// Built-in rules only
+    pub fn with_defaults() -> Self {
+        Self::compile(&CanonicalizationConfig::default()).expect("built-in rules compile")
+    }
+
+    /// Canonical source path for a payload's source and URL
+    pub fn canonicalize(&self, source: &str, url: &str) -> String {
+        self.explain(source, url).source_path
+    }
+
+    /// Canonicalize and report which rule applied
+    pub fn explain(&self, source: &str, url: &str) -> Canonicalization {
+        let parsed = url::Url::parse(url).ok();
+
+        for compiled in &self.rules {
+            let rule = &compiled.rule;
+            if !rule.sources.is_empty() && !rule.sources.iter().any(|s| s == source) {
+                continue;
+            }
+            let Some(caps) = compiled.regex.captures(url) else {
+                continue;
+            };
+            if let Some(source_path) = render_template(&rule.template, url, parsed.as_ref(), &caps) {
+                return Canonicalization {
+                    source_path,
+                    rule: Some(rule.clone()),
+                };
+            }
+        }
+
+        let source_path = match parsed {
+            Some(parsed)
+                if matches!(parsed.scheme(), "http" | "https") && !self.legacy_sources.contains(source) =>
+            {
+                self.clean_url(parsed)
+            }
+            _ if self.ruled_sources.contains(source) => url.to_string(),
+            _ => fallback(url),
+        };
+        Canonicalization {
+            source_path,
+            rule: None,
+        }
+    }
+
+    /// Generic cleanup of an http(s) URL
+    fn clean_url(&self, mut url: url::Url) -> String {
+        // The url crate already lowercases http(s) hosts and drops default ports
+        let host = url.host_str().unwrap_or_default().to_string();
+        let exceptions: Vec<&DomainException> = self
+            .domains
+            .iter()
+            .filter(|d| host == d.domain || host.ends_with(&format!(".{}", d.domain)))
+            .collect();
+
+        let keep = |key: &str| {
+            exceptions
+                .iter()
+                .any(|d| d.keep_params.iter().any(|p| p == "*" || param_matches(p, key)))
+        };
+        let strip = |key: &str| {
+            self.strip_params
+                .iter()
+                .chain(exceptions.iter().flat_map(|d| d.strip_params.iter()))
+                .any(|p| param_matches(p, key))
+        };
+
+        // Java-style session IDs ride along in the path (/page;jsessionid=...)
+        if !keep("jsessionid") {
+            if let Some(pos) = url.path().to_ascii_lowercase().find(";jsessionid=") {
+                let path = url.path()[..pos].to_string();
+                url.set_path(&path);
+            }
+        }
+
+        if let Some(query) = url.query() {
+            // Work on the raw pairs so their encoding is left as captured
+            let mut params: Vec<(String, &str)> = query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let raw_key = pair.split('=').next().unwrap_or_default();
+                    let key = urlencoding::decode(&raw_key.replace('+', " "))
+                        .map(|k| k.to_lowercase())
+                        .unwrap_or_else(|_| raw_key.to_lowercase());
+                    (key, pair)
+                })
+                .filter(|(key, _)| keep(key) || !strip(key))
+                .collect();
+            if !exceptions.iter().any(|d| d.keep_param_order) {
+                // Stable: repeated keys keep their relative order
+                params.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+
+            let query = params.iter().map(|(_, pair)| *pair).collect::<Vec<_>>().join("&");
+            url.set_query(if query.is_empty() { None } else { Some(&query) });
+        }
+
+        let keep_fragment = exceptions.iter().any(|d| d.keep_fragment)
+            || url
+                .fragment()
+                .is_some_and(|f| f.starts_with('/') || f.starts_with("!/"));
+        if !keep_fragment {
+            url.set_fragment(None);
+        }
+
+        url.to_string()
+    }
+}
+
+/// Whether a lowercased query key matches a strip/keep pattern
+fn param_matches(pattern: &str, key: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => key.starts_with(prefix),
+        None => pattern == key,
+    }
+}
+
+impl Default for CanonicalRules {
+    fn default() -> Self {
+        Self::with_defaults()
+    }
+}
+
+/// Canonical path for sources without rules: strip query params and fragments
+fn fallback(url: &str) -> String {
+    if let Ok(mut parsed) = url::Url::parse(url) {
+        parsed.set_query(None);
+        parsed.set_fragment(None);
+        parsed.to_string()
+    } else {
+        url.to_string()
+    }
+}
+
+/// Expand a template; `None` if any placeholder cannot be resolved
+fn render_template(
+    template: &str,
+    url: &str,
+    parsed: Option<&url::Url>,
+    caps: &regex::Captures,
+) -> Option<String> {
+    let mut out = String::with_capacity(template.len() + url.len());
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let close = open + rest[open..].find('}')?;
+        let mut parts = rest[open + 1..close].split('|');
+        let name = parts.next()?.trim();
+
+        let mut value = resolve_placeholder(name, url, parsed, caps)?;
+        for filter in parts {
+            value = match filter.trim() {
+                "trim" => value.trim().to_string(),
+                "lower" => value.to_lowercase(),
+                "urlencode" => urlencoding::encode(&value).into_owned(),
+                _ => return None,
+            };
+        }
+        out.push_str(&value);
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+fn resolve_placeholder(
+    name: &str,
+    url: &str,
+    parsed: Option<&url::Url>,
+    caps: &regex::Captures,
+) -> Option<String> {
+    match name {
+        "url" => Some(url.to_string()),
+        "host" => parsed?.host_str().map(String::from),
+        "path" => parsed.map(|p| p.path().to_string()),
+        _ => {
+            if let Some(param) = name.strip_prefix("query.") {
+                return parsed?
+                    .query_pairs()
+                    .find(|(k, _)| k == param)
+                    .map(|(_, v)| v.into_owned());
+            }
+            caps.name(name).map(|m| m.as_str().to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_google_workspace() {
+        let rules = CanonicalRules::with_defaults();
+        assert_eq!(
+            rules.canonicalize("gdocs", "https://docs.google.com/document/d/1AbC_d-9/edit?tab=t.0"),
+            "gdocs://1AbC_d-9"
+        );
+        assert_eq!(
+            rules.canonicalize("gsheets", "https://docs.google.com/spreadsheets/d/XYZ/edit#gid=0"),
+            "gsheets://XYZ"
+        );
+        // Known source whose URL matches no rule only gets the generic cleanup
+        assert_eq!(
+            rules.canonicalize("gdocs", "https://docs.google.com/u/0/?tgif=d"),
+            "https://docs.google.com/u/0/?tgif=d"
+        );
+    }
+
+    #[test]
+    fn test_builtin_query_rules() {
+        let rules = CanonicalRules::with_defaults();
+        assert_eq!(
+            rules.canonicalize("google-ai", "https://www.google.com/search?q=Rust+Lifetimes+&udm=50"),
+            "google-ai://search/rust%20lifetimes"
+        );
+        assert_eq!(
+            rules.canonicalize("jira", "https://acme.atlassian.net/jira/software/projects/OPS/boards/1?selectedIssue=OPS-42"),
+            "jira://acme.atlassian.net:OPS-42"
+        );
+        assert_eq!(
+            rules.canonicalize("jira", "https://acme.atlassian.net/browse/OPS-7?focused=1"),
+            "jira://acme.atlassian.net:OPS-7"
+        );
+        // Self-hosted Jira under a context path
+        assert_eq!(
+            rules.canonicalize("jira", "https://corp.example.com/jira/browse/OPS-7"),
+            "jira://corp.example.com:OPS-7"
+        );
+        assert_eq!(
+            rules.canonicalize("slack", "https://acme.slack.com/archives/C123/p1700000000?thread_ts=1"),
+            "slack://acme.slack.com:/archives/C123/p1700000000"
+        );
+    }
+
+    #[test]
+    fn test_generic_url_cleanup() {
+        let rules = CanonicalRules::with_defaults();
+        let cases = [
+            (
+                "https://Example.COM/Page?utm_source=news&b=2&a=1&fbclid=xyz#top",
+                "https://example.com/Page?a=1&b=2",
+            ),
+            ("https://example.com/page?utm_medium=email", "https://example.com/page"),
+            (
+                "https://example.com/page?b=2&PHPSESSID=abc&a=x%20y",
+                "https://example.com/page?a=x%20y&b=2",
+            ),
+            ("https://shop.example.com/cart;jsessionid=0A1B?item=3", "https://shop.example.com/cart?item=3"),
+            ("https://app.example.com/#/settings/profile", "https://app.example.com/#/settings/profile"),
+            ("https://mail.google.com/mail/u/0/#inbox/FMfcgz", "https://mail.google.com/mail/u/0/#inbox/FMfcgz"),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(rules.canonicalize("chrome", url), expected, "{}", url);
+            // Cleanup is idempotent
+            assert_eq!(rules.canonicalize("chrome", expected), expected);
+        }
+
+        // Non-http URLs keep the old fallback
+        assert_eq!(rules.canonicalize("chrome", "file:///tmp/notes.txt?x=1"), "file:///tmp/notes.txt");
+    }
+
+    #[test]
+    fn test_legacy_sources_keep_old_paths() {
+        let mut rules = CanonicalRules::with_defaults();
+        rules.set_legacy_sources(HashSet::from(["chrome".to_string(), "jira".to_string()]));
+        let url = "https://example.com/page?id=5&utm_source=feed#top";
+        assert_eq!(rules.canonicalize("chrome", url), "https://example.com/page");
+        assert_eq!(rules.canonicalize("firefox", url), "https://example.com/page?id=5");
+        // Sources with rules kept unmatched URLs verbatim
+        assert_eq!(rules.canonicalize("jira", url), url);
+
+        let config: CanonicalizationConfig = toml::from_str("[url]\nclean_legacy_sources = true").unwrap();
+        let mut rules = CanonicalRules::compile(&config).unwrap();
+        rules.set_legacy_sources(HashSet::from(["chrome".to_string()]));
+        assert_eq!(rules.canonicalize("chrome", url), "https://example.com/page?id=5");
+    }
+
+    #[test]
+    fn test_domain_exceptions() {
+        let config: CanonicalizationConfig = toml::from_str(
+            r#"
+            [url]
+            strip_params = ["ref"]
+
+            [[url.domains]]
+            domain = "legacy.example.com"
+            keep_params = ["sid"]
+            keep_fragment = true
+            keep_param_order = true
+
+            [[url.domains]]
+            domain = "news.example.org"
+            strip_params = ["page*"]
+            "#,
+        )
+        .unwrap();
+        let rules = CanonicalRules::compile(&config).unwrap();
+
+        assert_eq!(
+            rules.canonicalize("chrome", "https://legacy.example.com/view?z=1&sid=42&ref=hn#section-2"),
+            "https://legacy.example.com/view?z=1&sid=42#section-2"
+        );
+        assert_eq!(
+            rules.canonicalize("chrome", "https://www.news.example.org/story?id=9&pageview=1"),
+            "https://www.news.example.org/story?id=9"
+        );
+        assert_eq!(
+            rules.canonicalize("chrome", "https://example.com/a?sid=42&ref=hn"),
+            "https://example.com/a"
+        );
+    }
+
+    #[test]
+    fn test_configured_rule_takes_precedence() {
+        let config: CanonicalizationConfig = toml::from_str(
+            r#"
+            [[rules]]
+            sources = ["notion"]
+            pattern = 'notion\.so/(?:[^/]+/)?(?:[^/?#]*-)?(?P<page_id>[0-9a-f]{32})'
+            template = "notion://{page_id}"
+            "#,
+        )
+        .unwrap();
+        let rules = CanonicalRules::compile(&config).unwrap();
+
+        let result = rules.explain(
+            "notion",
+            "https://www.notion.so/acme/Roadmap-0123456789abcdef0123456789abcdef?pvs=4",
+        );
+        assert_eq!(result.source_path, "notion://0123456789abcdef0123456789abcdef");
+        assert_eq!(result.rule.unwrap().template, "notion://{page_id}");
+
+        // Built-ins still apply
+        assert_eq!(rules.canonicalize("gslides", "https://docs.google.com/presentation/d/P1/edit"), "gslides://P1");
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_reported() {
+        let config = CanonicalizationConfig {
+            builtin_rules: false,
+            rules: vec![CanonicalRule::new(&[], "(unclosed", "x://{url}")],
+            ..Default::default()
+        };
+        let err = CanonicalRules::compile(&config).unwrap_err();
+        assert!(err.to_string().contains("(unclosed"));
+    }
+}
@@ -0,0 +1,167 @@
+//! Ingestion server configuration
+//!
+//! Loaded from a TOML file; every field has a default so a missing or partial
+//! file is fine:
+//!
+//! ```toml
+//! socket_path = "/tmp/clace-ingestion.sock"
+//! db_path = "/path/to/content.db"
+//!
+//! [canonicalization]
+//! builtin_rules = true
+//!
+//! [backup]
+//! interval_minutes = 1440
+//!
+//! [compression]
+//! enabled = true
+//!
+//! [dedup]
+//! store_normalized = false
+//!
+//! [[masking.rules]]
+//! apps = ["Slack"]
+//! pattern = '\b\d+ unread\b'
+//!
+//! [redaction]
+//! redact_emails = true
+//!
+//! [chunker]
+//! max_tokens = 512
+//!
+//! [throttle]
+//! min_update_interval_secs = 30
+//!
+//! [significance.sources.ocr-capture]
+//! min_change_ratio = 0.02
+//!
+//! [quality.sources.clipboard]
+//! min_score = 0.0
+//! ```
+
+use crate::backup::BackupConfig;
+use crate::canonical::CanonicalizationConfig;
+use crate::chunker::ChunkerConfig;
+use crate::dedup::DedupConfig;
+use crate::quality::QualityConfig;
+use crate::significance::SignificanceConfig;
+use crate::storage::CompressionConfig;
+use crate::throttle::ThrottleConfig;
+use capture_protocol::mask::MaskConfig;
+use capture_protocol::redact::RedactionConfig;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Ingestion server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Path to the Unix socket
+    pub socket_path: PathBuf,
+    /// Path to the SQLite database
+    pub db_path: PathBuf,
+    /// Source path canonicalization rules
+    pub canonicalization: CanonicalizationConfig,
+    /// Snapshot location, schedule and rotation
+    pub backup: BackupConfig,
+    /// zstd compression of stored chunk text
+    pub compression: CompressionConfig,
+    /// What is stored of the text that content hashes normalize
+    pub dedup: DedupConfig,
+    /// Volatile text (relative times, counters) left out of content hashes
+    pub masking: MaskConfig,
+    /// PII redacted from every payload; stored text is redacted again by `ingestion-server reprocess`
+    pub redaction: RedactionConfig,
+    /// Chunk size and overlap; existing chunks are re-cut by `ingestion-server reprocess`
+    pub chunker: ChunkerConfig,
+    /// Per-source rate limits on stored updates
+    pub throttle: ThrottleConfig,
+    /// Smallest change worth storing as an update
+    pub significance: SignificanceConfig,
+    /// Boilerplate stripping and low-information filtering
+    pub quality: QualityConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        let data_dir = dirs::data_local_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("clace-ingestion");
+
+        Self {
+            socket_path: PathBuf::from("/tmp/clace-ingestion.sock"),
+            db_path: data_dir.join("content.db"),
+            canonicalization: CanonicalizationConfig::default(),
+            backup: BackupConfig::default(),
+            compression: CompressionConfig::default(),
+            dedup: DedupConfig::default(),
+            masking: MaskConfig::default(),
+            redaction: RedactionConfig::default(),
+            chunker: ChunkerConfig::default(),
+            throttle: ThrottleConfig::default(),
+            significance: SignificanceConfig::default(),
+            quality: QualityConfig::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Load configuration from the default path
+    pub fn load() -> Self {
+        Self::load_from_path(&Self::default_config_path())
+    }
+
+    /// Load configuration from a specific path, falling back to defaults
+    pub fn load_from_path(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => match toml::from_str(&contents) {
+                Ok(config) => {
+                    info!("Loaded configuration from {:?}", path);
+                    config
+                }
+                Err(e) => {
+                    warn!("Failed to parse config file: {}, using defaults", e);
+                    Self::default()
+                }
+            },
+            Err(_) => {
+                info!("No config file found at {:?}, using defaults", path);
+                Self::default()
+            }
+        }
+    }
+
+    /// Get the default configuration file path
+    pub fn default_config_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("clace-ingestion")
+            .join("config.toml")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_partial_toml() {
+        let config: ServerConfig = toml::from_str(
+            r#"
+            db_path = "/tmp/test-content.db"
+
+            [[canonicalization.rules]]
+            sources = ["linear"]
+            pattern = 'linear\.app/[^/]+/issue/(?P<key>[A-Z]+-\d+)'
+            template = "linear://{key}"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.db_path, PathBuf::from("/tmp/test-content.db"));
+        assert_eq!(config.socket_path, PathBuf::from("/tmp/clace-ingestion.sock"));
+        assert!(config.canonicalization.builtin_rules);
+        assert_eq!(config.canonicalization.rules.len(), 1);
+    }
+}
@@ -0,0 +1,354 @@
+//! Control requests accepted on the ingestion socket
+//!
+//! A line whose JSON object has a `command` field is a control request;
+//! anything else is treated as a `CapturePayload`.
+
+use crate::backup::SnapshotInfo;
+use crate::dedup::CacheStats;
+use crate::export::{ExportRecord, ImportSummary};
+use crate::integrity::IntegrityReport;
+use crate::metadata::Metadata;
+use crate::payload::{IngestionResponse, ResponseStatus};
+use crate::quality::QualityStats;
+use crate::storage::{ContentSource, GcSummary, SearchHit, SourceFilter, StorageStats, StoredChunk};
+use crate::throttle::ThrottleStats;
+use chrono::DateTime;
+use content_db::tombstone::{Tombstone, TombstoneKind};
+use serde::{Deserialize, Serialize};
+
+/// Search results returned when the request does not set a limit
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+fn default_search_limit() -> usize {
+    DEFAULT_SEARCH_LIMIT
+}
+
+/// Records per `export` response when the request does not set a limit
+pub const DEFAULT_EXPORT_LIMIT: usize = 100;
+
+fn default_export_limit() -> usize {
+    DEFAULT_EXPORT_LIMIT
+}
+
+/// Request sent over the socket instead of a capture payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// List sources, optionally restricted by type, capture time range and metadata
+    ListSources {
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        source_types: Vec<String>,
+        /// Unix seconds, inclusive
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        since: Option<i64>,
+        /// Unix seconds, exclusive
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        until: Option<i64>,
+        /// Metadata values every source must have
+        #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+        metadata: Metadata,
+    },
+    /// Write a point-in-time snapshot of the database (runs while ingesting)
+    Snapshot {
+        /// Override the configured compression setting
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        compress: Option<bool>,
+    },
+    /// Check sources against their chunks, optionally fixing what is found
+    Check {
+        #[serde(default)]
+        repair: bool,
+    },
+    /// Database and dedup cache statistics
+    Stats,
+    /// One source with its live chunks
+    Show { ehl_doc_id: String },
+    /// Case-insensitive substring search over live chunks
+    Search {
+        query: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        source_types: Vec<String>,
+        #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+        metadata: Metadata,
+        #[serde(default = "default_search_limit")]
+        limit: usize,
+    },
+    /// Delete sources; their chunks are soft-deleted
+    Remove { ehl_doc_ids: Vec<String> },
+    /// One page of export records for sources matching the same filter as
+    /// `list_sources`; the response's `next` is the `after` of the next page
+    Export {
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        source_types: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        since: Option<i64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        until: Option<i64>,
+        #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+        metadata: Metadata,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        after: Option<i64>,
+        #[serde(default = "default_export_limit")]
+        limit: usize,
+    },
+    /// Replay exported records through the dedup path, like `ingestion-server
+    /// import`: neither quality filtered nor throttled
+    Import { records: Vec<ExportRecord> },
+    /// Purge soft-deleted chunks
+    Gc {
+        /// Also purge chunks the vector index may still refer to
+        #[serde(default)]
+        include_indexed: bool,
+        #[serde(default)]
+        vacuum: bool,
+    },
+    /// Keep the connection open and stream an `IngestEvent` line per payload
+    Tail,
+    /// Add a tombstone and erase every source it covers
+    Forget {
+        kind: TombstoneKind,
+        value: String,
+        /// Unix seconds; forever when absent
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires_at: Option<i64>,
+    },
+    /// List tombstones
+    Tombstones,
+    /// Remove a tombstone by id
+    Unforget { id: i64 },
+}
+
+impl ControlRequest {
+    /// Whether a parsed socket line is a control request rather than a payload
+    pub fn is_control(value: &serde_json::Value) -> bool {
+        value.get("command").is_some()
+    }
+}
+
+/// Build a storage filter from socket-level unix timestamps
+pub fn source_filter(
+    source_types: Vec<String>,
+    since: Option<i64>,
+    until: Option<i64>,
+    metadata: Metadata,
+) -> SourceFilter {
+    SourceFilter {
+        source_types,
+        since: since.and_then(|secs| DateTime::from_timestamp(secs, 0)),
+        until: until.and_then(|secs| DateTime::from_timestamp(secs, 0)),
+        metadata,
+    }
+}
+
+/// Statistics returned by the `stats` command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceStats {
+    pub storage: StorageStats,
+    pub cache: CacheStats,
+    #[serde(default)]
+    pub throttle: ThrottleStats,
+    #[serde(default)]
+    pub quality: QualityStats,
+}
+
+/// A processed payload, streamed to `tail` clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestEvent {
+    /// When the payload was processed (unix milliseconds)
+    pub received_at: i64,
+    pub source: String,
+    pub url: String,
+    pub response: IngestionResponse,
+}
+
+/// Response to a control request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub status: ResponseStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sources: Option<Vec<ContentSource>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<Vec<StoredChunk>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hits: Option<Vec<SearchHit>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub records: Option<Vec<ExportRecord>>,
+    /// Cursor of the next `export` page; absent on the last one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ServiceStats>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<SnapshotInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<IntegrityReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub import: Option<ImportSummary>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gc: Option<GcSummary>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tombstones: Option<Vec<Tombstone>>,
+    /// IDs of a `remove` request that matched no source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_found: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl ControlResponse {
+    /// A successful response with no data
+    pub fn ok() -> Self {
+        Self {
+            status: ResponseStatus::Ok,
+            sources: None,
+            chunks: None,
+            hits: None,
+            records: None,
+            next: None,
+            stats: None,
+            snapshot: None,
+            integrity: None,
+            import: None,
+            gc: None,
+            tombstones: None,
+            not_found: None,
+            message: None,
+        }
+    }
+
+    pub fn sources(sources: Vec<ContentSource>) -> Self {
+        Self {
+            sources: Some(sources),
+            ..Self::ok()
+        }
+    }
+
+    pub fn source(source: ContentSource, chunks: Vec<StoredChunk>) -> Self {
+        Self {
+            sources: Some(vec![source]),
+            chunks: Some(chunks),
+            ..Self::ok()
+        }
+    }
+
+    /// Sources deleted by `remove`, and the requested IDs that matched none
+    pub fn removed(sources: Vec<ContentSource>, not_found: Vec<String>) -> Self {
+        Self {
+            sources: Some(sources),
+            not_found: Some(not_found),
+            ..Self::ok()
+        }
+    }
+
+    pub fn hits(hits: Vec<SearchHit>) -> Self {
+        Self {
+            hits: Some(hits),
+            ..Self::ok()
+        }
+    }
+
+    /// A page of `export` records, and the cursor of the next page if any
+    pub fn records(records: Vec<ExportRecord>, next: Option<i64>) -> Self {
+        Self {
+            records: Some(records),
+            next,
+            ..Self::ok()
+        }
+    }
+
+    pub fn stats(stats: ServiceStats) -> Self {
+        Self {
+            stats: Some(stats),
+            ..Self::ok()
+        }
+    }
+
+    pub fn snapshot(snapshot: SnapshotInfo) -> Self {
+        Self {
+            snapshot: Some(snapshot),
+            ..Self::ok()
+        }
+    }
+
+    pub fn integrity(report: IntegrityReport) -> Self {
+        Self {
+            integrity: Some(report),
+            ..Self::ok()
+        }
+    }
+
+    pub fn import(summary: ImportSummary) -> Self {
+        Self {
+            import: Some(summary),
+            ..Self::ok()
+        }
+    }
+
+    pub fn gc(summary: GcSummary) -> Self {
+        Self {
+            gc: Some(summary),
+            ..Self::ok()
+        }
+    }
+
+    pub fn tombstones(tombstones: Vec<Tombstone>) -> Self {
+        Self {
+            tombstones: Some(tombstones),
+            ..Self::ok()
+        }
+    }
+
+    pub fn error(message: &str) -> Self {
+        Self {
+            status: ResponseStatus::Error,
+            message: Some(message.to_string()),
+            ..Self::ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_detection() {
+        let control: serde_json::Value =
+            serde_json::from_str(r#"{"command": "list_sources", "since": 1700000000}"#).unwrap();
+        let payload: serde_json::Value =
+            serde_json::from_str(r#"{"source": "chrome", "url": "", "content": "x"}"#).unwrap();
+
+        assert!(ControlRequest::is_control(&control));
+        assert!(!ControlRequest::is_control(&payload));
+
+        let request: ControlRequest = serde_json::from_value(control).unwrap();
+        match request {
+            ControlRequest::ListSources { since, until, .. } => {
+                assert_eq!(since, Some(1700000000));
+                assert_eq!(until, None);
+            }
+            other => panic!("unexpected request {:?}", other),
+        }
+
+        let request: ControlRequest =
+            serde_json::from_str(r#"{"command": "snapshot", "compress": true}"#).unwrap();
+        assert!(matches!(request, ControlRequest::Snapshot { compress: Some(true) }));
+
+        let request: ControlRequest = serde_json::from_str(r#"{"command": "check"}"#).unwrap();
+        assert!(matches!(request, ControlRequest::Check { repair: false }));
+
+        let request: ControlRequest =
+            serde_json::from_str(r#"{"command": "search", "query": "roadmap"}"#).unwrap();
+        assert!(matches!(request, ControlRequest::Search { limit: DEFAULT_SEARCH_LIMIT, .. }));
+
+        let request: ControlRequest = serde_json::from_str(r#"{"command": "tail"}"#).unwrap();
+        assert!(matches!(request, ControlRequest::Tail));
+
+        let request: ControlRequest =
+            serde_json::from_str(r#"{"command": "forget", "kind": "site", "value": "bank.com"}"#).unwrap();
+        assert!(matches!(
+            request,
+            ControlRequest::Forget { kind: TombstoneKind::Site, expires_at: None, .. }
+        ));
+    }
+}
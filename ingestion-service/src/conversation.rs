@@ -0,0 +1,205 @@
+//! Chat messages rebuilt into a conversation document
+//!
+//! Payloads with `messages` are stored message by message
+//! (`content_db::message`), and the source text is rendered from every
+//! message stored so far. Messages are grouped into conversations, a thread
+//! or a run of channel messages without a long pause, and each conversation
+//! is one section of the text so chunks can be cut between them
+//! (`Chunker::chunk_sections`).
+
+use crate::dedup::compute_hash;
+use capture_protocol::ChatMessage;
+use chrono::DateTime;
+use content_db::message::StoredMessage;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// A pause this long between channel messages starts a new conversation
+pub const CONVERSATION_GAP_SECS: i64 = 30 * 60;
+
+/// Rendered text of a chat source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcript {
+    pub text: String,
+    /// Byte range of each conversation in `text`, in order
+    pub sections: Vec<Range<usize>>,
+}
+
+/// Identity of a message within its source: the site's message id, or a
+/// hash of author, time and text when there is none
+pub fn message_key(message: &ChatMessage) -> String {
+    match &message.message_id {
+        Some(id) => format!("id:{}", id),
+        None => compute_hash(&format!(
+            "{}\0{}\0{}",
+            message.author.as_deref().unwrap_or_default(),
+            message.timestamp.map(|t| t.to_string()).unwrap_or_default(),
+            message.text.trim()
+        )),
+    }
+}
+
+/// The messages of a payload as rows to store, blank ones left out. A
+/// message without a time is placed right after the one before it in the
+/// payload; leading ones at the first time in the payload, or at
+/// `captured_at` when no message has one.
+pub fn to_stored(messages: &[ChatMessage], captured_at: i64) -> Vec<StoredMessage> {
+    let mut sort_at = messages.iter().find_map(|m| m.timestamp).unwrap_or(captured_at);
+    messages
+        .iter()
+        .filter(|message| !message.text.trim().is_empty())
+        .map(|message| {
+            sort_at = message.timestamp.unwrap_or(sort_at);
+            StoredMessage {
+                message_key: message_key(message),
+                message_id: message.message_id.clone(),
+                thread_id: message.thread_id.clone(),
+                author: message.author.clone(),
+                sent_at: message.timestamp,
+                sort_at,
+                text: message.text.trim().to_string(),
+            }
+        })
+        .collect()
+}
+
+/// A stored message as sent on the wire, e.g. for export
+pub fn to_chat_message(message: &StoredMessage) -> ChatMessage {
+    ChatMessage {
+        text: message.text.clone(),
+        author: message.author.clone(),
+        timestamp: message.sent_at,
+        thread_id: message.thread_id.clone(),
+        message_id: message.message_id.clone(),
+    }
+}
+
+/// Render messages (in conversation order, as stored) one per line, with a
+/// blank line between conversations. Conversations appear in the order of
+/// their first message.
+pub fn render(messages: &[StoredMessage]) -> Transcript {
+    let mut conversations: Vec<Vec<&StoredMessage>> = Vec::new();
+    let mut threads: HashMap<&str, usize> = HashMap::new();
+    // The open channel conversation and the time of its last message
+    let mut channel: Option<(usize, i64)> = None;
+
+    for message in messages {
+        let index = match message.thread_id.as_deref() {
+            Some(thread) => *threads.entry(thread).or_insert_with(|| {
+                conversations.push(Vec::new());
+                conversations.len() - 1
+            }),
+            None => {
+                let index = match channel {
+                    Some((index, last)) if message.sort_at - last < CONVERSATION_GAP_SECS => index,
+                    _ => {
+                        conversations.push(Vec::new());
+                        conversations.len() - 1
+                    }
+                };
+                channel = Some((index, message.sort_at));
+                index
+            }
+        };
+        conversations[index].push(message);
+    }
+
+    let mut text = String::new();
+    let mut sections = Vec::new();
+    for conversation in conversations {
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        let start = text.len();
+        for (i, message) in conversation.into_iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            text.push_str(&render_line(message));
+        }
+        sections.push(start..text.len());
+    }
+    Transcript { text, sections }
+}
+
+/// `[2024-06-01 09:30] Ana: text`, leaving out what is unknown
+fn render_line(message: &StoredMessage) -> String {
+    let mut line = String::new();
+    if let Some(sent) = message.sent_at.and_then(|secs| DateTime::from_timestamp(secs, 0)) {
+        line.push_str(&sent.format("[%Y-%m-%d %H:%M] ").to_string());
+    }
+    if let Some(author) = &message.author {
+        line.push_str(author);
+        line.push_str(": ");
+    }
+    line.push_str(&message.text);
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(author: &str, timestamp: Option<i64>, text: &str) -> ChatMessage {
+        ChatMessage {
+            author: Some(author.to_string()),
+            timestamp,
+            ..ChatMessage::new(text)
+        }
+    }
+
+    #[test]
+    fn test_message_key() {
+        let hello = message("ana", Some(100), "hello");
+        assert_eq!(message_key(&hello), message_key(&message("ana", Some(100), "hello ")));
+        assert_ne!(message_key(&hello), message_key(&message("ana", Some(160), "hello")));
+        assert_ne!(message_key(&hello), message_key(&message("bo", Some(100), "hello")));
+
+        // With an id, an edit keeps its identity
+        let sent = ChatMessage { message_id: Some("171.01".to_string()), ..hello.clone() };
+        let edited = ChatMessage { text: "hello all".to_string(), ..sent.clone() };
+        assert_eq!(message_key(&sent), "id:171.01");
+        assert_eq!(message_key(&sent), message_key(&edited));
+    }
+
+    #[test]
+    fn test_to_stored_orders_untimed_messages() {
+        let messages = [
+            message("ana", None, "before any time"),
+            message("ana", Some(500), "timed"),
+            message("bo", None, "   "),
+            message("bo", None, "reply"),
+        ];
+        let stored = to_stored(&messages, 9_000);
+        let order: Vec<(i64, &str)> = stored.iter().map(|m| (m.sort_at, m.text.as_str())).collect();
+        assert_eq!(order, vec![(500, "before any time"), (500, "timed"), (500, "reply")]);
+        assert_eq!(stored[2].sent_at, None);
+
+        let untimed = to_stored(&[message("ana", None, "hi")], 9_000);
+        assert_eq!(untimed[0].sort_at, 9_000);
+    }
+
+    #[test]
+    fn test_render_conversations() {
+        let mut messages = vec![
+            message("ana", Some(1_717_232_400), "Standup in 5"),
+            message("bo", Some(1_717_232_460), "Joining"),
+            ChatMessage { thread_id: Some("t1".to_string()), ..message("cy", Some(1_717_232_500), "Deploy failed") },
+            ChatMessage { thread_id: Some("t1".to_string()), ..message("ana", Some(1_717_236_000), "Fixed") },
+            // Over half an hour after the last channel message
+            message("bo", Some(1_717_240_000), "Lunch?"),
+        ];
+        messages.push(ChatMessage::new("untimed, no author"));
+        let transcript = render(&to_stored(&messages, 0));
+
+        assert_eq!(
+            transcript.text,
+            "[2024-06-01 09:00] ana: Standup in 5\n[2024-06-01 09:01] bo: Joining\n\n\
+             [2024-06-01 09:01] cy: Deploy failed\n[2024-06-01 10:00] ana: Fixed\n\n\
+             [2024-06-01 11:06] bo: Lunch?\nuntimed, no author"
+        );
+        let sections: Vec<&str> = transcript.sections.iter().map(|r| &transcript.text[r.clone()]).collect();
+        assert_eq!(sections.len(), 3);
+        assert!(sections[1].starts_with("[2024-06-01 09:01] cy") && sections[1].ends_with("Fixed"));
+    }
+}
@@ -1,9 +1,22 @@
 //! Deduplication cache and logic
 
+use capture_protocol::mask::Masks;
+use capture_protocol::normalize::normalize;
+use capture_protocol::{CapturePayload, Metadata};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
+/// The `[dedup]` config section
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DedupConfig {
+    /// Store the normalized text instead of the text as captured (hashes
+    /// are always taken over normalized text)
+    pub store_normalized: bool,
+}
+
 /// Entry in the dedup cache
 #[derive(Debug, Clone)]
 struct CacheEntry {
@@ -71,6 +84,11 @@ impl DedupCache {
         );
     }
 
+    /// Forget a source, e.g. after it was deleted
+    pub fn remove(&mut self, source_path: &str) {
+        self.entries.remove(source_path);
+    }
+
     /// Remove expired entries
     fn evict_expired(&mut self) {
         let now = Instant::now();
@@ -97,7 +115,7 @@ pub enum DedupResult {
     Changed(String), // ehl_doc_id
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheStats {
     pub entries: usize,
     pub max_entries: usize,
@@ -110,6 +128,29 @@ pub fn compute_hash(content: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
+/// Hash of a payload's [`normalize`]d content and metadata, so whitespace or
+/// invisible characters don't count as a change but a metadata-only change
+/// (a ticket moving to "Done") does. Equal to [`compute_hash`] of the
+/// normalized content when there is no metadata.
+pub fn payload_hash(content: &str, metadata: &Metadata) -> String {
+    let content = normalize(content);
+    if metadata.is_empty() {
+        return compute_hash(&content);
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(content.as_bytes());
+    hasher.update(b"\0");
+    // BTreeMap keys serialize in order, so the JSON is canonical
+    hasher.update(serde_json::to_string(metadata).unwrap_or_default().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// The hash recorded for a source: [`payload_hash`] of the payload with its
+/// volatile content (relative times, counters) masked
+pub fn source_hash(payload: &CapturePayload, masks: &Masks) -> String {
+    payload_hash(&masks.mask_payload(payload), &payload.metadata)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -156,4 +197,17 @@ mod tests {
         assert_eq!(hash1, hash2);
         assert_ne!(hash1, hash3);
     }
+
+    #[test]
+    fn test_payload_hash() {
+        let mut metadata = Metadata::new();
+        assert_eq!(payload_hash("hello world", &metadata), compute_hash("hello world"));
+
+        metadata.insert("status".to_string(), "Open".into());
+        let open = payload_hash("hello world", &metadata);
+        assert_ne!(open, compute_hash("hello world"));
+        metadata.insert("status".to_string(), "Done".into());
+        assert_ne!(payload_hash("hello world", &metadata), open);
+        assert_eq!(payload_hash("hello\u{00A0}world \r\n", &metadata), payload_hash("hello world", &metadata));
+    }
 }
@@ -0,0 +1,329 @@
+//! JSONL export and import of the content corpus
+//!
+//! Each line is one `ExportRecord`: a content source with its live chunks and
+//! metadata. Imports are replayed through the normal ingestion path, so
+//! importing the same file twice is a no-op.
+
+use crate::conversation;
+use crate::payload::{CapturePayload, ContentType, IngestionAction, IngestionResponse};
+use crate::storage::{ContentSource, SourceFilter, Storage, StorageError, StoredChunk};
+use capture_protocol::{ChatMessage, Metadata};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+/// One exported content source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRecord {
+    pub source_type: String,
+    pub source_path: String,
+    pub ehl_doc_id: String,
+    pub content_hash: String,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Capture time of the latest revision (unix seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captured_at: Option<i64>,
+    /// URL as captured, when it differs from `source_path`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
+    /// Every stored message of a chat source, replayed on import
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<ChatMessage>,
+    /// Full document text as reconstructed by `Storage::get_source_content`
+    pub content: String,
+    pub chunks: Vec<StoredChunk>,
+}
+
+impl ExportRecord {
+    /// Build the capture payload used to replay this record on import
+    pub fn to_payload(&self) -> CapturePayload {
+        CapturePayload {
+            source: self.source_type.clone(),
+            url: self.original_url.clone().unwrap_or_else(|| self.source_path.clone()),
+            content: self.content.clone(),
+            content_type: ContentType::Plain,
+            title: self.title.clone(),
+            author: self.author.clone(),
+            channel: self.channel.clone(),
+            timestamp: self.captured_at,
+            app_name: self.app_name.clone(),
+            bundle_id: self.bundle_id.clone(),
+            metadata: self.metadata.clone(),
+            messages: self.messages.clone(),
+        }
+    }
+}
+
+/// Counts of what happened to each record during an import
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl ImportSummary {
+    /// Tally a single ingestion response
+    pub fn record(&mut self, response: &IngestionResponse) {
+        match response.action {
+            IngestionAction::Created => self.created += 1,
+            IngestionAction::Updated => self.updated += 1,
+            IngestionAction::Skipped => self.skipped += 1,
+            IngestionAction::Failed => self.failed += 1,
+        }
+    }
+
+    /// Add the counts of another batch
+    pub fn add(&mut self, other: &ImportSummary) {
+        self.created += other.created;
+        self.updated += other.updated;
+        self.skipped += other.skipped;
+        self.failed += other.failed;
+    }
+}
+
+/// Write every source matching `filter` as one JSON line; returns the record count
+pub fn write_jsonl<W: Write>(
+    storage: &Storage,
+    filter: &SourceFilter,
+    mut writer: W,
+) -> Result<usize, StorageError> {
+    let sources = storage.list_sources(filter)?;
+
+    for source in &sources {
+        serde_json::to_writer(&mut writer, &export_record(storage, source)?)?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()?;
+    Ok(sources.len())
+}
+
+/// Collect the live chunks and metadata of one source into an export record
+pub fn export_record(storage: &Storage, source: &ContentSource) -> Result<ExportRecord, StorageError> {
+    let chunks = storage.get_source_chunks(&source.ehl_doc_id)?;
+    let content = storage.get_source_content(&source.ehl_doc_id)?;
+
+    // Source-level metadata lives on the chunks; take it from the first one
+    let meta_str = |key: &str| {
+        chunks
+            .first()
+            .and_then(|c| c.meta.get(key))
+            .and_then(|v| v.as_str())
+            .map(String::from)
+    };
+
+    Ok(ExportRecord {
+        source_type: source.source_type.clone(),
+        source_path: source.source_path.clone(),
+        ehl_doc_id: source.ehl_doc_id.clone(),
+        content_hash: source.content_hash.clone(),
+        created_at: source.created_at.clone(),
+        updated_at: source.updated_at.clone(),
+        captured_at: Some(source.captured_at),
+        original_url: meta_str("original_url"),
+        title: meta_str("title"),
+        author: meta_str("author"),
+        channel: meta_str("channel"),
+        app_name: meta_str("app_name"),
+        bundle_id: meta_str("bundle_id"),
+        metadata: chunks
+            .first()
+            .and_then(|c| c.meta.get("metadata"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default(),
+        messages: storage
+            .get_messages(&source.ehl_doc_id)?
+            .iter()
+            .map(conversation::to_chat_message)
+            .collect(),
+        content,
+        chunks,
+    })
+}
+
+/// Parse an export file line by line, skipping blank lines
+pub fn read_jsonl<R: BufRead>(
+    reader: R,
+) -> impl Iterator<Item = Result<ExportRecord, StorageError>> {
+    reader.lines().filter_map(|line| match line {
+        Ok(line) if line.trim().is_empty() => None,
+        Ok(line) => Some(serde_json::from_str(&line).map_err(StorageError::from)),
+        Err(e) => Some(Err(e.into())),
+    })
+}
+
+/// Parse a date filter argument: either `YYYY-MM-DD` (midnight UTC) or RFC 3339
+pub fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{IngestionServer, ServerConfig};
+
+    fn make_server(dir: &tempfile::TempDir) -> IngestionServer {
+        IngestionServer::new(ServerConfig {
+            socket_path: dir.path().join("ingestion.sock"),
+            db_path: dir.path().join("content.db"),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn make_payload(source: &str, url: &str, content: &str) -> CapturePayload {
+        CapturePayload {
+            title: Some("Quarterly plan".to_string()),
+            author: Some("alice".to_string()),
+            app_name: Some("Google Chrome".to_string()),
+            bundle_id: Some("com.google.Chrome".to_string()),
+            ..CapturePayload::new(source, url, content)
+        }
+    }
+
+    async fn export_to_vec(server: &IngestionServer, filter: &SourceFilter) -> Vec<ExportRecord> {
+        let mut buf = Vec::new();
+        server.export_jsonl(filter, &mut buf).await.unwrap();
+        read_jsonl(buf.as_slice()).map(|r| r.unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let source = make_server(&source_dir);
+        let mut plan = make_payload(
+            "gdocs",
+            "https://docs.google.com/document/d/abc123/edit",
+            "Plan for the quarter with several goals",
+        );
+        plan.metadata.insert("owner".to_string(), "alice@example.com".into());
+        source.process(plan).await;
+        source
+            .process(make_payload(
+                "chrome",
+                "https://example.com/article?utm_source=feed",
+                "An article about ingestion pipelines",
+            ))
+            .await;
+
+        let mut exported = Vec::new();
+        let count = source
+            .export_jsonl(&SourceFilter::default(), &mut exported)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let target_dir = tempfile::tempdir().unwrap();
+        let target = make_server(&target_dir);
+
+        let summary = target.import_jsonl(exported.as_slice()).await.unwrap();
+        assert_eq!(summary.created, 2);
+        assert_eq!(summary.failed, 0);
+
+        // Replaying the same file is idempotent
+        let summary = target.import_jsonl(exported.as_slice()).await.unwrap();
+        assert_eq!(summary.skipped, 2);
+        assert_eq!(summary.created + summary.updated, 0);
+
+        let before: Vec<ExportRecord> = read_jsonl(exported.as_slice()).map(|r| r.unwrap()).collect();
+        assert_eq!(before[0].metadata["owner"], "alice@example.com");
+        assert_eq!(before[1].source_path, "https://example.com/article");
+        assert_eq!(
+            before[1].original_url.as_deref(),
+            Some("https://example.com/article?utm_source=feed")
+        );
+        let after = export_to_vec(&target, &SourceFilter::default()).await;
+        assert_eq!(before.len(), after.len());
+        for (a, b) in before.iter().zip(after.iter()) {
+            assert_eq!(a.source_path, b.source_path);
+            assert_eq!(a.source_type, b.source_type);
+            assert_eq!(a.content, b.content);
+            assert_eq!(a.captured_at, b.captured_at);
+            assert_eq!(a.original_url, b.original_url);
+            assert_eq!(a.title, b.title);
+            assert_eq!(a.author, b.author);
+            assert_eq!(a.bundle_id, b.bundle_id);
+            assert_eq!(a.metadata, b.metadata);
+            assert_eq!(a.chunks.len(), b.chunks.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_round_trip() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let source = make_server(&source_dir);
+        let mut chat = make_payload("slack", "https://acme.slack.com/archives/C1", "flattened");
+        chat.messages = vec![
+            ChatMessage { author: Some("ana".to_string()), timestamp: Some(1_717_232_400), ..ChatMessage::new("Ship it") },
+            ChatMessage { message_id: Some("m2".to_string()), ..ChatMessage::new("Shipped") },
+        ];
+        source.process(chat.clone()).await;
+
+        let before = export_to_vec(&source, &SourceFilter::default()).await;
+        assert_eq!(before[0].messages, chat.messages);
+
+        let target_dir = tempfile::tempdir().unwrap();
+        let target = make_server(&target_dir);
+        let mut exported = Vec::new();
+        source.export_jsonl(&SourceFilter::default(), &mut exported).await.unwrap();
+        assert_eq!(target.import_jsonl(exported.as_slice()).await.unwrap().created, 1);
+
+        let after = export_to_vec(&target, &SourceFilter::default()).await;
+        assert_eq!(after[0].messages, before[0].messages);
+        assert_eq!(after[0].content, before[0].content);
+        assert_eq!(after[0].content_hash, before[0].content_hash);
+    }
+
+    #[tokio::test]
+    async fn test_export_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server(&dir);
+        server
+            .process(make_payload("slack", "https://acme.slack.com/archives/C1", "standup notes"))
+            .await;
+        server
+            .process(make_payload("chrome", "https://example.com/", "homepage text"))
+            .await;
+
+        let filter = SourceFilter {
+            source_types: vec!["slack".to_string()],
+            ..Default::default()
+        };
+        let records = export_to_vec(&server, &filter).await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].source_type, "slack");
+
+        let future = SourceFilter {
+            since: parse_date("2999-01-01"),
+            ..Default::default()
+        };
+        assert!(export_to_vec(&server, &future).await.is_empty());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert!(parse_date("2024-03-01").is_some());
+        assert!(parse_date("2024-03-01T12:00:00Z").is_some());
+        assert!(parse_date("yesterday").is_none());
+    }
+}
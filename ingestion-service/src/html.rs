@@ -0,0 +1,901 @@
+//! Main-content extraction for HTML payloads
+//!
+//! The browser extension extracts page text itself, but scripts, the router,
+//! or the extension when its content script fails send the page markup as a
+//! `text/html` payload. [`extract`] picks the element holding the main content
+//! the way Readability does: paragraphs add a score to their ancestors by
+//! length and commas, ancestors are weighed by class names and link density,
+//! and the best one is kept along with siblings that score nearly as well.
+//! Scripts, navigation, sidebars, comments and hidden elements are dropped
+//! and the rest is converted to markdown, keeping headings, lists, links and
+//! tables.
+
+use ego_tree::iter::Edge;
+use ego_tree::NodeId;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::OnceLock;
+use url::Url;
+
+/// Paragraphs shorter than this (in characters) don't score their ancestors
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+/// Ancestor levels a paragraph's score reaches
+const SCORE_LEVELS: usize = 5;
+
+/// Candidates scoring within this share of the best one that share an ancestor
+/// make that ancestor the main content, as when an article is split into blocks
+const ALTERNATIVE_SHARE: f64 = 0.75;
+const MIN_ALTERNATIVES: usize = 3;
+
+/// Pages nested deeper than this are flattened to plain text instead of
+/// converted, as the conversion recurses
+const MAX_DEPTH: usize = 256;
+
+/// Elements never part of the content
+const DROPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object", "embed", "nav", "aside", "footer",
+    "dialog", "menu", "button", "input", "select", "textarea", "img", "picture", "video", "audio", "head",
+];
+
+/// ARIA roles never part of the content
+const DROPPED_ROLES: &[&str] = &[
+    "navigation", "banner", "contentinfo", "complementary", "dialog", "alertdialog", "menu", "menubar", "search",
+];
+
+/// Elements that start a new block in the markdown
+const BLOCK_TAGS: &[&str] = &[
+    "address", "article", "blockquote", "body", "caption", "dd", "details", "div", "dl", "dt", "fieldset",
+    "figcaption", "figure", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hgroup", "hr", "html", "li",
+    "main", "ol", "p", "pre", "section", "summary", "table", "tbody", "td", "tfoot", "th", "thead", "tr", "ul",
+];
+
+/// A div containing none of these reads as a paragraph
+const DIV_TO_P_TAGS: &[&str] = &["blockquote", "dl", "div", "img", "ol", "p", "pre", "table", "ul"];
+
+/// Elements whose text scores their ancestors
+const SCORED_TAGS: &[&str] = &["section", "h2", "h3", "h4", "h5", "h6", "p", "td", "pre"];
+
+/// Class or id of boilerplate; elements matching are dropped unless they also
+/// match [`maybe_candidate`]
+fn unlikely_candidate() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"(?i)-ad-|ai2html|banner|breadcrumbs|combx|comment|community|consent|cookie|cover-wrap|disqus|extra|footer|gdpr|legends|menu|newsletter|related|remark|replies|rss|share|shoutbox|sidebar|skyscraper|social|sponsor|supplemental|ad-break|agegate|pagination|pager|popup|yom-remote",
+        )
+        .expect("valid unlikely pattern")
+    })
+}
+
+fn maybe_candidate() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)and|article|body|column|content|main|shadow").expect("valid maybe pattern"))
+}
+
+fn positive_class() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)article|body|content|entry|hentry|h-entry|main|page|pagination|post|text|blog|story")
+            .expect("valid positive pattern")
+    })
+}
+
+fn negative_class() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"(?i)-ad-|hidden|^hid$| hid$| hid |^hid |banner|combx|comment|com-|contact|footer|gdpr|masthead|media|meta|outbrain|promo|related|scroll|share|shoutbox|sidebar|skyscraper|sponsor|shopping|tags|widget",
+        )
+        .expect("valid negative pattern")
+    })
+}
+
+/// Main content of a page
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extracted {
+    /// The page `<title>`, if any
+    pub title: Option<String>,
+    pub markdown: String,
+}
+
+/// Extract the main content of `html`, fetched from `url`, as markdown.
+/// Relative links are resolved against the page's `<base>` or `url`.
+pub fn extract(html: &str, url: &str) -> Extracted {
+    let document = Html::parse_document(html);
+    let page = Page::new(&document, url);
+    let title = page.title();
+
+    let body = page.body();
+    if depth(body) > MAX_DEPTH {
+        return Extracted {
+            title,
+            markdown: page.text(body),
+        };
+    }
+
+    let mut blocks = Vec::new();
+    for element in page.main_content() {
+        page.render_block(element, &mut blocks);
+    }
+    Extracted {
+        title,
+        markdown: blocks.join("\n\n"),
+    }
+}
+
+/// A parsed page with its boilerplate marked
+struct Page<'a> {
+    document: &'a Html,
+    /// Roots of dropped subtrees
+    junk: HashSet<NodeId>,
+    /// Text measures of every node, so scoring never re-walks a subtree
+    stats: HashMap<NodeId, TextStats>,
+    base: Option<Url>,
+}
+
+/// Measures of the text under a node outside dropped subtrees, as `Page::text`
+/// would return it. Built bottom up: a node's measures combine its children's.
+#[derive(Debug, Clone, Copy, Default)]
+struct TextStats {
+    /// Characters of the whitespace-collapsed, trimmed text
+    chars: usize,
+    /// Characters of that text inside links (nested links count once per link)
+    link_chars: usize,
+    commas: usize,
+    /// The untrimmed text starts or ends with whitespace, or is only whitespace
+    lead_space: bool,
+    trail_space: bool,
+    only_space: bool,
+    /// A descendant is one of [`DIV_TO_P_TAGS`]
+    has_block: bool,
+}
+
+impl TextStats {
+    fn of_text(text: &str) -> Self {
+        let mut collapsed = String::new();
+        push_text(&mut collapsed, text);
+        let trimmed = collapsed.trim();
+        Self {
+            chars: trimmed.chars().count(),
+            commas: trimmed.matches([',', '，']).count(),
+            lead_space: collapsed.starts_with(' '),
+            trail_space: collapsed.ends_with(' '),
+            only_space: trimmed.is_empty() && !collapsed.is_empty(),
+            ..Default::default()
+        }
+    }
+
+    /// Extend with the measures of the text that follows
+    fn append(&mut self, next: &TextStats) {
+        self.link_chars += next.link_chars;
+        self.commas += next.commas;
+        self.has_block |= next.has_block;
+        if next.chars == 0 {
+            // Whitespace at most, which only separates what is around it
+            if next.only_space && self.chars == 0 {
+                self.only_space = true;
+                self.lead_space = true;
+            } else if next.only_space {
+                self.trail_space = true;
+            }
+        } else if self.chars == 0 {
+            self.lead_space = self.only_space || next.lead_space;
+            self.only_space = false;
+            self.chars = next.chars;
+            self.trail_space = next.trail_space;
+        } else {
+            self.chars += next.chars + usize::from(self.trail_space || next.lead_space);
+            self.trail_space = next.trail_space;
+        }
+    }
+}
+
+impl<'a> Page<'a> {
+    fn new(document: &'a Html, url: &str) -> Self {
+        let mut page = Self {
+            document,
+            junk: HashSet::new(),
+            stats: HashMap::new(),
+            base: Url::parse(url).ok(),
+        };
+        page.mark_junk();
+        page.measure();
+
+        let base_href = document.root_element().descendent_elements().find(|e| e.value().name() == "base");
+        if let Some(href) = base_href.and_then(|e| e.attr("href")) {
+            page.base = match &page.base {
+                Some(base) => base.join(href).ok(),
+                None => Url::parse(href).ok(),
+            }
+            .or(page.base.take());
+        }
+        page
+    }
+
+    fn title(&self) -> Option<String> {
+        let title = self.document.root_element().descendent_elements().find(|e| e.value().name() == "title")?;
+        let mut text = String::new();
+        push_text(&mut text, &title.text().collect::<String>());
+        let text = text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    fn body(&self) -> ElementRef<'a> {
+        let root = self.document.root_element();
+        root.child_elements().find(|e| e.value().name() == "body").unwrap_or(root)
+    }
+
+    fn element(&self, id: NodeId) -> ElementRef<'a> {
+        ElementRef::wrap(self.document.tree.get(id).expect("node of this document")).expect("element node")
+    }
+
+    /// Mark boilerplate subtrees, top down so a dropped element's descendants aren't examined
+    fn mark_junk(&mut self) {
+        let mut skipping: Option<NodeId> = None;
+        // Class names inside tables and code are content, not layout
+        let mut literal_depth = 0;
+        for edge in self.document.root_element().traverse() {
+            match edge {
+                Edge::Open(node) => {
+                    let Some(element) = ElementRef::wrap(node) else { continue };
+                    if skipping.is_some() {
+                        continue;
+                    }
+                    if is_junk(element, literal_depth > 0) {
+                        self.junk.insert(node.id());
+                        skipping = Some(node.id());
+                    } else if matches!(element.value().name(), "table" | "code" | "pre") {
+                        literal_depth += 1;
+                    }
+                }
+                Edge::Close(node) => {
+                    if skipping == Some(node.id()) {
+                        skipping = None;
+                    } else if skipping.is_none()
+                        && ElementRef::wrap(node).is_some_and(|e| matches!(e.value().name(), "table" | "code" | "pre"))
+                    {
+                        literal_depth -= 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Measure every node's text; in reverse document order, children come first
+    fn measure(&mut self) {
+        let nodes: Vec<_> = self.document.tree.root().descendants().collect();
+        for node in nodes.into_iter().rev() {
+            let stats = match node.value() {
+                Node::Text(text) => TextStats::of_text(text),
+                Node::Element(element) if !self.junk.contains(&node.id()) => {
+                    let mut stats = TextStats::default();
+                    for child in node.children() {
+                        stats.append(&self.stats[&child.id()]);
+                        stats.has_block |= !self.junk.contains(&child.id())
+                            && child.value().as_element().is_some_and(|e| DIV_TO_P_TAGS.contains(&e.name()));
+                    }
+                    if element.name() == "a" {
+                        stats.link_chars += stats.chars;
+                    }
+                    stats
+                }
+                _ => TextStats::default(),
+            };
+            self.stats.insert(node.id(), stats);
+        }
+    }
+
+    fn stats(&self, element: ElementRef<'a>) -> TextStats {
+        self.stats.get(&element.id()).copied().unwrap_or_default()
+    }
+
+    /// Elements under `root` (itself included) outside dropped subtrees, in document order
+    fn elements(&self, root: ElementRef<'a>) -> Vec<ElementRef<'a>> {
+        let mut elements = Vec::new();
+        let mut skipping: Option<NodeId> = None;
+        for edge in root.traverse() {
+            match edge {
+                Edge::Open(node) if skipping.is_none() => {
+                    if self.junk.contains(&node.id()) {
+                        skipping = Some(node.id());
+                    } else if let Some(element) = ElementRef::wrap(node) {
+                        elements.push(element);
+                    }
+                }
+                Edge::Close(node) if skipping == Some(node.id()) => skipping = None,
+                _ => {}
+            }
+        }
+        elements
+    }
+
+    /// Text under `element` outside dropped subtrees, whitespace collapsed
+    fn text(&self, element: ElementRef<'a>) -> String {
+        let mut text = String::new();
+        let mut skipping: Option<NodeId> = None;
+        for edge in element.traverse() {
+            match edge {
+                Edge::Open(node) if skipping.is_none() => {
+                    if self.junk.contains(&node.id()) {
+                        skipping = Some(node.id());
+                    } else if let Node::Text(t) = node.value() {
+                        push_text(&mut text, t);
+                    }
+                }
+                Edge::Close(node) if skipping == Some(node.id()) => skipping = None,
+                _ => {}
+            }
+        }
+        text.trim().to_string()
+    }
+
+    /// Share of the text under `element` that is link text
+    fn link_density(&self, element: ElementRef<'a>) -> f64 {
+        let stats = self.stats(element);
+        if stats.chars == 0 {
+            return 0.0;
+        }
+        stats.link_chars as f64 / stats.chars as f64
+    }
+
+    /// Score every ancestor of a paragraph
+    fn score(&self) -> HashMap<NodeId, f64> {
+        let mut scores = HashMap::new();
+        for element in self.elements(self.body()) {
+            if !self.is_paragraph(element) {
+                continue;
+            }
+            let stats = self.stats(element);
+            let length = stats.chars;
+            if length < MIN_PARAGRAPH_CHARS {
+                continue;
+            }
+            let score = 1.0 + stats.commas as f64 + (length / 100).min(3) as f64;
+
+            let ancestors = element
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .filter(|e| e.value().name() != "html")
+                .take(SCORE_LEVELS);
+            for (level, ancestor) in ancestors.enumerate() {
+                let divider = match level {
+                    0 => 1.0,
+                    1 => 2.0,
+                    _ => level as f64 * 3.0,
+                };
+                *scores.entry(ancestor.id()).or_insert_with(|| initial_score(ancestor)) += score / divider;
+            }
+        }
+
+        for (id, score) in scores.iter_mut() {
+            *score *= 1.0 - self.link_density(self.element(*id));
+        }
+        scores
+    }
+
+    fn is_paragraph(&self, element: ElementRef<'a>) -> bool {
+        let name = element.value().name();
+        SCORED_TAGS.contains(&name) || (name == "div" && !self.stats(element).has_block)
+    }
+
+    /// The elements holding the main content, in document order
+    fn main_content(&self) -> Vec<ElementRef<'a>> {
+        let body = self.body();
+        let scores = self.score();
+        let mut ranked: Vec<(NodeId, f64)> = scores.iter().map(|(id, score)| (*id, *score)).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let Some(&(top_id, top_score)) = ranked.first() else { return vec![body] };
+        let mut top = self.element(top_id);
+
+        // Several near-best candidates inside one ancestor: the content is split up
+        let alternatives: Vec<ElementRef> = ranked[1..]
+            .iter()
+            .take(4)
+            .filter(|(_, score)| *score >= top_score * ALTERNATIVE_SHARE)
+            .map(|(id, _)| self.element(*id))
+            .collect();
+        if alternatives.len() >= MIN_ALTERNATIVES {
+            for parent in top.ancestors().filter_map(ElementRef::wrap).take_while(|e| e.id() != body.id()) {
+                let containing = alternatives.iter().filter(|a| a.ancestors().any(|n| n.id() == parent.id())).count();
+                if containing >= MIN_ALTERNATIVES {
+                    top = parent;
+                    break;
+                }
+            }
+        }
+
+        // Climb while the parent holds more of the content than the candidate
+        let mut last_score = scores.get(&top.id()).copied().unwrap_or(top_score);
+        let threshold = last_score / 3.0;
+        for parent in top.ancestors().filter_map(ElementRef::wrap).take_while(|e| e.id() != body.id()) {
+            let Some(&parent_score) = scores.get(&parent.id()) else { continue };
+            if parent_score < threshold {
+                break;
+            }
+            if parent_score > last_score {
+                top = parent;
+                break;
+            }
+            last_score = parent_score;
+        }
+        if top.id() == body.id() {
+            return vec![body];
+        }
+
+        let top_score = scores.get(&top.id()).copied().unwrap_or(0.0);
+        let threshold = (top_score * 0.2).max(10.0);
+        let top_class = top.attr("class").unwrap_or("");
+        let Some(parent) = top.parent().and_then(ElementRef::wrap) else { return vec![top] };
+        parent
+            .child_elements()
+            .filter(|sibling| !self.junk.contains(&sibling.id()))
+            .filter(|sibling| {
+                if sibling.id() == top.id() {
+                    return true;
+                }
+                let mut score = scores.get(&sibling.id()).copied().unwrap_or(0.0);
+                if !top_class.is_empty() && sibling.attr("class") == Some(top_class) {
+                    score += top_score * 0.2;
+                }
+                score >= threshold || (sibling.value().name() == "p" && self.is_prose(*sibling))
+            })
+            .collect()
+    }
+
+    /// A paragraph of sentences rather than links
+    fn is_prose(&self, element: ElementRef<'a>) -> bool {
+        let text = self.text(element);
+        let length = text.chars().count();
+        let link_density = self.link_density(element);
+        (length > 80 && link_density < 0.25)
+            || (length > 0 && link_density == 0.0 && (text.ends_with('.') || text.contains(". ")))
+    }
+
+    /// Render `element` and its content as markdown blocks
+    fn render_block(&self, element: ElementRef<'a>, blocks: &mut Vec<String>) {
+        let name = element.value().name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let text = self.inline(element);
+                if !text.is_empty() {
+                    let level = name[1..].parse().unwrap_or(1);
+                    blocks.push(format!("{} {}", "#".repeat(level), text.replace('\n', " ")));
+                }
+            }
+            "ul" | "ol" => self.render_list(element, blocks),
+            "table" if self.is_data_table(element) => self.render_table(element, blocks),
+            "pre" => {
+                let code: String = element.text().collect();
+                let code = code.trim_matches('\n');
+                if !code.trim().is_empty() {
+                    blocks.push(format!("```\n{}\n```", code));
+                }
+            }
+            "blockquote" => {
+                let mut quoted = Vec::new();
+                self.render_children(element, &mut quoted);
+                if !quoted.is_empty() {
+                    blocks.push(prefix_lines(&quoted.join("\n\n"), "> ", ">"));
+                }
+            }
+            "hr" => blocks.push("---".to_string()),
+            _ if BLOCK_TAGS.contains(&name) => self.render_children(element, blocks),
+            // Inline content passed as a block, e.g. a top-level <span>
+            _ => {
+                let text = self.inline(element);
+                if !text.is_empty() {
+                    blocks.push(text);
+                }
+            }
+        }
+    }
+
+    /// Render the children of a block: runs of inline content become paragraphs
+    fn render_children(&self, element: ElementRef<'a>, blocks: &mut Vec<String>) {
+        let mut paragraph = String::new();
+        for child in element.children() {
+            if self.junk.contains(&child.id()) {
+                continue;
+            }
+            match ElementRef::wrap(child) {
+                Some(child) if BLOCK_TAGS.contains(&child.value().name()) => {
+                    flush_paragraph(&mut paragraph, blocks);
+                    self.render_block(child, blocks);
+                }
+                Some(child) => self.render_inline(child, &mut paragraph),
+                None => {
+                    if let Node::Text(text) = child.value() {
+                        push_text(&mut paragraph, text);
+                    }
+                }
+            }
+        }
+        flush_paragraph(&mut paragraph, blocks);
+    }
+
+    fn render_list(&self, list: ElementRef<'a>, blocks: &mut Vec<String>) {
+        let ordered = list.value().name() == "ol";
+        // Bounded, so a hostile `start` can't overflow the item numbers
+        let start: u32 = list.attr("start").and_then(|s| s.trim().parse().ok()).unwrap_or(1);
+        let mut items = Vec::new();
+        for item in list.child_elements().filter(|e| !self.junk.contains(&e.id())) {
+            let mut content = Vec::new();
+            self.render_children(item, &mut content);
+            if content.is_empty() {
+                continue;
+            }
+            let marker = if ordered { format!("{}. ", start.saturating_add(items.len() as u32)) } else { "- ".to_string() };
+            let indent = " ".repeat(marker.len());
+            let body = prefix_lines(&content.join("\n"), &indent, "");
+            items.push(format!("{}{}", marker, &body[indent.len()..]));
+        }
+        if !items.is_empty() {
+            blocks.push(items.join("\n"));
+        }
+    }
+
+    /// Tables of data have headers, or more than one row and column; others lay out blocks
+    fn is_data_table(&self, table: ElementRef<'a>) -> bool {
+        if table.attr("role") == Some("presentation") {
+            return false;
+        }
+        let elements = self.elements(table);
+        if elements.iter().skip(1).any(|e| e.value().name() == "table") {
+            return false;
+        }
+        if elements.iter().any(|e| matches!(e.value().name(), "th" | "thead" | "caption")) {
+            return true;
+        }
+        let rows = self.rows(table);
+        rows.len() > 1 && rows.iter().map(Vec::len).max().unwrap_or(0) > 1
+    }
+
+    fn rows(&self, table: ElementRef<'a>) -> Vec<Vec<String>> {
+        self.elements(table)
+            .into_iter()
+            .filter(|e| e.value().name() == "tr")
+            .map(|row| {
+                let mut cells = Vec::new();
+                for cell in row.child_elements().filter(|e| matches!(e.value().name(), "td" | "th")) {
+                    if self.junk.contains(&cell.id()) {
+                        continue;
+                    }
+                    cells.push(self.inline(cell).replace('\n', " ").replace('|', "\\|"));
+                    let span: usize = cell.attr("colspan").and_then(|s| s.trim().parse().ok()).unwrap_or(1);
+                    cells.extend(std::iter::repeat_n(String::new(), span.clamp(1, 100) - 1));
+                }
+                cells
+            })
+            .filter(|cells| cells.iter().any(|c| !c.is_empty()))
+            .collect()
+    }
+
+    fn render_table(&self, table: ElementRef<'a>, blocks: &mut Vec<String>) {
+        if let Some(caption) = table.child_elements().find(|e| e.value().name() == "caption") {
+            let caption = self.inline(caption);
+            if !caption.is_empty() {
+                blocks.push(caption);
+            }
+        }
+        let rows = self.rows(table);
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        let line = |cells: &[String]| {
+            let mut line = String::from("|");
+            for column in 0..columns {
+                let _ = write!(line, " {} |", cells.get(column).map(String::as_str).unwrap_or(""));
+            }
+            line
+        };
+        let mut lines = vec![line(&rows[0]), line(&vec!["---".to_string(); columns])];
+        lines.extend(rows[1..].iter().map(|row| line(row)));
+        blocks.push(lines.join("\n"));
+    }
+
+    /// Inline markdown of an element's content, trimmed
+    fn inline(&self, element: ElementRef<'a>) -> String {
+        let mut text = String::new();
+        self.render_inline_children(element, &mut text);
+        trim_lines(&text)
+    }
+
+    fn render_inline_children(&self, element: ElementRef<'a>, text: &mut String) {
+        for child in element.children() {
+            if self.junk.contains(&child.id()) {
+                continue;
+            }
+            match ElementRef::wrap(child) {
+                Some(child) => self.render_inline(child, text),
+                None => {
+                    if let Node::Text(t) = child.value() {
+                        push_text(text, t);
+                    }
+                }
+            }
+        }
+    }
+
+    fn render_inline(&self, element: ElementRef<'a>, text: &mut String) {
+        match element.value().name() {
+            "br" => text.push('\n'),
+            "a" => {
+                let mut label = String::new();
+                self.render_inline_children(element, &mut label);
+                let label = label.trim();
+                let href = element.attr("href").unwrap_or("");
+                // Permalink and back-reference anchors: "¶", "#", "^"
+                if href.starts_with('#') && !label.chars().any(char::is_alphanumeric) {
+                    return;
+                }
+                match self.link(Some(href)) {
+                    Some(href) if !label.is_empty() => {
+                        let _ = write!(text, "[{}]({})", label.replace('\n', " "), href);
+                    }
+                    _ => text.push_str(label),
+                }
+            }
+            "strong" | "b" => self.render_emphasis(element, "**", text),
+            "em" | "i" => self.render_emphasis(element, "*", text),
+            "code" | "kbd" | "samp" => {
+                let mut code = String::new();
+                push_text(&mut code, &element.text().collect::<String>());
+                let code = code.trim();
+                if !code.is_empty() {
+                    let _ = write!(text, "`{}`", code);
+                }
+            }
+            name => {
+                // A block inside inline content, such as a <div> in a link, is kept apart by spaces
+                let block = BLOCK_TAGS.contains(&name);
+                if block {
+                    text.push(' ');
+                }
+                self.render_inline_children(element, text);
+                if block {
+                    text.push(' ');
+                }
+            }
+        }
+    }
+
+    fn render_emphasis(&self, element: ElementRef<'a>, marker: &str, text: &mut String) {
+        let mut inner = String::new();
+        self.render_inline_children(element, &mut inner);
+        let trimmed = inner.trim();
+        if trimmed.is_empty() {
+            text.push_str(&inner);
+            return;
+        }
+        if inner.starts_with(char::is_whitespace) {
+            text.push(' ');
+        }
+        let _ = write!(text, "{}{}{}", marker, trimmed, marker);
+        if inner.ends_with(char::is_whitespace) {
+            text.push(' ');
+        }
+    }
+
+    /// Absolute URL of a link worth keeping; in-page anchors and scripts are not
+    fn link(&self, href: Option<&str>) -> Option<String> {
+        let href = href?.trim();
+        if href.is_empty() || href.starts_with('#') {
+            return None;
+        }
+        let url = match &self.base {
+            Some(base) => base.join(href).ok()?,
+            None => Url::parse(href).ok()?,
+        };
+        matches!(url.scheme(), "http" | "https" | "mailto" | "ftp").then(|| url.to_string())
+    }
+}
+
+/// Whether an element is boilerplate or hidden; `literal` if inside a table or code
+fn is_junk(element: ElementRef, literal: bool) -> bool {
+    let value = element.value();
+    let name = value.name();
+    if DROPPED_TAGS.contains(&name) {
+        return true;
+    }
+    if value.attr("hidden").is_some() || value.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    if let Some(style) = value.attr("style") {
+        let style = style.replace(' ', "").to_ascii_lowercase();
+        if style.contains("display:none") || style.contains("visibility:hidden") {
+            return true;
+        }
+    }
+    if value.attr("role").is_some_and(|role| DROPPED_ROLES.contains(&role)) {
+        return true;
+    }
+    // The site header, as opposed to an article's
+    if name == "header" && element.descendent_elements().any(|e| e.value().name() == "nav") {
+        return true;
+    }
+    if literal || matches!(name, "html" | "body" | "a") {
+        return false;
+    }
+    let names = format!("{} {}", value.attr("class").unwrap_or(""), value.attr("id").unwrap_or(""));
+    unlikely_candidate().is_match(&names) && !maybe_candidate().is_match(&names)
+}
+
+/// Score a candidate starts with, by tag and class names
+fn initial_score(element: ElementRef) -> f64 {
+    let value = element.value();
+    let tag_score = match value.name() {
+        "div" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    let mut weight = 0.0;
+    for names in [value.attr("class"), value.attr("id")].into_iter().flatten() {
+        if negative_class().is_match(names) {
+            weight -= 25.0;
+        }
+        if positive_class().is_match(names) {
+            weight += 25.0;
+        }
+    }
+    if matches!(value.name(), "article" | "main") || value.attr("role") == Some("main") {
+        weight += 25.0;
+    }
+    tag_score + weight
+}
+
+/// Nesting depth of the deepest node under `root`
+fn depth(root: ElementRef) -> usize {
+    let (mut depth, mut deepest) = (0, 0);
+    for edge in root.traverse() {
+        match edge {
+            Edge::Open(_) => {
+                depth += 1;
+                deepest = deepest.max(depth);
+            }
+            Edge::Close(_) => depth -= 1,
+        }
+    }
+    deepest
+}
+
+/// Append text with runs of whitespace collapsed to one space
+fn push_text(out: &mut String, text: &str) {
+    for c in text.chars() {
+        if !c.is_whitespace() {
+            out.push(c);
+        } else if !out.ends_with([' ', '\n']) {
+            out.push(' ');
+        }
+    }
+}
+
+/// Trim each line and drop empty ones
+fn trim_lines(text: &str) -> String {
+    text.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+/// End a run of inline content; a blank line (`<br><br>`) splits it into paragraphs
+fn flush_paragraph(paragraph: &mut String, blocks: &mut Vec<String>) {
+    let mut lines = Vec::new();
+    for line in paragraph.lines().map(str::trim) {
+        if !line.is_empty() {
+            lines.push(line);
+        } else if !lines.is_empty() {
+            blocks.push(lines.join("\n"));
+            lines.clear();
+        }
+    }
+    if !lines.is_empty() {
+        blocks.push(lines.join("\n"));
+    }
+    paragraph.clear();
+}
+
+/// Prefix every line; empty lines get `empty` instead
+fn prefix_lines(text: &str, prefix: &str, empty: &str) -> String {
+    text.lines()
+        .map(|line| if line.is_empty() { empty.to_string() } else { format!("{}{}", prefix, line) })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_markdown_conversion() {
+        let html = r#"<html><head><title> Release notes </title><base href="/docs/"></head><body>
+            <article>
+              <h1>Version 2.0</h1>
+              <p>This release <strong>drops</strong> support for the old sync API, see the
+                 <a href="migrate.html">migration guide</a> or <a href="javascript:void(0)">this</a>.</p>
+              <ol start="3"><li>First</li><li>Second<ul><li>Nested</li></ul></li></ol>
+              <pre>cargo install tool
+tool --version</pre>
+              <table><tr><th>Flag</th><th>Default</th></tr><tr><td><code>--sync</code></td><td>off | on</td></tr></table>
+              <script>track()</script><div hidden>Secret</div>
+            </article></body></html>"#;
+
+        let extracted = extract(html, "https://example.com/blog/post");
+        assert_eq!(extracted.title.as_deref(), Some("Release notes"));
+        assert_eq!(
+            extracted.markdown,
+            "# Version 2.0\n\n\
+             This release **drops** support for the old sync API, see the [migration guide](https://example.com/docs/migrate.html) or this.\n\n\
+             3. First\n4. Second\n   - Nested\n\n\
+             ```\ncargo install tool\ntool --version\n```\n\n\
+             | Flag | Default |\n| --- | --- |\n| `--sync` | off \\| on |"
+        );
+    }
+
+    #[test]
+    fn test_list_numbers_saturate() {
+        let html = r#"<html><body><article><p>Steps to follow, in order:</p>
+            <ol start="4294967294"><li>First</li><li>Second</li><li>Third</li></ol>
+            <ol start="18446744073709551615"><li>Out of range</li></ol>
+            </article></body></html>"#;
+        assert_eq!(
+            extract(html, "https://example.com/").markdown,
+            "Steps to follow, in order:\n\n4294967294. First\n4294967295. Second\n4294967295. Third\n\n1. Out of range"
+        );
+    }
+
+    /// The bottom-up measures agree with walking each subtree
+    #[test]
+    fn test_text_stats_match_subtree_walks() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/html");
+        for entry in std::fs::read_dir(&corpus).unwrap() {
+            let html = std::fs::read_to_string(entry.unwrap().path().join("page.html")).unwrap();
+            let document = Html::parse_document(&html);
+            let page = Page::new(&document, "https://example.com/");
+            for element in page.elements(page.body()) {
+                let stats = page.stats(element);
+                assert_eq!(stats.chars, page.text(element).chars().count());
+                assert_eq!(stats.commas, page.text(element).matches([',', '，']).count());
+                let linked: usize = page
+                    .elements(element)
+                    .into_iter()
+                    .filter(|e| e.value().name() == "a")
+                    .map(|a| page.text(a).chars().count())
+                    .sum();
+                assert_eq!(stats.link_chars, linked);
+                let has_block =
+                    page.elements(element).into_iter().skip(1).any(|e| DIV_TO_P_TAGS.contains(&e.value().name()));
+                assert_eq!(stats.has_block, has_block);
+            }
+        }
+    }
+
+    /// Each directory in `tests/fixtures/html` holds a `page.html` whose main
+    /// content must come out as `expected.md`. The `synthetic_*` pages are
+    /// hand-written imitations of common layouts (news site, docs site,
+    /// MediaWiki, div-only blog), not captured pages; real pages added here
+    /// should be trimmed and have their source and license noted.
+    #[test]
+    fn test_html_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/html");
+        let mut pages: Vec<_> = std::fs::read_dir(&corpus)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_dir())
+            .collect();
+        pages.sort();
+        assert!(!pages.is_empty());
+
+        for page in pages {
+            let name = page.file_name().unwrap().to_string_lossy().to_string();
+            let html = std::fs::read_to_string(page.join("page.html")).unwrap();
+            let expected = std::fs::read_to_string(page.join("expected.md")).unwrap();
+            let extracted = extract(&html, &format!("https://example.com/{}/", name));
+            assert_eq!(extracted.markdown, expected.trim_end(), "page {}", name);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! Consistency checks between `content_sources` and `chunks`
+//!
+//! `content_sources.chunk_count` is maintained by every writer by hand and the
+//! chunks only point back at their source through `meta.id`, so the two
+//! tables can drift apart: the viewer and the accessibility daemon write them
+//! with their own code, a crash can leave chunks behind, and racing writers
+//! can insert the same chunk twice. [`check`] reports the damage and
+//! [`repair`] fixes it inside one write transaction.
+
+use crate::dedup::payload_hash;
+use crate::metadata::Metadata;
+use crate::storage::StorageError;
+use capture_protocol::mask::Masks;
+use rusqlite::{params, Connection, Transaction};
+use serde::{Deserialize, Serialize};
+
+/// Live chunks of each source, keyed by `meta.id`. Rows whose meta is not
+/// valid JSON are left out here and reported as orphans.
+/// (`json_extract` fails the whole query on malformed JSON, so it is guarded
+/// per row rather than relying on the WHERE clause running first.)
+const LIVE_CHUNKS: &str = "SELECT id, chunk_text(text, text_zst) AS text,
+        CASE WHEN json_valid(meta) THEN json_extract(meta, '$.id') END AS doc_id,
+        CASE WHEN json_valid(meta) THEN json_extract(meta, '$.chunk_index') END AS chunk_index,
+        CASE WHEN json_valid(meta) THEN json_extract(meta, '$.total_chunks') END AS total_chunks,
+        CASE WHEN json_valid(meta) THEN json_extract(meta, '$.metadata') END AS metadata,
+        CASE WHEN json_valid(meta) THEN json_extract(meta, '$.source') END AS source,
+        CASE WHEN json_valid(meta) THEN json_extract(meta, '$.app_name') END AS app_name,
+        CASE WHEN json_valid(meta) THEN json_extract(meta, '$.bundle_id') END AS bundle_id
+    FROM chunks WHERE is_deleted = 0 AND json_valid(meta)";
+
+/// A source whose recorded chunk count differs from its live chunks
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CountMismatch {
+    pub ehl_doc_id: String,
+    pub source_path: String,
+    pub recorded: i64,
+    pub actual: i64,
+}
+
+/// A chunk that does not belong to any source
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OrphanedChunk {
+    pub chunk_id: i64,
+    /// `meta.id`, if the meta could be read at all
+    pub ehl_doc_id: Option<String>,
+    pub is_deleted: bool,
+}
+
+/// Several live chunks of one source sharing a `chunk_index`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DuplicateIndex {
+    pub ehl_doc_id: String,
+    pub chunk_index: i64,
+    /// Row ids, oldest first; repair keeps the last one
+    pub chunk_ids: Vec<i64>,
+}
+
+/// A single-chunk source whose stored hash does not match its text
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HashMismatch {
+    pub ehl_doc_id: String,
+    pub source_path: String,
+    pub recorded: String,
+    pub actual: String,
+}
+
+/// Problems found by [`check`], or fixed by [`repair`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub count_mismatches: Vec<CountMismatch>,
+    pub orphaned_chunks: Vec<OrphanedChunk>,
+    pub duplicate_indexes: Vec<DuplicateIndex>,
+    pub hash_mismatches: Vec<HashMismatch>,
+}
+
+impl IntegrityReport {
+    /// Total number of problems
+    pub fn issue_count(&self) -> usize {
+        self.count_mismatches.len()
+            + self.orphaned_chunks.len()
+            + self.duplicate_indexes.len()
+            + self.hash_mismatches.len()
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.issue_count() == 0
+    }
+}
+
+/// Report every inconsistency without changing anything
+pub fn check(conn: &Connection, masks: &Masks) -> Result<IntegrityReport, StorageError> {
+    Ok(IntegrityReport {
+        count_mismatches: count_mismatches(conn)?,
+        orphaned_chunks: orphaned_chunks(conn)?,
+        duplicate_indexes: duplicate_indexes(conn)?,
+        hash_mismatches: hash_mismatches(conn, masks)?,
+    })
+}
+
+/// Fix every inconsistency and return what was fixed.
+///
+/// Orphans are deleted outright and surplus duplicates are soft-deleted
+/// (keeping the newest copy) before counts and hashes are recomputed, so the
+/// counts describe the chunks that remain.
+pub fn repair(tx: &Transaction, masks: &Masks) -> Result<IntegrityReport, StorageError> {
+    let orphaned_chunks = orphaned_chunks(tx)?;
+    for orphan in &orphaned_chunks {
+        tx.execute("DELETE FROM chunks WHERE id = ?1", params![orphan.chunk_id])?;
+    }
+
+    let duplicate_indexes = duplicate_indexes(tx)?;
+    for duplicate in &duplicate_indexes {
+        let (_newest, older) = duplicate
+            .chunk_ids
+            .split_last()
+            .expect("duplicates have at least two chunks");
+        for id in older {
+            tx.execute("UPDATE chunks SET is_deleted = 1 WHERE id = ?1", params![id])?;
+        }
+    }
+
+    let count_mismatches = count_mismatches(tx)?;
+    for mismatch in &count_mismatches {
+        tx.execute(
+            "UPDATE content_sources SET chunk_count = ?1 WHERE ehl_doc_id = ?2",
+            params![mismatch.actual, mismatch.ehl_doc_id],
+        )?;
+    }
+
+    let hash_mismatches = hash_mismatches(tx, masks)?;
+    for mismatch in &hash_mismatches {
+        tx.execute(
+            "UPDATE content_sources SET content_hash = ?1 WHERE ehl_doc_id = ?2",
+            params![mismatch.actual, mismatch.ehl_doc_id],
+        )?;
+    }
+
+    Ok(IntegrityReport {
+        count_mismatches,
+        orphaned_chunks,
+        duplicate_indexes,
+        hash_mismatches,
+    })
+}
+
+fn count_mismatches(conn: &Connection) -> Result<Vec<CountMismatch>, StorageError> {
+    let mut stmt = conn.prepare(&format!(
+        "WITH live AS ({}),
+              counts AS (SELECT doc_id, COUNT(*) AS n FROM live GROUP BY doc_id)
+         SELECT cs.ehl_doc_id, cs.source_path, cs.chunk_count, COALESCE(counts.n, 0)
+         FROM content_sources cs LEFT JOIN counts ON counts.doc_id = cs.ehl_doc_id
+         WHERE cs.chunk_count != COALESCE(counts.n, 0)
+         ORDER BY cs.id",
+        LIVE_CHUNKS
+    ))?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok(CountMismatch {
+            ehl_doc_id: row.get(0)?,
+            source_path: row.get(1)?,
+            recorded: row.get(2)?,
+            actual: row.get(3)?,
+        })
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+fn orphaned_chunks(conn: &Connection) -> Result<Vec<OrphanedChunk>, StorageError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, doc_id, is_deleted FROM (
+             SELECT id, is_deleted,
+                    CASE WHEN json_valid(meta) THEN json_extract(meta, '$.id') END AS doc_id
+             FROM chunks
+         )
+         WHERE doc_id IS NULL OR doc_id NOT IN (SELECT ehl_doc_id FROM content_sources)
+         ORDER BY id",
+    )?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok(OrphanedChunk {
+            chunk_id: row.get(0)?,
+            ehl_doc_id: row.get(1)?,
+            is_deleted: row.get::<_, Option<i64>>(2)?.unwrap_or(0) != 0,
+        })
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+fn duplicate_indexes(conn: &Connection) -> Result<Vec<DuplicateIndex>, StorageError> {
+    let mut stmt = conn.prepare(&format!(
+        "WITH live AS ({})
+         SELECT doc_id, chunk_index, group_concat(id) FROM live
+         WHERE chunk_index IS NOT NULL AND doc_id IN (SELECT ehl_doc_id FROM content_sources)
+         GROUP BY doc_id, chunk_index HAVING COUNT(*) > 1
+         ORDER BY doc_id, chunk_index",
+        LIVE_CHUNKS
+    ))?;
+
+    let rows = stmt.query_map([], |row| {
+        let ids: String = row.get(2)?;
+        let mut chunk_ids: Vec<i64> = ids.split(',').filter_map(|id| id.parse().ok()).collect();
+        chunk_ids.sort_unstable();
+        Ok(DuplicateIndex {
+            ehl_doc_id: row.get(0)?,
+            chunk_index: row.get(1)?,
+            chunk_ids,
+        })
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+/// Only single-chunk sources can be verified: their one chunk is the whole
+/// captured text, while longer documents are split with overlapping words.
+/// The recorded hash also covers the payload metadata, kept in the chunk meta,
+/// and is taken with `masks` applied for the chunk's source and app.
+fn hash_mismatches(conn: &Connection, masks: &Masks) -> Result<Vec<HashMismatch>, StorageError> {
+    let mut stmt = conn.prepare(&format!(
+        "WITH live AS ({})
+         SELECT cs.ehl_doc_id, cs.source_path, cs.content_hash, MIN(live.text), MIN(live.metadata),
+                MIN(live.source), MIN(live.app_name), MIN(live.bundle_id)
+         FROM content_sources cs JOIN live ON live.doc_id = cs.ehl_doc_id
+         GROUP BY cs.id
+         HAVING COUNT(*) = 1 AND MIN(live.total_chunks) = 1
+         ORDER BY cs.id",
+        LIVE_CHUNKS
+    ))?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, Option<String>>(6)?,
+            row.get::<_, Option<String>>(7)?,
+        ))
+    })?;
+
+    let mut mismatches = Vec::new();
+    for row in rows {
+        let (ehl_doc_id, source_path, recorded, text, metadata, source, app_name, bundle_id) = row?;
+        let metadata: Metadata = metadata.and_then(|m| serde_json::from_str(&m).ok()).unwrap_or_default();
+        let apps: Vec<&str> = [app_name.as_deref(), bundle_id.as_deref()].into_iter().flatten().collect();
+        let masked = masks.mask(source.as_deref().unwrap_or_default(), &apps, &text);
+        let actual = payload_hash(&masked, &metadata);
+        if actual != recorded {
+            mismatches.push(HashMismatch {
+                ehl_doc_id,
+                source_path,
+                recorded,
+                actual,
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunker::Chunker;
+    use crate::dedup::compute_hash;
+    use crate::payload::CapturePayload;
+    use crate::storage::Storage;
+    use rusqlite::Connection;
+    use std::path::Path;
+
+    fn payload(url: &str, content: &str) -> CapturePayload {
+        CapturePayload::new("chrome", url, content)
+    }
+
+    /// Three sources: a clean one, one with a single chunk and one with several
+    fn populated(path: &Path) -> Storage {
+        let mut storage = Storage::open(path).unwrap();
+        let chunker = Chunker::with_defaults();
+        let long = (0..1900).map(|i| format!("word{}", i)).collect::<Vec<_>>().join(" ");
+        for (i, content) in ["clean page", "short page", long.as_str()].iter().enumerate() {
+            let p = payload(&format!("https://example.com/{}", i), content);
+            storage
+                .insert_source(&p, &p.url, &compute_hash(content), &format!("doc-{}", i), &chunker.chunk(content))
+                .unwrap();
+        }
+        storage
+    }
+
+    #[test]
+    fn test_clean_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = populated(&dir.path().join("content.db"));
+        assert!(storage.check_integrity().unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_check_and_repair() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("content.db");
+        let mut storage = populated(&path);
+        let chunk_total = storage.get_source_chunks("doc-2").unwrap().len();
+        assert!(chunk_total >= 2);
+
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "-- Drifted count
+                 UPDATE content_sources SET chunk_count = 7 WHERE ehl_doc_id = 'doc-0';
+                 -- Stale hash
+                 UPDATE content_sources SET content_hash = 'stale' WHERE ehl_doc_id = 'doc-1';
+                 -- Chunk inserted twice
+                 INSERT INTO chunks (text, meta)
+                     SELECT text, meta FROM chunks WHERE json_extract(meta, '$.id') = 'doc-2'
+                     AND json_extract(meta, '$.chunk_index') = 1;
+                 -- Leftovers of a source deleted from the viewer, and garbage
+                 INSERT INTO chunks (text, meta, is_deleted) VALUES ('gone', '{\"id\": \"doc-x\"}', 1);
+                 INSERT INTO chunks (text, meta) VALUES ('gone', '{\"id\": \"doc-x\"}');
+                 INSERT INTO chunks (text, meta) VALUES ('broken', 'not json');",
+            )
+            .unwrap();
+
+        let report = storage.check_integrity().unwrap();
+        assert_eq!(report.issue_count(), 7);
+        assert_eq!(report.count_mismatches.len(), 2);
+        assert_eq!(report.count_mismatches[0].ehl_doc_id, "doc-0");
+        assert_eq!((report.count_mismatches[0].recorded, report.count_mismatches[0].actual), (7, 1));
+        // The duplicate chunk also shows up as a count mismatch
+        assert_eq!(report.count_mismatches[1].ehl_doc_id, "doc-2");
+        assert_eq!(report.orphaned_chunks.len(), 3);
+        assert_eq!(report.orphaned_chunks[0].ehl_doc_id.as_deref(), Some("doc-x"));
+        assert!(report.orphaned_chunks[0].is_deleted);
+        assert_eq!(report.orphaned_chunks[2].ehl_doc_id, None);
+        assert_eq!(report.duplicate_indexes.len(), 1);
+        assert_eq!(report.duplicate_indexes[0].chunk_index, 1);
+        assert_eq!(report.duplicate_indexes[0].chunk_ids.len(), 2);
+        assert_eq!(report.hash_mismatches.len(), 1);
+        assert_eq!(report.hash_mismatches[0].actual, compute_hash("short page"));
+
+        // Checking changes nothing
+        assert_eq!(storage.check_integrity().unwrap(), report);
+
+        let repaired = storage.repair_integrity().unwrap();
+        assert_eq!(repaired.orphaned_chunks, report.orphaned_chunks);
+        assert_eq!(repaired.duplicate_indexes, report.duplicate_indexes);
+        // doc-2's count was only off because of the duplicate
+        assert_eq!(repaired.count_mismatches.len(), 1);
+        assert_eq!(repaired.hash_mismatches.len(), 1);
+
+        assert!(storage.check_integrity().unwrap().is_clean());
+        let source = storage.find_source_by_path("https://example.com/1").unwrap().unwrap();
+        assert_eq!(source.content_hash, compute_hash("short page"));
+        assert_eq!(storage.get_source_chunks("doc-2").unwrap().len(), chunk_total);
+    }
+}
@@ -2,13 +2,29 @@
 //!
 //! Provides content ingestion with deduplication and chunking.
 //! Designed to be embedded in a Tauri application.
+//!
+//! The socket protocol and the chunker are shared with the other components
+//! through `capture-protocol` and re-exported here as [`payload`], [`metadata`]
+//! and [`chunker`].
 
-pub mod chunker;
+pub mod backup;
+pub mod canonical;
+pub mod config;
+pub mod control;
+pub mod conversation;
 pub mod dedup;
-pub mod payload;
+pub mod export;
+pub mod html;
+pub mod integrity;
+pub mod merge;
+pub mod quality;
+pub mod reprocess;
 pub mod server;
+pub mod significance;
 pub mod storage;
+pub mod throttle;
 
+pub use capture_protocol::{chunker, metadata, payload};
 pub use payload::CapturePayload;
-pub use server::IngestionServer;
+pub use server::{IngestionServer, ServerConfig};
 pub use storage::Storage;
@@ -0,0 +1,343 @@
+//! Line and paragraph alignment for merging successive OCR snapshots
+//!
+//! An OCR capture only sees the visible part of a document. Each snapshot is
+//! aligned against the stored text with an LCS over normalized lines. Exact
+//! matches anchor the alignment; the gaps between anchors are aligned again by
+//! line similarity, so edited lines replace their old version and new lines are
+//! inserted where they appear. Stored lines that are not visible in the
+//! snapshot are kept. Normalization only applies to matching: lines are
+//! written out as captured, with their indentation and blank lines.
+
+/// Minimum similarity (1 - normalized edit distance) for two lines to count as
+/// the same line edited
+const EDIT_SIMILARITY: f64 = 0.6;
+
+/// Lines shorter than this (normalized) are never paired as edits
+const MIN_EDIT_CHARS: usize = 3;
+
+/// Upper bound on line pairs compared when aligning a gap by similarity
+const MAX_GAP_PAIRS: usize = 2_500;
+
+/// Outcome of merging a snapshot into stored text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeResult {
+    /// The merged document text
+    pub text: String,
+    /// Snapshot lines inserted into the document
+    pub inserted_lines: usize,
+    /// Stored lines replaced by an edited version
+    pub replaced_lines: usize,
+}
+
+impl MergeResult {
+    /// Whether the snapshot added nothing to the stored text
+    pub fn is_unchanged(&self) -> bool {
+        self.inserted_lines == 0 && self.replaced_lines == 0
+    }
+}
+
+/// A non-blank line with its comparison key
+#[derive(Debug)]
+struct Line<'a> {
+    /// The line as written, indentation included
+    text: &'a str,
+    /// Trimmed, whitespace-collapsed and lowercased text, used for matching
+    key: String,
+    /// Blank lines before this one; the first line of a text counts as
+    /// starting a paragraph, so at least 1 there
+    blanks_before: usize,
+}
+
+/// One step of the merged document
+enum Op<'a, 'b> {
+    Keep(&'b Line<'a>),
+    Replace(&'b Line<'a>, &'b Line<'a>),
+    Insert(&'b Line<'a>),
+}
+
+/// Merge an OCR snapshot into the stored document text
+pub fn merge_snapshot(existing: &str, incoming: &str) -> MergeResult {
+    let old = split_lines(existing);
+    let new = split_lines(incoming);
+
+    let mut ops = Vec::with_capacity(old.len() + new.len());
+    let anchors = lcs_pairs(&old, &new, |a, b| a.key == b.key);
+
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for &(old_idx, new_idx) in &anchors {
+        merge_gap(&old[old_pos..old_idx], &new[new_pos..new_idx], false, true, &mut ops);
+        ops.push(Op::Keep(&old[old_idx]));
+        old_pos = old_idx + 1;
+        new_pos = new_idx + 1;
+    }
+    merge_gap(&old[old_pos..], &new[new_pos..], true, !anchors.is_empty(), &mut ops);
+
+    render(&ops)
+}
+
+/// Merge the unmatched lines between two anchors, pairing edited lines.
+/// `final_gap` marks the gap after the last anchor; `anchored` whether any
+/// anchor precedes it.
+fn merge_gap<'a, 'b>(
+    old: &'b [Line<'a>],
+    new: &'b [Line<'a>],
+    final_gap: bool,
+    anchored: bool,
+    ops: &mut Vec<Op<'a, 'b>>,
+) {
+    let pairs = if old.len() * new.len() <= MAX_GAP_PAIRS {
+        lcs_pairs(old, new, |a, b| similarity(a, b) >= EDIT_SIMILARITY)
+    } else {
+        Vec::new()
+    };
+
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for &(old_idx, new_idx) in &pairs {
+        push_unpaired(&old[old_pos..old_idx], &new[new_pos..new_idx], false, ops);
+        ops.push(Op::Replace(&old[old_idx], &new[new_idx]));
+        old_pos = old_idx + 1;
+        new_pos = new_idx + 1;
+    }
+    // Only the tail of the final gap follows a match; with no match at all the
+    // snapshot's position is unknown and its lines are appended
+    let trailing = final_gap && (anchored || !pairs.is_empty());
+    push_unpaired(&old[old_pos..], &new[new_pos..], trailing, ops);
+}
+
+/// Emit unpaired lines. The snapshot is contiguous, so after its last match its
+/// new lines sit directly below that match and any stored lines follow them;
+/// everywhere else stored lines come first and new lines lead into the next match.
+fn push_unpaired<'a, 'b>(
+    old: &'b [Line<'a>],
+    new: &'b [Line<'a>],
+    new_first: bool,
+    ops: &mut Vec<Op<'a, 'b>>,
+) {
+    if new_first {
+        ops.extend(new.iter().map(Op::Insert));
+        ops.extend(old.iter().map(Op::Keep));
+    } else {
+        ops.extend(old.iter().map(Op::Keep));
+        ops.extend(new.iter().map(Op::Insert));
+    }
+}
+
+/// Join merged lines back into text, restoring paragraph breaks
+fn render(ops: &[Op]) -> MergeResult {
+    let mut result = MergeResult {
+        text: String::new(),
+        inserted_lines: 0,
+        replaced_lines: 0,
+    };
+
+    for op in ops {
+        let line = match op {
+            Op::Keep(line) => line,
+            Op::Insert(line) => {
+                result.inserted_lines += 1;
+                line
+            }
+            Op::Replace(old, new) => {
+                if old.key != new.key {
+                    result.replaced_lines += 1;
+                }
+                new
+            }
+        };
+
+        if !result.text.is_empty() {
+            result.text.push('\n');
+            result.text.extend(std::iter::repeat_n('\n', line.blanks_before));
+        }
+        result.text.push_str(line.text);
+    }
+
+    result
+}
+
+/// Split text into non-blank lines, remembering the blank lines between them
+fn split_lines(text: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    let mut blanks = 0;
+
+    for raw in text.lines() {
+        if raw.trim().is_empty() {
+            blanks += 1;
+            continue;
+        }
+        lines.push(Line {
+            text: raw.trim_end(),
+            key: raw.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase(),
+            blanks_before: if lines.is_empty() { blanks.max(1) } else { blanks },
+        });
+        blanks = 0;
+    }
+
+    lines
+}
+
+/// Longest common subsequence of `a` and `b` under `eq`, as index pairs in order
+pub fn lcs_pairs<T>(a: &[T], b: &[T], eq: impl Fn(&T, &T) -> bool) -> Vec<(usize, usize)> {
+    let (n, m) = (a.len(), b.len());
+    if n == 0 || m == 0 {
+        return Vec::new();
+    }
+
+    // lengths[i * (m + 1) + j] = LCS length of a[i..] and b[j..]
+    let width = m + 1;
+    let mut lengths = vec![0u32; (n + 1) * width];
+    let mut matches = vec![false; n * m];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * width + j] = if eq(&a[i], &b[j]) {
+                matches[i * m + j] = true;
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::with_capacity(lengths[0] as usize);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if matches[i * m + j] && lengths[i * width + j] == lengths[(i + 1) * width + j + 1] + 1 {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Similarity of two lines in [0, 1], based on character edit distance
+fn similarity(a: &Line, b: &Line) -> f64 {
+    let (len_a, len_b) = (a.key.chars().count(), b.key.chars().count());
+    if len_a < MIN_EDIT_CHARS || len_b < MIN_EDIT_CHARS {
+        return 0.0;
+    }
+
+    // The length difference alone bounds the similarity; skip hopeless pairs
+    let longest = len_a.max(len_b) as f64;
+    if 1.0 - (len_a.abs_diff(len_b) as f64 / longest) < EDIT_SIMILARITY {
+        return 0.0;
+    }
+
+    1.0 - edit_distance(&a.key, &b.key) as f64 / longest
+}
+
+/// Levenshtein distance over characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_identical_snapshot_is_unchanged() {
+        let text = "First line of the doc\nSecond line\n\nNew paragraph here";
+        let result = merge_snapshot(text, text);
+        assert!(result.is_unchanged());
+        assert_eq!(result.text, text);
+    }
+
+    #[test]
+    fn test_empty_existing_takes_snapshot() {
+        let result = merge_snapshot("", "Alpha line\nBeta line");
+        assert_eq!(result.text, "Alpha line\nBeta line");
+        assert_eq!(result.inserted_lines, 2);
+    }
+
+    #[test]
+    fn test_unrelated_snapshot_is_appended() {
+        let result = merge_snapshot("Release notes for v2\nFixed login bug", "Quarterly revenue grew 12%");
+        assert_eq!(
+            result.text,
+            "Release notes for v2\nFixed login bug\n\nQuarterly revenue grew 12%"
+        );
+    }
+
+    #[test]
+    fn test_short_lines_are_not_duplicated() {
+        let existing = "Status\nOK\nAll systems operational";
+        let result = merge_snapshot(existing, "OK\nAll systems operational");
+        assert!(result.is_unchanged());
+    }
+
+    #[test]
+    fn test_indentation_and_blank_lines_are_kept() {
+        let existing = "fn main() {\n    let total = 0;\n\n\n    println!(\"{}\", total);\n}";
+        let incoming = "    let total = 0;\n    let count = items.len();\n\n\n    println!(\"{}\", total);";
+        let result = merge_snapshot(existing, incoming);
+        assert_eq!(result.inserted_lines, 1);
+        assert_eq!(
+            result.text,
+            "fn main() {\n    let total = 0;\n    let count = items.len();\n\n\n    println!(\"{}\", total);\n}"
+        );
+        // Indentation alone is not a change
+        assert!(merge_snapshot(&result.text, "let total = 0;\nlet count = items.len();").is_unchanged());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+
+    /// Replays each scenario in `tests/fixtures/ocr_snapshots`: snapshots
+    /// `01.txt`, `02.txt`, ... are merged in order and compared to `expected.txt`
+    #[test]
+    fn test_snapshot_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ocr_snapshots");
+        let mut scenarios: Vec<_> = std::fs::read_dir(&corpus)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_dir())
+            .collect();
+        scenarios.sort();
+        assert!(!scenarios.is_empty());
+
+        for scenario in scenarios {
+            let mut snapshots: Vec<_> = std::fs::read_dir(&scenario)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.file_name().unwrap() != "expected.txt")
+                .collect();
+            snapshots.sort();
+
+            let mut document = String::new();
+            for snapshot in &snapshots {
+                let incoming = std::fs::read_to_string(snapshot).unwrap();
+                document = merge_snapshot(&document, &incoming).text;
+            }
+
+            let expected = std::fs::read_to_string(scenario.join("expected.txt")).unwrap();
+            assert_eq!(
+                document,
+                expected.trim_end(),
+                "scenario {:?}",
+                scenario.file_name().unwrap()
+            );
+        }
+    }
+}
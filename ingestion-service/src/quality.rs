@@ -0,0 +1,369 @@
+//! Boilerplate stripping and information scoring before chunking
+//!
+//! Captures often carry the same navigation, cookie banners and sidebars on
+//! every page of a site. Per source type, the filter remembers which lines it
+//! has seen in which documents; a line found in `repeat_documents` different
+//! documents is boilerplate and is removed from every later capture. What is
+//! left gets an information score, and payloads below `min_score` (a
+//! "Loading..." screen, a lone menu) are not stored.
+//!
+//! ```toml
+//! [quality]
+//! min_score = 0.2
+//! repeat_documents = 5
+//!
+//! [quality.sources.clipboard]
+//! min_score = 0.0
+//! ```
+//!
+//! Learned lines live in memory only and are relearned after a restart.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Words at which the length factor of the score stops growing
+const FULL_LENGTH_WORDS: f64 = 20.0;
+
+/// Bits per character at which the entropy factor stops growing (English prose is around 4.1)
+const FULL_ENTROPY_BITS: f64 = 4.0;
+
+/// Leading words used for the unique-token ratio, so long documents aren't penalized
+const DIVERSITY_WINDOW: usize = 100;
+
+/// The `[quality]` config section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityConfig {
+    pub enabled: bool,
+    /// Payloads scoring below this (0-1) after stripping are skipped
+    pub min_score: f64,
+    /// A line seen in this many documents of one source type is boilerplate (0 = never strip)
+    pub repeat_documents: usize,
+    /// Lines remembered per source type before those not yet boilerplate are forgotten
+    pub max_tracked_lines: usize,
+    /// Per source type overrides
+    pub sources: HashMap<String, SourceQuality>,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_score: 0.2,
+            repeat_documents: 5,
+            max_tracked_lines: 50_000,
+            sources: HashMap::new(),
+        }
+    }
+}
+
+/// Overrides for one source type; unset fields use the section defaults
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourceQuality {
+    pub enabled: Option<bool>,
+    pub min_score: Option<f64>,
+    pub repeat_documents: Option<usize>,
+}
+
+/// Result of filtering one payload
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityReport {
+    /// Content with boilerplate lines removed
+    pub text: String,
+    pub stripped_lines: usize,
+    pub score: f64,
+    pub min_score: f64,
+}
+
+impl QualityReport {
+    pub fn passed(&self) -> bool {
+        self.score >= self.min_score
+    }
+}
+
+/// Counters returned by the `stats` command
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QualityStats {
+    /// Lines currently treated as boilerplate, over all source types
+    pub boilerplate_lines: usize,
+    /// Payloads skipped for a low score since startup
+    pub skipped: u64,
+    /// Boilerplate lines removed from payloads since startup
+    pub stripped_lines: u64,
+}
+
+/// Documents each line was seen in, for one source type
+#[derive(Debug, Default)]
+struct SourceLines {
+    /// Line key -> hashes of the source paths it appeared in, up to `repeat_documents`
+    lines: HashMap<u64, Vec<u64>>,
+}
+
+/// Content quality stage, keeping what it learned per source type
+pub struct QualityFilter {
+    config: QualityConfig,
+    sources: HashMap<String, SourceLines>,
+    skipped: u64,
+    stripped_lines: u64,
+}
+
+impl QualityFilter {
+    pub fn new(config: QualityConfig) -> Self {
+        Self {
+            config,
+            sources: HashMap::new(),
+            skipped: 0,
+            stripped_lines: 0,
+        }
+    }
+
+    /// Learn from `content`, strip its boilerplate and score the rest; `None` if disabled for `source`
+    pub fn assess(&mut self, source: &str, source_path: &str, content: &str) -> Option<QualityReport> {
+        let overrides = self.config.sources.get(source).cloned().unwrap_or_default();
+        if !overrides.enabled.unwrap_or(self.config.enabled) {
+            return None;
+        }
+        let repeat = overrides.repeat_documents.unwrap_or(self.config.repeat_documents);
+        let min_score = overrides.min_score.unwrap_or(self.config.min_score);
+
+        let known = self.sources.entry(source.to_string()).or_default();
+        known.observe(content, hash_of(source_path), repeat, self.config.max_tracked_lines);
+
+        let mut kept = Vec::new();
+        let mut stripped_lines = 0;
+        for line in content.lines() {
+            if known.is_boilerplate(line, repeat) {
+                stripped_lines += 1;
+            } else if !line.trim().is_empty() || kept.last().is_some_and(|last: &&str| !last.trim().is_empty()) {
+                // Blank lines left behind by stripping collapse into one
+                kept.push(line);
+            }
+        }
+        while kept.last().is_some_and(|line| line.trim().is_empty()) {
+            kept.pop();
+        }
+
+        // Untouched content is passed through as is, keeping its exact line endings
+        let text = if stripped_lines == 0 { content.to_string() } else { kept.join("\n") };
+        let report = QualityReport {
+            score: information_score(&text),
+            text,
+            stripped_lines,
+            min_score,
+        };
+
+        self.stripped_lines += stripped_lines as u64;
+        if !report.passed() {
+            self.skipped += 1;
+        }
+        Some(report)
+    }
+
+    pub fn stats(&self) -> QualityStats {
+        let boilerplate_lines = self
+            .sources
+            .iter()
+            .map(|(source, known)| {
+                let repeat = self
+                    .config
+                    .sources
+                    .get(source)
+                    .and_then(|overrides| overrides.repeat_documents)
+                    .unwrap_or(self.config.repeat_documents);
+                known.lines.values().filter(|documents| repeat > 0 && documents.len() >= repeat).count()
+            })
+            .sum();
+        QualityStats {
+            boilerplate_lines,
+            skipped: self.skipped,
+            stripped_lines: self.stripped_lines,
+        }
+    }
+}
+
+impl SourceLines {
+    fn observe(&mut self, content: &str, document: u64, repeat: usize, max_tracked: usize) {
+        if repeat == 0 {
+            return;
+        }
+        let keys: HashSet<u64> = content.lines().filter_map(line_key).collect();
+        for key in keys {
+            let documents = self.lines.entry(key).or_default();
+            if documents.len() < repeat && !documents.contains(&document) {
+                documents.push(document);
+            }
+        }
+
+        if self.lines.len() > max_tracked {
+            self.lines.retain(|_, documents| documents.len() >= repeat);
+        }
+    }
+
+    fn is_boilerplate(&self, line: &str, repeat: usize) -> bool {
+        repeat > 0
+            && line_key(line)
+                .and_then(|key| self.lines.get(&key))
+                .is_some_and(|documents| documents.len() >= repeat)
+    }
+}
+
+/// Lines are compared ignoring case and spacing; blank lines are never boilerplate
+fn line_key(line: &str) -> Option<u64> {
+    let normalized = line.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    (!normalized.is_empty()).then(|| hash_of(&normalized))
+}
+
+fn hash_of(value: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Information score in 0-1: the product of a length factor, the character
+/// entropy and the unique-word ratio of the leading words
+pub fn information_score(text: &str) -> f64 {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        return 0.0;
+    }
+
+    let length = (words.len() as f64 / FULL_LENGTH_WORDS).min(1.0).sqrt();
+    let entropy = (char_entropy(text) / FULL_ENTROPY_BITS).min(1.0);
+    let window = &words[..words.len().min(DIVERSITY_WINDOW)];
+    let unique: HashSet<&String> = window.iter().collect();
+    let diversity = unique.len() as f64 / window.len() as f64;
+
+    length * entropy * diversity
+}
+
+/// Shannon entropy of the non-whitespace characters, in bits per character
+fn char_entropy(text: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let mut total = 0;
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        *counts.entry(c).or_default() += 1;
+        total += 1;
+    }
+    counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_score_ranks_content() {
+        let prose = "The search revamp ships in August. Mobile offline mode moves to Q4 because the sync \
+                     engine needs another round of testing, and two backend roles are still open.";
+        assert!(information_score(prose) > 0.5);
+        assert!(information_score("Loading...") < 0.2);
+        assert!(information_score("Item\nItem\nItem\nItem\nItem\nItem\nItem\nItem") < 0.2);
+        assert_eq!(information_score(""), 0.0);
+        assert_eq!(information_score("... --- ..."), 0.0);
+    }
+
+    #[test]
+    fn test_repeated_lines_are_learned_per_source() {
+        let mut filter = QualityFilter::new(QualityConfig {
+            repeat_documents: 3,
+            ..Default::default()
+        });
+        let page = |n: usize| format!("Home | News | Sport\nArticle {} has its own text about topic {}", n, n);
+
+        // The same document seen again does not count twice
+        for _ in 0..5 {
+            assert_eq!(filter.assess("chrome", "https://news.example/1", &page(1)).unwrap().stripped_lines, 0);
+        }
+        assert_eq!(filter.assess("chrome", "https://news.example/2", &page(2)).unwrap().stripped_lines, 0);
+        let third = filter.assess("chrome", "https://news.example/3", &page(3)).unwrap();
+        assert_eq!(third.stripped_lines, 1);
+        assert_eq!(third.text, "Article 3 has its own text about topic 3");
+
+        // Other source types learn separately
+        let other = filter.assess("slack", "https://acme.slack.com/1", &page(4)).unwrap();
+        assert_eq!(other.stripped_lines, 0);
+        assert_eq!(filter.stats().boilerplate_lines, 1);
+    }
+
+    #[test]
+    fn test_per_source_overrides() {
+        let mut config = QualityConfig::default();
+        config.sources.insert(
+            "clipboard".to_string(),
+            SourceQuality {
+                min_score: Some(0.0),
+                ..Default::default()
+            },
+        );
+        config.sources.insert(
+            "raw".to_string(),
+            SourceQuality {
+                enabled: Some(false),
+                ..Default::default()
+            },
+        );
+        let mut filter = QualityFilter::new(config);
+
+        assert!(!filter.assess("chrome", "a", "Loading...").unwrap().passed());
+        assert!(filter.assess("clipboard", "", "Loading...").unwrap().passed());
+        assert!(filter.assess("raw", "a", "Loading...").is_none());
+        assert_eq!(filter.stats().skipped, 1);
+    }
+
+    /// Replays each scenario in `tests/fixtures/quality`: documents `01.txt`,
+    /// `02.txt`, ... are assessed in order as separate pages of one source, and
+    /// the last one must come out as `expected.txt`, or be skipped if that file
+    /// reads `<skipped>`
+    #[test]
+    fn test_quality_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/quality");
+        let mut scenarios: Vec<_> = std::fs::read_dir(&corpus)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_dir())
+            .collect();
+        scenarios.sort();
+        assert!(!scenarios.is_empty());
+
+        for scenario in scenarios {
+            let name = scenario.file_name().unwrap().to_string_lossy().to_string();
+            let mut documents: Vec<_> = std::fs::read_dir(&scenario)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.file_name().unwrap() != "expected.txt")
+                .collect();
+            documents.sort();
+
+            let mut filter = QualityFilter::new(QualityConfig::default());
+            let mut last = None;
+            for document in &documents {
+                let content = std::fs::read_to_string(document).unwrap();
+                let path = format!("fixture://{}/{}", name, document.file_name().unwrap().to_string_lossy());
+                last = filter.assess("fixture", &path, &content);
+            }
+
+            let report = last.unwrap();
+            let expected = std::fs::read_to_string(scenario.join("expected.txt")).unwrap();
+            if expected.trim_end() == "<skipped>" {
+                assert!(!report.passed(), "scenario {} scored {:.3}", name, report.score);
+            } else {
+                assert!(report.passed(), "scenario {} scored {:.3}", name, report.score);
+                assert_eq!(report.text.trim_end(), expected.trim_end(), "scenario {}", name);
+            }
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Re-processing stored sources after the pipeline changes
+//!
+//! Every chunk records the [`pipeline_version`] that produced it: the
+//! chunker's version and `[chunker]` settings, and the `[redaction]` rules
+//! its text went through. Changing any of them leaves the stored chunks out
+//! of date until [`run`] streams each affected source's full text back
+//! through the current redaction rules and chunker. Chunks whose text comes
+//! out the same keep their rows (and embeddings), as with any update.
+//!
+//! Sources are rewritten one transaction at a time and stamped as they go,
+//! so an interrupted run resumes where it stopped and a finished one has
+//! nothing left to do.
+//!
+//! Redaction is applied to the source text, its stored chat messages and its
+//! earlier revisions. Chunks soft-deleted before the run keep their text
+//! until `gc` purges them. The quality filter learns boilerplate from live
+//! captures and is not re-applied.
+
+use crate::chunker::Chunker;
+use crate::conversation;
+use crate::dedup::source_hash;
+use crate::export;
+use crate::payload::ChunkDiff;
+use crate::storage::{ContentSource, Storage, StorageError};
+use capture_protocol::redact::Redactor;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use tracing::warn;
+
+/// Reported after each source
+pub struct ReprocessProgress<'a> {
+    /// Sources handled so far, including this one
+    pub done: usize,
+    pub total: usize,
+    pub source: &'a ContentSource,
+    /// How the chunks changed, or why the source was left as it was
+    pub result: &'a Result<ChunkDiff, StorageError>,
+}
+
+/// Totals of a reprocessing run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReprocessSummary {
+    pub reprocessed: usize,
+    pub failed: usize,
+    /// Chunk changes summed over every reprocessed source
+    pub chunks: ChunkDiff,
+}
+
+/// The version recorded on every chunk: `Chunker::pipeline_version`, then
+/// `Redactor::version`
+pub fn pipeline_version(chunker: &Chunker, redactor: &Redactor) -> String {
+    format!("{}+{}", chunker.pipeline_version(), redactor.version())
+}
+
+/// Reprocess every source with chunks of another pipeline version than the
+/// one `storage` records, calling `on_progress` after each
+pub fn run(
+    storage: &mut Storage,
+    chunker: &Chunker,
+    redactor: &Redactor,
+    mut on_progress: impl FnMut(&ReprocessProgress),
+) -> Result<ReprocessSummary, StorageError> {
+    let sources = storage.stale_sources()?;
+    let mut summary = ReprocessSummary::default();
+
+    for (i, source) in sources.iter().enumerate() {
+        let result = reprocess_source(storage, chunker, redactor, source);
+        match &result {
+            Ok(diff) => {
+                summary.reprocessed += 1;
+                summary.chunks.added += diff.added;
+                summary.chunks.removed += diff.removed;
+                summary.chunks.kept += diff.kept;
+            }
+            Err(e) => {
+                warn!("Failed to reprocess {}: {}", source.source_path, e);
+                summary.failed += 1;
+            }
+        }
+        on_progress(&ReprocessProgress {
+            done: i + 1,
+            total: sources.len(),
+            source,
+            result: &result,
+        });
+    }
+
+    Ok(summary)
+}
+
+/// Redact and re-cut one source from its current text, keeping its capture time
+pub fn reprocess_source(
+    storage: &mut Storage,
+    chunker: &Chunker,
+    redactor: &Redactor,
+    source: &ContentSource,
+) -> Result<ChunkDiff, StorageError> {
+    let record = export::export_record(storage, source)?;
+    let mut payload = record.to_payload();
+    storage.redact_revisions(&source.ehl_doc_id, |text| redactor.redact(text))?;
+
+    let chunks = if record.messages.is_empty() {
+        payload.content = redactor.redact(&payload.content).into_owned();
+        chunker.chunk(&payload.content)
+    } else {
+        // Messages keep their keys; only the text of those with PII is rewritten
+        let mut messages = storage.get_messages(&source.ehl_doc_id)?;
+        let mut redacted = Vec::new();
+        for message in &mut messages {
+            if let Cow::Owned(text) = redactor.redact(&message.text) {
+                message.text = text;
+                redacted.push(message.clone());
+            }
+        }
+        storage.add_messages(&source.ehl_doc_id, &redacted)?;
+
+        let transcript = conversation::render(&messages);
+        payload.content = transcript.text;
+        payload.messages = messages.iter().map(conversation::to_chat_message).collect();
+        chunker.chunk_sections(&payload.content, &transcript.sections)
+    };
+
+    let content_hash = source_hash(&payload, storage.masks());
+    storage.rechunk_source(&source.ehl_doc_id, &source.source_path, &payload, &content_hash, &chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedup::payload_hash;
+    use crate::chunker::ChunkerConfig;
+    use crate::payload::CapturePayload;
+    use capture_protocol::redact::RedactionConfig;
+    use capture_protocol::ChatMessage;
+    use content_db::doc_id;
+
+    fn payload(url: &str, content: &str) -> CapturePayload {
+        CapturePayload {
+            title: Some("Notes".to_string()),
+            timestamp: Some(1_717_232_400),
+            ..CapturePayload::new("chrome", url, content)
+        }
+    }
+
+    fn words(count: usize) -> String {
+        (0..count).map(|i| format!("word{}", i)).collect::<Vec<_>>().join(" ")
+    }
+
+    fn store(storage: &mut Storage, chunker: &Chunker, payload: &CapturePayload) -> String {
+        let ehl_doc_id = doc_id::doc_id(&payload.source, &payload.url);
+        let hash = payload_hash(&payload.content, &payload.metadata);
+        storage
+            .insert_source(payload, &payload.url, &hash, &ehl_doc_id, &chunker.chunk(&payload.content))
+            .unwrap();
+        ehl_doc_id
+    }
+
+    fn versions(storage: &Storage, ehl_doc_id: &str) -> Vec<String> {
+        storage
+            .get_source_chunks(ehl_doc_id)
+            .unwrap()
+            .iter()
+            .map(|chunk| chunk.meta["pipeline_version"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_reprocess_recuts_stale_sources() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let redactor = Redactor::default();
+        let old = Chunker::with_defaults();
+        let long = store(&mut storage, &old, &payload("https://example.com/long", &words(300)));
+        let short = store(&mut storage, &old, &payload("https://example.com/short", "a few words"));
+        assert!(storage.stale_sources().unwrap().is_empty());
+        assert_eq!(versions(&storage, &long), vec![pipeline_version(&old, &redactor)]);
+
+        let new = Chunker::new(ChunkerConfig { max_tokens: 100, overlap_tokens: 10 });
+        storage.set_pipeline_version(pipeline_version(&new, &redactor));
+        assert_eq!(storage.stale_sources().unwrap().len(), 2);
+        assert_eq!(storage.stats().unwrap().stale_chunk_count, 2);
+
+        let mut progress = Vec::new();
+        let summary = run(&mut storage, &new, &redactor, |p| progress.push((p.done, p.total, p.result.is_ok()))).unwrap();
+        assert_eq!(progress, vec![(1, 2, true), (2, 2, true)]);
+        assert_eq!((summary.reprocessed, summary.failed), (2, 0));
+        // The short source's only chunk is unchanged and keeps its row
+        assert_eq!(summary.chunks.kept, 1);
+        assert_eq!(summary.chunks.removed, 1);
+
+        assert_eq!(versions(&storage, &long), vec![pipeline_version(&new, &redactor); 4]);
+        assert_eq!(versions(&storage, &short), vec![pipeline_version(&new, &redactor)]);
+        let source = storage.find_source_by_doc_id(&long).unwrap().unwrap();
+        assert_eq!((source.chunk_count, source.captured_at), (4, 1_717_232_400));
+        assert_eq!(storage.get_source_content(&long).unwrap(), words(300));
+        assert_eq!(storage.stats().unwrap().stale_chunk_count, 0);
+
+        // Nothing left the second time
+        let summary = run(&mut storage, &new, &redactor, |_| panic!("no source is stale")).unwrap();
+        assert_eq!(summary, ReprocessSummary::default());
+    }
+
+    #[test]
+    fn test_reprocess_applies_new_redaction_rules() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let chunker = Chunker::with_defaults();
+        let text = "Ask ana@example.com for the rota";
+        let doc = store(&mut storage, &chunker, &payload("https://example.com/rota", text));
+
+        let mut chat = payload("https://acme.slack.com/archives/C1", "");
+        chat.source = "slack".to_string();
+        chat.messages = vec![ChatMessage::new("mail me at bo@example.com"), ChatMessage::new("ok")];
+        let chat_id = doc_id::doc_id(&chat.source, &chat.url);
+        storage.add_messages(&chat_id, &conversation::to_stored(&chat.messages, 0)).unwrap();
+        chat.content = conversation::render(&storage.get_messages(&chat_id).unwrap()).text;
+        store(&mut storage, &chunker, &chat);
+
+        let redactor = Redactor::new(RedactionConfig {
+            redact_emails: true,
+            ..Default::default()
+        });
+        storage.set_pipeline_version(pipeline_version(&chunker, &redactor));
+        assert_eq!(storage.stale_sources().unwrap().len(), 2);
+
+        let summary = run(&mut storage, &chunker, &redactor, |_| {}).unwrap();
+        assert_eq!((summary.reprocessed, summary.failed), (2, 0));
+        assert_eq!(storage.get_source_content(&doc).unwrap(), "Ask [REDACTED_EMAIL] for the rota");
+        let messages = storage.get_messages(&chat_id).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].text, "mail me at [REDACTED_EMAIL]");
+        assert!(!storage.get_source_content(&chat_id).unwrap().contains("bo@example.com"));
+        assert!(storage.search("example.com", &[], &Default::default(), 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reprocess_chat_source() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let redactor = Redactor::default();
+        let mut chat = payload("https://acme.slack.com/archives/C1", "");
+        chat.source = "slack".to_string();
+        chat.messages = (0..3)
+            .map(|i| ChatMessage {
+                timestamp: Some(1_717_232_400 + i * 3 * 60 * 60),
+                ..ChatMessage::new(words(40))
+            })
+            .collect();
+        let ehl_doc_id = doc_id::doc_id(&chat.source, &chat.url);
+        storage.add_messages(&ehl_doc_id, &conversation::to_stored(&chat.messages, 0)).unwrap();
+        let transcript = conversation::render(&storage.get_messages(&ehl_doc_id).unwrap());
+        chat.content = transcript.text.clone();
+        let old = Chunker::with_defaults();
+        let hash = payload_hash(&chat.content, &chat.metadata);
+        let chunks = old.chunk_sections(&chat.content, &transcript.sections);
+        storage.insert_source(&chat, &chat.url, &hash, &ehl_doc_id, &chunks).unwrap();
+        assert_eq!(chunks.len(), 1);
+
+        // Each conversation is now a chunk of its own
+        let new = Chunker::new(ChunkerConfig { max_tokens: 50, overlap_tokens: 0 });
+        storage.set_pipeline_version(pipeline_version(&new, &redactor));
+        let summary = run(&mut storage, &new, &redactor, |_| {}).unwrap();
+        assert_eq!(summary.reprocessed, 1);
+        let source = storage.find_source_by_doc_id(&ehl_doc_id).unwrap().unwrap();
+        assert_eq!((source.chunk_count, source.content_hash), (3, hash));
+        assert_eq!(versions(&storage, &ehl_doc_id), vec![pipeline_version(&new, &redactor); 3]);
+    }
+}
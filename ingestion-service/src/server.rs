@@ -1,48 +1,65 @@
 //! Unix socket server for receiving capture payloads
 
+use crate::backup::{BackupError, SnapshotInfo, Snapshotter};
+use crate::canonical::CanonicalRules;
 use crate::chunker::Chunker;
-use crate::dedup::{compute_hash, DedupCache, DedupResult};
-use crate::payload::{CapturePayload, IngestionResponse};
-use crate::storage::Storage;
-use std::path::{Path, PathBuf};
+use crate::control::{self, ControlRequest, ControlResponse, IngestEvent, ServiceStats};
+use crate::conversation;
+use crate::dedup::{source_hash, DedupCache, DedupConfig, DedupResult};
+use crate::export::{self, ImportSummary};
+use crate::html;
+use crate::merge::merge_snapshot;
+use crate::metadata;
+use crate::payload::{CapturePayload, ContentType, IngestionResponse, SkipReason, TextDiff};
+use crate::quality::QualityFilter;
+use crate::reprocess;
+use crate::significance::{text_diff, SignificanceConfig};
+use crate::storage::{ContentSource, GcOptions, SourceFilter, Storage, StorageError};
+use crate::throttle::{Admission, Throttle};
+use capture_protocol::mask::Masks;
+use capture_protocol::normalize::normalize;
+use capture_protocol::redact::Redactor;
+use content_db::doc_id;
+
+pub use crate::config::ServerConfig;
+use std::borrow::Cow;
+use std::io::{BufRead, Write};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 use tracing::{error, info, warn};
 
-/// Ingestion server configuration
-pub struct ServerConfig {
-    /// Path to the Unix socket
-    pub socket_path: PathBuf,
-    /// Path to the SQLite database
-    pub db_path: PathBuf,
-}
-
-impl Default for ServerConfig {
-    fn default() -> Self {
-        let data_dir = dirs::data_local_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("clace-ingestion");
+/// Events buffered per `tail` client before the slowest one starts missing some
+const EVENT_BUFFER: usize = 256;
 
-        Self {
-            socket_path: PathBuf::from("/tmp/clace-ingestion.sock"),
-            db_path: data_dir.join("content.db"),
-        }
-    }
-}
+/// How often deferred payloads are checked and stored once their source is allowed again
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Shared state for the ingestion service
 struct ServiceState {
     storage: Storage,
     cache: DedupCache,
+    dedup: DedupConfig,
+    masks: Masks,
+    redactor: Redactor,
+    significance: SignificanceConfig,
     chunker: Chunker,
+    rules: CanonicalRules,
+    throttle: Throttle,
+    quality: QualityFilter,
+    /// Processed payloads, for `tail` clients
+    events: broadcast::Sender<IngestEvent>,
 }
 
 /// Ingestion server that listens on a Unix socket
 pub struct IngestionServer {
     config: ServerConfig,
     state: Arc<Mutex<ServiceState>>,
+    snapshotter: Arc<Snapshotter>,
 }
 
 impl IngestionServer {
@@ -53,22 +70,51 @@ impl IngestionServer {
             std::fs::create_dir_all(parent)?;
         }
 
-        let storage = Storage::open(&config.db_path)?;
+        let chunker = Chunker::new(config.chunker.clone());
+        let masks = Masks::compile(&config.masking)?;
+        let redactor = Redactor::new(config.redaction.clone());
+        let mut storage = Storage::open(&config.db_path)?;
+        storage.set_pipeline_version(reprocess::pipeline_version(&chunker, &redactor));
+        storage.set_masks(masks.clone());
+        if config.compression.enabled {
+            storage.enable_compression(config.compression.level)?;
+        }
         let cache = DedupCache::with_defaults();
-        let chunker = Chunker::with_defaults();
+        let mut rules = CanonicalRules::compile(&config.canonicalization)?;
+        rules.set_legacy_sources(storage.legacy_url_sources()?);
+        let throttle = Throttle::new(config.throttle.clone());
+        let quality = QualityFilter::new(config.quality.clone());
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
 
         let state = Arc::new(Mutex::new(ServiceState {
             storage,
             cache,
+            dedup: config.dedup.clone(),
+            masks,
+            redactor,
+            significance: config.significance.clone(),
             chunker,
+            rules,
+            throttle,
+            quality,
+            events,
         }));
 
-        Ok(Self { config, state })
+        let snapshotter = Arc::new(Snapshotter::new(
+            config.db_path.clone(),
+            config.backup.clone(),
+        ));
+
+        Ok(Self {
+            config,
+            state,
+            snapshotter,
+        })
     }
 
-    /// Create a server with default configuration
+    /// Create a server with configuration from the default config file
     pub fn with_defaults() -> Result<Self, Box<dyn std::error::Error>> {
-        Self::new(ServerConfig::default())
+        Self::new(ServerConfig::load())
     }
 
     /// Start the server and listen for connections
@@ -81,12 +127,20 @@ impl IngestionServer {
         let listener = UnixListener::bind(&self.config.socket_path)?;
         info!("Ingestion server listening on {:?}", self.config.socket_path);
 
+        if let Some(interval) = self.snapshotter.interval() {
+            tokio::spawn(schedule_snapshots(Arc::clone(&self.snapshotter), interval));
+        }
+        if self.config.throttle.enabled {
+            tokio::spawn(flush_deferred_loop(Arc::clone(&self.state)));
+        }
+
         loop {
             match listener.accept().await {
                 Ok((stream, _addr)) => {
                     let state = Arc::clone(&self.state);
+                    let snapshotter = Arc::clone(&self.snapshotter);
                     tokio::spawn(async move {
-                        if let Err(e) = handle_connection(stream, state).await {
+                        if let Err(e) = handle_connection(stream, state, snapshotter).await {
                             error!("Connection error: {}", e);
                         }
                     });
@@ -106,7 +160,57 @@ impl IngestionServer {
     /// Process a single payload (for direct integration without socket)
     pub async fn process(&self, payload: CapturePayload) -> IngestionResponse {
         let mut state = self.state.lock().await;
-        process_payload(&mut state, payload)
+        ingest(&mut state, payload, Origin::Live)
+    }
+
+    /// Store deferred payloads whose source is no longer throttled; returns how many
+    pub async fn flush_deferred(&self) -> usize {
+        let mut state = self.state.lock().await;
+        flush_deferred(&mut state, Instant::now())
+    }
+
+    /// Handle a control request (for direct integration without socket)
+    pub async fn control(&self, request: ControlRequest) -> ControlResponse {
+        handle_control(&self.state, &self.snapshotter, request).await
+    }
+
+    /// Snapshot the database without pausing ingestion, then rotate old snapshots
+    pub async fn snapshot(&self, compress: Option<bool>) -> Result<SnapshotInfo, BackupError> {
+        run_snapshot(&self.snapshotter, compress).await
+    }
+
+    /// Export sources matching `filter` as JSONL; returns the number of records written
+    pub async fn export_jsonl<W: Write>(
+        &self,
+        filter: &SourceFilter,
+        writer: W,
+    ) -> Result<usize, StorageError> {
+        let state = self.state.lock().await;
+        export::write_jsonl(&state.storage, filter, writer)
+    }
+
+    /// Import a JSONL export by replaying each record through the normal dedup path
+    pub async fn import_jsonl<R: BufRead>(&self, reader: R) -> Result<ImportSummary, StorageError> {
+        let mut summary = ImportSummary::default();
+
+        for record in export::read_jsonl(reader) {
+            let record = match record {
+                Ok(record) => record,
+                Err(StorageError::Io(e)) => return Err(StorageError::Io(e)),
+                Err(e) => {
+                    warn!("Skipping unreadable import record: {}", e);
+                    summary.failed += 1;
+                    continue;
+                }
+            };
+
+            // Replayed records are neither filtered nor throttled
+            let mut state = self.state.lock().await;
+            let response = ingest(&mut state, record.to_payload(), Origin::Import);
+            summary.record(&response);
+        }
+
+        Ok(summary)
     }
 }
 
@@ -114,27 +218,52 @@ impl IngestionServer {
 async fn handle_connection(
     stream: UnixStream,
     state: Arc<Mutex<ServiceState>>,
+    snapshotter: Arc<Snapshotter>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let (reader, mut writer) = stream.into_split();
     let mut reader = BufReader::new(reader);
     let mut line = String::new();
 
-    // Read one JSON payload per line
+    // Read one JSON payload or control request per line
     while reader.read_line(&mut line).await? > 0 {
-        let response = match serde_json::from_str::<CapturePayload>(&line) {
-            Ok(payload) => {
-                info!("Received: {} - {}", payload.source, payload.url);
-                let mut state = state.lock().await;
-                process_payload(&mut state, payload)
+        let value = serde_json::from_str::<serde_json::Value>(&line);
+
+        let response_json = match value {
+            Ok(value) if ControlRequest::is_control(&value) => {
+                let response = match serde_json::from_value::<ControlRequest>(value) {
+                    Ok(ControlRequest::Tail) => {
+                        info!("Tail client connected");
+                        let events = state.lock().await.events.subscribe();
+                        return stream_events(reader, writer, events).await;
+                    }
+                    Ok(request) => {
+                        info!("Control request: {:?}", request);
+                        handle_control(&state, &snapshotter, request).await
+                    }
+                    Err(e) => {
+                        warn!("Failed to parse control request: {}", e);
+                        ControlResponse::error(&format!("Parse error: {}", e))
+                    }
+                };
+                serde_json::to_string(&response)?
             }
-            Err(e) => {
-                warn!("Failed to parse payload: {}", e);
-                IngestionResponse::error(&format!("Parse error: {}", e))
+            value => {
+                let response = match value.and_then(serde_json::from_value::<CapturePayload>) {
+                    Ok(payload) => {
+                        info!("Received: {} - {}", payload.source, payload.url);
+                        let mut state = state.lock().await;
+                        ingest(&mut state, payload, Origin::Live)
+                    }
+                    Err(e) => {
+                        warn!("Failed to parse payload: {}", e);
+                        IngestionResponse::error(&format!("Parse error: {}", e))
+                    }
+                };
+                serde_json::to_string(&response)?
             }
         };
 
         // Send response
-        let response_json = serde_json::to_string(&response)?;
         writer.write_all(response_json.as_bytes()).await?;
         writer.write_all(b"\n").await?;
         writer.flush().await?;
@@ -145,139 +274,639 @@ async fn handle_connection(
     Ok(())
 }
 
-/// Process a single payload
-fn process_payload(state: &mut ServiceState, payload: CapturePayload) -> IngestionResponse {
-    let content_hash = compute_hash(&payload.content);
-    
+/// Stream ingest events to a `tail` client until it disconnects
+async fn stream_events(
+    mut reader: BufReader<OwnedReadHalf>,
+    mut writer: OwnedWriteHalf,
+    mut events: broadcast::Receiver<IngestEvent>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let ack = serde_json::to_string(&ControlResponse::ok())?;
+    writer.write_all(ack.as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    writer.flush().await?;
+
+    let mut discard = String::new();
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            // Anything the client sends, or EOF, ends the stream
+            _ = reader.read_line(&mut discard) => return Ok(()),
+        };
+
+        let event = match event {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!("Tail client fell behind; dropped {} events", missed);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+
+        let line = serde_json::to_string(&event)?;
+        if writer.write_all(line.as_bytes()).await.is_err() || writer.write_all(b"\n").await.is_err() {
+            return Ok(());
+        }
+        writer.flush().await?;
+    }
+}
+
+/// Handle a single control request
+async fn handle_control(
+    state: &Mutex<ServiceState>,
+    snapshotter: &Arc<Snapshotter>,
+    request: ControlRequest,
+) -> ControlResponse {
+    match control_result(state, snapshotter, request).await {
+        Ok(response) => response,
+        Err(e) => {
+            error!("Storage error in control request: {}", e);
+            ControlResponse::error(&format!("Storage error: {}", e))
+        }
+    }
+}
+
+/// Run a control request; the state is only locked by requests that use it
+async fn control_result(
+    state: &Mutex<ServiceState>,
+    snapshotter: &Arc<Snapshotter>,
+    request: ControlRequest,
+) -> Result<ControlResponse, StorageError> {
+    match request {
+        // Runs on its own connection, so ingestion continues meanwhile
+        ControlRequest::Snapshot { compress } => Ok(match run_snapshot(snapshotter, compress).await {
+            Ok(info) => ControlResponse::snapshot(info),
+            Err(e) => {
+                error!("Snapshot failed: {}", e);
+                ControlResponse::error(&format!("Snapshot error: {}", e))
+            }
+        }),
+        // A socket connection turns into an event stream before getting here
+        ControlRequest::Tail => Ok(ControlResponse::error(
+            "tail streams events and is only available over the socket",
+        )),
+        ControlRequest::ListSources {
+            source_types,
+            since,
+            until,
+            metadata,
+        } => {
+            let filter = control::source_filter(source_types, since, until, metadata);
+            Ok(ControlResponse::sources(state.lock().await.storage.list_sources(&filter)?))
+        }
+        ControlRequest::Check { repair } => {
+            let mut state = state.lock().await;
+            let report = if repair {
+                state.storage.repair_integrity()?
+            } else {
+                state.storage.check_integrity()?
+            };
+            if repair && !report.is_clean() {
+                info!("Repaired {} integrity issues", report.issue_count());
+            }
+            Ok(ControlResponse::integrity(report))
+        }
+        ControlRequest::Stats => {
+            let state = state.lock().await;
+            Ok(ControlResponse::stats(ServiceStats {
+                storage: state.storage.stats()?,
+                cache: state.cache.stats(),
+                throttle: state.throttle.stats(),
+                quality: state.quality.stats(),
+            }))
+        }
+        ControlRequest::Show { ehl_doc_id } => {
+            let state = state.lock().await;
+            match state.storage.find_source_by_doc_id(&ehl_doc_id)? {
+                Some(source) => {
+                    // `ehl_doc_id` may be an alias; the chunks carry the current ID
+                    let chunks = state.storage.get_source_chunks(&source.ehl_doc_id)?;
+                    Ok(ControlResponse::source(source, chunks))
+                }
+                None => Ok(ControlResponse::error(&format!("No source with id {}", ehl_doc_id))),
+            }
+        }
+        ControlRequest::Search {
+            query,
+            source_types,
+            metadata,
+            limit,
+        } => {
+            let state = state.lock().await;
+            Ok(ControlResponse::hits(state.storage.search(&query, &source_types, &metadata, limit)?))
+        }
+        ControlRequest::Remove { ehl_doc_ids } => {
+            let mut state = state.lock().await;
+            let mut removed = Vec::new();
+            let mut not_found = Vec::new();
+            for ehl_doc_id in ehl_doc_ids {
+                match state.storage.delete_source(&ehl_doc_id)? {
+                    Some(source) => {
+                        info!("Removed {} ({})", source.ehl_doc_id, source.source_path);
+                        // Otherwise the next capture would be matched against the deleted source
+                        state.cache.remove(&source.source_path);
+                        state.throttle.discard(&source.source_path);
+                        removed.push(source);
+                    }
+                    None => not_found.push(ehl_doc_id),
+                }
+            }
+            Ok(ControlResponse::removed(removed, not_found))
+        }
+        ControlRequest::Export {
+            source_types,
+            since,
+            until,
+            metadata,
+            after,
+            limit,
+        } => {
+            // A page per request, so a large corpus is neither built up in
+            // memory nor exported under one long hold of the lock
+            let state = state.lock().await;
+            let filter = control::source_filter(source_types, since, until, metadata);
+            let limit = limit.max(1);
+            let mut sources = state.storage.list_sources_page(&filter, after, limit + 1)?;
+            let next = if sources.len() > limit {
+                sources.truncate(limit);
+                sources.last().map(|source| source.id)
+            } else {
+                None
+            };
+            let records = sources
+                .iter()
+                .map(|source| export::export_record(&state.storage, source))
+                .collect::<Result<_, _>>()?;
+            Ok(ControlResponse::records(records, next))
+        }
+        ControlRequest::Import { records } => {
+            let mut state = state.lock().await;
+            // Replayed records are neither filtered nor throttled
+            let mut summary = ImportSummary::default();
+            for record in records {
+                summary.record(&ingest(&mut state, record.to_payload(), Origin::Import));
+            }
+            Ok(ControlResponse::import(summary))
+        }
+        ControlRequest::Gc {
+            include_indexed,
+            vacuum,
+        } => {
+            let summary = state.lock().await.storage.gc(GcOptions {
+                include_indexed,
+                vacuum,
+            })?;
+            info!(
+                "GC purged {} deleted chunks ({} kept for the vector index)",
+                summary.purged_chunks, summary.kept_indexed
+            );
+            Ok(ControlResponse::gc(summary))
+        }
+        ControlRequest::Forget {
+            kind,
+            value,
+            expires_at,
+        } => {
+            let mut state = state.lock().await;
+            let result = state.storage.forget(kind, &value, expires_at)?;
+            info!(
+                "Forgot {:?} {} ({} sources erased)",
+                result.tombstone.kind,
+                result.tombstone.value,
+                result.removed.len()
+            );
+            for source in &result.removed {
+                state.cache.remove(&source.source_path);
+                state.throttle.discard(&source.source_path);
+            }
+            Ok(ControlResponse {
+                tombstones: Some(vec![result.tombstone]),
+                ..ControlResponse::sources(result.removed)
+            })
+        }
+        ControlRequest::Tombstones => Ok(ControlResponse::tombstones(state.lock().await.storage.list_tombstones()?)),
+        ControlRequest::Unforget { id } => match state.lock().await.storage.remove_tombstone(id)? {
+            Some(tombstone) => Ok(ControlResponse::tombstones(vec![tombstone])),
+            None => Ok(ControlResponse::error(&format!("No tombstone with id {}", id))),
+        },
+    }
+}
+
+/// Take a snapshot on the blocking thread pool
+async fn run_snapshot(
+    snapshotter: &Arc<Snapshotter>,
+    compress: Option<bool>,
+) -> Result<SnapshotInfo, BackupError> {
+    let snapshotter = Arc::clone(snapshotter);
+    tokio::task::spawn_blocking(move || snapshotter.snapshot(compress))
+        .await
+        .map_err(|e| BackupError::Io(std::io::Error::other(e)))?
+}
+
+/// Take a snapshot every `interval` until the server stops
+async fn schedule_snapshots(snapshotter: Arc<Snapshotter>, interval: std::time::Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // The first tick completes immediately; don't snapshot at startup
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        match run_snapshot(&snapshotter, None).await {
+            Ok(info) => info!(
+                "Scheduled snapshot {:?} ({} bytes, {} rotated)",
+                info.path,
+                info.size_bytes,
+                info.rotated.len()
+            ),
+            Err(e) => error!("Scheduled snapshot failed: {}", e),
+        }
+    }
+}
+
+/// Store deferred payloads every `FLUSH_INTERVAL` until the server stops
+async fn flush_deferred_loop(state: Arc<Mutex<ServiceState>>) {
+    let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        let mut state = state.lock().await;
+        flush_deferred(&mut state, Instant::now());
+    }
+}
+
+/// Store the deferred payloads that are due; they were already filtered and admitted
+fn flush_deferred(state: &mut ServiceState, now: Instant) -> usize {
+    let due = state.throttle.take_due(now);
+    let count = due.len();
+    for payload in due {
+        info!("Storing deferred update: {} - {}", payload.source, payload.url);
+        ingest(state, payload, Origin::Deferred);
+    }
+    count
+}
+
+/// Where a payload handed to `ingest` comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    /// A capture: quality filtered, rate limited and held to the significance threshold
+    Live,
+    /// A throttled capture stored once its source is due; it was already
+    /// filtered and admitted, but its change is still held to the threshold
+    Deferred,
+    /// A replayed export record; its revisions were already judged worth storing
+    Import,
+}
+
+impl Origin {
+    /// Whether a change is stored only if it is significant
+    fn checks_significance(self) -> bool {
+        self != Origin::Import
+    }
+}
+
+/// Process a payload and announce the result to `tail` clients
+fn ingest(state: &mut ServiceState, payload: CapturePayload, origin: Origin) -> IngestionResponse {
+    if state.events.receiver_count() == 0 {
+        return process_payload(state, payload, origin);
+    }
+
+    let (source, url) = (payload.source.clone(), payload.url.clone());
+    let response = process_payload(state, payload, origin);
+    let _ = state.events.send(IngestEvent {
+        received_at: chrono::Utc::now().timestamp_millis(),
+        source,
+        url,
+        response: response.clone(),
+    });
+    response
+}
+
+/// Process a single payload; live captures are quality filtered and rate limited first
+fn process_payload(state: &mut ServiceState, mut payload: CapturePayload, origin: Origin) -> IngestionResponse {
+    if let Err(e) = metadata::validate(&payload.metadata) {
+        warn!("Rejected metadata from {}: {}", payload.url, e);
+        return IngestionResponse::error(&format!("Invalid metadata: {}", e));
+    }
+
     // Normalize the URL to create a canonical source path
     // This handles cases like Google Docs where URLs have varying query params
-    let source_path = normalize_source_path(&payload.source, &payload.url);
+    let source_path = state.rules.canonicalize(&payload.source, &payload.url);
+
+    // Never store anything the user asked to forget; if we can't tell, don't store
+    match state.storage.find_tombstone(&source_path, &payload.url) {
+        Ok(Some(tombstone)) => {
+            info!("Forgotten source, not storing: {}", source_path);
+            return IngestionResponse::skipped(
+                SkipReason::Forgotten,
+                &format!("Forgotten by {:?} tombstone {}", tombstone.kind, tombstone.value),
+            );
+        }
+        Ok(None) => {}
+        Err(e) => {
+            error!("Tombstone lookup error: {}", e);
+            return IngestionResponse::error(&format!("Storage error: {}", e));
+        }
+    }
+
+    // The router redacts too, but not every client goes through it
+    for message in &mut payload.messages {
+        if let Cow::Owned(text) = state.redactor.redact(&message.text) {
+            message.text = text;
+        }
+    }
+
+    // Chat messages are deduplicated one by one and never throttled: a deferred
+    // payload would replace earlier ones, and with them messages scrolled out of view
+    if !payload.messages.is_empty() {
+        return process_message_payload(state, payload, &source_path);
+    }
+
+    // Markup is reduced to the page's main content before anything else sees it
+    if payload.content_type == ContentType::Html {
+        let extracted = html::extract(&payload.content, &payload.url);
+        payload.content = extracted.markdown;
+        payload.content_type = ContentType::Markdown;
+        if payload.title.is_none() {
+            payload.title = extracted.title;
+        }
+    }
+
+    if let Cow::Owned(content) = state.redactor.redact(&payload.content) {
+        payload.content = content;
+    }
+    if state.dedup.store_normalized {
+        payload.content = normalize(&payload.content);
+    }
+
+    // Strip boilerplate before anything is hashed, chunked or stored
+    if origin == Origin::Live {
+        if let Some(report) = state.quality.assess(&payload.source, &source_path, &payload.content) {
+            if !report.passed() {
+                info!("Low information content, not storing: {} (score {:.2})", source_path, report.score);
+                return IngestionResponse::skipped(
+                    SkipReason::LowInformation,
+                    &format!("Low information content (score {:.2} < {:.2})", report.score, report.min_score),
+                );
+            }
+            payload.content = report.text;
+        }
+    }
+    let content_hash = source_hash(&payload, &state.masks);
+    let is_ocr = payload.source == "ocr-capture" || payload.url.starts_with("ocr://");
+
+    // An OCR snapshot is merged into the stored document, and the cache holds the
+    // merged hash; whether a snapshot adds anything is only known after merging
+    if is_ocr {
+        match ocr_snapshot_is_unchanged(state, &source_path, &payload.content) {
+            Ok(false) => {}
+            Ok(true) => {
+                info!("OCR duplicate (nothing new): {}", source_path);
+                return IngestionResponse::skipped(SkipReason::Unchanged, "Content unchanged");
+            }
+            Err(e) => {
+                error!("Storage query error: {}", e);
+                return IngestionResponse::error(&format!("Storage error: {}", e));
+            }
+        }
+    }
+
+    // Only writes are rate limited. Content matching the cached revision is cheap to skip,
+    // and a deferred payload for the same source is then out of date.
+    let payload = if let DedupResult::Duplicate(_) = state.cache.check(&source_path, &content_hash) {
+        state.throttle.discard(&source_path);
+        payload
+    } else if origin == Origin::Live {
+        match state.throttle.admit(&source_path, payload, Instant::now()) {
+            Admission::Process(payload) => *payload,
+            Admission::Deferred { retry_in, .. } => {
+                info!("Throttled, deferring update: {}", source_path);
+                return IngestionResponse::skipped(
+                    SkipReason::Throttled,
+                    &format!("Updated too often; latest content will be stored in {}s", retry_in.as_secs().max(1)),
+                );
+            }
+            Admission::Dropped => {
+                warn!("Throttled, too many deferred updates; dropping: {}", source_path);
+                return IngestionResponse::skipped(SkipReason::Throttled, "Updated too often; payload dropped");
+            }
+        }
+    } else {
+        payload
+    };
 
     // For OCR sources, use metadata-based deduplication with content appending
-    if payload.source == "ocr-capture" || payload.url.starts_with("ocr://") {
-        return process_ocr_payload(state, payload, &source_path, &content_hash);
+    if is_ocr {
+        return process_ocr_payload(state, payload, &source_path, &content_hash, origin);
     }
 
     // Check in-memory cache first
-    let cache_result = state.cache.check(&source_path, &content_hash);
+    if let DedupResult::Duplicate(_ehl_doc_id) = state.cache.check(&source_path, &content_hash) {
+        info!("Duplicate content (cache hit): {}", source_path);
+        return IngestionResponse::skipped(SkipReason::Unchanged, "Content unchanged (cache)");
+    }
 
-    match cache_result {
-        DedupResult::Duplicate(_ehl_doc_id) => {
-            info!("Duplicate content (cache hit): {}", source_path);
-            IngestionResponse::skipped("Content unchanged (cache)")
-        }
+    // Check database: a cache miss doesn't mean it's truly new, and a change
+    // is compared with the stored text
+    match state.storage.find_source_by_path(&source_path) {
+        Ok(Some(existing)) => {
+            if existing.content_hash == content_hash {
+                // Same content, just wasn't in cache
+                info!("Duplicate content (db hit): {}", source_path);
+                state.cache.insert(
+                    source_path,
+                    content_hash,
+                    existing.ehl_doc_id.clone(),
+                );
+                return IngestionResponse::skipped(SkipReason::Unchanged, "Content unchanged (db)");
+            }
 
-        DedupResult::Changed(ehl_doc_id) => {
-            info!("Content changed, updating: {}", source_path);
+            update_existing(state, &existing, source_path, &payload, content_hash, origin)
+        }
 
-            // Chunk the new content
+        Ok(None) => {
+            // Truly new content
+            info!("New content: {}", source_path);
+            let ehl_doc_id = doc_id::doc_id(&payload.source, &source_path);
             let chunks = state.chunker.chunk(&payload.content);
 
-            // Update in storage
             match state
                 .storage
-                .update_source(&ehl_doc_id, &payload, &content_hash, &chunks)
+                .insert_source(&payload, &source_path, &content_hash, &ehl_doc_id, &chunks)
             {
-                Ok(()) => {
-                    // Update cache
+                Ok(_) => {
                     state.cache.insert(
                         source_path,
                         content_hash,
                         ehl_doc_id.clone(),
                     );
-                    IngestionResponse::updated(ehl_doc_id, chunks.len())
+                    IngestionResponse::created(ehl_doc_id, chunks.len())
                 }
                 Err(e) => {
-                    error!("Storage update error: {}", e);
+                    error!("Storage insert error: {}", e);
                     IngestionResponse::error(&format!("Storage error: {}", e))
                 }
             }
         }
 
-        DedupResult::New => {
-            // Check database (cache miss doesn't mean it's truly new)
-            match state.storage.find_source_by_path(&source_path) {
-                Ok(Some(existing)) => {
-                    if existing.content_hash == content_hash {
-                        // Same content, just wasn't in cache
-                        info!("Duplicate content (db hit): {}", source_path);
-                        state.cache.insert(
-                            source_path,
-                            content_hash,
-                            existing.ehl_doc_id.clone(),
-                        );
-                        return IngestionResponse::skipped("Content unchanged (db)");
-                    }
+        Err(e) => {
+            error!("Storage query error: {}", e);
+            IngestionResponse::error(&format!("Storage error: {}", e))
+        }
+    }
+}
 
-                    // Content changed
-                    info!("Content changed (db), updating: {}", source_path);
-                    let chunks = state.chunker.chunk(&payload.content);
+/// Skip a change below the source's significance threshold
+fn insignificant(source_path: &str, text_diff: TextDiff) -> IngestionResponse {
+    info!(
+        "Insignificant change, not storing: {} ({:.2}% changed)",
+        source_path,
+        text_diff.change_ratio * 100.0
+    );
+    IngestionResponse::skipped(
+        SkipReason::Insignificant,
+        &format!("Changed {:.2}% of the text, below the threshold", text_diff.change_ratio * 100.0),
+    )
+    .with_text_diff(text_diff)
+}
 
-                    match state.storage.update_source(
-                        &existing.ehl_doc_id,
-                        &payload,
-                        &content_hash,
-                        &chunks,
-                    ) {
-                        Ok(()) => {
-                            state.cache.insert(
-                                source_path,
-                                content_hash,
-                                existing.ehl_doc_id.clone(),
-                            );
-                            IngestionResponse::updated(existing.ehl_doc_id, chunks.len())
-                        }
-                        Err(e) => {
-                            error!("Storage update error: {}", e);
-                            IngestionResponse::error(&format!("Storage error: {}", e))
-                        }
-                    }
-                }
+/// Update a source whose content changed, unless a captured change is below
+/// the source's significance threshold
+fn update_existing(
+    state: &mut ServiceState,
+    existing: &ContentSource,
+    source_path: String,
+    payload: &CapturePayload,
+    content_hash: String,
+    origin: Origin,
+) -> IngestionResponse {
+    let text_diff = match state.storage.get_source_content(&existing.ehl_doc_id) {
+        Ok(stored) => masked_text_diff(&state.masks, &stored, payload),
+        Err(e) => {
+            error!("Failed to get existing content: {}", e);
+            return IngestionResponse::error(&format!("Storage error: {}", e));
+        }
+    };
+    let age_secs = payload.captured_at() - existing.captured_at;
+    if origin.checks_significance() && !state.significance.is_significant(&payload.source, &text_diff, age_secs) {
+        return insignificant(&source_path, text_diff);
+    }
 
-                Ok(None) => {
-                    // Truly new content
-                    info!("New content: {}", source_path);
-                    let ehl_doc_id = uuid::Uuid::new_v4().to_string();
-                    let chunks = state.chunker.chunk(&payload.content);
+    info!(
+        "Content changed, updating: {} (+{} -{} chars)",
+        source_path, text_diff.inserted_chars, text_diff.deleted_chars
+    );
+    let chunks = state.chunker.chunk(&payload.content);
 
-                    // Create a modified payload with the normalized URL for storage
-                    let mut storage_payload = payload.clone();
-                    storage_payload.url = source_path.clone();
+    match state
+        .storage
+        .update_source(&existing.ehl_doc_id, &source_path, payload, &content_hash, &chunks)
+    {
+        Ok(diff) => {
+            state.cache.insert(
+                source_path,
+                content_hash,
+                existing.ehl_doc_id.clone(),
+            );
+            IngestionResponse::updated(existing.ehl_doc_id.clone(), chunks.len(), diff).with_text_diff(text_diff)
+        }
+        Err(e) => {
+            error!("Storage update error: {}", e);
+            IngestionResponse::error(&format!("Storage error: {}", e))
+        }
+    }
+}
 
-                    match state
-                        .storage
-                        .insert_source(&storage_payload, &content_hash, &ehl_doc_id, &chunks)
-                    {
-                        Ok(_) => {
-                            state.cache.insert(
-                                source_path,
-                                content_hash,
-                                ehl_doc_id.clone(),
-                            );
-                            IngestionResponse::created(ehl_doc_id, chunks.len())
-                        }
-                        Err(e) => {
-                            error!("Storage insert error: {}", e);
-                            IngestionResponse::error(&format!("Storage error: {}", e))
-                        }
-                    }
-                }
+/// How a payload's text differs from the stored text, both masked and
+/// normalized as for hashing
+fn masked_text_diff(masks: &Masks, stored: &str, payload: &CapturePayload) -> TextDiff {
+    text_diff(
+        &normalize(&masks.mask_like(payload, stored)),
+        &normalize(&masks.mask_payload(payload)),
+    )
+}
 
-                Err(e) => {
-                    error!("Storage query error: {}", e);
-                    IngestionResponse::error(&format!("Storage error: {}", e))
-                }
-            }
+/// Store the messages of a chat payload, skipping those already stored, and
+/// rebuild the source from every message seen so far
+fn process_message_payload(state: &mut ServiceState, mut payload: CapturePayload, source_path: &str) -> IngestionResponse {
+    let existing = match state.storage.find_source_by_path(source_path) {
+        Ok(existing) => existing,
+        Err(e) => {
+            error!("Storage query error: {}", e);
+            return IngestionResponse::error(&format!("Storage error: {}", e));
         }
+    };
+    let ehl_doc_id = match &existing {
+        Some(source) => source.ehl_doc_id.clone(),
+        None => doc_id::doc_id(&payload.source, source_path),
+    };
+
+    let messages = conversation::to_stored(&payload.messages, payload.captured_at());
+    let stored = state
+        .storage
+        .add_messages(&ehl_doc_id, &messages)
+        .and_then(|changed| Ok((changed, state.storage.get_messages(&ehl_doc_id)?)));
+    let (changed, all_messages) = match stored {
+        Ok(stored) => stored,
+        Err(e) => {
+            error!("Storage message error: {}", e);
+            return IngestionResponse::error(&format!("Storage error: {}", e));
+        }
+    };
+
+    if all_messages.is_empty() {
+        return IngestionResponse::skipped(SkipReason::LowInformation, "No message text");
+    }
+
+    let transcript = conversation::render(&all_messages);
+    payload.content = transcript.text;
+    let content_hash = source_hash(&payload, &state.masks);
+    if existing.as_ref().is_some_and(|source| source.content_hash == content_hash) {
+        info!("No new messages: {}", source_path);
+        state.cache.insert(source_path.to_string(), content_hash, ehl_doc_id);
+        return IngestionResponse::skipped(SkipReason::Unchanged, "No new messages");
     }
+
+    info!("{} new or edited messages: {} ({} in total)", changed, source_path, all_messages.len());
+    let chunks = state.chunker.chunk_sections(&payload.content, &transcript.sections);
+    let response = match existing {
+        Some(_) => state
+            .storage
+            .update_source(&ehl_doc_id, source_path, &payload, &content_hash, &chunks)
+            .map(|diff| IngestionResponse::updated(ehl_doc_id.clone(), chunks.len(), diff)),
+        None => state
+            .storage
+            .insert_source(&payload, source_path, &content_hash, &ehl_doc_id, &chunks)
+            .map(|_| IngestionResponse::created(ehl_doc_id.clone(), chunks.len())),
+    };
+    match response {
+        Ok(response) => {
+            state.cache.insert(source_path.to_string(), content_hash, ehl_doc_id);
+            response
+        }
+        Err(e) => {
+            error!("Storage write error: {}", e);
+            IngestionResponse::error(&format!("Storage error: {}", e))
+        }
+    }
+}
+
+/// Whether merging an OCR snapshot would leave the stored document as it is
+fn ocr_snapshot_is_unchanged(state: &ServiceState, source_path: &str, snapshot: &str) -> Result<bool, StorageError> {
+    let Some(existing) = state.storage.find_source_by_path(source_path)? else {
+        return Ok(false);
+    };
+    let stored = state.storage.get_source_content(&existing.ehl_doc_id)?;
+    Ok(merge_snapshot(&stored, snapshot).is_unchanged())
 }
 
-/// Process OCR payload with metadata-based deduplication and content appending
+/// Process OCR payload, merging each snapshot into the stored document
 fn process_ocr_payload(
     state: &mut ServiceState,
     payload: CapturePayload,
     source_path: &str,
     content_hash: &str,
+    origin: Origin,
 ) -> IngestionResponse {
     // First, check for exact path match
     match state.storage.find_source_by_path(source_path) {
@@ -290,45 +919,53 @@ fn process_ocr_payload(
                     content_hash.to_string(),
                     existing.ehl_doc_id.clone(),
                 );
-                return IngestionResponse::skipped("Content unchanged");
+                return IngestionResponse::skipped(SkipReason::Unchanged, "Content unchanged");
             }
 
-            // Content changed - get existing content and find new text to append
+            // Content changed - align the snapshot against the stored document
             match state.storage.get_source_content(&existing.ehl_doc_id) {
                 Ok(existing_content) => {
-                    let new_text = extract_new_content(&existing_content, &payload.content);
-                    
-                    if new_text.is_empty() || new_text.len() < 50 {
-                        info!("OCR no significant new content: {}", source_path);
-                        return IngestionResponse::skipped("No significant new content");
+                    let merged = merge_snapshot(&existing_content, &payload.content);
+
+                    if merged.is_unchanged() {
+                        info!("OCR no new content: {}", source_path);
+                        return IngestionResponse::skipped(SkipReason::Unchanged, "Content unchanged");
                     }
 
-                    info!("OCR appending {} chars to: {}", new_text.len(), source_path);
-                    
-                    // Chunk only the new content
-                    let new_chunks = state.chunker.chunk(&new_text);
-                    
-                    // Compute hash of combined content
-                    let combined_content = format!("{}\n\n{}", existing_content, new_text);
-                    let combined_hash = compute_hash(&combined_content);
+                    info!(
+                        "OCR merging into {}: {} lines inserted, {} replaced",
+                        source_path, merged.inserted_lines, merged.replaced_lines
+                    );
 
-                    match state.storage.append_to_source(
+                    // Rechunk the merged document so chunks stay in document order;
+                    // it is also what gets stored as the new revision
+                    let payload = CapturePayload { content: merged.text, ..payload };
+                    let text_diff = masked_text_diff(&state.masks, &existing_content, &payload);
+                    let age_secs = payload.captured_at() - existing.captured_at;
+                    if origin.checks_significance() && !state.significance.is_significant(&payload.source, &text_diff, age_secs) {
+                        return insignificant(source_path, text_diff);
+                    }
+                    let chunks = state.chunker.chunk(&payload.content);
+                    let merged_hash = source_hash(&payload, &state.masks);
+
+                    match state.storage.update_source(
                         &existing.ehl_doc_id,
+                        source_path,
                         &payload,
-                        &new_text,
-                        &combined_hash,
-                        &new_chunks,
+                        &merged_hash,
+                        &chunks,
                     ) {
-                        Ok(()) => {
+                        Ok(diff) => {
                             state.cache.insert(
                                 source_path.to_string(),
-                                combined_hash,
+                                merged_hash,
                                 existing.ehl_doc_id.clone(),
                             );
-                            IngestionResponse::updated(existing.ehl_doc_id, new_chunks.len())
+                            IngestionResponse::updated(existing.ehl_doc_id, chunks.len(), diff)
+                                .with_text_diff(text_diff)
                         }
                         Err(e) => {
-                            error!("Storage append error: {}", e);
+                            error!("Storage update error: {}", e);
                             IngestionResponse::error(&format!("Storage error: {}", e))
                         }
                     }
@@ -342,13 +979,10 @@ fn process_ocr_payload(
         Ok(None) => {
             // No exact match - create new entry
             info!("New OCR content: {}", source_path);
-            let ehl_doc_id = uuid::Uuid::new_v4().to_string();
+            let ehl_doc_id = doc_id::doc_id(&payload.source, source_path);
             let chunks = state.chunker.chunk(&payload.content);
 
-            let mut storage_payload = payload.clone();
-            storage_payload.url = source_path.to_string();
-
-            match state.storage.insert_source(&storage_payload, content_hash, &ehl_doc_id, &chunks) {
+            match state.storage.insert_source(&payload, source_path, content_hash, &ehl_doc_id, &chunks) {
                 Ok(_) => {
                     state.cache.insert(
                         source_path.to_string(),
@@ -370,198 +1004,487 @@ fn process_ocr_payload(
     }
 }
 
-/// Extract new content that doesn't exist in the existing content
-/// Uses word-level comparison to find genuinely new text
-fn extract_new_content(existing: &str, incoming: &str) -> String {
-    // Split into sentences/lines for comparison
-    let existing_lines: std::collections::HashSet<&str> = existing
-        .lines()
-        .map(|l| l.trim())
-        .filter(|l| l.len() > 10)
-        .collect();
-    
-    let existing_words: std::collections::HashSet<&str> = existing
-        .split_whitespace()
-        .collect();
-    
-    // Find lines in incoming that are genuinely new
-    let mut new_lines: Vec<&str> = Vec::new();
-    
-    for line in incoming.lines() {
-        let trimmed = line.trim();
-        if trimmed.len() < 10 {
-            continue;
-        }
-        
-        // Check if this exact line exists
-        if existing_lines.contains(trimmed) {
-            continue;
-        }
-        
-        // Check word overlap - if >80% of words already exist, skip
-        let line_words: Vec<&str> = trimmed.split_whitespace().collect();
-        if line_words.is_empty() {
-            continue;
-        }
-        
-        let overlap_count = line_words.iter()
-            .filter(|w| existing_words.contains(*w))
-            .count();
-        
-        let overlap_ratio = overlap_count as f64 / line_words.len() as f64;
-        
-        // Only include if less than 80% overlap
-        if overlap_ratio < 0.8 {
-            new_lines.push(trimmed);
-        }
-    }
-    
-    new_lines.join("\n")
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::DEFAULT_EXPORT_LIMIT;
+    use crate::payload::{ChunkDiff, IngestionAction, ResponseStatus};
+    use capture_protocol::ChatMessage;
 
-/// Normalize source path to create a canonical identifier
-/// This handles URLs with varying query parameters (e.g., Google Docs, Jira)
-fn normalize_source_path(source: &str, url: &str) -> String {
-    match source {
-        "gdocs" => {
-            // Extract document ID and create canonical URL
-            // Pattern: /document/d/DOC_ID/...
-            if let Some(caps) = regex::Regex::new(r"/document/d/([a-zA-Z0-9_-]+)")
-                .ok()
-                .and_then(|re| re.captures(url))
-            {
-                if let Some(doc_id) = caps.get(1) {
-                    return format!("gdocs://{}", doc_id.as_str());
-                }
-            }
-            url.to_string()
-        }
-        "gsheets" => {
-            // Extract spreadsheet ID and create canonical URL
-            // Pattern: /spreadsheets/d/SPREADSHEET_ID/...
-            if let Some(caps) = regex::Regex::new(r"/spreadsheets/d/([a-zA-Z0-9_-]+)")
-                .ok()
-                .and_then(|re| re.captures(url))
-            {
-                if let Some(sheet_id) = caps.get(1) {
-                    return format!("gsheets://{}", sheet_id.as_str());
-                }
-            }
-            url.to_string()
-        }
-        "gslides" => {
-            // Extract presentation ID and create canonical URL
-            // Pattern: /presentation/d/PRESENTATION_ID/...
-            if let Some(caps) = regex::Regex::new(r"/presentation/d/([a-zA-Z0-9_-]+)")
-                .ok()
-                .and_then(|re| re.captures(url))
-            {
-                if let Some(pres_id) = caps.get(1) {
-                    return format!("gslides://{}", pres_id.as_str());
-                }
-            }
-            url.to_string()
-        }
-        "gemini" => {
-            // Gemini conversations use canonical URLs like gemini://conversation/ID
-            // The content script already provides canonical URLs, so just use them
-            // But also handle raw URLs if they come through
-            if url.starts_with("gemini://") {
-                return url.to_string();
-            }
-            // Extract conversation ID from raw URL
-            if let Some(caps) = regex::Regex::new(r"/(?:app|c)/([a-zA-Z0-9_-]+)")
-                .ok()
-                .and_then(|re| re.captures(url))
-            {
-                if let Some(conv_id) = caps.get(1) {
-                    return format!("gemini://conversation/{}", conv_id.as_str());
-                }
-            }
-            url.to_string()
+    fn make_server(dir: &tempfile::TempDir) -> IngestionServer {
+        make_server_with(dir, ServerConfig::default())
+    }
+
+    /// A server with `config`, storing in `dir`
+    fn make_server_with(dir: &tempfile::TempDir, config: ServerConfig) -> IngestionServer {
+        IngestionServer::new(ServerConfig {
+            socket_path: dir.path().join("ingestion.sock"),
+            db_path: dir.path().join("content.db"),
+            ..config
+        })
+        .unwrap()
+    }
+
+    fn payload(content: &str) -> CapturePayload {
+        CapturePayload::new("chrome", "https://example.com/ticker", content)
+    }
+
+    #[tokio::test]
+    async fn test_throttled_update_is_stored_later() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server(&dir);
+
+        let created = server.process(payload("Share price closed at 100 today")).await;
+        let doc_id = created.ehl_doc_id.unwrap();
+        server.process(payload("Share price closed at 101 today")).await;
+        let throttled = server.process(payload("Share price closed at 102 today")).await;
+        assert_eq!(throttled.reason, Some(SkipReason::Throttled));
+        assert_eq!(server.flush_deferred().await, 0);
+
+        let mut state = server.state.lock().await;
+        assert_eq!(state.storage.get_source_content(&doc_id).unwrap(), "Share price closed at 100 today");
+        assert_eq!(flush_deferred(&mut state, Instant::now() + Duration::from_secs(11)), 1);
+        assert_eq!(state.storage.get_source_content(&doc_id).unwrap(), "Share price closed at 102 today");
+        assert_eq!(state.throttle.stats().pending, 0);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_content_cancels_deferred_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server(&dir);
+
+        server.process(payload("Share price closed at 100 today")).await;
+        server.process(payload("Share price closed at 101 today")).await;
+        let unchanged = server.process(payload("Share price closed at 100 today")).await;
+        assert_eq!(unchanged.reason, Some(SkipReason::Unchanged));
+
+        let mut state = server.state.lock().await;
+        assert_eq!(flush_deferred(&mut state, Instant::now() + Duration::from_secs(11)), 0);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_ocr_snapshot_uses_no_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let burst = 3;
+        let server = make_server_with(
+            &dir,
+            ServerConfig {
+                throttle: crate::throttle::ThrottleConfig {
+                    min_update_interval_secs: 0,
+                    per_path: crate::throttle::BucketConfig { burst: burst as f64, per_minute: 1.0 },
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let snapshot = |content: &str| CapturePayload::new("ocr-capture", "ocr://Preview/report.pdf", content);
+
+        let created = server.process(snapshot("Quarterly report\nRevenue grew by twelve percent\nCosts stayed flat")).await;
+        assert_eq!(created.action, IngestionAction::Created);
+        // Scrolled down: merged into the stored document, whose hash differs from the snapshot's
+        let scrolled = "Costs stayed flat\nHeadcount rose to forty engineers across three offices";
+        assert_eq!(server.process(snapshot(scrolled)).await.action, IngestionAction::Updated);
+
+        for _ in 0..=burst {
+            let repeat = server.process(snapshot(scrolled)).await;
+            assert_eq!(repeat.reason, Some(SkipReason::Unchanged));
         }
-        "google-ai" | "google-search" => {
-            // Google AI Mode and Search use canonical URLs based on query
-            // The content script already provides canonical URLs like google-ai://search/query
-            if url.starts_with("google-ai://") || url.starts_with("google://") {
-                return url.to_string();
-            }
-            // Extract query from raw URL and normalize
-            if let Ok(parsed) = url::Url::parse(url) {
-                if let Some(query) = parsed.query_pairs()
-                    .find(|(k, _)| k == "q")
-                    .map(|(_, v)| v.to_string())
-                {
-                    let normalized_query = query.to_lowercase().trim().to_string();
-                    let prefix = if source == "google-ai" { "google-ai" } else { "google" };
-                    return format!("{}://search/{}", prefix, urlencoding::encode(&normalized_query));
-                }
-            }
-            url.to_string()
-        }
-        "jira" => {
-            // Extract issue key or board ID for canonical path
-            // Pattern: selectedIssue=PROJ-123 or /browse/PROJ-123
-            if let Ok(parsed) = url::Url::parse(url) {
-                // Check for selectedIssue query param
-                if let Some(issue) = parsed.query_pairs()
-                    .find(|(k, _)| k == "selectedIssue")
-                    .map(|(_, v)| v.to_string())
-                {
-                    let host = parsed.host_str().unwrap_or("jira");
-                    return format!("jira://{}:{}", host, issue);
-                }
-                
-                // Check for /browse/PROJ-123 pattern
-                if let Some(caps) = regex::Regex::new(r"/browse/([A-Z][A-Z0-9]+-\d+)")
-                    .ok()
-                    .and_then(|re| re.captures(parsed.path()))
-                {
-                    if let Some(issue) = caps.get(1) {
-                        let host = parsed.host_str().unwrap_or("jira");
-                        return format!("jira://{}:{}", host, issue.as_str());
-                    }
-                }
-            }
-            url.to_string()
-        }
-        "slack" => {
-            // Normalize Slack URLs to channel + thread
-            if let Ok(parsed) = url::Url::parse(url) {
-                let path = parsed.path();
-                // Pattern: /archives/CHANNEL_ID/pTIMESTAMP
-                if path.contains("/archives/") {
-                    let host = parsed.host_str().unwrap_or("slack");
-                    return format!("slack://{}:{}", host, path);
-                }
-            }
-            url.to_string()
+        assert_eq!(server.state.lock().await.throttle.stats().deferred, 0);
+
+        // The last token is still there; had the repeats taken it, this would be deferred
+        let more = "Headcount rose to forty engineers across three offices\nA fourth office opens in Lisbon next spring";
+        assert_eq!(server.process(snapshot(more)).await.action, IngestionAction::Updated);
+    }
+
+    #[tokio::test]
+    async fn test_ocr_merge_is_held_to_the_significance_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut significance = SignificanceConfig::default();
+        significance.sources.insert(
+            "ocr-capture".to_string(),
+            crate::significance::SourceSignificance { min_change_ratio: Some(0.5), ..Default::default() },
+        );
+        let server = make_server_with(
+            &dir,
+            ServerConfig {
+                significance,
+                throttle: crate::throttle::ThrottleConfig {
+                    enabled: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let snapshot = |content: &str| CapturePayload::new("ocr-capture", "ocr://Preview/report.pdf", content);
+
+        server.process(snapshot("Quarterly report\nRevenue grew by twelve percent\nCosts stayed flat")).await;
+        // One new line, well under half the document
+        let scrolled = server.process(snapshot("Costs stayed flat\nHeadcount rose to forty engineers")).await;
+        assert_eq!(scrolled.reason, Some(SkipReason::Insignificant));
+        assert_eq!(scrolled.text_diff.unwrap().inserted_lines, 1);
+    }
+
+    #[tokio::test]
+    async fn test_small_ocr_edit_is_stored_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server_with(
+            &dir,
+            ServerConfig {
+                throttle: crate::throttle::ThrottleConfig {
+                    enabled: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let snapshot = |content: &str| CapturePayload::new("ocr-capture", "ocr://Preview/report.pdf", content);
+
+        server.process(snapshot("Quarterly report\nRevenue grew by 12 percent\nCosts stayed flat")).await;
+        let corrected = server.process(snapshot("Quarterly report\nRevenue grew by 13 percent\nCosts stayed flat")).await;
+        assert_eq!(corrected.action, IngestionAction::Updated);
+        assert_eq!(corrected.text_diff.unwrap().inserted_chars, 1);
+    }
+
+    #[tokio::test]
+    async fn test_import_request_is_not_throttled() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server_with(
+            &dir,
+            ServerConfig {
+                throttle: crate::throttle::ThrottleConfig {
+                    min_update_interval_secs: 0,
+                    per_path: crate::throttle::BucketConfig { burst: 1.0, per_minute: 1.0 },
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        // Takes the only token for the path
+        server.process(payload("Share price closed at 100 today")).await;
+
+        let export = ControlRequest::Export {
+            source_types: Vec::new(),
+            since: None,
+            until: None,
+            metadata: Default::default(),
+            after: None,
+            limit: DEFAULT_EXPORT_LIMIT,
+        };
+        let mut records = server.control(export).await.records.unwrap();
+        records[0].content = "Share price closed at 120 today after strong earnings".to_string();
+        let response = server.control(ControlRequest::Import { records }).await;
+        assert_eq!(response.import.unwrap().updated, 1);
+        assert_eq!(server.state.lock().await.throttle.stats().deferred, 0);
+    }
+
+    #[tokio::test]
+    async fn test_doc_ids_are_derived_from_the_source() {
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let dir = tempfile::tempdir().unwrap();
+            let server = make_server(&dir);
+            ids.push(server.process(payload("Share price closed at 100 today")).await.ehl_doc_id.unwrap());
         }
-        "teams" => {
-            // Normalize Teams URLs - accessibility:// URLs are already canonical
-            if url.starts_with("accessibility://") {
-                return url.to_string();
-            }
-            // For web URLs, extract conversation/channel ID
-            if let Ok(parsed) = url::Url::parse(url) {
-                let path = parsed.path();
-                // Pattern: /conversations/CONVERSATION_ID or /channel/CHANNEL_ID
-                if path.contains("/conversations/") || path.contains("/channel/") {
-                    let host = parsed.host_str().unwrap_or("teams");
-                    return format!("teams://{}:{}", host, path);
-                }
+        assert_eq!(ids[0], ids[1]);
+        assert_eq!(ids[0], doc_id::doc_id("chrome", "https://example.com/ticker"));
+    }
+
+    #[tokio::test]
+    async fn test_show_and_remove_accept_an_alias_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server(&dir);
+        let ehl_doc_id = server
+            .process(payload("Share price closed at 100 today"))
+            .await
+            .ehl_doc_id
+            .unwrap();
+        // As left behind by the version 7 migration of a random ID
+        rusqlite::Connection::open(dir.path().join("content.db"))
+            .unwrap()
+            .execute(
+                "INSERT INTO doc_id_aliases (old_id, ehl_doc_id) VALUES ('old-doc', ?1)",
+                [&ehl_doc_id],
+            )
+            .unwrap();
+
+        let shown = server.control(ControlRequest::Show { ehl_doc_id: "old-doc".to_string() }).await;
+        assert_eq!(shown.sources.unwrap()[0].ehl_doc_id, ehl_doc_id);
+        assert_eq!(shown.chunks.unwrap().len(), 1);
+
+        let removed = server
+            .control(ControlRequest::Remove {
+                ehl_doc_ids: vec!["old-doc".to_string(), "no-such-doc".to_string()],
+            })
+            .await;
+        assert_eq!(removed.sources.unwrap()[0].ehl_doc_id, ehl_doc_id);
+        assert_eq!(removed.not_found.unwrap(), vec!["no-such-doc".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_export_pages_through_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server(&dir);
+        for i in 0..5 {
+            let mut page = payload(&format!("Quarterly report number {} for the board", i));
+            page.url = format!("https://example.com/report/{}", i);
+            server.process(page).await;
+        }
+
+        let mut after = None;
+        let mut pages = Vec::new();
+        loop {
+            let response = server
+                .control(ControlRequest::Export {
+                    source_types: Vec::new(),
+                    since: None,
+                    until: None,
+                    metadata: Default::default(),
+                    after,
+                    limit: 2,
+                })
+                .await;
+            let records = response.records.unwrap();
+            pages.push(records.iter().map(|r| r.source_path.clone()).collect::<Vec<_>>());
+            match response.next {
+                Some(next) => after = Some(next),
+                None => break,
             }
-            url.to_string()
-        }
-        _ => {
-            // For other sources, strip query params and fragments
-            if let Ok(mut parsed) = url::Url::parse(url) {
-                parsed.set_query(None);
-                parsed.set_fragment(None);
-                parsed.to_string()
+        }
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 2, 1]);
+        let paths: Vec<String> = pages.concat();
+        let expected: Vec<String> = (0..5).map(|i| format!("https://example.com/report/{}", i)).collect();
+        assert_eq!(paths, expected);
+    }
+
+    #[tokio::test]
+    async fn test_payload_is_redacted_before_storing() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server(&dir);
+        let created = server.process(payload("Card 4532015112830366 was charged today")).await;
+
+        let state = server.state.lock().await;
+        let content = state.storage.get_source_content(&created.ehl_doc_id.unwrap()).unwrap();
+        assert_eq!(content, "Card [REDACTED_CARD] was charged today");
+    }
+
+    #[tokio::test]
+    async fn test_low_information_payload_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server(&dir);
+
+        let created = server.process(payload("Share price closed at 100 today")).await;
+        let loading = server.process(payload("Loading...")).await;
+        assert_eq!(loading.reason, Some(SkipReason::LowInformation));
+
+        // The stored revision is left alone
+        let state = server.state.lock().await;
+        let content = state.storage.get_source_content(&created.ehl_doc_id.unwrap()).unwrap();
+        assert_eq!(content, "Share price closed at 100 today");
+    }
+
+    #[tokio::test]
+    async fn test_invisible_changes_are_unchanged() {
+        for store_normalized in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let server = make_server_with(
+                &dir,
+                ServerConfig {
+                    dedup: DedupConfig { store_normalized },
+                    ..Default::default()
+                },
+            );
+
+            let created = server.process(payload("Share price\u{00A0}closed at 100 today\r\n")).await;
+            let again = server.process(payload("Share price closed\u{200B} at 100  today")).await;
+            assert_eq!(again.reason, Some(SkipReason::Unchanged));
+
+            let state = server.state.lock().await;
+            let content = state.storage.get_source_content(&created.ehl_doc_id.unwrap()).unwrap();
+            let expected = if store_normalized {
+                "Share price closed at 100 today"
             } else {
-                url.to_string()
-            }
+                "Share price\u{00A0}closed at 100 today\r\n"
+            };
+            assert_eq!(content, expected);
         }
     }
+
+    #[tokio::test]
+    async fn test_volatile_changes_are_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = ServerConfig::default();
+        config.masking.rules.push(capture_protocol::mask::MaskRule {
+            sources: vec!["chrome".to_string()],
+            apps: Vec::new(),
+            pattern: r"\d+ views".to_string(),
+            replacement: String::new(),
+        });
+        let server = make_server_with(&dir, config);
+
+        let created = server.process(payload("Share price closed at 100 today\nUpdated 3 minutes ago, 1200 views")).await;
+        let again = server.process(payload("Share price closed at 100 today\nUpdated 5 minutes ago, 1250 views")).await;
+        assert_eq!(again.reason, Some(SkipReason::Unchanged));
+
+        let state = server.state.lock().await;
+        let content = state.storage.get_source_content(&created.ehl_doc_id.unwrap()).unwrap();
+        assert_eq!(content, "Share price closed at 100 today\nUpdated 3 minutes ago, 1200 views");
+        assert!(state.storage.check_integrity().unwrap().is_clean());
+    }
+
+    #[tokio::test]
+    async fn test_html_payload_is_stored_as_markdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server(&dir);
+
+        let page = |body: &str| CapturePayload {
+            content_type: ContentType::Html,
+            ..payload(&format!(
+                "<html><head><title>Quarterly update</title></head><body><nav><a href=\"/\">Home</a></nav>\
+                 <article><h1>Quarterly update</h1><p>Revenue grew by twelve percent, driven by the new \
+                 <a href=\"/pricing\">pricing plans</a> and renewals.</p></article>{}</body></html>",
+                body
+            ))
+        };
+        let created = server.process(page("")).await;
+        assert_eq!(created.action, IngestionAction::Created);
+
+        // Markup outside the main content doesn't count as a change
+        let again = server.process(page("<footer>Rendered in 41 ms</footer>")).await;
+        assert_eq!(again.reason, Some(SkipReason::Unchanged));
+
+        let state = server.state.lock().await;
+        let content = state.storage.get_source_content(&created.ehl_doc_id.unwrap()).unwrap();
+        assert_eq!(
+            content,
+            "# Quarterly update\n\nRevenue grew by twelve percent, driven by the new \
+             [pricing plans](https://example.com/pricing) and renewals."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insignificant_change_is_skipped_until_deferred() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server_with(
+            &dir,
+            ServerConfig {
+                significance: SignificanceConfig {
+                    min_change_ratio: 0.1,
+                    max_defer_secs: 3600,
+                    ..Default::default()
+                },
+                throttle: crate::throttle::ThrottleConfig {
+                    enabled: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let page = |content: &str, timestamp: i64| CapturePayload {
+            timestamp: Some(timestamp),
+            ..payload(content)
+        };
+        let text = "Quarterly planning notes\nShip the search revamp in August\nHire two backend engineers";
+
+        server.process(page(text, 1_000)).await;
+        let typo = text.replace("August", "Augusts");
+        let skipped = server.process(page(&typo, 1_060)).await;
+        assert_eq!(skipped.reason, Some(SkipReason::Insignificant));
+        let diff = skipped.text_diff.unwrap();
+        assert_eq!((diff.inserted_chars, diff.deleted_chars, diff.inserted_lines, diff.deleted_lines), (1, 0, 1, 1));
+
+        // A larger change is stored and reports what changed
+        let rewritten = format!("{}\nBudget approved for two more roles", text);
+        let updated = server.process(page(&rewritten, 1_120)).await;
+        assert_eq!(updated.action, IngestionAction::Updated);
+        assert_eq!(updated.text_diff.unwrap().inserted_lines, 1);
+
+        // A small change is stored once the stored revision is old enough
+        let late = server.process(page(&rewritten.replace("August", "Augusts"), 1_120 + 3_600)).await;
+        assert_eq!(late.action, IngestionAction::Updated);
+
+        // Deferred captures are held to the threshold, imports are not
+        let mut state = server.state.lock().await;
+        let deferred = ingest(&mut state, page(&rewritten.replace("two", "three"), 1_120 + 3_630), Origin::Deferred);
+        assert_eq!(deferred.reason, Some(SkipReason::Insignificant));
+        let imported = ingest(&mut state, page(&rewritten, 1_120 + 3_660), Origin::Import);
+        assert_eq!(imported.action, IngestionAction::Updated);
+    }
+
+    #[tokio::test]
+    async fn test_metadata_change_updates_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server(&dir);
+
+        let ticket = |status: &str| {
+            let mut ticket = payload("PROJ-7 Fix the login redirect loop on Safari");
+            ticket.metadata.insert("status".to_string(), status.into());
+            ticket
+        };
+        let created = server.process(ticket("Open")).await;
+        assert_eq!(server.process(ticket("Open")).await.reason, Some(SkipReason::Unchanged));
+
+        // Same text, new status: chunks are kept and their meta rewritten.
+        // Imported, so the update is not held back by the throttle.
+        let mut state = server.state.lock().await;
+        let updated = ingest(&mut state, ticket("Done"), Origin::Import);
+        assert_eq!(updated.action, IngestionAction::Updated);
+        assert_eq!(updated.chunk_diff, Some(ChunkDiff { added: 0, removed: 0, kept: 1 }));
+
+        let chunks = state.storage.get_source_chunks(&created.ehl_doc_id.unwrap()).unwrap();
+        assert_eq!(chunks[0].meta["metadata"]["status"], "Done");
+        assert!(state.storage.check_integrity().unwrap().is_clean());
+    }
+
+    #[tokio::test]
+    async fn test_chat_messages_are_stored_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server(&dir);
+
+        let message = |author: &str, minute: i64, text: &str| ChatMessage {
+            author: Some(author.to_string()),
+            timestamp: Some(1_717_232_400 + minute * 60),
+            ..ChatMessage::new(text)
+        };
+        let window = |messages: Vec<ChatMessage>| CapturePayload {
+            source: "slack".to_string(),
+            messages,
+            ..payload("flattened window text")
+        };
+
+        let created = server
+            .process(window(vec![message("ana", 0, "Standup in 5"), message("bo", 1, "Joining")]))
+            .await;
+        let doc_id = created.ehl_doc_id.unwrap();
+
+        // The window scrolled: the first message is gone, one is new. Chat
+        // captures are not throttled, so this is stored right away.
+        let updated = server
+            .process(window(vec![message("bo", 1, "Joining"), message("cy", 2, "Me too")]))
+            .await;
+        assert_eq!(updated.action, IngestionAction::Updated);
+        let unchanged = server.process(window(vec![message("cy", 2, "Me too")])).await;
+        assert_eq!(unchanged.reason, Some(SkipReason::Unchanged));
+
+        let state = server.state.lock().await;
+        assert_eq!(
+            state.storage.get_source_content(&doc_id).unwrap(),
+            "[2024-06-01 09:00] ana: Standup in 5\n[2024-06-01 09:01] bo: Joining\n[2024-06-01 09:02] cy: Me too"
+        );
+        assert_eq!(state.storage.get_messages(&doc_id).unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_metadata_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = make_server(&dir);
+
+        let mut ticket = payload("PROJ-7 Fix the login redirect loop on Safari");
+        ticket.metadata.insert("recipients".to_string(), "ana@example.com".into());
+        let response = server.process(ticket).await;
+        assert_eq!(response.status, ResponseStatus::Error);
+        assert!(response.message.unwrap().starts_with("Invalid metadata"));
+
+        let state = server.state.lock().await;
+        assert!(state.storage.list_sources(&SourceFilter::default()).unwrap().is_empty());
+    }
 }
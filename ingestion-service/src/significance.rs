@@ -0,0 +1,210 @@
+//! Diff statistics and the change significance threshold
+//!
+//! Before an update is stored, the incoming text is compared with the stored
+//! revision: both are masked and normalized as for hashing, aligned line by
+//! line, and the unmatched stretches compared character by character. The
+//! counts go back to the client as a [`TextDiff`]. A change smaller than
+//! `min_change_ratio` of the text is insignificant and not stored; since the
+//! stored text stays as it was, small changes add up until they are worth an
+//! update. With `max_defer_secs`, an insignificant change is stored anyway
+//! once the stored revision is that much older than the capture.
+//!
+//! ```toml
+//! [significance]
+//! min_change_ratio = 0.0
+//!
+//! [significance.sources.ocr-capture]
+//! min_change_ratio = 0.02
+//! max_defer_secs = 3600
+//! ```
+//!
+//! Text identical after masking always counts as significant: the payload
+//! hash differs, so its metadata changed.
+
+use crate::merge::lcs_pairs;
+use crate::payload::TextDiff;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Upper bound on line pairs compared when aligning the changed region
+const MAX_LINE_PAIRS: usize = 1_000_000;
+
+/// Upper bound on character pairs compared within one unmatched stretch; past
+/// it the stretch counts as replaced outright
+const MAX_CHAR_PAIRS: usize = 4_000_000;
+
+/// The `[significance]` config section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SignificanceConfig {
+    /// Changes below this share (0-1) of the text are not stored; 0 stores every change
+    pub min_change_ratio: f64,
+    /// Store an insignificant change anyway once the stored revision is this
+    /// many seconds older than the capture (0 = never)
+    pub max_defer_secs: u64,
+    /// Per source type overrides
+    pub sources: HashMap<String, SourceSignificance>,
+}
+
+impl Default for SignificanceConfig {
+    fn default() -> Self {
+        Self {
+            min_change_ratio: 0.0,
+            max_defer_secs: 0,
+            sources: HashMap::new(),
+        }
+    }
+}
+
+/// Overrides for one source type; unset fields use the section defaults
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourceSignificance {
+    pub min_change_ratio: Option<f64>,
+    pub max_defer_secs: Option<u64>,
+}
+
+impl SignificanceConfig {
+    /// Whether a change should be stored. `age_secs` is how much older the
+    /// stored revision is than the capture.
+    pub fn is_significant(&self, source: &str, diff: &TextDiff, age_secs: i64) -> bool {
+        let overrides = self.sources.get(source).cloned().unwrap_or_default();
+        let min_change_ratio = overrides.min_change_ratio.unwrap_or(self.min_change_ratio);
+        let max_defer_secs = overrides.max_defer_secs.unwrap_or(self.max_defer_secs);
+
+        diff.is_empty()
+            || diff.change_ratio >= min_change_ratio
+            || (max_defer_secs > 0 && age_secs >= max_defer_secs as i64)
+    }
+}
+
+/// Compare two texts line by line, then character by character within the
+/// lines that don't match
+pub fn text_diff(old: &str, new: &str) -> TextDiff {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    // Most updates touch a small region; only it needs aligning
+    let prefix = old_lines.iter().zip(&new_lines).take_while(|(a, b)| a == b).count();
+    let suffix = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_lines = &old_lines[prefix..old_lines.len() - suffix];
+    let new_lines = &new_lines[prefix..new_lines.len() - suffix];
+
+    let anchors = if old_lines.len() * new_lines.len() <= MAX_LINE_PAIRS {
+        lcs_pairs(old_lines, new_lines, |a, b| a == b)
+    } else {
+        Vec::new()
+    };
+
+    let mut diff = TextDiff::default();
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for (old_idx, new_idx) in anchors.into_iter().chain([(old_lines.len(), new_lines.len())]) {
+        add_stretch(&mut diff, &old_lines[old_pos..old_idx], &new_lines[new_pos..new_idx]);
+        old_pos = old_idx + 1;
+        new_pos = new_idx + 1;
+    }
+
+    let longest = old.chars().count().max(new.chars().count());
+    if longest > 0 {
+        diff.change_ratio = diff.inserted_chars.max(diff.deleted_chars) as f64 / longest as f64;
+    }
+    diff
+}
+
+/// Count an unmatched stretch of lines replaced by another
+fn add_stretch(diff: &mut TextDiff, old: &[&str], new: &[&str]) {
+    if old.is_empty() && new.is_empty() {
+        return;
+    }
+    diff.deleted_lines += old.len();
+    diff.inserted_lines += new.len();
+
+    let old: Vec<char> = old.join("\n").chars().collect();
+    let new: Vec<char> = new.join("\n").chars().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let common = if old.len() * new.len() <= MAX_CHAR_PAIRS { lcs_len(old, new) } else { 0 };
+    diff.deleted_chars += old.len() - common;
+    diff.inserted_chars += new.len() - common;
+}
+
+/// Length of the longest common subsequence of two character runs
+fn lcs_len(a: &[char], b: &[char]) -> usize {
+    let mut prev = vec![0; b.len() + 1];
+    let mut curr = vec![0; b.len() + 1];
+    for ca in a {
+        for (j, cb) in b.iter().enumerate() {
+            curr[j + 1] = if ca == cb { prev[j] + 1 } else { prev[j + 1].max(curr[j]) };
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_diff_counts() {
+        let old = "Roadmap\nShip search in August\nHire two engineers\nOffsite in May";
+        assert_eq!(text_diff(old, old), TextDiff::default());
+
+        // An edited word: the line counts as replaced, but only the changed characters count
+        let edited = text_diff(old, "Roadmap\nShip search in August\nHire three engineers\nOffsite in May");
+        assert_eq!(
+            (edited.inserted_lines, edited.deleted_lines, edited.inserted_chars, edited.deleted_chars),
+            (1, 1, 4, 2)
+        );
+
+        let appended = text_diff(old, &format!("{}\nBudget approved", old));
+        assert_eq!((appended.inserted_lines, appended.deleted_lines), (1, 0));
+        assert_eq!((appended.inserted_chars, appended.deleted_chars), (15, 0));
+
+        let removed = text_diff(old, "Roadmap\nOffsite in May");
+        assert_eq!((removed.inserted_lines, removed.deleted_lines), (0, 2));
+        assert_eq!(removed.inserted_chars, 0);
+        assert_eq!(removed.deleted_chars, "Ship search in August\nHire two engineers".len());
+
+        assert_eq!(text_diff("a", "b").change_ratio, 1.0);
+        assert_eq!(text_diff("", "new page").change_ratio, 1.0);
+        assert!(edited.change_ratio < 0.1);
+    }
+
+    #[test]
+    fn test_threshold_and_deferral() {
+        let mut config = SignificanceConfig::default();
+        config.sources.insert(
+            "ocr-capture".to_string(),
+            SourceSignificance {
+                min_change_ratio: Some(0.05),
+                max_defer_secs: Some(600),
+            },
+        );
+        let small = TextDiff {
+            inserted_chars: 1,
+            deleted_chars: 1,
+            inserted_lines: 1,
+            deleted_lines: 1,
+            change_ratio: 0.01,
+        };
+
+        assert!(config.is_significant("chrome", &small, 0));
+        assert!(!config.is_significant("ocr-capture", &small, 60));
+        assert!(config.is_significant("ocr-capture", &small, 600));
+        // Metadata-only changes are always stored
+        assert!(config.is_significant("ocr-capture", &TextDiff::default(), 0));
+    }
+}
@@ -1,9 +1,27 @@
 //! SQLite storage for content_sources and chunks
 
-use crate::chunker::Chunk;
-use crate::payload::CapturePayload;
-use rusqlite::{params, Connection};
+use crate::chunker::{Chunk, Chunker};
+use crate::dedup::compute_hash;
+use crate::integrity::{self, IntegrityReport};
+use crate::metadata::Metadata;
+use crate::payload::{CapturePayload, ChunkDiff};
+use crate::reprocess;
+use capture_protocol::mask::Masks;
+use capture_protocol::redact::Redactor;
+use capture_protocol::ChunkMeta;
+use chrono::{DateTime, Utc};
+use content_db::compression::{self, CompressionStats, Compressor};
+use content_db::doc_id;
+use content_db::message::{self, StoredMessage};
+use content_db::revision;
+use content_db::tombstone::{self, Tombstone, TombstoneKind};
+use content_db::{BusyError, OpenMode, RetryPolicy};
+use regex::{Regex, RegexBuilder};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, Row, Transaction};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 use thiserror::Error;
 
@@ -17,6 +35,12 @@ pub enum StorageError {
     Io(#[from] std::io::Error),
 }
 
+impl BusyError for StorageError {
+    fn is_busy(&self) -> bool {
+        matches!(self, StorageError::Sqlite(e) if e.is_busy())
+    }
+}
+
 /// Content source record
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContentSource {
@@ -29,41 +53,109 @@ pub struct ContentSource {
     pub ingestion_status: String,
     pub created_at: String,
     pub updated_at: String,
+    /// Capture time of the latest revision (unix seconds)
+    pub captured_at: i64,
 }
 
-/// Chunk metadata stored in the meta JSON field
+/// A live chunk as stored in the chunks table
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ChunkMeta {
-    pub id: String,             // ehl_doc_id
-    pub source: String,         // source type
-    pub url: String,            // source URL
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub title: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub author: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub channel: Option<String>,
-    pub chunk_index: usize,
-    pub total_chunks: usize,
-    pub source_type: String,    // "browser_capture"
-    /// Application display name (e.g., "Microsoft Word")
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub app_name: Option<String>,
-    /// Application bundle ID (e.g., "com.microsoft.Word")
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub bundle_id: Option<String>,
+pub struct StoredChunk {
+    pub text: String,
+    /// Raw meta JSON (kept untyped so rows written by other components survive)
+    pub meta: serde_json::Value,
+}
+
+/// Filter for listing content sources
+#[derive(Debug, Clone, Default)]
+pub struct SourceFilter {
+    /// Only include these source types (empty = all)
+    pub source_types: Vec<String>,
+    /// Only include sources captured at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only include sources captured before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Only include sources whose metadata has all of these values; a list
+    /// value matches when it contains the filter value
+    pub metadata: Metadata,
+}
+
+pub use content_db::schema::SCHEMA_VERSION;
+
+/// Schema version of an open database (0 for databases that predate versioning)
+pub fn schema_version(conn: &Connection) -> Result<i32, StorageError> {
+    Ok(content_db::schema::version(conn)?)
+}
+
+/// Columns selected for a ContentSource, in `source_from_row` order.
+/// Rows written before capture times were recorded fall back to `updated_at`.
+const SOURCE_COLUMNS: &str = "id, source_type, source_path, content_hash, ehl_doc_id, chunk_count, 
+    ingestion_status, created_at, updated_at, 
+    COALESCE(captured_at, CAST(strftime('%s', updated_at) AS INTEGER))";
+
+/// SQL condition on a chunk not cut by pipeline version `?1`, leaving out
+/// chunks written by the accessibility daemon
+const STALE_CHUNK: &str = "json_extract(meta, '$.source_type') IS NOT 'accessibility'
+    AND json_extract(meta, '$.pipeline_version') IS NOT ?1";
+
+/// SQL condition on the chunk `meta` column: its `metadata.<key>` equals
+/// `value`, or contains it when the stored value is a list. Parameters are
+/// appended to `values`.
+fn metadata_condition(key: &str, value: &serde_json::Value, values: &mut Vec<Value>) -> String {
+    values.push(Value::Text(format!("$.metadata.\"{}\"", key)));
+    let path = values.len();
+    values.push(match value {
+        serde_json::Value::Bool(b) => Value::Integer(*b as i64),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Real(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => Value::Text(s.clone()),
+        other => Value::Text(other.to_string()),
+    });
+    format!(
+        "EXISTS (SELECT 1 FROM json_each(CASE WHEN json_valid(meta) THEN meta ELSE '{{}}' END, ?{}) WHERE value = ?{})",
+        path,
+        values.len()
+    )
+}
+
+/// Map a `SELECT {SOURCE_COLUMNS}` row to a ContentSource
+fn source_from_row(row: &Row) -> rusqlite::Result<ContentSource> {
+    Ok(ContentSource {
+        id: row.get(0)?,
+        source_type: row.get(1)?,
+        source_path: row.get(2)?,
+        content_hash: row.get(3)?,
+        ehl_doc_id: row.get(4)?,
+        chunk_count: row.get(5)?,
+        ingestion_status: row.get(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+        captured_at: row.get(9)?,
+    })
 }
 
 /// SQLite storage manager
 pub struct Storage {
     conn: Connection,
+    /// Set when new chunk text is stored compressed
+    compressor: Option<Compressor>,
+    /// Recorded on every chunk written (see `reprocess::pipeline_version`)
+    pipeline_version: String,
+    /// Masking the recorded source hashes were taken with, for the integrity check
+    masks: Masks,
 }
 
 impl Storage {
     /// Open or create the database at the given path
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StorageError> {
-        let conn = Connection::open(path)?;
-        let storage = Self { conn };
+        let conn = content_db::open(path, OpenMode::ReadWrite)?;
+        let storage = Self {
+            conn,
+            compressor: None,
+            pipeline_version: reprocess::pipeline_version(&Chunker::with_defaults(), &Redactor::default()),
+            masks: Masks::builtin(),
+        };
         storage.init_schema()?;
         Ok(storage)
     }
@@ -71,69 +163,50 @@ impl Storage {
     /// Open an in-memory database (for testing)
     pub fn open_in_memory() -> Result<Self, StorageError> {
         let conn = Connection::open_in_memory()?;
-        let storage = Self { conn };
+        compression::register_functions(&conn)?;
+        let storage = Self {
+            conn,
+            compressor: None,
+            pipeline_version: reprocess::pipeline_version(&Chunker::with_defaults(), &Redactor::default()),
+            masks: Masks::builtin(),
+        };
         storage.init_schema()?;
         Ok(storage)
     }
 
-    /// Initialize database schema
-    fn init_schema(&self) -> Result<(), StorageError> {
-        self.conn.execute_batch(
-            r#"
-            -- Content sources table (tracks what we've ingested)
-            CREATE TABLE IF NOT EXISTS content_sources (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                source_type TEXT NOT NULL,
-                source_path TEXT NOT NULL UNIQUE,
-                content_hash TEXT NOT NULL,
-                ehl_doc_id TEXT NOT NULL UNIQUE,
-                chunk_count INTEGER NOT NULL DEFAULT 0,
-                ingestion_status TEXT NOT NULL DEFAULT 'ingested',
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
-            );
+    /// Record `version` on the chunks written from now on; sources with
+    /// chunks of another version are listed by `stale_sources`
+    pub fn set_pipeline_version(&mut self, version: String) {
+        self.pipeline_version = version;
+    }
 
-            CREATE INDEX IF NOT EXISTS idx_content_sources_path ON content_sources(source_path);
-            CREATE INDEX IF NOT EXISTS idx_content_sources_ehl_doc_id ON content_sources(ehl_doc_id);
-            CREATE INDEX IF NOT EXISTS idx_content_sources_hash ON content_sources(content_hash);
+    /// Masking rules that source hashes are taken with (the built-in ones by default)
+    pub fn set_masks(&mut self, masks: Masks) {
+        self.masks = masks;
+    }
 
-            -- Chunks table (actual content chunks)
-            CREATE TABLE IF NOT EXISTS chunks (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                vector_index INTEGER,
-                text TEXT NOT NULL,
-                meta TEXT NOT NULL,
-                is_deleted INTEGER DEFAULT 0,
-                created_at TEXT NOT NULL DEFAULT (datetime('now'))
-            );
+    pub fn masks(&self) -> &Masks {
+        &self.masks
+    }
 
-            CREATE INDEX IF NOT EXISTS idx_chunks_deleted ON chunks(is_deleted);
-            "#,
-        )?;
-        Ok(())
+    /// Initialize database schema
+    fn init_schema(&self) -> Result<(), StorageError> {
+        Ok(content_db::schema::migrate(&self.conn)?)
+    }
+
+    /// Source types whose http(s) paths predate the generic URL cleanup
+    pub fn legacy_url_sources(&self) -> Result<HashSet<String>, StorageError> {
+        Ok(content_db::schema::legacy_url_sources(&self.conn)?)
     }
 
     /// Find a content source by its path (URL)
     pub fn find_source_by_path(&self, source_path: &str) -> Result<Option<ContentSource>, StorageError> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, source_type, source_path, content_hash, ehl_doc_id, chunk_count, 
-                    ingestion_status, created_at, updated_at 
-             FROM content_sources WHERE source_path = ?1"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM content_sources WHERE source_path = ?1",
+            SOURCE_COLUMNS
+        ))?;
 
-        let result = stmt.query_row(params![source_path], |row| {
-            Ok(ContentSource {
-                id: row.get(0)?,
-                source_type: row.get(1)?,
-                source_path: row.get(2)?,
-                content_hash: row.get(3)?,
-                ehl_doc_id: row.get(4)?,
-                chunk_count: row.get(5)?,
-                ingestion_status: row.get(6)?,
-                created_at: row.get(7)?,
-                updated_at: row.get(8)?,
-            })
-        });
+        let result = stmt.query_row(params![source_path], source_from_row);
 
         match result {
             Ok(source) => Ok(Some(source)),
@@ -142,6 +215,22 @@ impl Storage {
         }
     }
 
+    /// Find a content source by its document id, or by an id it had before
+    /// document ids were derived from the source (`content_db::doc_id`)
+    pub fn find_source_by_doc_id(&self, ehl_doc_id: &str) -> Result<Option<ContentSource>, StorageError> {
+        let ehl_doc_id = doc_id::resolve(&self.conn, ehl_doc_id)?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM content_sources WHERE ehl_doc_id = ?1",
+            SOURCE_COLUMNS
+        ))?;
+
+        match stmt.query_row(params![ehl_doc_id], source_from_row) {
+            Ok(source) => Ok(Some(source)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     /// Find sources with similar paths (for OCR metadata-based dedup)
     /// Matches sources where the path starts with the same prefix (source type + title base)
     pub fn find_similar_sources(&self, source_type: &str, title_prefix: &str) -> Result<Vec<ContentSource>, StorageError> {
@@ -149,28 +238,15 @@ impl Storage {
         // e.g., "ocr://vscode/ocr-extraction-md" should match existing entries for the same document
         let pattern = format!("ocr://{}/%{}%", source_type, title_prefix);
         
-        let mut stmt = self.conn.prepare(
-            "SELECT id, source_type, source_path, content_hash, ehl_doc_id, chunk_count, 
-                    ingestion_status, created_at, updated_at 
-             FROM content_sources 
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM content_sources 
              WHERE source_path LIKE ?1
              ORDER BY updated_at DESC
-             LIMIT 10"
-        )?;
+             LIMIT 10",
+            SOURCE_COLUMNS
+        ))?;
 
-        let rows = stmt.query_map(params![pattern], |row| {
-            Ok(ContentSource {
-                id: row.get(0)?,
-                source_type: row.get(1)?,
-                source_path: row.get(2)?,
-                content_hash: row.get(3)?,
-                ehl_doc_id: row.get(4)?,
-                chunk_count: row.get(5)?,
-                ingestion_status: row.get(6)?,
-                created_at: row.get(7)?,
-                updated_at: row.get(8)?,
-            })
-        })?;
+        let rows = stmt.query_map(params![pattern], source_from_row)?;
 
         let mut sources = Vec::new();
         for row in rows {
@@ -179,10 +255,16 @@ impl Storage {
         Ok(sources)
     }
 
-    /// Get the current content for a source (concatenated from chunks)
+    /// Get the current content for a source: the text of its latest
+    /// revision, exactly as captured. Sources without a stored revision are
+    /// approximated by joining their chunks.
     pub fn get_source_content(&self, ehl_doc_id: &str) -> Result<String, StorageError> {
+        if let Some(revision) = revision::latest(&self.conn, ehl_doc_id)? {
+            return Ok(revision.text);
+        }
+
         let mut stmt = self.conn.prepare(
-            "SELECT text FROM chunks 
+            "SELECT chunk_text(text, text_zst) FROM chunks
              WHERE json_extract(meta, '$.id') = ?1 AND is_deleted = 0
              ORDER BY json_extract(meta, '$.chunk_index')"
         )?;
@@ -201,163 +283,670 @@ impl Storage {
         Ok(content)
     }
 
-    /// Append new content to an existing source (for incremental OCR updates)
-    pub fn append_to_source(
-        &mut self,
-        ehl_doc_id: &str,
-        payload: &CapturePayload,
-        _new_content: &str,  // Content already chunked, kept for potential future use
-        new_content_hash: &str,
-        chunks: &[Chunk],
-    ) -> Result<(), StorageError> {
-        let tx = self.conn.transaction()?;
+    /// List content sources matching a filter, oldest first
+    pub fn list_sources(&self, filter: &SourceFilter) -> Result<Vec<ContentSource>, StorageError> {
+        self.query_sources(filter, None, None)
+    }
 
-        // Get current chunk count to continue indexing
-        let current_chunk_count: i32 = tx.query_row(
-            "SELECT chunk_count FROM content_sources WHERE ehl_doc_id = ?1",
-            params![ehl_doc_id],
-            |row| row.get(0),
-        )?;
+    /// Up to `limit` sources matching a filter whose `id` is greater than
+    /// `after`, oldest first; pass the last `id` of a page to get the next one
+    pub fn list_sources_page(
+        &self,
+        filter: &SourceFilter,
+        after: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<ContentSource>, StorageError> {
+        self.query_sources(filter, after, Some(limit))
+    }
+
+    fn query_sources(
+        &self,
+        filter: &SourceFilter,
+        after: Option<i64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<ContentSource>, StorageError> {
+        let mut conditions: Vec<String> = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+
+        if let Some(after) = after {
+            values.push(Value::Integer(after));
+            conditions.push(format!("id > ?{}", values.len()));
+        }
+
+        if !filter.source_types.is_empty() {
+            let placeholders: Vec<String> = filter
+                .source_types
+                .iter()
+                .map(|source_type| {
+                    values.push(Value::Text(source_type.clone()));
+                    format!("?{}", values.len())
+                })
+                .collect();
+            conditions.push(format!("source_type IN ({})", placeholders.join(", ")));
+        }
+        if let Some(since) = &filter.since {
+            values.push(Value::Integer(since.timestamp()));
+            conditions.push(format!(
+                "COALESCE(captured_at, CAST(strftime('%s', updated_at) AS INTEGER)) >= ?{}",
+                values.len()
+            ));
+        }
+        if let Some(until) = &filter.until {
+            values.push(Value::Integer(until.timestamp()));
+            conditions.push(format!(
+                "COALESCE(captured_at, CAST(strftime('%s', updated_at) AS INTEGER)) < ?{}",
+                values.len()
+            ));
+        }
+
+        for (key, value) in &filter.metadata {
+            let condition = metadata_condition(key, value, &mut values);
+            conditions.push(format!(
+                "ehl_doc_id IN (SELECT json_extract(meta, '$.id') FROM chunks WHERE is_deleted = 0 AND {})",
+                condition
+            ));
+        }
+
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let limit_clause = match limit {
+            Some(limit) => {
+                values.push(Value::Integer(limit as i64));
+                format!("LIMIT ?{}", values.len())
+            }
+            None => String::new(),
+        };
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM content_sources {} ORDER BY id {}",
+            SOURCE_COLUMNS, where_clause, limit_clause
+        ))?;
+
+        let rows = stmt.query_map(params_from_iter(values.iter()), source_from_row)?;
+
+        let mut sources = Vec::new();
+        for row in rows {
+            sources.push(row?);
+        }
+        Ok(sources)
+    }
+
+    /// Sources with a live chunk not cut by the current pipeline version,
+    /// oldest first. Chunks written by the accessibility daemon are left out:
+    /// the daemon re-cuts them itself on its next capture.
+    pub fn stale_sources(&self) -> Result<Vec<ContentSource>, StorageError> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM content_sources WHERE ehl_doc_id IN (
+                 SELECT json_extract(meta, '$.id') FROM chunks WHERE is_deleted = 0 AND {})
+             ORDER BY id",
+            SOURCE_COLUMNS, STALE_CHUNK
+        ))?;
+        let rows = stmt.query_map(params![self.pipeline_version], source_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
 
-        // Update content source with new hash and chunk count
-        tx.execute(
-            "UPDATE content_sources SET content_hash = ?1, chunk_count = ?2, 
-             updated_at = datetime('now') WHERE ehl_doc_id = ?3",
-            params![new_content_hash, current_chunk_count + chunks.len() as i32, ehl_doc_id],
+    /// Get the live chunks of a source in chunk order
+    pub fn get_source_chunks(&self, ehl_doc_id: &str) -> Result<Vec<StoredChunk>, StorageError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT chunk_text(text, text_zst), meta FROM chunks
+             WHERE json_extract(meta, '$.id') = ?1 AND is_deleted = 0
+             ORDER BY json_extract(meta, '$.chunk_index')"
         )?;
 
-        // Append new chunks (don't delete old ones)
-        for (i, chunk) in chunks.iter().enumerate() {
-            let meta = ChunkMeta {
-                id: ehl_doc_id.to_string(),
-                source: payload.source.clone(),
-                url: payload.url.clone(),
-                title: payload.title.clone(),
-                author: payload.author.clone(),
-                channel: payload.channel.clone(),
-                chunk_index: current_chunk_count as usize + i,
-                total_chunks: current_chunk_count as usize + chunks.len(),
-                source_type: "capture".to_string(),
-                app_name: payload.app_name.clone(),
-                bundle_id: payload.bundle_id.clone(),
-            };
+        let rows = stmt.query_map(params![ehl_doc_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
 
-            let meta_json = serde_json::to_string(&meta)?;
+        let mut chunks = Vec::new();
+        for row in rows {
+            let (text, meta_json) = row?;
+            chunks.push(StoredChunk {
+                text,
+                meta: serde_json::from_str(&meta_json)?,
+            });
+        }
+        Ok(chunks)
+    }
+
+    /// Case-insensitive substring search over live chunks, newest first,
+    /// optionally restricted by source type and metadata (see [`SourceFilter`])
+    pub fn search(
+        &self,
+        query: &str,
+        source_types: &[String],
+        metadata: &Metadata,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, StorageError> {
+        let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let mut values: Vec<Value> = vec![Value::Text(format!("%{}%", escaped))];
+
+        let type_clause = if source_types.is_empty() {
+            String::new()
+        } else {
+            let placeholders: Vec<String> = source_types
+                .iter()
+                .map(|source_type| {
+                    values.push(Value::Text(source_type.clone()));
+                    format!("?{}", values.len())
+                })
+                .collect();
+            format!("WHERE source_type IN ({})", placeholders.join(", "))
+        };
+        let metadata_clause: String = metadata
+            .iter()
+            .map(|(key, value)| format!(" AND {}", metadata_condition(key, value, &mut values)))
+            .collect();
+        values.push(Value::Integer(limit as i64));
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT json_extract(meta, '$.id'), json_extract(meta, '$.chunk_index'), chunk_text(text, text_zst)
+             FROM chunks
+             WHERE is_deleted = 0 AND chunk_text(text, text_zst) LIKE ?1 ESCAPE '\\'
+               AND json_extract(meta, '$.id') IN (SELECT ehl_doc_id FROM content_sources {}){}
+             ORDER BY id DESC LIMIT ?{}",
+            type_clause,
+            metadata_clause,
+            values.len()
+        ))?;
 
+        let rows = stmt.query_map(params_from_iter(values.iter()), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<i64>>(1)?.unwrap_or(0),
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+
+        let pattern = RegexBuilder::new(&regex::escape(query))
+            .case_insensitive(true)
+            .build()
+            .expect("escaped pattern is valid");
+
+        let mut hits = Vec::new();
+        for row in rows {
+            let (ehl_doc_id, chunk_index, text) = row?;
+            if let Some(source) = self.find_source_by_doc_id(&ehl_doc_id)? {
+                hits.push(SearchHit {
+                    source,
+                    chunk_index: chunk_index as usize,
+                    snippet: snippet(&text, &pattern),
+                });
+            }
+        }
+        Ok(hits)
+    }
+
+    /// Remove a source: its chunks are soft-deleted, and the source row and
+    /// its chat messages are dropped. Returns the removed source, or None if
+    /// there was none.
+    pub fn delete_source(&mut self, ehl_doc_id: &str) -> Result<Option<ContentSource>, StorageError> {
+        let Some(source) = self.find_source_by_doc_id(ehl_doc_id)? else {
+            return Ok(None);
+        };
+        let ehl_doc_id = source.ehl_doc_id.as_str();
+
+        self.write(|tx| {
             tx.execute(
-                "INSERT INTO chunks (text, meta) VALUES (?1, ?2)",
-                params![chunk.text, meta_json],
+                "UPDATE chunks SET is_deleted = 1
+                 WHERE json_extract(CASE WHEN json_valid(meta) THEN meta END, '$.id') = ?1",
+                params![ehl_doc_id],
+            )?;
+            tx.execute("DELETE FROM content_sources WHERE ehl_doc_id = ?1", params![ehl_doc_id])?;
+            message::delete(tx, ehl_doc_id)?;
+            Ok(())
+        })?;
+        Ok(Some(source))
+    }
+
+    /// Store the messages of a chat payload; returns how many were new or edited
+    pub fn add_messages(&mut self, ehl_doc_id: &str, messages: &[StoredMessage]) -> Result<usize, StorageError> {
+        self.write(|tx| {
+            let mut changed = 0;
+            for stored in messages {
+                if message::upsert(tx, ehl_doc_id, stored)? {
+                    changed += 1;
+                }
+            }
+            Ok(changed)
+        })
+    }
+
+    /// Messages of a chat source, in conversation order
+    pub fn get_messages(&self, ehl_doc_id: &str) -> Result<Vec<StoredMessage>, StorageError> {
+        Ok(message::list(&self.conn, ehl_doc_id)?)
+    }
+
+    /// The unexpired tombstone covering a capture, if any
+    pub fn find_tombstone(&self, source_path: &str, url: &str) -> Result<Option<Tombstone>, StorageError> {
+        Ok(tombstone::find_match(&self.conn, source_path, url)?)
+    }
+
+    /// All tombstones, including expired ones
+    pub fn list_tombstones(&self) -> Result<Vec<Tombstone>, StorageError> {
+        Ok(tombstone::list(&self.conn)?)
+    }
+
+    /// Delete a tombstone so matching content can be captured again
+    pub fn remove_tombstone(&mut self, id: i64) -> Result<Option<Tombstone>, StorageError> {
+        self.write(|tx| Ok(tombstone::remove(tx, id)?))
+    }
+
+    /// Add a tombstone and erase every stored source it covers, in one
+    /// transaction. Chunks are soft-deleted (the vector index may refer to
+    /// them) but their text is blanked, and captured messages are deleted.
+    pub fn forget(
+        &mut self,
+        kind: TombstoneKind,
+        value: &str,
+        expires_at: Option<i64>,
+    ) -> Result<ForgetResult, StorageError> {
+        self.write(|tx| {
+            let tombstone = tombstone::insert(tx, kind, value, expires_at)?;
+
+            let mut stmt = tx.prepare(&format!("SELECT {} FROM content_sources ORDER BY id", SOURCE_COLUMNS))?;
+            let removed: Vec<ContentSource> = stmt
+                .query_map([], source_from_row)?
+                .filter(|source| {
+                    source
+                        .as_ref()
+                        .map_or(true, |s| tombstone.matches(&s.source_path, &s.source_path))
+                })
+                .collect::<Result<_, _>>()?;
+
+            let has_messages: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'messages')",
+                [],
+                |row| row.get(0),
             )?;
+
+            for source in &removed {
+                tx.execute(
+                    "UPDATE chunks SET is_deleted = 1, text = '', text_zst = NULL
+                     WHERE json_extract(CASE WHEN json_valid(meta) THEN meta END, '$.id') = ?1",
+                    params![source.ehl_doc_id],
+                )?;
+                revision::delete(tx, &source.ehl_doc_id)?;
+                message::delete(tx, &source.ehl_doc_id)?;
+                tx.execute("DELETE FROM content_sources WHERE id = ?1", params![source.id])?;
+                // Per-message history kept by the accessibility daemon
+                if has_messages {
+                    tx.execute("DELETE FROM messages WHERE source_url = ?1", params![source.source_path])?;
+                }
+            }
+
+            Ok(ForgetResult { tombstone, removed })
+        })
+    }
+
+    /// Purge soft-deleted chunks, revisions no chunk points into any more and
+    /// expired tombstones, then checkpoint the WAL
+    pub fn gc(&mut self, options: GcOptions) -> Result<GcSummary, StorageError> {
+        let size_before = self.size_bytes()?;
+
+        let (purged_chunks, kept_indexed, purged_revisions, expired_tombstones) = self.write(|tx| {
+            let purged = if options.include_indexed {
+                tx.execute("DELETE FROM chunks WHERE is_deleted != 0", [])?
+            } else {
+                tx.execute("DELETE FROM chunks WHERE is_deleted != 0 AND vector_index IS NULL", [])?
+            };
+            let kept: i64 = tx.query_row("SELECT COUNT(*) FROM chunks WHERE is_deleted != 0", [], |row| {
+                row.get(0)
+            })?;
+            Ok((purged, kept as usize, revision::purge_superseded(tx)?, tombstone::purge_expired(tx)?))
+        })?;
+
+        if options.vacuum {
+            content_db::retry_busy(&RetryPolicy::default(), || self.conn.execute_batch("VACUUM"))?;
         }
+        // Readers may hold the WAL open; a partial checkpoint is fine
+        self.conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .or_else(|e| if e.is_busy() { Ok(()) } else { Err(e) })?;
 
-        tx.commit()?;
-        Ok(())
+        Ok(GcSummary {
+            purged_chunks,
+            kept_indexed,
+            purged_revisions,
+            expired_tombstones,
+            size_before,
+            size_after: self.size_bytes()?,
+        })
     }
 
-    /// Insert a new content source and its chunks
+    /// Insert a new content source and its chunks under `source_path`.
+    /// `chunks` must be cut from `payload.content`, which is stored as the
+    /// source's first revision.
     pub fn insert_source(
         &mut self,
         payload: &CapturePayload,
+        source_path: &str,
         content_hash: &str,
         ehl_doc_id: &str,
         chunks: &[Chunk],
     ) -> Result<i64, StorageError> {
-        let tx = self.conn.transaction()?;
-
-        // Insert content source
-        tx.execute(
-            "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![
-                payload.source,
-                payload.url,
-                content_hash,
-                ehl_doc_id,
-                chunks.len() as i32
-            ],
-        )?;
+        self.write(|tx| {
+            // Insert content source, dated by when the content was captured
+            tx.execute(
+                "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count,
+                                              created_at, updated_at, captured_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, datetime(?6, 'unixepoch'), datetime(?6, 'unixepoch'), ?6)",
+                params![
+                    payload.source,
+                    source_path,
+                    content_hash,
+                    ehl_doc_id,
+                    chunks.len() as i32,
+                    payload.captured_at()
+                ],
+            )?;
 
-        let source_id = tx.last_insert_rowid();
+            let source_id = tx.last_insert_rowid();
+            let revision_id = self.insert_revision(tx, ehl_doc_id, content_hash, payload)?;
 
-        // Insert chunks
-        for chunk in chunks {
-            let meta = ChunkMeta {
-                id: ehl_doc_id.to_string(),
-                source: payload.source.clone(),
-                url: payload.url.clone(),
-                title: payload.title.clone(),
-                author: payload.author.clone(),
-                channel: payload.channel.clone(),
-                chunk_index: chunk.chunk_index,
-                total_chunks: chunk.total_chunks,
-                source_type: "capture".to_string(),
-                app_name: payload.app_name.clone(),
-                bundle_id: payload.bundle_id.clone(),
-            };
+            // Insert chunks
+            for chunk in chunks {
+                let meta = self.chunk_meta(ehl_doc_id, source_path, payload, chunk, Some(revision_id));
 
-            let meta_json = serde_json::to_string(&meta)?;
+                let meta_json = serde_json::to_string(&meta)?;
 
-            tx.execute(
-                "INSERT INTO chunks (text, meta) VALUES (?1, ?2)",
-                params![chunk.text, meta_json],
-            )?;
-        }
+                let (text, text_zst) = self.encode_chunk(&chunk.text)?;
+                tx.execute(
+                    "INSERT INTO chunks (text, meta, content_hash, text_zst) VALUES (?1, ?2, ?3, ?4)",
+                    params![text, meta_json, compute_hash(&chunk.text), text_zst],
+                )?;
+            }
 
-        tx.commit()?;
-        Ok(source_id)
+            Ok(source_id)
+        })
     }
 
     /// Update an existing content source with new content
+    ///
+    /// Chunks are matched to the current revision by content hash: a chunk
+    /// whose text is already stored keeps its row (and its `vector_index`, so
+    /// it is not re-embedded) and only has its metadata relinked. Only new
+    /// text is inserted, and live chunks missing from the new revision are
+    /// soft-deleted. As for `insert_source`, `chunks` must be cut from
+    /// `payload.content`, which becomes the new revision.
     pub fn update_source(
         &mut self,
         ehl_doc_id: &str,
+        source_path: &str,
         payload: &CapturePayload,
         content_hash: &str,
         chunks: &[Chunk],
-    ) -> Result<(), StorageError> {
-        let tx = self.conn.transaction()?;
+    ) -> Result<ChunkDiff, StorageError> {
+        self.write(|tx| {
+            // Update content source
+            tx.execute(
+                "UPDATE content_sources SET content_hash = ?1, chunk_count = ?2, 
+                 updated_at = datetime(?3, 'unixepoch'), captured_at = ?3 WHERE ehl_doc_id = ?4",
+                params![content_hash, chunks.len() as i32, payload.captured_at(), ehl_doc_id],
+            )?;
+            let revision_id = self.insert_revision(tx, ehl_doc_id, content_hash, payload)?;
 
-        // Soft-delete old chunks
-        tx.execute(
-            "UPDATE chunks SET is_deleted = 1 WHERE json_extract(meta, '$.id') = ?1",
-            params![ehl_doc_id],
-        )?;
+            self.replace_chunks(tx, ehl_doc_id, source_path, payload, revision_id, chunks)
+        })
+    }
+
+    /// Replace the chunks of a source without recording a new capture: the
+    /// source keeps its capture time, and `payload.content` is only stored as
+    /// a revision when it differs from the latest one. Chunks are matched as
+    /// in `update_source`.
+    pub fn rechunk_source(
+        &mut self,
+        ehl_doc_id: &str,
+        source_path: &str,
+        payload: &CapturePayload,
+        content_hash: &str,
+        chunks: &[Chunk],
+    ) -> Result<ChunkDiff, StorageError> {
+        self.write(|tx| {
+            tx.execute(
+                "UPDATE content_sources SET content_hash = ?1, chunk_count = ?2 WHERE ehl_doc_id = ?3",
+                params![content_hash, chunks.len() as i32, ehl_doc_id],
+            )?;
+            let revision_id = match revision::latest(tx, ehl_doc_id)? {
+                Some(latest) if latest.content_hash == content_hash => latest.id,
+                _ => self.insert_revision(tx, ehl_doc_id, content_hash, payload)?,
+            };
+
+            self.replace_chunks(tx, ehl_doc_id, source_path, payload, revision_id, chunks)
+        })
+    }
+
+    /// Rewrite the stored revisions of a source whose text `redact` changes;
+    /// returns how many changed
+    pub fn redact_revisions(
+        &mut self,
+        ehl_doc_id: &str,
+        redact: impl Fn(&str) -> Cow<'_, str>,
+    ) -> Result<usize, StorageError> {
+        let revisions: Vec<(i64, String)> = self
+            .conn
+            .prepare("SELECT id, chunk_text(text, text_zst) FROM source_revisions WHERE ehl_doc_id = ?1")?
+            .query_map(params![ehl_doc_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
 
-        // Update content source
-        tx.execute(
-            "UPDATE content_sources SET content_hash = ?1, chunk_count = ?2, 
-             updated_at = datetime('now') WHERE ehl_doc_id = ?3",
-            params![content_hash, chunks.len() as i32, ehl_doc_id],
+        self.write(|tx| {
+            let mut changed = 0;
+            for (id, text) in &revisions {
+                if let Cow::Owned(redacted) = redact(text) {
+                    let (text, text_zst) = self.encode_chunk(&redacted)?;
+                    tx.execute(
+                        "UPDATE source_revisions SET text = ?1, text_zst = ?2 WHERE id = ?3",
+                        params![text, text_zst, id],
+                    )?;
+                    changed += 1;
+                }
+            }
+            Ok(changed)
+        })
+    }
+
+    /// Link `chunks` to `revision_id`, reusing live rows with the same text,
+    /// inserting the rest and soft-deleting live rows left over
+    fn replace_chunks(
+        &self,
+        tx: &Transaction,
+        ehl_doc_id: &str,
+        source_path: &str,
+        payload: &CapturePayload,
+        revision_id: i64,
+        chunks: &[Chunk],
+    ) -> Result<ChunkDiff, StorageError> {
+        // Live chunk ids by content hash; a document can repeat a chunk
+        let mut live: HashMap<String, Vec<i64>> = HashMap::new();
+        let mut stmt = tx.prepare(
+            "SELECT id, chunk_text(text, text_zst), content_hash FROM chunks
+             WHERE json_extract(meta, '$.id') = ?1 AND is_deleted = 0
+             ORDER BY id DESC",
         )?;
+        let rows = stmt.query_map(params![ehl_doc_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+        })?;
+        for row in rows {
+            let (id, text, hash) = row?;
+            live.entry(hash.unwrap_or_else(|| compute_hash(&text))).or_default().push(id);
+        }
 
-        // Insert new chunks
+        let mut diff = ChunkDiff::default();
         for chunk in chunks {
-            let meta = ChunkMeta {
-                id: ehl_doc_id.to_string(),
-                source: payload.source.clone(),
-                url: payload.url.clone(),
-                title: payload.title.clone(),
-                author: payload.author.clone(),
-                channel: payload.channel.clone(),
-                chunk_index: chunk.chunk_index,
-                total_chunks: chunk.total_chunks,
-                source_type: "capture".to_string(),
-                app_name: payload.app_name.clone(),
-                bundle_id: payload.bundle_id.clone(),
-            };
+            // Kept chunks move to the new revision too, with their new offsets
+            let meta = self.chunk_meta(ehl_doc_id, source_path, payload, chunk, Some(revision_id));
 
             let meta_json = serde_json::to_string(&meta)?;
+            let chunk_hash = compute_hash(&chunk.text);
 
-            tx.execute(
-                "INSERT INTO chunks (text, meta) VALUES (?1, ?2)",
-                params![chunk.text, meta_json],
-            )?;
+            // Oldest matching row first, so long-lived chunks stay put
+            match live.get_mut(&chunk_hash).and_then(Vec::pop) {
+                Some(id) => {
+                    tx.execute(
+                        "UPDATE chunks SET meta = ?1, content_hash = ?2 WHERE id = ?3",
+                        params![meta_json, chunk_hash, id],
+                    )?;
+                    diff.kept += 1;
+                }
+                None => {
+                    let (text, text_zst) = self.encode_chunk(&chunk.text)?;
+                    tx.execute(
+                        "INSERT INTO chunks (text, meta, content_hash, text_zst) VALUES (?1, ?2, ?3, ?4)",
+                        params![text, meta_json, chunk_hash, text_zst],
+                    )?;
+                    diff.added += 1;
+                }
+            }
         }
 
-        tx.commit()?;
+        // Whatever was not matched is gone from the new revision
+        for id in live.into_values().flatten() {
+            tx.execute("UPDATE chunks SET is_deleted = 1 WHERE id = ?1", params![id])?;
+            diff.removed += 1;
+        }
+
+        Ok(diff)
+    }
+
+    /// `ChunkMeta::for_chunk`, stamped with the current pipeline version
+    fn chunk_meta(
+        &self,
+        ehl_doc_id: &str,
+        source_path: &str,
+        payload: &CapturePayload,
+        chunk: &Chunk,
+        revision_id: Option<i64>,
+    ) -> ChunkMeta {
+        ChunkMeta {
+            pipeline_version: Some(self.pipeline_version.clone()),
+            ..ChunkMeta::for_chunk(ehl_doc_id, source_path, payload, chunk, revision_id)
+        }
+    }
+
+    /// Store new chunk text compressed, with the newest trained dictionary
+    pub fn enable_compression(&mut self, level: i32) -> Result<(), StorageError> {
+        self.compressor = Some(Compressor::load(&self.conn, level)?);
         Ok(())
     }
 
+    /// Store `payload.content` as the newest revision of a source
+    fn insert_revision(
+        &self,
+        tx: &Transaction,
+        ehl_doc_id: &str,
+        content_hash: &str,
+        payload: &CapturePayload,
+    ) -> Result<i64, StorageError> {
+        let (text, text_zst) = self.encode_chunk(&payload.content)?;
+        Ok(revision::insert(tx, ehl_doc_id, content_hash, payload.captured_at(), &text, text_zst.as_deref())?)
+    }
+
+    /// The `(text, text_zst)` pair to store for a chunk or revision
+    fn encode_chunk(&self, text: &str) -> Result<(String, Option<Vec<u8>>), StorageError> {
+        match &self.compressor {
+            Some(compressor) => Ok(compressor.encode(text)?),
+            None => Ok((text.to_string(), None)),
+        }
+    }
+
+    /// Train a zstd dictionary on the most recent live chunks and use it for
+    /// new chunks from now on; returns the dictionary id
+    pub fn train_compression_dictionary(&mut self, sample_limit: usize) -> Result<u32, StorageError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT chunk_text(text, text_zst) FROM chunks WHERE is_deleted = 0 ORDER BY id DESC LIMIT ?1",
+        )?;
+        let samples = stmt
+            .query_map(params![sample_limit as i64], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        drop(stmt);
+
+        let dict = compression::train(&samples, compression::DICT_MAX_SIZE)?;
+        let dict_id = self.write(|tx| Ok(compression::store_dictionary(tx, &dict, samples.len())?))?;
+        let level = self.compressor.as_ref().map_or(compression::DEFAULT_LEVEL, Compressor::level);
+        self.compressor = Some(Compressor::with_dictionary(level, &dict));
+        Ok(dict_id)
+    }
+
+    /// Compress chunk and revision rows still stored as plain text,
+    /// `batch_size` rows per write transaction so other writers are never
+    /// blocked for long. Uses the configured compressor, or the default level
+    /// and newest dictionary. Returns how many rows were compressed; rows too
+    /// short to benefit stay plain.
+    pub fn compress_existing(&mut self, batch_size: usize) -> Result<usize, StorageError> {
+        if self.compressor.is_none() {
+            self.enable_compression(compression::DEFAULT_LEVEL)?;
+        }
+        Ok(self.compress_rows("chunks", batch_size)? + self.compress_rows("source_revisions", batch_size)?)
+    }
+
+    /// `compress_existing` for one table with `text` and `text_zst` columns
+    fn compress_rows(&mut self, table: &str, batch_size: usize) -> Result<usize, StorageError> {
+        let mut compressed = 0;
+        let mut last_id = 0i64;
+        loop {
+            let (seen, batch_compressed, batch_last_id) = self.write(|tx| {
+                let mut stmt = tx.prepare(&format!(
+                    "SELECT id, text FROM {} WHERE id > ?1 AND text_zst IS NULL ORDER BY id LIMIT ?2",
+                    table
+                ))?;
+                let rows = stmt
+                    .query_map(params![last_id, batch_size as i64], |row| {
+                        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let mut batch_compressed = 0;
+                for (id, text) in &rows {
+                    let (_, Some(frame)) = self.encode_chunk(text)? else {
+                        continue;
+                    };
+                    if table == "chunks" {
+                        // Keep the hash so updates can still match this chunk
+                        tx.execute(
+                            "UPDATE chunks SET text = '', text_zst = ?1, content_hash = COALESCE(content_hash, ?2)
+                             WHERE id = ?3",
+                            params![frame, compute_hash(text), id],
+                        )?;
+                    } else {
+                        tx.execute(
+                            &format!("UPDATE {} SET text = '', text_zst = ?1 WHERE id = ?2", table),
+                            params![frame, id],
+                        )?;
+                    }
+                    batch_compressed += 1;
+                }
+                Ok((rows.len(), batch_compressed, rows.last().map_or(last_id, |(id, _)| *id)))
+            })?;
+
+            compressed += batch_compressed;
+            last_id = batch_last_id;
+            if seen < batch_size {
+                return Ok(compressed);
+            }
+        }
+    }
+
+    /// Report inconsistencies between sources and their chunks
+    pub fn check_integrity(&self) -> Result<IntegrityReport, StorageError> {
+        integrity::check(&self.conn, &self.masks)
+    }
+
+    /// Fix inconsistencies between sources and their chunks in one transaction
+    pub fn repair_integrity(&mut self) -> Result<IntegrityReport, StorageError> {
+        self.write(|tx| integrity::repair(tx, &self.masks))
+    }
+
+    /// Run `f` in a write transaction, retried while another process holds the lock
+    fn write<T>(
+        &self,
+        f: impl FnMut(&Transaction) -> Result<T, StorageError>,
+    ) -> Result<T, StorageError> {
+        content_db::write_transaction(&self.conn, &RetryPolicy::default(), f)
+    }
+
     /// Get storage statistics
     pub fn stats(&self) -> Result<StorageStats, StorageError> {
         let source_count: i64 = self.conn.query_row(
@@ -372,35 +961,171 @@ impl Storage {
             |row| row.get(0),
         )?;
 
+        let deleted_chunk_count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM chunks WHERE is_deleted != 0",
+            [],
+            |row| row.get(0),
+        )?;
+
+        let stale_chunk_count: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM chunks WHERE is_deleted = 0 AND {}", STALE_CHUNK),
+            params![self.pipeline_version],
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT source_type, COUNT(*) FROM content_sources GROUP BY source_type ORDER BY source_type",
+        )?;
+        let sources_by_type = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize)))?
+            .collect::<Result<_, _>>()?;
+
         Ok(StorageStats {
             source_count: source_count as usize,
             chunk_count: chunk_count as usize,
+            deleted_chunk_count: deleted_chunk_count as usize,
+            stale_chunk_count: stale_chunk_count as usize,
+            sources_by_type,
+            size_bytes: self.size_bytes()?,
+            compression: compression::stats(&self.conn)?,
         })
     }
+
+    /// Size of the main database file in bytes (excluding the WAL)
+    fn size_bytes(&self) -> Result<u64, StorageError> {
+        let pages: i64 = self.conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        let page_size: i64 = self.conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        Ok((pages * page_size) as u64)
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageStats {
     pub source_count: usize,
+    /// Live chunks
     pub chunk_count: usize,
+    /// Soft-deleted chunks still in the table (see `Storage::gc`)
+    #[serde(default)]
+    pub deleted_chunk_count: usize,
+    /// Live chunks cut by another pipeline version (see `Storage::stale_sources`)
+    #[serde(default)]
+    pub stale_chunk_count: usize,
+    #[serde(default)]
+    pub sources_by_type: BTreeMap<String, usize>,
+    #[serde(default)]
+    pub size_bytes: u64,
+    #[serde(default)]
+    pub compression: CompressionStats,
+}
+
+/// The `[compression]` config section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Store new chunk text as zstd frames (existing rows: `ingestion-server compress`)
+    pub enabled: bool,
+    /// zstd level, 1-22
+    pub level: i32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            level: compression::DEFAULT_LEVEL,
+        }
+    }
+}
+
+/// Result of `Storage::forget`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForgetResult {
+    pub tombstone: Tombstone,
+    /// Sources that matched and were erased
+    pub removed: Vec<ContentSource>,
+}
+
+/// A live chunk matching a text search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub source: ContentSource,
+    pub chunk_index: usize,
+    /// Text around the first match, on one line
+    pub snippet: String,
+}
+
+/// What `Storage::gc` may remove
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GcOptions {
+    /// Also purge deleted chunks that were given a `vector_index`; only safe
+    /// once the vector index has been rebuilt without them
+    pub include_indexed: bool,
+    /// Rebuild the file afterwards to return free pages to the filesystem
+    pub vacuum: bool,
+}
+
+/// Result of `Storage::gc`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GcSummary {
+    pub purged_chunks: usize,
+    /// Deleted chunks kept because the vector index may still refer to them
+    pub kept_indexed: usize,
+    /// Superseded revision texts no remaining chunk points into
+    #[serde(default)]
+    pub purged_revisions: usize,
+    #[serde(default)]
+    pub expired_tombstones: usize,
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+/// Characters of context kept on each side of a search match
+const SNIPPET_CONTEXT: usize = 60;
+
+/// Cut `text` down to the first match of `pattern` with some context
+fn snippet(text: &str, pattern: &Regex) -> String {
+    let (start, end) = pattern.find(text).map(|m| (m.start(), m.end())).unwrap_or((0, 0));
+
+    let from = text[..start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT - 1)
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    let to = text[end..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT)
+        .map(|(i, _)| end + i)
+        .unwrap_or(text.len());
+
+    let mut snippet = text[from..to].split_whitespace().collect::<Vec<_>>().join(" ");
+    if from > 0 {
+        snippet.insert(0, '…');
+    }
+    if to < text.len() {
+        snippet.push('…');
+    }
+    snippet
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chunker::Chunker;
+    use crate::chunker::{Chunker, ChunkerConfig};
+    use serde_json::json;
 
     fn make_payload() -> CapturePayload {
         CapturePayload {
-            source: "slack".to_string(),
-            url: "https://workspace.slack.com/archives/C123".to_string(),
-            content: "Hello world this is a test message".to_string(),
             title: Some("engineering".to_string()),
-            author: None,
             channel: Some("engineering".to_string()),
             timestamp: Some(1234567890),
             app_name: Some("Slack".to_string()),
             bundle_id: Some("com.tinyspeck.slackmacgap".to_string()),
+            ..CapturePayload::new(
+                "slack",
+                "https://workspace.slack.com/archives/C123",
+                "Hello world this is a test message",
+            )
         }
     }
 
@@ -412,7 +1137,7 @@ mod tests {
         let chunks = chunker.chunk(&payload.content);
 
         storage
-            .insert_source(&payload, "hash123", "doc-uuid", &chunks)
+            .insert_source(&payload, &payload.url, "hash123", "doc-uuid", &chunks)
             .unwrap();
 
         let found = storage.find_source_by_path(&payload.url).unwrap();
@@ -432,7 +1157,7 @@ mod tests {
         let chunks = chunker.chunk(&payload.content);
 
         storage
-            .insert_source(&payload, "hash123", "doc-uuid", &chunks)
+            .insert_source(&payload, &payload.url, "hash123", "doc-uuid", &chunks)
             .unwrap();
 
         // Update with new content
@@ -441,10 +1166,457 @@ mod tests {
         let new_chunks = chunker.chunk(&updated_payload.content);
 
         storage
-            .update_source("doc-uuid", &updated_payload, "hash456", &new_chunks)
+            .update_source("doc-uuid", &payload.url, &updated_payload, "hash456", &new_chunks)
             .unwrap();
 
         let found = storage.find_source_by_path(&payload.url).unwrap().unwrap();
         assert_eq!(found.content_hash, "hash456");
     }
+
+    #[test]
+    fn test_update_keeps_unchanged_chunks() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let payload = make_payload();
+        let chunker = Chunker::new(ChunkerConfig {
+            max_tokens: 4,
+            overlap_tokens: 0,
+        });
+        let first = chunker.chunk("one two three four five six seven eight nine ten eleven twelve");
+        storage
+            .insert_source(&payload, &payload.url, "hash1", "doc-uuid", &first)
+            .unwrap();
+        // Stands in for the vector index having embedded the first revision
+        storage.conn.execute("UPDATE chunks SET vector_index = id", []).unwrap();
+
+        // Only the middle chunk changes
+        let second = chunker.chunk("one two three four FIVE six seven eight nine ten eleven twelve");
+        let diff = storage
+            .update_source("doc-uuid", &payload.url, &payload, "hash2", &second)
+            .unwrap();
+        assert_eq!(diff, ChunkDiff { added: 1, removed: 1, kept: 2 });
+
+        let stored = storage.get_source_chunks("doc-uuid").unwrap();
+        let texts: Vec<&str> = stored.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["one two three four", "FIVE six seven eight", "nine ten eleven twelve"]);
+        let indexed: i64 = storage
+            .conn
+            .query_row("SELECT COUNT(*) FROM chunks WHERE is_deleted = 0 AND vector_index IS NOT NULL", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(indexed, 2);
+
+        // A chunk that moves position is relinked, not rewritten
+        let third = chunker.chunk("nine ten eleven twelve one two three four");
+        let diff = storage
+            .update_source("doc-uuid", &payload.url, &payload, "hash3", &third)
+            .unwrap();
+        assert_eq!(diff, ChunkDiff { added: 0, removed: 1, kept: 2 });
+        let stored = storage.get_source_chunks("doc-uuid").unwrap();
+        assert_eq!(stored[0].text, "nine ten eleven twelve");
+        assert_eq!(stored[0].meta["chunk_index"], 0);
+        assert_eq!(storage.stats().unwrap().deleted_chunk_count, 2);
+    }
+
+    #[test]
+    fn test_redact_revisions() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        storage.enable_compression(3).unwrap();
+        let payload = CapturePayload {
+            content: "Card 4532015112830366 on file".to_string(),
+            ..make_payload()
+        };
+        storage
+            .insert_source(&payload, &payload.url, "hash1", "doc-uuid", &Chunker::with_defaults().chunk(&payload.content))
+            .unwrap();
+
+        let redactor = Redactor::default();
+        assert_eq!(storage.redact_revisions("doc-uuid", |text| redactor.redact(text)).unwrap(), 1);
+        assert_eq!(storage.redact_revisions("doc-uuid", |text| redactor.redact(text)).unwrap(), 0);
+        let text: String = storage
+            .conn
+            .query_row("SELECT chunk_text(text, text_zst) FROM source_revisions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(text, "Card [REDACTED_CARD] on file");
+    }
+
+    #[test]
+    fn test_revision_text_and_chunk_offsets() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let chunker = Chunker::new(ChunkerConfig {
+            max_tokens: 4,
+            overlap_tokens: 1,
+        });
+        let first = CapturePayload {
+            content: "  Café  menu:\n\n soup,  bread,\tcheese\n\nand a   glass of wine.\n".to_string(),
+            ..make_payload()
+        };
+        storage
+            .insert_source(&first, &first.url, "hash1", "doc-uuid", &chunker.chunk(&first.content))
+            .unwrap();
+
+        // Overlapping chunks no longer leak into the reconstruction
+        assert_eq!(storage.get_source_content("doc-uuid").unwrap(), first.content);
+
+        let check_spans = |storage: &Storage, content: &str| {
+            let chars: Vec<char> = content.chars().collect();
+            let chunks = storage.get_source_chunks("doc-uuid").unwrap();
+            assert!(chunks.len() > 1);
+            for chunk in &chunks {
+                let meta: ChunkMeta = serde_json::from_value(chunk.meta.clone()).unwrap();
+                let span: String = chars[meta.char_start.unwrap()..meta.char_end.unwrap()].iter().collect();
+                assert_eq!(span, chunk.text);
+            }
+            chunks[0].meta["revision_id"].as_i64().unwrap()
+        };
+        let first_revision = check_spans(&storage, &first.content);
+
+        // Kept chunks point into the new revision at their new offsets
+        let second = CapturePayload {
+            content: format!("Today only, tonight!\n{}", first.content),
+            ..make_payload()
+        };
+        let diff = storage
+            .update_source("doc-uuid", &second.url, &second, "hash2", &chunker.chunk(&second.content))
+            .unwrap();
+        assert_eq!(diff, ChunkDiff { added: 1, removed: 0, kept: 3 });
+        assert_eq!(storage.get_source_content("doc-uuid").unwrap(), second.content);
+        assert_ne!(check_spans(&storage, &second.content), first_revision);
+
+        // The first revision goes once no chunk points into it
+        let summary = storage.gc(GcOptions::default()).unwrap();
+        assert_eq!(summary.purged_revisions, 1);
+        assert_eq!(storage.get_source_content("doc-uuid").unwrap(), second.content);
+    }
+
+    #[test]
+    fn test_compression_is_transparent() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let chunker = Chunker::with_defaults();
+        let plain = CapturePayload {
+            content: "Standup notes: the roadmap review moved to Friday. ".repeat(40),
+            ..make_payload()
+        };
+        storage
+            .insert_source(&plain, &plain.url, "hash1", "doc-plain", &chunker.chunk(&plain.content))
+            .unwrap();
+
+        storage.enable_compression(compression::DEFAULT_LEVEL).unwrap();
+        let packed = CapturePayload {
+            url: "https://example.com/packed".to_string(),
+            content: "Quarterly planning: ship the compressed chunk store. ".repeat(40),
+            ..make_payload()
+        };
+        storage
+            .insert_source(&packed, &packed.url, "hash2", "doc-packed", &chunker.chunk(&packed.content))
+            .unwrap();
+
+        let stored: (String, Option<Vec<u8>>) = storage
+            .conn
+            .query_row("SELECT text, text_zst FROM chunks WHERE id = 2", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(stored.0, "");
+        assert!(stored.1.unwrap().len() < packed.content.len());
+
+        // Reads, search and updates see plain text either way
+        assert_eq!(storage.get_source_content("doc-packed").unwrap(), packed.content);
+        assert_eq!(storage.search("compressed chunk", &[], &Metadata::new(), 10).unwrap().len(), 1);
+        let diff = storage
+            .update_source("doc-packed", &packed.url, &packed, "hash3", &chunker.chunk(&packed.content))
+            .unwrap();
+        assert_eq!(diff.kept, 1);
+
+        // Migrating the chunk and revision written before compression was enabled
+        assert_eq!(storage.compress_existing(1).unwrap(), 2);
+        assert_eq!(storage.compress_existing(1).unwrap(), 0);
+        assert_eq!(storage.get_source_content("doc-plain").unwrap(), plain.content);
+
+        let stats = storage.stats().unwrap().compression;
+        assert_eq!((stats.compressed_chunks, stats.uncompressed_chunks), (2, 0));
+        assert!(stats.ratio > 5.0, "ratio {}", stats.ratio);
+    }
+
+    #[test]
+    fn test_capture_time_persisted() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let payload = make_payload();
+        let chunks = Chunker::with_defaults().chunk(&payload.content);
+
+        storage
+            .insert_source(&payload, &payload.url, "hash123", "doc-uuid", &chunks)
+            .unwrap();
+
+        let source = storage.find_source_by_path(&payload.url).unwrap().unwrap();
+        assert_eq!(source.captured_at, 1234567890);
+        assert_eq!(source.created_at, "2009-02-13 23:31:30");
+        assert_eq!(source.updated_at, "2009-02-13 23:31:30");
+
+        let stored = storage.get_source_chunks("doc-uuid").unwrap();
+        assert_eq!(stored[0].meta["captured_at"], 1234567890);
+
+        // A later revision moves the capture time forward
+        let mut revised = payload.clone();
+        revised.timestamp = Some(1234567990);
+        storage
+            .update_source("doc-uuid", &payload.url, &revised, "hash456", &chunks)
+            .unwrap();
+        let source = storage.find_source_by_path(&payload.url).unwrap().unwrap();
+        assert_eq!(source.captured_at, 1234567990);
+        assert_eq!(source.created_at, "2009-02-13 23:31:30");
+    }
+
+    #[test]
+    fn test_list_sources_by_time_range() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let chunker = Chunker::with_defaults();
+
+        for (i, timestamp) in [1_000_000_000i64, 1_100_000_000, 1_200_000_000].iter().enumerate() {
+            let mut payload = make_payload();
+            payload.url = format!("https://example.com/{}", i);
+            payload.timestamp = Some(*timestamp);
+            let chunks = chunker.chunk(&payload.content);
+            storage
+                .insert_source(&payload, &payload.url, "hash", &format!("doc-{}", i), &chunks)
+                .unwrap();
+        }
+
+        let filter = SourceFilter {
+            since: DateTime::from_timestamp(1_050_000_000, 0),
+            until: DateTime::from_timestamp(1_200_000_000, 0),
+            ..Default::default()
+        };
+        let sources = storage.list_sources(&filter).unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].ehl_doc_id, "doc-1");
+    }
+
+    #[test]
+    fn test_list_sources_by_metadata() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let chunker = Chunker::with_defaults();
+        let tickets = [
+            json!({ "ticket_key": "PROJ-1", "status": "Done", "labels": ["backend", "p1"], "points": 3 }),
+            json!({ "ticket_key": "PROJ-2", "status": "In Progress", "labels": ["frontend"], "blocked": true }),
+        ];
+        for (i, metadata) in tickets.into_iter().enumerate() {
+            let mut payload = make_payload();
+            payload.url = format!("https://example.atlassian.net/browse/PROJ-{}", i + 1);
+            payload.metadata = serde_json::from_value(metadata).unwrap();
+            let chunks = chunker.chunk(&payload.content);
+            storage
+                .insert_source(&payload, &payload.url, "hash", &format!("doc-{}", i), &chunks)
+                .unwrap();
+        }
+
+        let matching = |metadata: serde_json::Value| -> Vec<String> {
+            let filter = SourceFilter {
+                metadata: serde_json::from_value(metadata).unwrap(),
+                ..Default::default()
+            };
+            storage.list_sources(&filter).unwrap().into_iter().map(|s| s.ehl_doc_id).collect()
+        };
+        assert_eq!(matching(json!({ "status": "Done" })), vec!["doc-0"]);
+        assert_eq!(matching(json!({ "labels": "frontend" })), vec!["doc-1"]);
+        assert_eq!(matching(json!({ "points": 3 })), vec!["doc-0"]);
+        assert_eq!(matching(json!({ "blocked": true })), vec!["doc-1"]);
+        assert_eq!(matching(json!({ "status": "Done", "labels": "p1" })), vec!["doc-0"]);
+        assert!(matching(json!({ "status": "Done", "labels": "frontend" })).is_empty());
+        assert!(matching(json!({ "owner": "ana" })).is_empty());
+
+        let metadata: Metadata = serde_json::from_value(json!({ "ticket_key": "PROJ-2" })).unwrap();
+        let hits = storage.search("hello", &[], &metadata, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].source.ehl_doc_id, "doc-1");
+    }
+
+    #[test]
+    fn test_search() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let chunker = Chunker::with_defaults();
+        let pages = [
+            ("slack", "https://workspace.slack.com/archives/C1", "The Quarterly Roadmap review is on Friday"),
+            ("chrome", "https://example.com/roadmap", "Roadmap draft: 100% of items_left are open"),
+        ];
+        for (i, (source, url, content)) in pages.iter().enumerate() {
+            let mut payload = make_payload();
+            payload.source = source.to_string();
+            payload.url = url.to_string();
+            payload.content = content.to_string();
+            storage
+                .insert_source(&payload, url, "hash", &format!("doc-{}", i), &chunker.chunk(content))
+                .unwrap();
+        }
+
+        // Case-insensitive, newest first
+        let hits = storage.search("roadmap", &[], &Metadata::new(), 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].source.ehl_doc_id, "doc-1");
+        assert_eq!(hits[1].snippet, "The Quarterly Roadmap review is on Friday");
+
+        let hits = storage.search("roadmap", &["slack".to_string()], &Metadata::new(), 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].source.source_type, "slack");
+
+        // LIKE wildcards in the query are literal
+        assert_eq!(storage.search("100%", &[], &Metadata::new(), 10).unwrap().len(), 1);
+        assert_eq!(storage.search("items_left", &[], &Metadata::new(), 10).unwrap().len(), 1);
+        assert!(storage.search("items%left", &[], &Metadata::new(), 10).unwrap().is_empty());
+
+        assert_eq!(storage.search("roadmap", &[], &Metadata::new(), 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_snippet() {
+        let pattern = RegexBuilder::new("needle").case_insensitive(true).build().unwrap();
+        let text = format!("{} NEEDLE\n{}", "é".repeat(100), "b".repeat(100));
+        let snippet = snippet(&text, &pattern);
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains(&format!("{} NEEDLE {}", "é".repeat(59), "b".repeat(59))));
+    }
+
+    #[test]
+    fn test_delete_and_gc() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let payload = make_payload();
+        let chunks = Chunker::with_defaults().chunk(&payload.content);
+        storage
+            .insert_source(&payload, &payload.url, "hash", "doc-uuid", &chunks)
+            .unwrap();
+        let revised = Chunker::with_defaults().chunk("A second revision of the message");
+        storage
+            .update_source("doc-uuid", &payload.url, &payload, "hash2", &revised)
+            .unwrap();
+        // One chunk from the first revision has been picked up by the vector index
+        storage
+            .conn
+            .execute("UPDATE chunks SET vector_index = 0 WHERE id = 1", [])
+            .unwrap();
+
+        let removed = storage.delete_source("doc-uuid").unwrap().unwrap();
+        assert_eq!(removed.source_path, payload.url);
+        assert!(storage.delete_source("doc-uuid").unwrap().is_none());
+        assert!(storage.find_source_by_doc_id("doc-uuid").unwrap().is_none());
+
+        let stats = storage.stats().unwrap();
+        assert_eq!((stats.source_count, stats.chunk_count, stats.deleted_chunk_count), (0, 0, 2));
+
+        let summary = storage.gc(GcOptions::default()).unwrap();
+        assert_eq!((summary.purged_chunks, summary.kept_indexed), (1, 1));
+        let summary = storage
+            .gc(GcOptions {
+                include_indexed: true,
+                vacuum: true,
+            })
+            .unwrap();
+        assert_eq!((summary.purged_chunks, summary.kept_indexed), (1, 0));
+        assert_eq!(storage.stats().unwrap().deleted_chunk_count, 0);
+    }
+
+    #[test]
+    fn test_forget() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let payload = make_payload();
+        let chunks = Chunker::with_defaults().chunk(&payload.content);
+        storage
+            .insert_source(&payload, &payload.url, "hash", "doc-uuid", &chunks)
+            .unwrap();
+        let other = CapturePayload {
+            url: "https://example.com/notes".to_string(),
+            ..make_payload()
+        };
+        storage
+            .insert_source(&other, &other.url, "hash", "other-uuid", &chunks)
+            .unwrap();
+
+        let result = storage.forget(TombstoneKind::Site, "https://Workspace.Slack.com/", None).unwrap();
+        assert_eq!(result.tombstone.value, "workspace.slack.com");
+        assert_eq!(result.removed.len(), 1);
+        assert_eq!(result.removed[0].ehl_doc_id, "doc-uuid");
+
+        // The source is gone and its chunk text erased, not just hidden
+        assert!(storage.find_source_by_doc_id("doc-uuid").unwrap().is_none());
+        assert!(storage.find_source_by_doc_id("other-uuid").unwrap().is_some());
+        let leftover: i64 = storage
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM chunks WHERE text LIKE '%Hello world%' AND is_deleted != 0",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(leftover, 0);
+
+        let blocked = storage
+            .find_tombstone(&payload.url, "https://workspace.slack.com/archives/C999")
+            .unwrap();
+        assert_eq!(blocked.map(|t| t.id), Some(result.tombstone.id));
+        assert!(storage.find_tombstone(&other.url, &other.url).unwrap().is_none());
+
+        assert!(storage.remove_tombstone(result.tombstone.id).unwrap().is_some());
+        assert!(storage.list_tombstones().unwrap().is_empty());
+        assert!(storage.find_tombstone(&payload.url, &payload.url).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_malformed_meta_does_not_block_removal() {
+        let mut storage = Storage::open_in_memory().unwrap();
+        let payload = make_payload();
+        let chunks = Chunker::with_defaults().chunk(&payload.content);
+        storage
+            .insert_source(&payload, &payload.url, "hash", "doc-uuid", &chunks)
+            .unwrap();
+        let other = CapturePayload {
+            url: "https://example.com/notes".to_string(),
+            ..make_payload()
+        };
+        storage
+            .insert_source(&other, &other.url, "hash", "other-uuid", &chunks)
+            .unwrap();
+        storage
+            .conn
+            .execute("INSERT INTO chunks (text, meta) VALUES ('broken', '{not json')", [])
+            .unwrap();
+
+        assert!(storage.delete_source("doc-uuid").unwrap().is_some());
+        assert_eq!(storage.forget(TombstoneKind::Path, &other.url, None).unwrap().removed.len(), 1);
+        assert_eq!(storage.gc(GcOptions::default()).unwrap().purged_chunks, 2 * chunks.len());
+    }
+
+    #[test]
+    fn test_migrates_schema_without_captured_at() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("content.db");
+
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE content_sources (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                source_type TEXT NOT NULL,
+                source_path TEXT NOT NULL UNIQUE,
+                content_hash TEXT NOT NULL,
+                ehl_doc_id TEXT NOT NULL UNIQUE,
+                chunk_count INTEGER NOT NULL DEFAULT 0,
+                ingestion_status TEXT NOT NULL DEFAULT 'ingested',
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, updated_at)
+            VALUES ('chrome', 'https://old.example.com', 'hash', 'old-doc', '2020-01-01 00:00:00');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let storage = Storage::open(&path).unwrap();
+        let source = storage
+            .find_source_by_path("https://old.example.com")
+            .unwrap()
+            .unwrap();
+        assert_eq!(source.captured_at, 1577836800);
+        assert_eq!(schema_version(&storage.conn).unwrap(), SCHEMA_VERSION);
+
+        // The random id is replaced by the derived one and still resolves
+        assert_eq!(source.ehl_doc_id, doc_id::doc_id("chrome", "https://old.example.com"));
+        let aliased = storage.find_source_by_doc_id("old-doc").unwrap().unwrap();
+        assert_eq!(aliased.ehl_doc_id, source.ehl_doc_id);
+    }
 }
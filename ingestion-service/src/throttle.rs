@@ -0,0 +1,414 @@
+//! Per-source rate limiting of stored updates
+//!
+//! Every payload that would write is admitted against two token buckets, one
+//! for its canonical source path and one for its `source` type, and against a
+//! minimum interval between updates of the same path. A payload that arrives
+//! too early is deferred rather than dropped: only the latest deferred payload
+//! per path is kept, and it is stored once the path is allowed again, so a
+//! window that changes every second is written at most once per interval.
+//!
+//! ```toml
+//! [throttle]
+//! min_update_interval_secs = 10
+//! per_path = { burst = 10, per_minute = 2 }
+//! per_source = { burst = 100, per_minute = 300 }
+//! ```
+
+use crate::payload::CapturePayload;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// The `[throttle]` config section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    /// Turn all limits off
+    pub enabled: bool,
+    /// Minimum time between stored updates of one source path
+    pub min_update_interval_secs: u64,
+    /// Bucket per canonical source path
+    pub per_path: BucketConfig,
+    /// Bucket per `source` type, shared by all of its paths
+    pub per_source: BucketConfig,
+    /// Deferred payloads held at once; beyond this, payloads for other paths are dropped
+    pub max_pending: usize,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_update_interval_secs: 10,
+            per_path: BucketConfig {
+                burst: 10.0,
+                per_minute: 2.0,
+            },
+            per_source: BucketConfig {
+                burst: 100.0,
+                per_minute: 300.0,
+            },
+            max_pending: 1000,
+        }
+    }
+}
+
+/// Token bucket size and refill rate; `per_minute = 0` disables the bucket
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BucketConfig {
+    pub burst: f64,
+    pub per_minute: f64,
+}
+
+impl BucketConfig {
+    fn unlimited(&self) -> bool {
+        self.per_minute <= 0.0
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn full(config: &BucketConfig, now: Instant) -> Self {
+        Self {
+            tokens: config.burst,
+            refilled_at: now,
+        }
+    }
+
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.per_minute / 60.0).min(config.burst);
+        self.refilled_at = now;
+    }
+
+    /// Time until a token is available
+    fn wait(&mut self, config: &BucketConfig, now: Instant) -> Duration {
+        if config.unlimited() {
+            return Duration::ZERO;
+        }
+        self.refill(config, now);
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) * 60.0 / config.per_minute)
+        }
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+
+    fn is_full(&self, config: &BucketConfig) -> bool {
+        config.unlimited() || self.tokens >= config.burst
+    }
+}
+
+#[derive(Debug)]
+struct PathState {
+    source: String,
+    bucket: TokenBucket,
+    last_admitted: Option<Instant>,
+    /// Latest payload held back for this path
+    pending: Option<CapturePayload>,
+}
+
+/// Outcome of [`Throttle::admit`]
+#[derive(Debug)]
+pub enum Admission {
+    /// Store it now
+    Process(Box<CapturePayload>),
+    /// Held back; the latest deferred payload for the path is stored after `retry_in`
+    Deferred { retry_in: Duration, superseded: bool },
+    /// Held back and not kept, because `max_pending` payloads are already deferred
+    Dropped,
+}
+
+/// Counters returned by the `stats` command
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThrottleStats {
+    /// Payloads currently deferred
+    pub pending: usize,
+    /// Payloads deferred since startup
+    pub deferred: u64,
+    /// Payloads dropped since startup
+    pub dropped: u64,
+}
+
+/// Rate limiter state for all sources
+pub struct Throttle {
+    config: ThrottleConfig,
+    paths: HashMap<String, PathState>,
+    sources: HashMap<String, TokenBucket>,
+    pending: usize,
+    deferred: u64,
+    dropped: u64,
+}
+
+impl Throttle {
+    pub fn new(config: ThrottleConfig) -> Self {
+        Self {
+            config,
+            paths: HashMap::new(),
+            sources: HashMap::new(),
+            pending: 0,
+            deferred: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Whether any limits apply
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Decide whether a payload for `source_path` may be stored now
+    pub fn admit(&mut self, source_path: &str, payload: CapturePayload, now: Instant) -> Admission {
+        if !self.config.enabled {
+            return Admission::Process(Box::new(payload));
+        }
+
+        let config = &self.config;
+        let path = self
+            .paths
+            .entry(source_path.to_string())
+            .or_insert_with(|| PathState {
+                source: payload.source.clone(),
+                bucket: TokenBucket::full(&config.per_path, now),
+                last_admitted: None,
+                pending: None,
+            });
+        let source = self
+            .sources
+            .entry(payload.source.clone())
+            .or_insert_with(|| TokenBucket::full(&config.per_source, now));
+
+        let retry_in = wait(config, path, source, now);
+        if retry_in.is_zero() {
+            admit_now(path, source, now);
+            // A newer payload replaces whatever was deferred
+            if path.pending.take().is_some() {
+                self.pending -= 1;
+            }
+            return Admission::Process(Box::new(payload));
+        }
+
+        let superseded = path.pending.is_some();
+        if !superseded && self.pending >= config.max_pending {
+            self.dropped += 1;
+            return Admission::Dropped;
+        }
+        path.pending = Some(payload);
+        if !superseded {
+            self.pending += 1;
+        }
+        self.deferred += 1;
+        Admission::Deferred { retry_in, superseded }
+    }
+
+    /// Drop the deferred payload for a path, e.g. because the stored content caught up
+    pub fn discard(&mut self, source_path: &str) {
+        if let Some(path) = self.paths.get_mut(source_path) {
+            if path.pending.take().is_some() {
+                self.pending -= 1;
+            }
+        }
+    }
+
+    /// Deferred payloads that may be stored now; they are counted as admitted
+    pub fn take_due(&mut self, now: Instant) -> Vec<CapturePayload> {
+        let config = &self.config;
+        let mut due = Vec::new();
+
+        for path in self.paths.values_mut().filter(|path| path.pending.is_some()) {
+            let source = self
+                .sources
+                .entry(path.source.clone())
+                .or_insert_with(|| TokenBucket::full(&config.per_source, now));
+            if wait(config, path, source, now).is_zero() {
+                admit_now(path, source, now);
+                due.extend(path.pending.take());
+            }
+        }
+        self.pending -= due.len();
+
+        // Idle paths and sources behave exactly like unseen ones; don't keep them
+        let interval = Duration::from_secs(config.min_update_interval_secs);
+        self.paths.retain(|_, path| {
+            path.bucket.refill(&config.per_path, now);
+            path.pending.is_some()
+                || !path.bucket.is_full(&config.per_path)
+                || path.last_admitted.is_some_and(|at| now.saturating_duration_since(at) < interval)
+        });
+        self.sources.retain(|_, bucket| {
+            bucket.refill(&config.per_source, now);
+            !bucket.is_full(&config.per_source)
+        });
+
+        due
+    }
+
+    pub fn stats(&self) -> ThrottleStats {
+        ThrottleStats {
+            pending: self.pending,
+            deferred: self.deferred,
+            dropped: self.dropped,
+        }
+    }
+}
+
+/// Time until a path may be updated again
+fn wait(config: &ThrottleConfig, path: &mut PathState, source: &mut TokenBucket, now: Instant) -> Duration {
+    let interval = path
+        .last_admitted
+        .map(|at| (at + Duration::from_secs(config.min_update_interval_secs)).saturating_duration_since(now))
+        .unwrap_or_default();
+    interval
+        .max(path.bucket.wait(&config.per_path, now))
+        .max(source.wait(&config.per_source, now))
+}
+
+fn admit_now(path: &mut PathState, source: &mut TokenBucket, now: Instant) {
+    path.bucket.take();
+    source.take();
+    path.last_admitted = Some(now);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(source: &str, url: &str, content: &str) -> CapturePayload {
+        CapturePayload::new(source, url, content)
+    }
+
+    fn config(min_update_interval_secs: u64, per_path: (f64, f64), per_source: (f64, f64)) -> ThrottleConfig {
+        ThrottleConfig {
+            enabled: true,
+            min_update_interval_secs,
+            per_path: BucketConfig {
+                burst: per_path.0,
+                per_minute: per_path.1,
+            },
+            per_source: BucketConfig {
+                burst: per_source.0,
+                per_minute: per_source.1,
+            },
+            max_pending: 10,
+        }
+    }
+
+    fn processed(admission: Admission) -> Option<String> {
+        match admission {
+            Admission::Process(payload) => Some(payload.content),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_min_interval_coalesces_latest() {
+        let mut throttle = Throttle::new(config(10, (100.0, 0.0), (100.0, 0.0)));
+        let start = Instant::now();
+        let path = "https://example.com/ticker";
+
+        assert!(processed(throttle.admit(path, payload("chrome", path, "v1"), start)).is_some());
+
+        let second = throttle.admit(path, payload("chrome", path, "v2"), start + Duration::from_secs(2));
+        assert!(matches!(second, Admission::Deferred { retry_in, superseded: false } if retry_in == Duration::from_secs(8)));
+        let third = throttle.admit(path, payload("chrome", path, "v3"), start + Duration::from_secs(4));
+        assert!(matches!(third, Admission::Deferred { superseded: true, .. }));
+        assert_eq!(throttle.stats().pending, 1);
+
+        assert!(throttle.take_due(start + Duration::from_secs(9)).is_empty());
+        let due = throttle.take_due(start + Duration::from_secs(10));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].content, "v3");
+        assert_eq!(throttle.stats().pending, 0);
+
+        // Flushing counts as an update, so the interval starts over
+        let next = throttle.admit(path, payload("chrome", path, "v4"), start + Duration::from_secs(12));
+        assert!(matches!(next, Admission::Deferred { .. }));
+    }
+
+    #[test]
+    fn test_path_bucket_limits_bursts() {
+        let mut throttle = Throttle::new(config(0, (3.0, 6.0), (100.0, 0.0)));
+        let start = Instant::now();
+        let path = "ocr://Terminal/log";
+
+        for i in 0..3 {
+            assert!(processed(throttle.admit(path, payload("ocr-capture", path, "x"), start + Duration::from_millis(i))).is_some());
+        }
+        // Empty bucket, 6 tokens per minute: one every 10 seconds
+        let deferred = throttle.admit(path, payload("ocr-capture", path, "y"), start + Duration::from_millis(3));
+        assert!(matches!(deferred, Admission::Deferred { retry_in, .. } if retry_in > Duration::from_secs(9)));
+
+        // Other paths are unaffected
+        let other = "ocr://Terminal/other";
+        assert!(processed(throttle.admit(other, payload("ocr-capture", other, "z"), start)).is_some());
+
+        assert_eq!(throttle.take_due(start + Duration::from_secs(10)).len(), 1);
+    }
+
+    #[test]
+    fn test_source_bucket_is_shared() {
+        let mut throttle = Throttle::new(config(0, (100.0, 0.0), (2.0, 60.0)));
+        let start = Instant::now();
+
+        assert!(processed(throttle.admit("a", payload("slack", "a", "1"), start)).is_some());
+        assert!(processed(throttle.admit("b", payload("slack", "b", "1"), start)).is_some());
+        assert!(matches!(throttle.admit("c", payload("slack", "c", "1"), start), Admission::Deferred { .. }));
+        // A different source type has its own bucket
+        assert!(processed(throttle.admit("d", payload("gmail", "d", "1"), start)).is_some());
+    }
+
+    #[test]
+    fn test_newer_payload_replaces_deferred() {
+        let mut throttle = Throttle::new(config(10, (100.0, 0.0), (100.0, 0.0)));
+        let start = Instant::now();
+        let path = "https://example.com/";
+
+        throttle.admit(path, payload("chrome", path, "v1"), start);
+        throttle.admit(path, payload("chrome", path, "v2"), start + Duration::from_secs(1));
+        let later = throttle.admit(path, payload("chrome", path, "v3"), start + Duration::from_secs(11));
+        assert_eq!(processed(later).as_deref(), Some("v3"));
+        assert_eq!(throttle.stats().pending, 0);
+        assert!(throttle.take_due(start + Duration::from_secs(30)).is_empty());
+    }
+
+    #[test]
+    fn test_discard_and_max_pending() {
+        let mut throttle = Throttle::new(ThrottleConfig {
+            max_pending: 1,
+            ..config(10, (100.0, 0.0), (100.0, 0.0))
+        });
+        let start = Instant::now();
+        let later = start + Duration::from_secs(1);
+
+        throttle.admit("a", payload("chrome", "a", "1"), start);
+        throttle.admit("b", payload("chrome", "b", "1"), start);
+        assert!(matches!(throttle.admit("a", payload("chrome", "a", "2"), later), Admission::Deferred { .. }));
+        assert!(matches!(throttle.admit("b", payload("chrome", "b", "2"), later), Admission::Dropped));
+
+        throttle.discard("a");
+        assert!(matches!(throttle.admit("b", payload("chrome", "b", "3"), later), Admission::Deferred { .. }));
+        assert_eq!(throttle.stats().dropped, 1);
+        assert_eq!(throttle.take_due(start + Duration::from_secs(10))[0].content, "3");
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut throttle = Throttle::new(ThrottleConfig {
+            enabled: false,
+            ..config(60, (1.0, 1.0), (1.0, 1.0))
+        });
+        let now = Instant::now();
+        for _ in 0..5 {
+            assert!(processed(throttle.admit("a", payload("chrome", "a", "x"), now)).is_some());
+        }
+    }
+}
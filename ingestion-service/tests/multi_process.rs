@@ -0,0 +1,122 @@
+//! Multi-process stress test for the shared WAL connection setup.
+//!
+//! The test re-runs its own binary as child processes: several writers insert
+//! and update sources through `Storage` while a reader polls through a
+//! read-only connection, all against one database file. None of them may see
+//! "database is locked".
+
+use ingestion_service::chunker::Chunker;
+use ingestion_service::{CapturePayload, Storage};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const CHILD_ROLE: &str = "CONTENT_DB_STRESS_ROLE";
+const CHILD_DB: &str = "CONTENT_DB_STRESS_PATH";
+const WRITERS: usize = 4;
+const SOURCES_PER_WRITER: usize = 25;
+
+fn payload(writer: usize, source: usize, revision: usize) -> CapturePayload {
+    CapturePayload::new(
+        "stress",
+        format!("https://example.com/{}/{}", writer, source),
+        format!("writer {} source {} revision {}\n", writer, source, revision).repeat(40),
+    )
+}
+
+fn run_writer(db: &Path, writer: usize) {
+    let mut storage = Storage::open(db).unwrap();
+    let chunker = Chunker::with_defaults();
+
+    for source in 0..SOURCES_PER_WRITER {
+        let doc_id = format!("doc-{}-{}", writer, source);
+        let first = payload(writer, source, 0);
+        storage
+            .insert_source(&first, &first.url, "hash-0", &doc_id, &chunker.chunk(&first.content))
+            .unwrap();
+
+        let second = payload(writer, source, 1);
+        storage
+            .update_source(&doc_id, &second.url, &second, "hash-1", &chunker.chunk(&second.content))
+            .unwrap();
+    }
+}
+
+fn run_reader(db: &Path) {
+    let conn = content_db::open(db, content_db::OpenMode::ReadOnly).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let expected = (WRITERS * SOURCES_PER_WRITER) as i64;
+
+    loop {
+        let sources: i64 = conn
+            .query_row("SELECT COUNT(*) FROM content_sources", [], |row| row.get(0))
+            .unwrap();
+        // Every committed source has its chunks; a reader never sees half a write
+        let orphaned: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM content_sources cs WHERE cs.chunk_count != (
+                     SELECT COUNT(*) FROM chunks c
+                     WHERE json_extract(c.meta, '$.id') = cs.ehl_doc_id AND c.is_deleted = 0)",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(orphaned, 0);
+
+        let updated: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM content_sources WHERE content_hash = 'hash-1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        if sources == expected && updated == expected {
+            return;
+        }
+        assert!(Instant::now() < deadline, "reader timed out at {} sources", sources);
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// Entry point for child processes; a no-op when run as a normal test
+#[test]
+fn stress_child() {
+    let (Ok(role), Ok(db)) = (std::env::var(CHILD_ROLE), std::env::var(CHILD_DB)) else {
+        return;
+    };
+    let db = Path::new(&db);
+    match role.strip_prefix("writer-") {
+        Some(writer) => run_writer(db, writer.parse().unwrap()),
+        None => run_reader(db),
+    }
+}
+
+#[test]
+fn test_concurrent_processes() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("content.db");
+    // Create the schema up front so the reader has something to open
+    drop(Storage::open(&db).unwrap());
+
+    let exe = std::env::current_exe().unwrap();
+    let spawn = |role: String| {
+        Command::new(&exe)
+            .args(["stress_child", "--exact", "--nocapture", "--test-threads=1"])
+            .env(CHILD_ROLE, role)
+            .env(CHILD_DB, &db)
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap()
+    };
+
+    let mut children: Vec<_> = (0..WRITERS).map(|w| spawn(format!("writer-{}", w))).collect();
+    children.push(spawn("reader".to_string()));
+
+    for mut child in children {
+        assert!(child.wait().unwrap().success(), "child process failed");
+    }
+
+    let storage = Storage::open(&db).unwrap();
+    let stats = storage.stats().unwrap();
+    assert_eq!(stats.source_count, WRITERS * SOURCES_PER_WRITER);
+}
@@ -19,6 +19,7 @@
 //! ingestion --no-accessibility # Disable accessibility extraction
 //! ```
 
+use capture_protocol::{IngestionResponse, ResponseStatus};
 use std::path::PathBuf;
 use std::process::{Child, Command, Stdio};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -325,9 +326,9 @@ async fn send_to_ingestion_server(
     let mut response_line = String::new();
     reader.read_line(&mut response_line).await?;
 
-    let response: serde_json::Value = serde_json::from_str(&response_line)?;
+    let response: IngestionResponse = serde_json::from_str(&response_line)?;
 
-    if response["status"] == "ok" || response["status"] == "created" || response["status"] == "updated" || response["status"] == "skipped" {
+    if response.status == ResponseStatus::Ok {
         Ok(())
     } else {
         Err(format!("Ingestion server error: {:?}", response).into())
@@ -2,6 +2,8 @@
 //!
 //! Loads configuration from TOML files and provides runtime defaults.
 
+use capture_protocol::mask::MaskConfig;
+use capture_protocol::redact::RedactionConfig;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use tracing::{info, warn};
@@ -26,6 +28,11 @@ pub struct Config {
 
     #[serde(default)]
     pub multi_display: MultiDisplayConfig,
+
+    /// Volatile text (relative times, counters) left out of the content hash,
+    /// same format as the ingestion server's `[masking]` section
+    #[serde(default)]
+    pub masking: MaskConfig,
 }
 
 impl Default for Config {
@@ -37,6 +44,7 @@ impl Default for Config {
             extractors: ExtractorsConfig::default(),
             privacy: PrivacyConfig::default(),
             multi_display: MultiDisplayConfig::default(),
+            masking: MaskConfig::default(),
         }
     }
 }
@@ -203,6 +211,19 @@ pub struct PrivacyConfig {
     pub redact_phone_numbers: bool,
 }
 
+impl PrivacyConfig {
+    /// The redaction settings, as the shared redactor takes them
+    pub fn redaction(&self) -> RedactionConfig {
+        RedactionConfig {
+            redact_credit_cards: self.redact_credit_cards,
+            redact_ssn: self.redact_ssn,
+            redact_api_keys: self.redact_api_keys,
+            redact_emails: self.redact_emails,
+            redact_phone_numbers: self.redact_phone_numbers,
+        }
+    }
+}
+
 impl Default for PrivacyConfig {
     fn default() -> Self {
         Self {
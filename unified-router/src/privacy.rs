@@ -2,11 +2,11 @@
 //!
 //! This module provides functionality to:
 //! - Block extraction from sensitive applications
-//! - Redact personally identifiable information (PII) from extracted content
+//! - Redact personally identifiable information (PII) from extracted content,
+//!   with the rules in `capture_protocol::redact`
 
 use crate::config::PrivacyConfig;
-use lazy_static::lazy_static;
-use regex::Regex;
+use capture_protocol::redact::Redactor;
 use tracing::debug;
 
 /// Applications that are always blacklisted and cannot be unblocked.
@@ -38,48 +38,13 @@ pub const ALWAYS_BLACKLISTED_PATTERNS: &[&str] = &[
     "*davinciresolve*",
 ];
 
-lazy_static! {
-    // Credit card: 13-19 digits, optionally with spaces/dashes
-    static ref CREDIT_CARD: Regex = Regex::new(
-        r"\b(?:\d{4}[-\s]?){3,4}\d{1,4}\b"
-    ).unwrap();
-
-    // SSN: XXX-XX-XXXX format
-    static ref SSN: Regex = Regex::new(
-        r"\b\d{3}-\d{2}-\d{4}\b"
-    ).unwrap();
-
-    // API keys: common patterns
-    static ref API_KEY: Regex = Regex::new(
-        r#"(?i)(api[_-]?key|apikey|secret[_-]?key|access[_-]?token|auth[_-]?token)['"]?\s*[:=]\s*['"]?([a-zA-Z0-9_\-]{20,})"#
-    ).unwrap();
-
-    // AWS keys
-    static ref AWS_KEY: Regex = Regex::new(
-        r"(?i)(AKIA[0-9A-Z]{16})"
-    ).unwrap();
-
-    // Email addresses
-    static ref EMAIL: Regex = Regex::new(
-        r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b"
-    ).unwrap();
-
-    // Phone numbers (various formats)
-    static ref PHONE: Regex = Regex::new(
-        r"\b(?:\+1[-.\s]?)?\(?\d{3}\)?[-.\s]?\d{3}[-.\s]?\d{4}\b"
-    ).unwrap();
-
-    // Password fields in common formats
-    static ref PASSWORD_FIELD: Regex = Regex::new(
-        r#"(?i)(password|passwd|pwd)['"]?\s*[:=]\s*['"]?([^\s'"]{4,})"#
-    ).unwrap();
-}
-
 /// Privacy filter for content redaction and app blocking
 pub struct PrivacyFilter {
     config: PrivacyConfig,
     /// Compiled patterns for blocked apps
     blocked_patterns: Vec<glob::Pattern>,
+    /// The redaction rules shared with the ingestion server
+    redactor: Redactor,
 }
 
 impl PrivacyFilter {
@@ -99,6 +64,7 @@ impl PrivacyFilter {
             .collect();
 
         Self {
+            redactor: Redactor::new(config.redaction()),
             config,
             blocked_patterns,
         }
@@ -153,69 +119,7 @@ impl PrivacyFilter {
 
     /// Redact PII from content based on configuration
     pub fn redact(&self, content: &str) -> String {
-        let mut result = content.to_string();
-
-        if self.config.redact_credit_cards {
-            result = self.redact_credit_cards(&result);
-        }
-
-        if self.config.redact_ssn {
-            result = SSN.replace_all(&result, "[REDACTED_SSN]").to_string();
-        }
-
-        if self.config.redact_api_keys {
-            result = self.redact_api_keys(&result);
-        }
-
-        if self.config.redact_emails {
-            result = EMAIL.replace_all(&result, "[REDACTED_EMAIL]").to_string();
-        }
-
-        if self.config.redact_phone_numbers {
-            result = PHONE.replace_all(&result, "[REDACTED_PHONE]").to_string();
-        }
-
-        result
-    }
-
-    /// Redact credit card numbers with Luhn validation
-    fn redact_credit_cards(&self, content: &str) -> String {
-        CREDIT_CARD
-            .replace_all(content, |caps: &regex::Captures| {
-                let matched = &caps[0];
-                // Extract digits only
-                let digits: String = matched.chars().filter(|c| c.is_ascii_digit()).collect();
-
-                // Validate with Luhn algorithm to reduce false positives
-                if is_valid_luhn(&digits) {
-                    "[REDACTED_CARD]".to_string()
-                } else {
-                    matched.to_string()
-                }
-            })
-            .to_string()
-    }
-
-    /// Redact API keys and tokens
-    fn redact_api_keys(&self, content: &str) -> String {
-        let mut result = content.to_string();
-
-        // Generic API key patterns
-        result = API_KEY
-            .replace_all(&result, "$1=[REDACTED_KEY]")
-            .to_string();
-
-        // AWS access keys
-        result = AWS_KEY
-            .replace_all(&result, "[REDACTED_AWS_KEY]")
-            .to_string();
-
-        // Password fields
-        result = PASSWORD_FIELD
-            .replace_all(&result, "$1=[REDACTED_PASSWORD]")
-            .to_string();
-
-        result
+        self.redactor.redact(content).into_owned()
     }
 
     /// Add an app to the blocklist at runtime
@@ -254,58 +158,10 @@ impl Default for PrivacyFilter {
     }
 }
 
-/// Luhn algorithm for credit card validation
-fn is_valid_luhn(number: &str) -> bool {
-    let digits: Vec<u32> = number
-        .chars()
-        .filter(|c| c.is_ascii_digit())
-        .filter_map(|c| c.to_digit(10))
-        .collect();
-
-    // Credit cards are typically 13-19 digits
-    if digits.len() < 13 || digits.len() > 19 {
-        return false;
-    }
-
-    let sum: u32 = digits
-        .iter()
-        .rev()
-        .enumerate()
-        .map(|(i, &d)| {
-            if i % 2 == 1 {
-                let doubled = d * 2;
-                if doubled > 9 {
-                    doubled - 9
-                } else {
-                    doubled
-                }
-            } else {
-                d
-            }
-        })
-        .sum();
-
-    sum % 10 == 0
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_luhn_valid() {
-        // Test Visa card number (passes Luhn)
-        assert!(is_valid_luhn("4532015112830366"));
-        // Test Mastercard
-        assert!(is_valid_luhn("5425233430109903"));
-    }
-
-    #[test]
-    fn test_luhn_invalid() {
-        assert!(!is_valid_luhn("1234567890123456"));
-        assert!(!is_valid_luhn("0000000000000000"));
-    }
-
     #[test]
     fn test_is_blocked() {
         let filter = PrivacyFilter::default();
@@ -18,6 +18,8 @@ use crate::types::{
     WindowId, WindowInfo, WindowState,
 };
 use crate::window_tracker::{WindowChanges, WindowTracker};
+use capture_protocol::mask::Masks;
+use capture_protocol::normalize::normalize;
 use std::collections::HashMap;
 use std::time::Instant;
 use tokio::sync::mpsc;
@@ -35,6 +37,8 @@ pub struct UnifiedRouter {
     capture_service: CaptureService,
     /// Privacy filter
     privacy_filter: PrivacyFilter,
+    /// Masking of volatile text before hashing
+    masks: Masks,
     /// Extractor registry
     extractor_registry: ExtractorRegistry,
     /// Accessibility extractor
@@ -58,6 +62,10 @@ impl UnifiedRouter {
     pub fn new(config: Config, content_tx: mpsc::Sender<CapturePayload>) -> Self {
         let change_detector = ChangeDetector::new(config.change_detection.hash_sensitivity);
         let privacy_filter = PrivacyFilter::new(config.privacy.clone());
+        let masks = Masks::compile(&config.masking).unwrap_or_else(|e| {
+            warn!("{}, using the built-in masking rules", e);
+            Masks::builtin()
+        });
 
         Self {
             config,
@@ -65,6 +73,7 @@ impl UnifiedRouter {
             change_detector,
             capture_service: CaptureService::new(),
             privacy_filter,
+            masks,
             extractor_registry: ExtractorRegistry::new(),
             accessibility_extractor: AccessibilityExtractor::new(),
             ocr_extractor: OcrExtractor::new(),
@@ -407,10 +416,14 @@ impl UnifiedRouter {
             state.last_extraction = Some(chrono::Utc::now());
             state.extraction_count += 1;
 
-            // Compute content hash for dedup
+            // Compute content hash for dedup, over the text the ingestion server hashes
             use sha2::{Sha256, Digest};
+            let apps: Vec<&str> = std::iter::once(content.app_name.as_str())
+                .chain(content.bundle_id.as_deref())
+                .collect();
+            let masked = self.masks.mask(&content.source, &apps, &content.content);
             let mut hasher = Sha256::new();
-            hasher.update(content.content.as_bytes());
+            hasher.update(normalize(&masked).as_bytes());
             let hash = format!("{:x}", hasher.finalize());
 
             // Skip if content hasn't changed
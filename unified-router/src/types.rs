@@ -153,21 +153,9 @@ pub struct ExtractedContent {
     pub confidence: Option<f32>,
 }
 
-/// Payload sent to ingestion service
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CapturePayload {
-    pub source: String,
-    pub url: String,
-    pub content: String,
-    pub title: Option<String>,
-    pub author: Option<String>,
-    pub channel: Option<String>,
-    pub timestamp: Option<i64>,
-    /// Application name for display
-    pub app_name: Option<String>,
-    /// Application bundle ID for icon lookup
-    pub bundle_id: Option<String>,
-}
+/// Payload sent to ingestion service (shared with it through `capture-protocol`)
+pub use capture_protocol::CapturePayload;
+use capture_protocol::ContentType;
 
 impl From<ExtractedContent> for CapturePayload {
     fn from(content: ExtractedContent) -> Self {
@@ -211,12 +199,15 @@ impl From<ExtractedContent> for CapturePayload {
             source: source_type,
             url,
             content: content.content,
+            content_type: ContentType::Plain,
             title: content.title,
             author: None,
             channel: None,
             timestamp: Some(content.timestamp),
             app_name: Some(content.app_name),
             bundle_id: content.bundle_id,
+            metadata: Default::default(),
+            messages: Vec::new(),
         }
     }
 }
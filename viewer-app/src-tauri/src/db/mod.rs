@@ -2,7 +2,10 @@ mod types;
 
 pub use types::*;
 
-use rusqlite::{Connection, Result as SqliteResult};
+use content_db::{message, revision};
+use content_db::tombstone::{self, TombstoneKind};
+use content_db::{OpenMode, RetryPolicy};
+use rusqlite::{Connection, OptionalExtension, Result as SqliteResult};
 use std::path::PathBuf;
 
 /// Get the default database path (~/Library/Application Support/clace-ingestion/content.db)
@@ -31,15 +34,20 @@ pub fn get_default_db_path() -> String {
 }
 
 /// Database connection and query handler
+///
+/// Queries go through a read-only connection so the viewer never holds a write
+/// lock the ingestion server or ax-daemon could be waiting on.
 pub struct ViewerDb {
     conn: Connection,
+    /// Database file, reopened read-write for the rare user-initiated write
+    path: Option<PathBuf>,
 }
 
 #[cfg(test)]
 impl ViewerDb {
     /// Create ViewerDb from an existing connection (for testing only)
     pub fn from_connection(conn: Connection) -> Self {
-        Self { conn }
+        Self { conn, path: None }
     }
 }
 
@@ -64,9 +72,13 @@ impl ViewerDb {
             return Err(DbError::NotFound(expanded_path));
         }
 
-        let conn = Connection::open(&expanded_path).map_err(DbError::Connection)?;
+        let conn =
+            content_db::open(&expanded_path, OpenMode::ReadOnly).map_err(DbError::Connection)?;
 
-        Ok(Self { conn })
+        Ok(Self {
+            conn,
+            path: Some(PathBuf::from(expanded_path)),
+        })
     }
 
     /// Get paginated content sources with preview
@@ -74,21 +86,42 @@ impl ViewerDb {
         &self,
         page: i32,
         limit: i32,
+    ) -> Result<PaginatedResponse<ContentSourceView>, DbError> {
+        self.get_sources_in_range(page, limit, None, None)
+    }
+
+    /// Get paginated content sources captured within a time range (unix seconds,
+    /// `since` inclusive, `until` exclusive). Sources without a recorded capture
+    /// time fall back to `updated_at`.
+    pub fn get_sources_in_range(
+        &self,
+        page: i32,
+        limit: i32,
+        since: Option<i64>,
+        until: Option<i64>,
     ) -> Result<PaginatedResponse<ContentSourceView>, DbError> {
         let offset = page * limit;
 
+        // Build the time range condition; NULL bounds match everything
+        let range_clause = r#"
+            (?1 IS NULL OR COALESCE(cs.captured_at, CAST(strftime('%s', cs.updated_at) AS INTEGER)) >= ?1)
+            AND (?2 IS NULL OR COALESCE(cs.captured_at, CAST(strftime('%s', cs.updated_at) AS INTEGER)) < ?2)
+        "#;
+
         // Get total count
         let total: i64 = self
             .conn
-            .query_row("SELECT COUNT(*) FROM content_sources", [], |row| {
-                row.get(0)
-            })
+            .query_row(
+                &format!("SELECT COUNT(*) FROM content_sources cs WHERE {}", range_clause),
+                rusqlite::params![since, until],
+                |row| row.get(0),
+            )
             .map_err(DbError::Query)?;
 
         // Get paginated sources
         let mut stmt = self
             .conn
-            .prepare(
+            .prepare(&format!(
                 r#"
                 SELECT 
                     cs.id,
@@ -97,16 +130,19 @@ impl ViewerDb {
                     cs.ehl_doc_id,
                     cs.chunk_count,
                     cs.created_at,
-                    cs.updated_at
+                    cs.updated_at,
+                    cs.captured_at
                 FROM content_sources cs
+                WHERE {}
                 ORDER BY cs.updated_at DESC
-                LIMIT ?1 OFFSET ?2
+                LIMIT ?3 OFFSET ?4
                 "#,
-            )
+                range_clause
+            ))
             .map_err(DbError::Query)?;
 
         let sources: Vec<ContentSourceView> = stmt
-            .query_map([limit, offset], |row| {
+            .query_map(rusqlite::params![since, until, limit, offset], |row| {
                 let ehl_doc_id: String = row.get(3)?;
                 Ok(ContentSourceView {
                     id: row.get(0)?,
@@ -116,6 +152,7 @@ impl ViewerDb {
                     chunk_count: row.get(4)?,
                     created_at: row.get(5)?,
                     updated_at: row.get(6)?,
+                    captured_at: row.get(7)?,
                     title: None,       // Will be populated from chunk meta
                     preview_text: String::new(), // Will be populated from first chunk
                     app_name: None,    // Will be populated from chunk meta
@@ -155,7 +192,7 @@ impl ViewerDb {
     fn get_source_preview(&self, ehl_doc_id: &str) -> Result<(Option<String>, String, Option<String>, Option<String>), DbError> {
         let result: Result<(String, String), _> = self.conn.query_row(
             r#"
-            SELECT text, meta FROM chunks 
+            SELECT chunk_text(text, text_zst), meta FROM chunks
             WHERE json_extract(meta, '$.id') = ?1 
             AND is_deleted = 0
             ORDER BY json_extract(meta, '$.chunk_index') ASC
@@ -199,11 +236,11 @@ impl ViewerDb {
     /// Get full content detail by ehl_doc_id
     pub fn get_detail(&self, ehl_doc_id: &str) -> Result<ContentDetail, DbError> {
         // Get content source
-        let source: (i64, String, String, i32, String, String) = self
+        let source: (i64, String, String, i32, String, String, Option<i64>) = self
             .conn
             .query_row(
                 r#"
-                SELECT id, source_type, source_path, chunk_count, created_at, updated_at
+                SELECT id, source_type, source_path, chunk_count, created_at, updated_at, captured_at
                 FROM content_sources
                 WHERE ehl_doc_id = ?1
                 "#,
@@ -216,6 +253,7 @@ impl ViewerDb {
                         row.get(3)?,
                         row.get(4)?,
                         row.get(5)?,
+                        row.get(6)?,
                     ))
                 },
             )
@@ -226,7 +264,7 @@ impl ViewerDb {
             .conn
             .prepare(
                 r#"
-                SELECT text, meta FROM chunks
+                SELECT chunk_text(text, text_zst), meta FROM chunks
                 WHERE json_extract(meta, '$.id') = ?1
                 AND is_deleted = 0
                 ORDER BY json_extract(meta, '$.chunk_index') ASC
@@ -257,12 +295,16 @@ impl ViewerDb {
             })
             .unwrap_or((None, None, None, None, None));
 
-        // Reconstruct full text (use newline separator between chunks)
-        let full_text = chunks
-            .iter()
-            .map(|(text, _)| text.as_str())
-            .collect::<Vec<_>>()
-            .join("\n");
+        // Prefer the stored revision text; databases written before revisions
+        // existed (no table yet) fall back to joining the overlapping chunks
+        let full_text = match revision::latest(&self.conn, ehl_doc_id) {
+            Ok(Some(latest)) => latest.text,
+            Ok(None) | Err(_) => chunks
+                .iter()
+                .map(|(text, _)| text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
 
         Ok(ContentDetail {
             id: source.0,
@@ -272,6 +314,7 @@ impl ViewerDb {
             chunk_count: source.3,
             created_at: source.4,
             updated_at: source.5,
+            captured_at: source.6,
             title,
             author,
             channel,
@@ -309,29 +352,68 @@ impl ViewerDb {
         })
     }
 
-    /// Delete a content source and its associated chunks by ehl_doc_id
-    pub fn delete_content_source(&self, ehl_doc_id: &str) -> Result<(), DbError> {
-        // Mark chunks as deleted (soft delete)
-        self.conn
-            .execute(
+    /// Erase a content source by ehl_doc_id the way `ingestion-ctl forget`
+    /// does: its chunk text, revisions and messages go with it. With `forget`
+    /// its path is also tombstoned so it is not captured again.
+    pub fn delete_content_source(&self, ehl_doc_id: &str, forget: bool) -> Result<(), DbError> {
+        // The query connection is read-only; writes get their own short-lived one
+        let writer;
+        let conn = match &self.path {
+            Some(path) => {
+                writer = content_db::open(path, OpenMode::ReadWrite).map_err(DbError::Connection)?;
+                &writer
+            }
+            None => &self.conn,
+        };
+
+        content_db::write_transaction(conn, &RetryPolicy::default(), |tx| {
+            let source_path: Option<String> = tx
+                .query_row(
+                    "SELECT source_path FROM content_sources WHERE ehl_doc_id = ?1",
+                    [ehl_doc_id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let Some(source_path) = source_path else {
+                return Ok(());
+            };
+            if forget {
+                // Tombstone the path so the next capture doesn't bring it back
+                tombstone::ensure_schema(tx)?;
+                tombstone::insert(tx, TombstoneKind::Path, &source_path, None)?;
+            }
+
+            // Soft-delete the chunks, but don't leave their text behind
+            tx.execute(
                 r#"
-                UPDATE chunks 
-                SET is_deleted = 1 
-                WHERE json_extract(meta, '$.id') = ?1
+                UPDATE chunks
+                SET is_deleted = 1, text = '', text_zst = NULL
+                WHERE json_extract(CASE WHEN json_valid(meta) THEN meta END, '$.id') = ?1
                 "#,
                 [ehl_doc_id],
-            )
-            .map_err(DbError::Query)?;
+            )?;
+            revision::ensure_schema(tx)?;
+            revision::delete(tx, ehl_doc_id)?;
+            message::ensure_schema(tx)?;
+            message::delete(tx, ehl_doc_id)?;
+
+            // Per-message history kept by the accessibility daemon
+            let has_messages: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'messages')",
+                [],
+                |row| row.get(0),
+            )?;
+            if has_messages {
+                tx.execute("DELETE FROM messages WHERE source_url = ?1", [&source_path])?;
+            }
 
-        // Delete the content source record
-        self.conn
-            .execute(
+            tx.execute(
                 "DELETE FROM content_sources WHERE ehl_doc_id = ?1",
                 [ehl_doc_id],
-            )
-            .map_err(DbError::Query)?;
-
-        Ok(())
+            )?;
+            Ok(())
+        })
+        .map_err(DbError::Query)
     }
 }
 
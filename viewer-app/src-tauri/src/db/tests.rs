@@ -22,7 +22,8 @@ fn create_test_db() -> Connection {
             chunk_count INTEGER NOT NULL DEFAULT 0,
             ingestion_status TEXT NOT NULL DEFAULT 'ingested',
             created_at TEXT NOT NULL DEFAULT (datetime('now')),
-            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+            captured_at INTEGER
         );
 
         CREATE TABLE chunks (
@@ -31,11 +32,14 @@ fn create_test_db() -> Connection {
             text TEXT NOT NULL,
             meta TEXT NOT NULL,
             is_deleted INTEGER DEFAULT 0,
-            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            content_hash TEXT,
+            text_zst BLOB
         );
         "#,
     )
     .expect("Failed to create schema");
+    content_db::compression::register_functions(&conn).expect("Failed to register functions");
 
     conn
 }
@@ -91,6 +95,163 @@ mod unit_tests {
         assert!(!result.has_more);
     }
 
+    #[test]
+    fn test_get_sources_in_range() {
+        let conn = create_test_db();
+        for (i, captured_at) in [1_000_000_000i64, 1_100_000_000, 1_200_000_000].iter().enumerate() {
+            conn.execute(
+                "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count, captured_at)
+                 VALUES ('browser', ?1, 'hash', ?2, 0, ?3)",
+                rusqlite::params![format!("https://example.com/{}", i), format!("doc-{}", i), captured_at],
+            )
+            .expect("Failed to insert source");
+        }
+        // Legacy row without captured_at falls back to updated_at
+        conn.execute(
+            "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count, updated_at)
+             VALUES ('browser', 'https://example.com/legacy', 'hash', 'doc-legacy', 0, '2005-01-01 00:00:00')",
+            [],
+        )
+        .expect("Failed to insert legacy source");
+        let db = viewer_db_from_conn(conn);
+
+        let result = db
+            .get_sources_in_range(0, 50, Some(1_050_000_000), Some(1_200_000_000))
+            .unwrap();
+        assert_eq!(result.total, 2);
+        let ids: Vec<&str> = result.items.iter().map(|s| s.ehl_doc_id.as_str()).collect();
+        assert!(ids.contains(&"doc-1"));
+        assert!(ids.contains(&"doc-legacy"));
+        assert_eq!(
+            result.items.iter().find(|s| s.ehl_doc_id == "doc-1").unwrap().captured_at,
+            Some(1_100_000_000)
+        );
+
+        let all = db.get_sources(0, 50).unwrap();
+        assert_eq!(all.total, 4);
+    }
+
+    #[test]
+    fn test_open_is_read_only_but_delete_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("content.db");
+        let conn = create_test_db();
+        conn.execute(
+            "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count)
+             VALUES ('browser', 'https://example.com/', 'hash', 'doc-1', 1)",
+            [],
+        )
+        .unwrap();
+        conn.execute("VACUUM INTO ?1", [path.to_str().unwrap()]).unwrap();
+
+        let db = ViewerDb::open(path.to_str().unwrap()).unwrap();
+        assert!(db
+            .conn
+            .execute("DELETE FROM content_sources", [])
+            .is_err());
+
+        db.delete_content_source("doc-1", true).unwrap();
+        assert_eq!(db.get_source_count().unwrap(), 0);
+
+        // Forgetting also keeps the page from being captured again
+        let writer = Connection::open(&path).unwrap();
+        let blocked = content_db::tombstone::find_match(&writer, "https://example.com/", "").unwrap();
+        assert!(blocked.is_some());
+    }
+
+    #[test]
+    fn test_delete_erases_text_without_forgetting() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("content.db");
+        let conn = create_test_db();
+        content_db::revision::ensure_schema(&conn).unwrap();
+        conn.execute_batch(
+            r#"
+            INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count)
+            VALUES ('browser', 'https://example.com/', 'hash', 'doc-1', 1);
+            INSERT INTO chunks (text, meta) VALUES ('private text', '{"id":"doc-1","chunk_index":0}');
+            INSERT INTO chunks (text, meta) VALUES ('other text', 'not json');
+            "#,
+        )
+        .unwrap();
+        content_db::revision::insert(&conn, "doc-1", "hash", 1_700_000_000, "private text", None).unwrap();
+        conn.execute("VACUUM INTO ?1", [path.to_str().unwrap()]).unwrap();
+
+        let db = ViewerDb::open(path.to_str().unwrap()).unwrap();
+        db.delete_content_source("doc-1", false).unwrap();
+        assert_eq!(db.get_source_count().unwrap(), 0);
+
+        let writer = Connection::open(&path).unwrap();
+        let texts: Vec<String> = writer
+            .prepare("SELECT text FROM chunks ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(texts, ["", "other text"]);
+        let revisions: i64 = writer
+            .query_row("SELECT COUNT(*) FROM source_revisions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(revisions, 0);
+        content_db::tombstone::ensure_schema(&writer).unwrap();
+        assert!(content_db::tombstone::find_match(&writer, "https://example.com/", "").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_get_detail_reads_compressed_chunks() {
+        let conn = create_test_db();
+        conn.execute(
+            "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count)
+             VALUES ('browser', 'https://example.com/', 'hash', 'doc-1', 2)",
+            [],
+        )
+        .unwrap();
+
+        let text = "compressed chunk text ".repeat(20);
+        let frame = content_db::compression::Compressor::new(3).compress(&text).unwrap();
+        conn.execute(
+            "INSERT INTO chunks (text, meta, text_zst) VALUES ('', ?1, ?2)",
+            rusqlite::params![r#"{"id":"doc-1","chunk_index":0}"#, frame],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO chunks (text, meta) VALUES ('plain tail', ?1)",
+            [r#"{"id":"doc-1","chunk_index":1}"#],
+        )
+        .unwrap();
+
+        let db = viewer_db_from_conn(conn);
+        let detail = db.get_detail("doc-1").unwrap();
+        assert_eq!(detail.full_text, format!("{}\nplain tail", text));
+    }
+
+    #[test]
+    fn test_get_detail_prefers_revision_text() {
+        let conn = create_test_db();
+        content_db::revision::ensure_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO content_sources (source_type, source_path, content_hash, ehl_doc_id, chunk_count)
+             VALUES ('browser', 'https://example.com/', 'hash', 'doc-1', 2)",
+            [],
+        )
+        .unwrap();
+        for (index, text) in ["one two three", "three four"].iter().enumerate() {
+            conn.execute(
+                "INSERT INTO chunks (text, meta) VALUES (?1, ?2)",
+                rusqlite::params![text, format!(r#"{{"id":"doc-1","chunk_index":{}}}"#, index)],
+            )
+            .unwrap();
+        }
+
+        // Joined chunks repeat the overlap
+        let db = viewer_db_from_conn(conn);
+        assert_eq!(db.get_detail("doc-1").unwrap().full_text, "one two three\nthree four");
+
+        content_db::revision::insert(&db.conn, "doc-1", "hash", 0, "one  two three\n\nfour", None).unwrap();
+        assert_eq!(db.get_detail("doc-1").unwrap().full_text, "one  two three\n\nfour");
+    }
+
     #[test]
     fn test_get_source_count_empty() {
         let conn = create_test_db();
@@ -11,6 +11,8 @@ pub struct ContentSourceView {
     pub chunk_count: i32,
     pub created_at: String,
     pub updated_at: String,
+    /// Capture time of the latest revision (unix seconds), if recorded
+    pub captured_at: Option<i64>,
     /// Extracted from first chunk's meta
     pub title: Option<String>,
     pub preview_text: String,
@@ -30,6 +32,8 @@ pub struct ContentDetail {
     pub chunk_count: i32,
     pub created_at: String,
     pub updated_at: String,
+    /// Capture time of the latest revision (unix seconds), if recorded
+    pub captured_at: Option<i64>,
     pub title: Option<String>,
     pub author: Option<String>,
     pub channel: Option<String>,
@@ -41,18 +41,22 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! Welcome to the Viewer App.", name)
 }
 
-/// Get paginated content sources
+/// Get paginated content sources, optionally limited to a capture time range
+/// (unix seconds)
 #[tauri::command]
 fn get_content_sources(
     state: State<'_, AppState>,
     page: i32,
     limit: i32,
+    since: Option<i64>,
+    until: Option<i64>,
 ) -> Result<db::PaginatedResponse<db::ContentSourceView>, String> {
     let db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard
         .as_ref()
         .ok_or_else(|| "Database not initialized".to_string())?;
-    db.get_sources(page, limit).map_err(|e| e.to_string())
+    db.get_sources_in_range(page, limit, since, until)
+        .map_err(|e| e.to_string())
 }
 
 /// Get full content detail by ehl_doc_id
@@ -78,17 +82,18 @@ fn get_stats(state: State<'_, AppState>) -> Result<db::DbStats, String> {
     db.get_stats().map_err(|e| e.to_string())
 }
 
-/// Delete a content source by ehl_doc_id
+/// Delete a content source by ehl_doc_id; `forget` also stops it being captured again
 #[tauri::command]
 fn delete_content_source(
     state: State<'_, AppState>,
     ehl_doc_id: String,
+    forget: bool,
 ) -> Result<(), String> {
     let db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard
         .as_ref()
         .ok_or_else(|| "Database not initialized".to_string())?;
-    db.delete_content_source(&ehl_doc_id).map_err(|e| e.to_string())
+    db.delete_content_source(&ehl_doc_id, forget).map_err(|e| e.to_string())
 }
 
 /// Start watching the database file for changes
@@ -62,10 +62,11 @@ function App() {
   /**
    * Handle delete action - deletes content source from database
    * @param ehlDocId - The EHL document ID to delete
+   * @param forget - Also tombstone its path so it is not captured again
    */
-  const handleDelete = useCallback(async (ehlDocId: string) => {
+  const handleDelete = useCallback(async (ehlDocId: string, forget: boolean) => {
     try {
-      await invoke('delete_content_source', { ehlDocId });
+      await invoke('delete_content_source', { ehlDocId, forget });
       // Refresh the list after deletion
       refresh();
     } catch (err) {
@@ -19,7 +19,7 @@ import type { ContentSourceView } from '../types';
 export interface ContentCardProps {
   source: ContentSourceView;
   onClick: () => void;
-  onDelete?: (ehlDocId: string) => void;
+  onDelete?: (ehlDocId: string, forget: boolean) => void;
 }
 
 /**
@@ -28,7 +28,8 @@ export interface ContentCardProps {
  * 
  * @param source - The content source data to display
  * @param onClick - Callback when the card is clicked
- * @param onDelete - Callback when the delete button is clicked
+ * @param onDelete - Callback when the delete or forget button is clicked;
+ *   `forget` is true for forget, which also stops the page being captured again
  */
 export const ContentCard: React.FC<ContentCardProps> = ({ source, onClick, onDelete }) => {
   const [isHovered, setIsHovered] = useState(false);
@@ -50,10 +51,10 @@ export const ContentCard: React.FC<ContentCardProps> = ({ source, onClick, onDel
   // Format source type for display (capitalize first letter)
   const sourceTypeLabel = source.source_type.charAt(0).toUpperCase() + source.source_type.slice(1);
 
-  const handleDelete = (e: React.MouseEvent) => {
+  const handleDelete = (forget: boolean) => (e: React.MouseEvent) => {
     e.stopPropagation();
     if (onDelete) {
-      onDelete(source.ehl_doc_id);
+      onDelete(source.ehl_doc_id, forget);
     }
   };
 
@@ -76,10 +77,23 @@ export const ContentCard: React.FC<ContentCardProps> = ({ source, onClick, onDel
       data-source-type={source.source_type}
       style={cardStyle}
     >
+      {isHovered && onDelete && (
+        <button
+          className="content-card__delete content-card__delete--forget"
+          onClick={handleDelete(true)}
+          aria-label={`Forget ${displayTitle}`}
+          title="Delete and never capture again"
+        >
+          <svg width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2" strokeLinecap="round" strokeLinejoin="round">
+            <circle cx="12" cy="12" r="10"></circle>
+            <line x1="4.93" y1="4.93" x2="19.07" y2="19.07"></line>
+          </svg>
+        </button>
+      )}
       {isHovered && onDelete && (
         <button
           className="content-card__delete"
-          onClick={handleDelete}
+          onClick={handleDelete(false)}
           aria-label={`Delete ${displayTitle}`}
           title="Delete"
         >
@@ -17,7 +17,7 @@ export interface GridViewProps {
   sources: ContentSourceView[];
   onCardClick: (ehlDocId: string) => void;
   onLoadMore: () => void;
-  onDelete?: (ehlDocId: string) => void;
+  onDelete?: (ehlDocId: string, forget: boolean) => void;
   hasMore: boolean;
   loading: boolean;
 }
@@ -29,7 +29,7 @@ export interface GridViewProps {
  * @param sources - Array of content sources to display
  * @param onCardClick - Callback when a card is clicked, receives ehlDocId
  * @param onLoadMore - Callback to load more content
- * @param onDelete - Callback when delete button is clicked, receives ehlDocId
+ * @param onDelete - Callback when delete or forget is clicked, receives ehlDocId and whether to forget
  * @param hasMore - Whether there are more items to load
  * @param loading - Whether content is currently being loaded
  */
@@ -22,6 +22,8 @@ export interface ContentSourceView {
   chunk_count: number;
   created_at: string;
   updated_at: string;
+  /** Capture time of the latest revision (unix seconds), null for legacy rows */
+  captured_at?: number | null;
   /** Title extracted from first chunk's meta, may be null */
   title: string | null;
   /** Preview text for card display */
@@ -44,6 +46,8 @@ export interface ContentDetail {
   chunk_count: number;
   created_at: string;
   updated_at: string;
+  /** Capture time of the latest revision (unix seconds), null for legacy rows */
+  captured_at?: number | null;
   /** Full title, may be null */
   title: string | null;
   /** Author from chunk metadata, may be null */